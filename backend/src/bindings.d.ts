@@ -0,0 +1,264 @@
+// Hand-written TypeScript declarations for exports whose wasm-bindgen signature would be
+// `any`. Embedded into the generated index.d.ts via `typescript_custom_section`; the
+// matching exports are marked `skip_typescript` so each is declared exactly once.
+
+/** `[start, end)` file byte range of one line. */
+export type ByteRange = [start: number, end: number];
+
+/** A decoded line, or `null` when rejected in strict UTF-8 mode. */
+export type DecodedLine = string | null;
+
+/** `set_invalid_utf8_mode` codes: 0 trim trailing cut (default), 1 replace, 2 strict. */
+export type InvalidUtf8Mode = 0 | 1 | 2 | 3;
+
+/** `set_line_ending_mode` codes: 0 keep terminators (default), 1 strip each line's own. */
+export type LineEndingMode = 0 | 1;
+
+/** `decode_lines_opts` flag bits: 1 control pictures, 2 `\xNN` escapes, 4 expand tabs. */
+export type DecodeFlags = number;
+
+export interface WriteRegion {
+  /** Address in WASM linear memory. Do not cache. */
+  ptr: number;
+  /** Bytes that may be written at `ptr`. */
+  capacity: number;
+}
+
+export interface WindowPlan {
+  start_line: number;
+  file_start: number;
+  file_end: number;
+  /** Line ends relative to `file_start`; pass unchanged to `decode_lines_from_blob`. */
+  line_ends: Uint32Array;
+}
+
+export interface SearchStats {
+  bytes_scanned: number;
+  duration_ms: number;
+}
+
+export interface LimitedSearch {
+  lines: number[];
+  /** True when another matching line exists past the last one returned. */
+  truncated: boolean;
+}
+
+export interface SearchSummary {
+  line_count: number;
+  /** Non-overlapping occurrences in the matching lines. */
+  occurrence_count: number;
+}
+
+export interface LineMatchPositions {
+  line: number;
+  /** Byte offsets of the hits from the line start. */
+  positions: number[];
+  /** More hits than `max_positions_per_line`. */
+  truncated: boolean;
+}
+
+export interface RenderedLine {
+  n: number;
+  /** Decoded line without its terminator. */
+  text: string;
+  /** `[start, end)` of each match in `text`, in characters (code points). */
+  matches: [number, number][];
+}
+
+export interface BytePosition {
+  line: number;
+  /** Bytes from the line start, or characters when `chars` is true. */
+  column: number;
+  chars: boolean;
+}
+
+export interface ProfileEntry {
+  category: "scan" | "search" | "classify" | "decode" | "filter" | "printability";
+  total_ms: number;
+  calls: number;
+}
+
+export interface TopLine {
+  /** A line with this content. */
+  line: number;
+  count: number;
+  /** The true count is in `[count - error, count]`. */
+  error: number;
+}
+
+export interface LinePreview {
+  line: number;
+  /** The first bytes of the line, decoded. */
+  text: string;
+  /** The line is longer than `text`. */
+  truncated: boolean;
+}
+
+export interface TokenCount {
+  token: string;
+  count: number;
+}
+
+export interface TokenFrequencies {
+  /** Highest count first; ties in byte order. */
+  tokens: TokenCount[];
+  /** More distinct tokens than are tracked; rarer ones may be missing. */
+  overflowed: boolean;
+}
+
+export interface NumericStats {
+  count: number;
+  /** Matching lines whose value did not parse as a number. */
+  skipped: number;
+  sum: number;
+  /** `undefined` until a value has been parsed, like the figures below. */
+  min: number | undefined;
+  max: number | undefined;
+  mean: number | undefined;
+  p50: number | undefined;
+  p95: number | undefined;
+  p99: number | undefined;
+  /** False once quantiles are estimated from a sample. */
+  exact_quantiles: boolean;
+}
+
+export interface DetectedFormat {
+  format: "json" | "access_log" | "syslog" | "logfmt" | "plain" | "unknown";
+  /** Share of the sampled lines in `format`; below 1 for a mixed file. */
+  confidence: number;
+  sampled_lines: number;
+  /** Each format's share of the sampled lines. */
+  scores: {
+    json: number;
+    access_log: number;
+    syslog: number;
+    logfmt: number;
+    plain: number;
+  };
+}
+
+export interface ExportStats {
+  total_lines: number;
+  /** Bytes in the export, line terminators included. */
+  total_bytes: number;
+  emitted_lines: number;
+  emitted_bytes: number;
+  /** True once `export_next_ranges` has returned every range. */
+  done: boolean;
+}
+
+export interface BoundaryState {
+  /** Bytes indexed so far: the file offset the next chunk starts at. */
+  total_bytes: number;
+  last_chunk_ended_with_newline: boolean;
+  line_count: number;
+  buffer_len: number;
+  buffer_capacity: number;
+}
+
+export interface IngestStats {
+  indexed_bytes: number;
+  /** From `set_expected_total_bytes`; `null` when no size was announced. */
+  expected_bytes: number | null;
+  /** Expected minus indexed bytes, never negative; `null` without an expected size. */
+  lag_bytes: number | null;
+  /** Time since the first chunk, paused spans excluded. */
+  active_ms: number;
+  bytes_per_ms: number;
+  /** Remaining time at `bytes_per_ms`; 0 once complete, `null` while unknown. */
+  eta_ms: number | null;
+  paused: boolean;
+}
+
+export interface MemoryStats {
+  line_count: number;
+  offsets_bytes: number;
+  offsets_wide: boolean;
+  buffer_capacity: number;
+  buffer_high_water: number;
+  decode_scratch_bytes: number;
+  ci_index_bytes: number;
+  top_lines_bytes: number;
+  previews_bytes: number;
+  numeric_extractor_bytes: number;
+  counters_bytes: number;
+  printability_bytes: number;
+  live_bytes: number;
+  match_set_bytes: number;
+  total_bytes: number;
+  compact_before_bytes: number;
+  compact_after_bytes: number;
+  search_handles: number;
+  decode_cache_lines: number;
+}
+
+export interface Rule {
+  name: string;
+  pattern: string;
+}
+
+export function get_write_region(size: number): WriteRegion;
+export function get_line_byte_ranges(start: number, end: number): ByteRange[];
+export function get_line_byte_ranges_checked(start: number, end: number): ByteRange[];
+export function decode_lines_from_blob(
+  blob: Uint8Array,
+  line_ends: Uint32Array,
+  plan?: WindowPlan | null,
+): DecodedLine[];
+export function plan_window(start_line: number, end_line: number): WindowPlan;
+/** `undefined` unless every line in the range is cached. */
+export function get_cached_lines(start_line: number, end_line: number): DecodedLine[] | undefined;
+export function decode_lines_opts(
+  blob: Uint8Array,
+  line_ends: Uint32Array,
+  flags: DecodeFlags,
+  tab_width: number,
+): DecodedLine[];
+export function render_window(
+  blob: Uint8Array,
+  line_ends: Uint32Array,
+  needle: string,
+  first_line_index: number,
+): RenderedLine[];
+export function decode_lines_from_blob_big(
+  blob: Uint8Array,
+  line_ends: BigUint64Array,
+): DecodedLine[];
+export function set_invalid_utf8_mode(mode: InvalidUtf8Mode): void;
+export function set_line_ending_mode(mode: LineEndingMode): void;
+export function search(needle: Uint8Array): number[];
+export function search_escaped(pattern: string): number[];
+export function count_content_lines(prefixes: Uint8Array[], whitespace_is_blank?: boolean | null): number;
+export function content_line_indices(prefixes: Uint8Array[], whitespace_is_blank?: boolean | null): Uint32Array;
+export function search_limit(needle: Uint8Array, max: number): LimitedSearch;
+export function search_summary(needle: Uint8Array): SearchSummary;
+export function position_at_byte(byte_offset: number, char_column: boolean): BytePosition;
+export function search_with_positions(
+  needle: Uint8Array,
+  max_positions_per_line: number,
+): LineMatchPositions[];
+export function search_any(needles: Uint8Array[]): Uint32Array;
+/** Rejects with an `Error` named `"Cancelled"` after `request_cancel(op_id)`. */
+export function search_async(needle: Uint8Array, op_id: number): Promise<number[]>;
+/** Returns interleaved `[line, mask]` pairs; bit `i` of `mask` = rule `i` matched. */
+export function classify_by_rules(rules: Rule[]): Uint32Array;
+export function search_stats(): SearchStats;
+export function get_profile(): ProfileEntry[];
+export function get_memory_stats(): MemoryStats;
+export function get_ingest_stats(): IngestStats;
+export function boundary_state(): BoundaryState;
+export function get_top_lines(k: number): TopLine[];
+export function get_line_previews(start: number, end: number): LinePreview[];
+export function get_numeric_stats(id: number): NumericStats;
+export function get_detected_format(): DetectedFormat;
+export function export_stats(token: number): ExportStats;
+export function register_counters(patterns: Uint8Array[]): Uint32Array;
+export function token_frequencies(
+  start_line: number,
+  end_line: number,
+  blob: Uint8Array,
+  blob_offset: number,
+  top_k: number,
+  min_len: number,
+  skip_numbers: boolean,
+): TokenFrequencies;
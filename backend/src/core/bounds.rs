@@ -0,0 +1,128 @@
+//! Validation of line arguments coming from JS. A `usize` parameter silently wraps what
+//! JS passes (-1 becomes 4294967295, 2^53 loses its high bits), so exports that must not
+//! guess take `f64` and convert here, and the strict range exports check the bounds
+//! against the index instead of clamping.
+
+use std::fmt;
+
+use super::ffi_num::MAX_SAFE_INTEGER;
+
+/// An invalid line argument or line range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RangeError {
+    /// `name` is negative, not finite, or too large to be a line index.
+    NotAnIndex { name: &'static str, value: f64 },
+    /// `start` is after `end`.
+    Reversed { start: usize, end: usize },
+    /// `end` is past the last line.
+    PastEnd { end: usize, line_count: usize },
+    /// `start` names a line evicted by retention.
+    Evicted { start: usize, first_line: usize },
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RangeError::NotAnIndex { name, value } => write!(
+                f,
+                "{name} = {value} is not a line index (must be an integer from 0 to 2^53 - 1)"
+            ),
+            RangeError::Reversed { start, end } => {
+                write!(f, "start = {start} is after end = {end}")
+            }
+            RangeError::PastEnd { end, line_count } => write!(
+                f,
+                "end = {end} is past the last line (line count {line_count})"
+            ),
+            RangeError::Evicted { start, first_line } => write!(
+                f,
+                "start = {start} was evicted; the first retained line is {first_line}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RangeError {}
+
+/// Converts JS argument `name` to a line index. Fractions are truncated; negative,
+/// non-finite and unsafe-integer values, and values past `usize::MAX`, are errors.
+pub fn line_index(name: &'static str, value: f64) -> Result<usize, RangeError> {
+    let valid = value.is_finite() && (0.0..=MAX_SAFE_INTEGER as f64).contains(&value);
+    let index = value as u64;
+    match usize::try_from(index) {
+        Ok(index) if valid => Ok(index),
+        _ => Err(RangeError::NotAnIndex { name, value }),
+    }
+}
+
+/// Checks that [start, end) is a range of existing lines: `first_line <= start <= end <=
+/// line_count`. An empty range (`start == end`) is valid.
+pub fn check_line_range(
+    start: usize,
+    end: usize,
+    first_line: usize,
+    line_count: usize,
+) -> Result<(), RangeError> {
+    if start > end {
+        return Err(RangeError::Reversed { start, end });
+    }
+    if end > line_count {
+        return Err(RangeError::PastEnd { end, line_count });
+    }
+    if start < first_line {
+        return Err(RangeError::Evicted { start, first_line });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn js_numbers_and_ranges_are_validated() {
+        assert_eq!(line_index("start", 0.0), Ok(0));
+        assert_eq!(line_index("start", 7.9), Ok(7));
+        for bad in [-1.0, -0.5, f64::NAN, f64::INFINITY, 2f64.powi(53) + 2.0] {
+            assert!(matches!(
+                line_index("end", bad),
+                Err(RangeError::NotAnIndex { name: "end", .. })
+            ));
+        }
+        // 2^53 - 1 is the largest safe integer; 2^53 already aliases 2^53 + 1.
+        assert_eq!(
+            line_index("end", MAX_SAFE_INTEGER as f64),
+            Ok(MAX_SAFE_INTEGER as usize)
+        );
+        let err = line_index("end", 2f64.powi(53)).unwrap_err();
+        assert!(err.to_string().contains("0 to 2^53 - 1"));
+
+        assert_eq!(check_line_range(3, 3, 0, 10), Ok(()));
+        assert_eq!(check_line_range(0, 10, 0, 10), Ok(()));
+        assert_eq!(check_line_range(10, 10, 0, 10), Ok(()));
+        assert_eq!(
+            check_line_range(5, 4, 0, 10),
+            Err(RangeError::Reversed { start: 5, end: 4 })
+        );
+        assert_eq!(
+            check_line_range(1_000_000, 2_000_000, 0, 10),
+            Err(RangeError::PastEnd {
+                end: 2_000_000,
+                line_count: 10
+            })
+        );
+        assert_eq!(
+            check_line_range(2, 5, 3, 10),
+            Err(RangeError::Evicted {
+                start: 2,
+                first_line: 3
+            })
+        );
+        assert!(RangeError::PastEnd {
+            end: 11,
+            line_count: 10
+        }
+        .to_string()
+        .contains("line count 10"));
+    }
+}
@@ -3,40 +3,239 @@
 //! Holds the shared buffer (written by JS), the line-offset index, and
 //! streaming state for boundary handling across chunks.
 
+use std::collections::{HashMap, HashSet};
+
+use super::bounds::{check_line_range, RangeError};
+use super::export::LineExport;
+use super::ffi_num::{to_f64, PrecisionLoss};
+use super::ingest::{GrantError, IngestClock, IngestPaused};
+use super::line_stats::LineLengthStats;
+use super::live::{LiveWindow, MixedIngest, DEFAULT_LIVE_RETAINED_BYTES};
+use super::offsets::{check_ascending, LineOffsets, OffsetOrderError};
+use super::printability::Printability;
+use super::profile::{now_ms, Category, Profile};
+use super::retention::RetentionPolicy;
+use super::types::{
+    BoundaryState, BytePosition, DetectedFormat, ExportStats, FormatScores, IngestStats,
+    LinePreview, MemoryStats, SearchStats, WindowPlan, WriteRegion,
+};
+use crate::decode::cache::{DecodeCache, DecodedLine};
+use crate::decode::lines::{
+    clamped_line_slices, decode_line, decode_line_prefix, decode_line_slice, decode_window_line,
+    DecodeConfig, LineEndings, Utf8Mode,
+};
+use crate::indexer::format::{FormatDetector, LogFormat};
+use crate::indexer::nul_runs::NulRuns;
+use crate::indexer::numeric::{NumericExtractor, NumericSummary};
+use crate::indexer::observer::ChunkObserver;
+use crate::indexer::previews::LinePreviews;
+use crate::indexer::top_lines::{HeavyHitter, TopLines};
+use crate::search::ci_index::CaseIndex;
+use crate::search::compiled::CompiledSearch;
+use crate::search::counters::PatternCounters;
+use crate::search::stream::StreamFilter;
+use crate::search::unseen::{UnseenMatches, STREAM_FILTER_ID};
+
+/// Bytes per entry of the coarse block index (see `LogEngine::block_offsets`).
+pub const BLOCK_INDEX_BYTES: u64 = 1 << 20;
+
+/// Default capacity the chunk buffer may keep between chunks (see `set_chunk_buffer_cap`).
+pub const DEFAULT_CHUNK_BUFFER_CAP: usize = 64 * 1024 * 1024;
+
 /// Global log engine state: single buffer + index, shared between JS and Rust.
 pub struct LogEngine {
     /// Pre-allocated buffer into which JS writes chunk data. Rust reads in place (zero-copy).
     buffer: Vec<u8>,
+    /// Largest capacity the buffer keeps once a chunk is discarded; above it, it is freed.
+    chunk_buffer_cap: usize,
+    /// Largest buffer capacity reserved since the last `clear`.
+    buffer_high_water: usize,
+    /// Bytes the next file-ingest call may submit (see `take_grant`).
+    grant: Option<usize>,
     /// Byte offsets of each line start in the logical file (cumulative across chunks).
     /// Line `i` runs from `offsets[i]` to `offsets[i+1] - 1` (or EOF for last line).
-    offsets: Vec<u64>,
+    /// Stored as `u32` while the file is under 4GB (see `LineOffsets`).
+    offsets: LineOffsets,
+    /// Coarse index over `offsets`: (start, line) of the first line starting in each
+    /// `BLOCK_INDEX_BYTES` block that has a line start, ascending. Narrows byte → line
+    /// lookups to one block's lines (~10k entries for 10GB) before touching `offsets`.
+    block_offsets: Vec<(u64, u64)>,
+    /// Length histogram and longest lines, updated as lines complete.
+    line_stats: LineLengthStats,
     /// Total number of bytes indexed so far (file position of the start of the current chunk).
     total_bytes_indexed: u64,
     /// True if the previous chunk ended with a newline (so next chunk starts a new line).
     /// Used to handle the boundary case where a line is split across two chunks.
     last_chunk_ended_with_newline: bool,
+    /// Fixed record size when the file has no line delimiters (see `set_record_size`).
+    record_size: Option<u64>,
+    /// NUL-run skipping (see `set_skip_nul_runs`).
+    nul_runs: Option<NulRuns>,
+    /// Length of a binary header before the first line (see `set_header_skip`).
+    header_skip: u64,
+    /// Set by `mark_complete` once JS has fed the final chunk; cleared if more data is
+    /// indexed afterwards (live append) and by `clear`.
+    complete: bool,
+    /// Total file size JS announced with `set_expected_size`, for progress reporting.
+    expected_size: Option<u64>,
+    /// Pause flag and active ingest time, for `ingest_stats` throughput.
+    ingest_clock: IngestClock,
+    /// Decode settings: ASCII fast path (bytes as Latin-1, no UTF-8 validation; non-ASCII
+    /// decodes lossily) and the invalid-UTF-8 handling mode.
+    decode_config: DecodeConfig,
+    /// Reusable scratch space that decode exports copy JS window blobs into. Grows to the
+    /// largest window seen and is reused, so decoding does not allocate per call.
+    decode_scratch: Vec<u8>,
+    /// Recently decoded lines by index (see `set_decode_cache_size`). Disabled by default.
+    decode_cache: DecodeCache,
+    /// Counters from the last search, updated by the search exports.
+    last_search: SearchStats,
+    /// `heap_bytes` before and after the last `compact`; zeros until it first runs.
+    last_compact: (usize, usize),
+    /// Ids of long-running async operations JS asked to cancel. Checked between batches.
+    cancelled_ops: HashSet<u32>,
+    /// Per-subsystem timings. Survives `clear` so it covers a whole user session.
+    profile: Profile,
+    /// Prepared searches by handle (see `add_search`).
+    searches: HashMap<u32, CompiledSearch>,
+    /// Next handle to hand out. Never reused, so a stale handle cannot alias a new search.
+    next_search_handle: u32,
+    /// Regex tested against each line as it is indexed (see `set_stream_filter`).
+    stream_filter: Option<StreamFilter>,
+    /// Stream filter matches before this line count as seen (see `mark_seen`).
+    filter_first_unseen: u64,
+    /// Unseen-match trackers for compiled searches, by handle (see `mark_seen`).
+    watched: HashMap<u32, UnseenMatches>,
+    /// Case-insensitive shadow index, built while indexing once enabled.
+    ci_index: Option<CaseIndex>,
+    /// Most-frequent-lines sketch, fed while indexing once enabled.
+    top_lines: Option<TopLines>,
+    /// First bytes of each line, kept while indexing once enabled.
+    previews: Option<LinePreviews>,
+    /// Numeric extractors fed while indexing; the id is the index.
+    numeric_extractors: Vec<NumericExtractor>,
+    /// Classifies the first lines of the file (see `detected_format`).
+    format_detector: FormatDetector,
+    /// Literal patterns counted while indexing; the id is the index.
+    counter_patterns: Vec<Vec<u8>>,
+    /// Automaton and counts for `counter_patterns`, `None` until one is registered.
+    counters: Option<PatternCounters>,
+    /// Observers registered with `add_observer`, fed after the built-in features.
+    extra_observers: Vec<Box<dyn ChunkObserver + Send + Sync>>,
+    /// Text-byte share per block of the file, for graying out binary regions.
+    printability: Printability,
+    /// Recent bytes of a live stream (see `begin_live`); `None` for file sessions.
+    live: Option<LiveWindow>,
+    /// Target size of the live window.
+    live_retained_bytes: usize,
+    /// Line caps applied after each live append (see `set_retention`).
+    retention: RetentionPolicy,
+    /// Exports in progress by token (see `begin_export`).
+    exports: HashMap<u32, LineExport>,
+    /// Next export token. Never reused, like search handles.
+    next_export_token: u32,
 }
 
 impl LogEngine {
     pub fn new() -> Self {
         Self {
             buffer: Vec::new(),
-            offsets: Vec::new(),
+            chunk_buffer_cap: DEFAULT_CHUNK_BUFFER_CAP,
+            buffer_high_water: 0,
+            grant: None,
+            offsets: LineOffsets::new(),
+            block_offsets: Vec::new(),
+            line_stats: LineLengthStats::default(),
             total_bytes_indexed: 0,
             last_chunk_ended_with_newline: true,
+            record_size: None,
+            nul_runs: None,
+            header_skip: 0,
+            complete: false,
+            expected_size: None,
+            ingest_clock: IngestClock::default(),
+            decode_config: DecodeConfig::default(),
+            decode_scratch: Vec::new(),
+            decode_cache: DecodeCache::default(),
+            last_search: SearchStats::default(),
+            last_compact: (0, 0),
+            cancelled_ops: HashSet::new(),
+            profile: Profile::default(),
+            searches: HashMap::new(),
+            next_search_handle: 1,
+            stream_filter: None,
+            filter_first_unseen: 0,
+            watched: HashMap::new(),
+            ci_index: None,
+            top_lines: None,
+            previews: None,
+            numeric_extractors: Vec::new(),
+            format_detector: FormatDetector::default(),
+            counter_patterns: Vec::new(),
+            counters: None,
+            extra_observers: Vec::new(),
+            printability: Printability::default(),
+            live: None,
+            live_retained_bytes: DEFAULT_LIVE_RETAINED_BYTES,
+            retention: RetentionPolicy::default(),
+            exports: HashMap::new(),
+            next_export_token: 1,
         }
     }
 
-    /// Reserves space for the next chunk of at least `size` bytes and returns a pointer
-    /// to the start of that region (at current buffer length). JS writes chunk data here.
-    /// Does not change buffer length; call `append_chunk` from `index_chunk` after writing.
+    /// Reserves space for the next chunk of `size` bytes, grants exactly that many to the
+    /// next submission, and returns a pointer to the start of that region (at current
+    /// buffer length). JS writes chunk data here. Does not change buffer length; call
+    /// `append_chunk` from `index_chunk` after writing.
     /// Caller must not cache this pointer: it is invalid after any operation that may reallocate.
     #[inline(always)]
     pub fn get_buffer_pointer(&mut self, size: usize) -> *mut u8 {
-        self.buffer.reserve(size);
+        let old_capacity = self.buffer.capacity();
+        self.buffer.reserve_exact(size);
+        self.grant = Some(size);
+        if self.buffer.capacity() != old_capacity {
+            log::debug!(
+                "buffer reallocated: {} -> {} bytes",
+                old_capacity,
+                self.buffer.capacity()
+            );
+            self.buffer_high_water = self.buffer_high_water.max(self.buffer.capacity());
+        }
         unsafe { self.buffer.as_mut_ptr().add(self.buffer.len()) }
     }
 
+    /// Like `get_buffer_pointer`, but also returns the writable capacity at the pointer,
+    /// which is at least `size` and may be larger. `index_chunk` accepts any length up to it.
+    /// The same invalidation rule applies: do not cache the pointer.
+    #[inline(always)]
+    pub fn get_write_region(&mut self, size: usize) -> WriteRegion {
+        let ptr = self.get_buffer_pointer(size);
+        let capacity = self.buffer.capacity() - self.buffer.len();
+        self.grant = Some(capacity);
+        WriteRegion {
+            ptr: ptr as usize as u64,
+            capacity: capacity as u64,
+        }
+    }
+
+    /// Bytes granted to the next submission by `get_buffer_pointer` or `get_write_region`
+    /// (0 without a grant).
+    #[inline(always)]
+    pub fn granted_size(&self) -> usize {
+        self.grant.unwrap_or(0)
+    }
+
+    /// Consumes the write grant for a submission of `len` bytes. Fails without a grant
+    /// (none requested since the last submission, `discard_buffer_after_indexing` or
+    /// `clear_data`) or if `len` exceeds it; the grant is gone either way.
+    pub fn take_grant(&mut self, len: usize) -> Result<(), GrantError> {
+        match self.grant.take() {
+            None => Err(GrantError::Missing),
+            Some(granted) if len > granted => Err(GrantError::Exceeded { len, granted }),
+            Some(_) => Ok(()),
+        }
+    }
+
     /// Appends `chunk_len` bytes to the buffer (must not exceed the size passed to
     /// `get_buffer_pointer`). Returns a slice of the newly appended chunk for indexing.
     #[inline(always)]
@@ -51,9 +250,59 @@ impl LogEngine {
         &self.buffer[start..new_len]
     }
 
+    /// Undoes the last `append_chunk(chunk_len)` without indexing it. The bytes stay in
+    /// the reserved capacity, so appending the same length again sees the same data.
+    #[inline(always)]
+    pub fn unappend_chunk(&mut self, chunk_len: usize) {
+        let len = self.buffer.len().saturating_sub(chunk_len);
+        self.buffer.truncate(len);
+    }
+
+    /// `append_offsets` for starts computed from JS-supplied positions (e.g. a region's base
+    /// offset): rejects them, appending nothing, unless they continue the index in strictly
+    /// ascending order.
+    pub fn append_offsets_checked(&mut self, new_offsets: &[u64]) -> Result<(), OffsetOrderError> {
+        let last = self.offsets.len().checked_sub(1);
+        let prev = last.and_then(|i| self.offsets.get(i));
+        check_ascending(prev, new_offsets, self.offsets.len())?;
+        self.append_offsets(new_offsets);
+        Ok(())
+    }
+
     /// Appends new line-start offsets from the indexer. Called by the scanner for each chunk.
     #[inline(always)]
     pub fn append_offsets(&mut self, new_offsets: &[u64]) {
+        // The current last line may grow with this chunk.
+        let last_line = self.offsets.len().saturating_sub(1);
+        self.decode_cache.forget_from(last_line as u64);
+        // Each new start after the first ends the line before it (just after its `\n`;
+        // fixed-size records and lines cut at a skipped NUL run have no terminator).
+        let terminator = if self.record_size.is_some() { 0 } else { 1 };
+        let nul_runs = self.nul_runs.as_ref();
+        let mut prev = self
+            .offsets
+            .len()
+            .checked_sub(1)
+            .and_then(|i| self.offsets.get(i));
+        for (index, &start) in (self.offsets.len() as u64..).zip(new_offsets) {
+            if let Some(prev_start) = prev {
+                let terminator = match nul_runs {
+                    Some(runs) if runs.starts_unterminated(start) => 0,
+                    _ => terminator,
+                };
+                let len = start.saturating_sub(prev_start + terminator);
+                self.line_stats.record(index - 1, len);
+            }
+            let block = start / BLOCK_INDEX_BYTES;
+            if self
+                .block_offsets
+                .last()
+                .is_none_or(|&(first, _)| first / BLOCK_INDEX_BYTES < block)
+            {
+                self.block_offsets.push((start, index));
+            }
+            prev = Some(start);
+        }
         self.offsets.extend_from_slice(new_offsets);
     }
 
@@ -61,17 +310,61 @@ impl LogEngine {
     /// Call `discard_buffer_after_indexing()` after this to free chunk memory (keeps only offsets).
     #[inline(always)]
     pub fn advance_after_chunk(&mut self, chunk_len: usize, ended_with_newline: bool) {
+        self.ingest_clock.start(now_ms());
         self.total_bytes_indexed += chunk_len as u64;
         self.last_chunk_ended_with_newline = ended_with_newline;
+        self.complete = false;
+    }
+
+    /// Like `advance_after_chunk`, but sets the cumulative byte count to `total_bytes`
+    /// directly. Used for regions indexed outside the buffer at an explicit file offset.
+    #[inline(always)]
+    pub fn advance_to(&mut self, total_bytes: u64, ended_with_newline: bool) {
+        self.ingest_clock.start(now_ms());
+        self.total_bytes_indexed = total_bytes;
+        self.last_chunk_ended_with_newline = ended_with_newline;
+        self.complete = false;
+    }
+
+    /// Records that the whole file has been indexed, so the last line's end (and the line
+    /// count) are final. Indexing more data afterwards clears the flag again.
+    #[inline(always)]
+    pub fn mark_complete(&mut self) {
+        self.complete = true;
+    }
+
+    /// True between `mark_complete` and the next indexed chunk or `clear`.
+    #[inline(always)]
+    pub fn is_complete(&self) -> bool {
+        self.complete
     }
 
     /// Discards buffer content while keeping the line-offset index. Use after each `index_chunk`
     /// to avoid accumulating the full file in WASM memory (WASM32 address space is limited).
     /// Line content must be obtained by JS reading file byte ranges and calling decode API.
+    /// The capacity is kept for the next chunk, so `get_buffer_pointer` does not reallocate
+    /// per chunk, unless it exceeds the chunk buffer cap.
     #[inline(always)]
     pub fn discard_buffer_after_indexing(&mut self) {
         self.buffer.clear();
-        self.buffer.shrink_to_fit();
+        self.grant = None;
+        if self.buffer.capacity() > self.chunk_buffer_cap {
+            self.buffer.shrink_to_fit();
+        }
+    }
+
+    /// Sets the largest capacity the chunk buffer keeps between chunks; a larger buffer
+    /// (after an unusually big chunk) is freed when the chunk is discarded.
+    #[inline(always)]
+    pub fn set_chunk_buffer_cap(&mut self, bytes: usize) {
+        self.chunk_buffer_cap = bytes;
+    }
+
+    /// Allocated size of the chunk buffer.
+    #[cfg(test)]
+    #[inline(always)]
+    pub fn buffer_capacity(&self) -> usize {
+        self.buffer.capacity()
     }
 
     #[inline(always)]
@@ -84,43 +377,1152 @@ impl LogEngine {
         self.last_chunk_ended_with_newline
     }
 
+    /// Switches indexing to fixed-size records of `size` bytes (`None`: newline-delimited
+    /// lines). Only meaningful before the first chunk.
+    #[inline(always)]
+    pub fn set_record_size(&mut self, size: Option<u64>) {
+        self.record_size = size;
+    }
+
+    #[inline(always)]
+    pub fn record_size(&self) -> Option<u64> {
+        self.record_size
+    }
+
+    /// Makes the first `bytes` bytes of the file a header that belongs to no line: line 0
+    /// starts right after it. Only meaningful before the first chunk.
+    #[inline(always)]
+    pub fn set_header_skip(&mut self, bytes: u64) {
+        self.header_skip = bytes;
+    }
+
+    #[inline(always)]
+    pub fn header_skip(&self) -> u64 {
+        self.header_skip
+    }
+
+    /// Makes runs of at least `min_run` NUL bytes skip regions on lines of their own
+    /// (`None`: off). Only meaningful before the first chunk.
+    pub fn set_skip_nul_runs(&mut self, min_run: Option<u64>) {
+        self.nul_runs = min_run.map(NulRuns::new);
+        self.decode_config.skip_nul_runs = min_run.unwrap_or(0);
+        self.decode_cache.invalidate();
+    }
+
+    /// Takes the NUL-run scanner out for a scan that also borrows the buffer; hand it back
+    /// with `restore_nul_runs` before appending the scanned offsets.
+    #[inline(always)]
+    pub fn take_nul_runs(&mut self) -> Option<NulRuns> {
+        self.nul_runs.take()
+    }
+
+    #[inline(always)]
+    pub fn restore_nul_runs(&mut self, nul_runs: Option<NulRuns>) {
+        self.nul_runs = nul_runs;
+    }
+
+    /// Skipped NUL runs as `[start, end)` file offsets, ascending.
+    pub fn skip_regions(&self) -> &[(u64, u64)] {
+        self.nul_runs.as_ref().map_or(&[], NulRuns::regions)
+    }
+
+    /// Enables or disables the ASCII fast path for decoding.
+    #[inline(always)]
+    pub fn set_ascii_mode(&mut self, enabled: bool) {
+        self.decode_config.ascii_mode = enabled;
+        self.decode_cache.invalidate();
+    }
+
+    /// Sets whether decoded lines keep their terminators.
+    #[inline(always)]
+    pub fn set_line_endings(&mut self, mode: LineEndings) {
+        self.decode_config.line_endings = mode;
+        self.decode_cache.invalidate();
+    }
+
+    /// Sets how invalid UTF-8 in lines is decoded.
+    #[inline(always)]
+    pub fn set_utf8_mode(&mut self, mode: Utf8Mode) {
+        self.decode_config.utf8_mode = mode;
+        self.decode_cache.invalidate();
+    }
+
+    #[inline(always)]
+    pub fn decode_config(&self) -> DecodeConfig {
+        self.decode_config
+    }
+
+    /// The last line as `(line, len)`, terminator excluded: it is not in the length stats
+    /// until the next line starts. `None` when it is empty.
+    fn pending_line(&self) -> Option<(u64, u64)> {
+        let line = self.offsets.len().checked_sub(1)?;
+        let terminated = self.record_size.is_none() && self.last_chunk_ended_with_newline;
+        let len = self.total_bytes_indexed - self.offsets.get(line)? - terminated as u64;
+        (len > 0).then_some((line as u64, len))
+    }
+
+    /// Line count per log2 length bucket (see `LineLengthStats::histogram`), including
+    /// the unterminated last line.
+    pub fn length_histogram(&self) -> Vec<u64> {
+        self.line_stats.histogram(self.pending_line())
+    }
+
+    /// Up to `k` longest lines as `(line, len)`, longest first, including the
+    /// unterminated last line.
+    pub fn longest_lines(&self, k: usize) -> Vec<(u64, u64)> {
+        self.line_stats.longest(k, self.pending_line())
+    }
+
+    /// True when `needle` is longer than every indexed line, so no line can contain it.
+    /// A trailing `\n` in the needle is a line's own terminator and not counted.
+    pub fn needle_exceeds_lines(&self, needle: &[u8]) -> bool {
+        let content = needle.strip_suffix(b"\n").unwrap_or(needle);
+        let longest = self.longest_lines(1).first().map_or(0, |&(_, len)| len);
+        content.len() as u64 > longest
+    }
+
+    /// Bounds the decoded-line cache to `lines` entries; 0 disables and frees it.
+    pub fn set_decode_cache_size(&mut self, lines: usize) {
+        self.decode_cache.set_capacity(lines);
+    }
+
+    /// Decodes the window last copied into the decode scratch (`blob_len` bytes, line `i`
+    /// being file line `start_line + i`). Cached lines are not decoded again; the others
+    /// are decoded and cached.
+    pub fn decode_scratch_cached(
+        &mut self,
+        blob_len: usize,
+        line_ends: &[u32],
+        start_line: u64,
+    ) -> Vec<DecodedLine> {
+        let blob = &self.decode_scratch[..blob_len];
+        clamped_line_slices(blob, line_ends)
+            .into_iter()
+            .zip(start_line..)
+            .map(|(slice, line)| {
+                if let Some(hit) = self.decode_cache.get(line) {
+                    return hit.clone();
+                }
+                let decoded = decode_window_line(slice, self.decode_config);
+                self.decode_cache.insert(line, decoded.clone());
+                decoded
+            })
+            .collect()
+    }
+
+    /// Lines [start, end) from the decode cache, or `None` unless every one is cached.
+    pub fn cached_lines(&mut self, start: u64, end: u64) -> Option<Vec<DecodedLine>> {
+        (start..end)
+            .map(|line| self.decode_cache.get(line).cloned())
+            .collect()
+    }
+
+    #[inline(always)]
+    pub fn decode_cache_enabled(&self) -> bool {
+        self.decode_cache.is_enabled()
+    }
+
+    /// Returns `len` bytes of reusable decode scratch space, growing it only when `len`
+    /// exceeds what previous windows needed. Contents are whatever the last caller wrote.
+    #[inline(always)]
+    pub fn decode_scratch(&mut self, len: usize) -> &mut [u8] {
+        if self.decode_scratch.len() < len {
+            self.decode_scratch.resize(len, 0);
+        }
+        &mut self.decode_scratch[..len]
+    }
+
+    /// Allocated size of the decode scratch buffer.
+    #[cfg(test)]
+    #[inline(always)]
+    pub fn decode_scratch_capacity(&self) -> usize {
+        self.decode_scratch.capacity()
+    }
+
+    /// Records telemetry for the search that just ran.
+    #[inline(always)]
+    pub fn record_search(&mut self, stats: SearchStats) {
+        self.last_search = stats;
+    }
+
+    #[inline(always)]
+    pub fn last_search_stats(&self) -> SearchStats {
+        self.last_search
+    }
+
+    /// Adds the time since `started_ms` (from `profile::now_ms`) to `category`.
+    #[inline(always)]
+    pub fn record_profile(&mut self, category: Category, started_ms: f64) {
+        self.profile.record(category, started_ms);
+    }
+
+    #[inline(always)]
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    pub fn reset_profile(&mut self) {
+        self.profile.reset();
+    }
+
+    /// Stores a compiled search and returns its handle (never 0, never reused).
+    pub fn add_search(&mut self, search: CompiledSearch) -> u32 {
+        let handle = self.next_search_handle;
+        self.next_search_handle += 1;
+        self.searches.insert(handle, search);
+        handle
+    }
+
+    #[inline(always)]
+    pub fn compiled_search(&self, handle: u32) -> Option<&CompiledSearch> {
+        self.searches.get(&handle)
+    }
+
+    /// Frees a compiled search and its unseen counter. Returns false if `handle` was
+    /// unknown or already dropped.
+    pub fn drop_search(&mut self, handle: u32) -> bool {
+        self.watched.remove(&handle);
+        self.searches.remove(&handle).is_some()
+    }
+
+    /// Marks matches up to and including `line` as seen for `id`: `STREAM_FILTER_ID` for
+    /// the stream filter, otherwise a compiled search handle. The first call for a search
+    /// starts testing it against lines indexed from then on. The baseline only moves
+    /// forward. Returns false for an unknown handle or when no stream filter is set.
+    pub fn mark_seen(&mut self, id: u32, line: u64) -> bool {
+        if id == STREAM_FILTER_ID {
+            if self.stream_filter.is_none() {
+                return false;
+            }
+            self.filter_first_unseen = self.filter_first_unseen.max(line + 1);
+            return true;
+        }
+        if !self.searches.contains_key(&id) {
+            return false;
+        }
+        let mid_line = !self.last_chunk_ended_with_newline;
+        let current = (self.offsets.len() as u64).saturating_sub(mid_line as u64);
+        self.watched
+            .entry(id)
+            .or_insert_with(|| UnseenMatches::new(current, mid_line, 0))
+            .mark_seen(line);
+        true
+    }
+
+    /// Matches after the `mark_seen` baseline of `id` (0 for a search never marked), or
+    /// `None` for an unknown handle or a missing stream filter. The final unterminated
+    /// line counts once the stream is marked complete.
+    pub fn unseen_count(&self, id: u32) -> Option<usize> {
+        if id == STREAM_FILTER_ID {
+            let matches = self.stream_filter_results()?;
+            let seen = matches.partition_point(|&l| l < self.filter_first_unseen);
+            return Some(matches.len() - seen);
+        }
+        let search = self.searches.get(&id)?;
+        Some(
+            self.watched
+                .get(&id)
+                .map_or(0, |unseen| unseen.count(search, self.complete)),
+        )
+    }
+
+    /// Installs (or with `None`, removes) the ingest-time line filter. Lines indexed from
+    /// now on are tested as their bytes arrive; a line already in progress is skipped
+    /// because its start has been discarded.
+    pub fn set_stream_filter(&mut self, regex: Option<regex::bytes::Regex>) {
+        let mid_line = !self.last_chunk_ended_with_newline;
+        let current = (self.offsets.len() as u64).saturating_sub(mid_line as u64);
+        self.stream_filter = regex.map(|regex| StreamFilter::new(regex, current, mid_line));
+        self.filter_first_unseen = 0;
+    }
+
+    /// Starts building the case-insensitive shadow index (see `CaseIndex`) for lines
+    /// indexed from now on. No-op if already enabled.
+    pub fn enable_ci_index(&mut self) {
+        if self.ci_index.is_none() {
+            let mid_line = !self.last_chunk_ended_with_newline;
+            let current = (self.offsets.len() as u64).saturating_sub(mid_line as u64);
+            self.ci_index = Some(CaseIndex::new(current, mid_line));
+        }
+    }
+
+    #[inline(always)]
+    pub fn ci_index(&self) -> Option<&CaseIndex> {
+        self.ci_index.as_ref()
+    }
+
+    /// Starts (or restarts, dropping earlier counts) the most-frequent-lines sketch with
+    /// `capacity` counters, hashing each line from byte `skip_bytes` on.
+    pub fn enable_top_lines(&mut self, capacity: usize, skip_bytes: usize) {
+        let mid_line = !self.last_chunk_ended_with_newline;
+        let current = (self.offsets.len() as u64).saturating_sub(mid_line as u64);
+        self.top_lines = Some(TopLines::new(capacity, skip_bytes, current, mid_line));
+    }
+
+    /// The `k` most frequent lines so far, or `None` if the sketch is not enabled.
+    pub fn top_lines(&self, k: usize) -> Option<Vec<HeavyHitter>> {
+        self.top_lines.as_ref().map(|t| t.top(k))
+    }
+
+    /// Starts (or restarts, dropping earlier previews) keeping the first `max_bytes` of
+    /// every line indexed from now on.
+    pub fn enable_line_previews(&mut self, max_bytes: usize) {
+        let mid_line = !self.last_chunk_ended_with_newline;
+        let current = (self.offsets.len() as u64).saturating_sub(mid_line as u64);
+        self.previews = Some(LinePreviews::new(max_bytes, current, mid_line));
+    }
+
+    /// Previews of lines [start, end) decoded with the current settings, skipping lines
+    /// that have none (indexed before previews were enabled). `None` if not enabled.
+    pub fn line_previews(&self, start: usize, end: usize) -> Option<Vec<LinePreview>> {
+        let previews = self.previews.as_ref()?;
+        let end = end.min(self.line_count());
+        let previews = previews.previews(start as u64, end as u64).into_iter();
+        Some(
+            previews
+                .map(|(line, bytes, truncated)| LinePreview {
+                    line,
+                    text: if truncated {
+                        decode_line_prefix(bytes, self.decode_config)
+                    } else {
+                        decode_line_slice(bytes, self.decode_config)
+                    },
+                    truncated,
+                })
+                .collect(),
+        )
+    }
+
+    /// Registers a numeric extractor for lines indexed from now on (a line already in
+    /// progress is skipped) and returns its id.
+    pub fn add_numeric_extractor(&mut self, regex: regex::bytes::Regex, group: usize) -> u32 {
+        let mid_line = !self.last_chunk_ended_with_newline;
+        self.numeric_extractors
+            .push(NumericExtractor::new(regex, group, mid_line));
+        (self.numeric_extractors.len() - 1) as u32
+    }
+
+    /// Figures of extractor `id` so far, or `None` for an unknown id. The final
+    /// unterminated line is included once the stream is marked complete.
+    pub fn numeric_summary(&self, id: u32) -> Option<NumericSummary> {
+        self.numeric_extractors
+            .get(id as usize)
+            .map(|e| e.summary(self.complete))
+    }
+
+    /// Adds literal `patterns` to the ingest-time line counters (rebuilding the automaton
+    /// over all of them) and returns their ids. Only meaningful before the first chunk.
+    pub fn add_counters(
+        &mut self,
+        patterns: Vec<Vec<u8>>,
+    ) -> Result<Vec<u32>, aho_corasick::BuildError> {
+        let first = self.counter_patterns.len() as u32;
+        let mut all = self.counter_patterns.clone();
+        all.extend(patterns);
+        self.counters = Some(PatternCounters::new(&all)?);
+        self.counter_patterns = all;
+        Ok((first..self.counter_patterns.len() as u32).collect())
+    }
+
+    /// Lines containing counter pattern `id` so far, or `None` for an unknown id.
+    pub fn counter(&self, id: u32) -> Option<u64> {
+        self.counters.as_ref()?.count(id as usize)
+    }
+
+    /// Line counts of every counter pattern, in id order.
+    pub fn all_counters(&self) -> &[u64] {
+        self.counters.as_ref().map_or(&[], |c| c.counts())
+    }
+
+    /// Format of the file judged from its first lines: the format most sampled lines
+    /// have, the share of the sample it has, and every format's share. `"unknown"` until
+    /// a non-blank line has been indexed.
+    pub fn detected_format(&self) -> DetectedFormat {
+        let counts = self.format_detector.counts(self.complete);
+        let sampled: u32 = counts.iter().sum();
+        let share = |i: usize| {
+            if sampled == 0 {
+                0.0
+            } else {
+                counts[i] as f64 / sampled as f64
+            }
+        };
+        // On a tie the more specific format (earlier in `ALL`) wins.
+        let best = (0..counts.len())
+            .rev()
+            .max_by_key(|&i| counts[i])
+            .filter(|_| sampled > 0);
+        DetectedFormat {
+            format: best
+                .map_or("unknown", |i| LogFormat::ALL[i].name())
+                .to_string(),
+            confidence: best.map_or(0.0, share),
+            sampled_lines: sampled,
+            scores: FormatScores {
+                json: share(0),
+                access_log: share(1),
+                syslog: share(2),
+                logfmt: share(3),
+                plain: share(4),
+            },
+        }
+    }
+
+    /// The ingest-time features in the order they observe each chunk: format detector,
+    /// pattern counters, stream filter, case index, top-lines sketch, line previews and
+    /// numeric extractors (those enabled), then the observers added with `add_observer`.
+    fn observers(&mut self) -> Vec<&mut dyn ChunkObserver> {
+        let mut observers: Vec<&mut dyn ChunkObserver> = vec![&mut self.format_detector];
+        if let Some(counters) = &mut self.counters {
+            observers.push(counters);
+        }
+        if let Some(filter) = &mut self.stream_filter {
+            observers.push(filter);
+        }
+        if let Some(index) = &mut self.ci_index {
+            observers.push(index);
+        }
+        if let Some(top) = &mut self.top_lines {
+            observers.push(top);
+        }
+        if let Some(previews) = &mut self.previews {
+            observers.push(previews);
+        }
+        for extractor in &mut self.numeric_extractors {
+            observers.push(extractor);
+        }
+        for observer in &mut self.extra_observers {
+            observers.push(observer.as_mut());
+        }
+        observers
+    }
+
+    /// Adds an observer fed every chunk indexed from now on. Reset by `clear_data()`,
+    /// dropped by `clear()`.
+    #[cfg(test)]
+    pub fn add_observer(&mut self, observer: Box<dyn ChunkObserver + Send + Sync>) {
+        self.extra_observers.push(observer);
+    }
+
+    /// `observe_chunk` for the last `len` bytes of the buffer (the chunk just indexed,
+    /// from file offset `base_offset`). Must run before the chunk is discarded.
+    pub fn observe_appended(&mut self, len: usize, base_offset: u64, new_line_starts: &[u64]) {
+        // Moved out (not copied) so the observers can borrow the engine meanwhile.
+        let buffer = std::mem::take(&mut self.buffer);
+        self.observe_chunk(&buffer[buffer.len() - len..], base_offset, new_line_starts);
+        self.buffer = buffer;
+    }
+
+    /// Hands an indexed chunk and the line starts scanned in it to every observer and to
+    /// the unseen-match trackers (see `ChunkObserver::observe`). They work on
+    /// newline-delimited lines, so fixed-size records skip them, as do empty chunks.
+    pub fn observe_chunk(&mut self, chunk: &[u8], base_offset: u64, new_line_starts: &[u64]) {
+        if self.record_size.is_some() || chunk.is_empty() {
+            return;
+        }
+        for observer in self.observers() {
+            observer.observe(chunk, base_offset, new_line_starts);
+        }
+        for (handle, unseen) in &mut self.watched {
+            unseen.feed(chunk, &self.searches[handle]);
+        }
+    }
+
+    /// Scores the last `len` bytes of the buffer (the chunk just indexed) for the
+    /// printability profile. Byte-based, so fixed-size records are scored too.
+    pub fn score_appended(&mut self, len: usize) {
+        let chunk = &self.buffer[self.buffer.len() - len..];
+        self.printability.feed(chunk);
+    }
+
+    /// Scores bytes indexed from outside the buffer (see `index_shared_region`).
+    pub fn score_bytes(&mut self, bytes: &[u8]) {
+        self.printability.feed(bytes);
+    }
+
+    /// Share of text bytes (printable ASCII, whitespace, complete UTF-8 sequences) in each
+    /// of `buckets` equal byte spans of the indexed data. Empty before any data.
+    pub fn printability_profile(&self, buckets: usize) -> Vec<f64> {
+        self.printability.profile(buckets)
+    }
+
+    /// Lines matched by the stream filter so far, or `None` if no filter is set. The
+    /// final unterminated line is included once the stream is marked complete.
+    pub fn stream_filter_results(&self) -> Option<Vec<u64>> {
+        self.stream_filter
+            .as_ref()
+            .map(|f| f.results(self.complete))
+    }
+
+    /// Makes this session a live stream (no-op if it already is). Fails once file data
+    /// has been indexed.
+    pub fn begin_live(&mut self) -> Result<(), MixedIngest> {
+        if self.live.is_none() {
+            if self.total_bytes_indexed > 0 {
+                return Err(MixedIngest {
+                    live_rejected: true,
+                });
+            }
+            self.live = Some(LiveWindow::new(self.live_retained_bytes));
+        }
+        Ok(())
+    }
+
+    /// Fails if this session is a live stream, so file chunks are not mixed into it.
+    #[inline(always)]
+    pub fn check_file_ingest(&self) -> Result<(), MixedIngest> {
+        match self.live {
+            Some(_) => Err(MixedIngest {
+                live_rejected: false,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Keeps just-indexed live bytes for display, records that `new_lines` lines started
+    /// at `now_ms`, and evicts the lines the retention policy no longer allows. No-op
+    /// outside a live session.
+    pub fn retain_live(&mut self, bytes: &[u8], new_lines: usize, now_ms: f64) {
+        let Some(live) = &mut self.live else {
+            return;
+        };
+        live.push(bytes);
+        if self.retention.max_age_ms > 0.0 {
+            live.push_arrivals(new_lines, now_ms);
+        }
+        let retained = self.offsets.len() - self.offsets.first_line();
+        let evict = self.retention.evictions(retained, live.arrivals(), now_ms);
+        if evict > 0 {
+            self.evict_lines(evict);
+        }
+    }
+
+    /// Drops the oldest `count` retained lines from the index and from every per-line
+    /// structure. Line numbers stay absolute; aggregates (histogram, counters, top lines,
+    /// numeric stats) keep counting the evicted lines.
+    fn evict_lines(&mut self, count: usize) {
+        self.offsets.evict_front(count);
+        let first = self.offsets.first_line() as u64;
+        let first_kept = self
+            .offsets
+            .get(first as usize)
+            .unwrap_or(self.total_bytes_indexed);
+        log::debug!("retention evicted {count} lines, first retained line is {first}");
+        // A block whose first line was evicted loses its entry; lookups there start from
+        // the first retained line instead.
+        let dropped = self
+            .block_offsets
+            .partition_point(|&(_, line)| line < first);
+        self.block_offsets.drain(..dropped);
+        self.line_stats.evict_before(first);
+        if let Some(filter) = &mut self.stream_filter {
+            filter.evict_before(first);
+        }
+        for unseen in self.watched.values_mut() {
+            unseen.evict_before(first);
+        }
+        if let Some(index) = &mut self.ci_index {
+            index.evict_before(first);
+        }
+        if let Some(previews) = &mut self.previews {
+            previews.evict_before(first);
+        }
+        if let Some(live) = &mut self.live {
+            live.evict_lines(count, first_kept);
+        }
+    }
+
+    /// Sets the line caps for live sessions, applied after each `append_live`. Lines
+    /// already retained when an age cap is first set count as arriving now. Kept by
+    /// `clear_data`.
+    pub fn set_retention(&mut self, policy: RetentionPolicy) {
+        self.retention = policy;
+        if let Some(live) = &mut self.live {
+            if policy.max_age_ms > 0.0 {
+                let retained = self.offsets.len() - self.offsets.first_line();
+                live.backfill_arrivals(retained, now_ms());
+            } else {
+                live.clear_arrivals();
+            }
+        }
+    }
+
+    /// Lines evicted by retention since the session began (also the first retained line).
+    #[inline(always)]
+    pub fn retention_evictions(&self) -> u64 {
+        self.offsets.first_line() as u64
+    }
+
+    /// Sets how many recent live bytes are kept (at least this many, at most twice).
+    /// Takes effect from the next append; kept by `clear_data`.
+    pub fn set_live_retained_bytes(&mut self, bytes: usize) {
+        self.live_retained_bytes = bytes;
+        if let Some(live) = &mut self.live {
+            live.set_target(bytes);
+        }
+    }
+
+    /// Bytes of lines [start, end) of a live stream, or `None` outside a live session or
+    /// when part of the range has been evicted.
+    pub fn read_live(&self, start: usize, end: usize) -> Option<&[u8]> {
+        let live = self.live.as_ref()?;
+        if start < end.min(self.offsets.first_line()) {
+            return None;
+        }
+        let boundaries = self.get_line_boundaries(start, end);
+        match (boundaries.first(), boundaries.last()) {
+            (Some(&from), Some(&to)) => live.slice(from, to),
+            _ => Some(&[]),
+        }
+    }
+
+    /// The bytes still held for search and the file offset of the first: the live window in
+    /// a live session, otherwise the buffer (empty once chunks are discarded).
+    pub fn retained_window(&self) -> (&[u8], u64) {
+        match &self.live {
+            Some(live) => (live.bytes(), live.start()),
+            None => (&self.buffer, 0),
+        }
+    }
+
+    /// First line whose bytes are all retained in the live window, or `None` outside a
+    /// live session.
+    pub fn live_first_line(&self) -> Option<usize> {
+        let live = self.live.as_ref()?;
+        Some(self.offsets.partition_point(|s| s < live.start()))
+    }
+
+    /// Starts exporting `lines` (any order; lines past the last are dropped) and returns a
+    /// token for `export_next_ranges` (never 0, never reused). The export covers the data
+    /// indexed so far.
+    pub fn begin_export(&mut self, lines: Vec<u64>) -> u32 {
+        let export = LineExport::new(lines, &self.offsets, self.total_bytes_indexed);
+        let token = self.next_export_token;
+        self.next_export_token += 1;
+        self.exports.insert(token, export);
+        token
+    }
+
+    /// Next byte ranges of export `token` (see `LineExport::next_ranges`), or `None` for
+    /// an unknown token.
+    pub fn export_next_ranges(&mut self, token: u32, max_bytes: u64) -> Option<Vec<(u64, u64)>> {
+        let export = self.exports.get_mut(&token)?;
+        Some(export.next_ranges(&self.offsets, max_bytes))
+    }
+
+    pub fn export_stats(&self, token: u32) -> Option<ExportStats> {
+        let export = self.exports.get(&token)?;
+        let (total_lines, total_bytes, emitted_lines, emitted_bytes) = export.progress();
+        Some(ExportStats {
+            total_lines,
+            total_bytes,
+            emitted_lines,
+            emitted_bytes,
+            done: export.is_done(),
+        })
+    }
+
+    /// Frees an export. Returns false if `token` was unknown or already ended.
+    pub fn end_export(&mut self, token: u32) -> bool {
+        self.exports.remove(&token).is_some()
+    }
+
+    /// Flags async operation `op_id` for cancellation; it stops at its next batch boundary.
+    #[inline(always)]
+    pub fn request_cancel(&mut self, op_id: u32) {
+        self.cancelled_ops.insert(op_id);
+    }
+
+    /// Returns whether `op_id` was cancelled, consuming the flag so the id can be reused.
+    #[inline(always)]
+    pub fn take_cancel(&mut self, op_id: u32) -> bool {
+        self.cancelled_ops.remove(&op_id)
+    }
+
     /// Number of lines (number of line-start offsets).
     #[inline(always)]
     pub fn line_count(&self) -> usize {
         self.offsets.len()
     }
 
-    /// Immutable view of line offsets for slicing and search.
+    /// Immutable view of line offsets for slicing and search. Values read back as `u64`
+    /// regardless of the compact storage.
     #[inline(always)]
-    pub fn offsets(&self) -> &[u64] {
+    pub fn offsets(&self) -> &LineOffsets {
         &self.offsets
     }
 
-    /// (start, end) byte ranges for lines in [start, end). get_lines uses this to slice
+    /// Pre-reserves the line index for about `lines` lines in total, e.g. a count
+    /// extrapolated from a sample of the file. Returns false if the allocation failed.
+    pub fn reserve_lines(&mut self, lines: usize) -> bool {
+        self.offsets.reserve_lines(lines)
+    }
+
+    /// Records the total size of the file being streamed, for `index_progress`.
+    #[inline(always)]
+    pub fn set_expected_size(&mut self, bytes: u64) {
+        self.expected_size = Some(bytes);
+    }
+
+    /// Fraction of the expected size indexed so far, in [0, 1]. A file that turns out
+    /// larger than expected reports 1.0 until done. 0.0 when no (or a zero) size was set,
+    /// unless the engine is complete.
+    pub fn index_progress(&self) -> f64 {
+        if self.complete {
+            return 1.0;
+        }
+        match self.expected_size {
+            Some(expected) if expected > 0 => {
+                (self.total_bytes_indexed as f64 / expected as f64).min(1.0)
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Snapshot of the chunk-boundary state, for diagnostics.
+    pub fn boundary_state(&self) -> BoundaryState {
+        BoundaryState {
+            total_bytes: self.total_bytes_indexed,
+            last_chunk_ended_with_newline: self.last_chunk_ended_with_newline,
+            line_count: self.offsets.len() as u64,
+            buffer_len: self.buffer.len(),
+            buffer_capacity: self.buffer.capacity(),
+        }
+    }
+
+    /// Stops file ingestion until `resume_ingest`: chunks are rejected by
+    /// `check_not_paused`. Boundary state is untouched. Returns false if already paused.
+    pub fn pause_ingest(&mut self) -> bool {
+        self.ingest_clock.pause(now_ms())
+    }
+
+    /// Returns false if ingestion was not paused.
+    pub fn resume_ingest(&mut self) -> bool {
+        self.ingest_clock.resume(now_ms())
+    }
+
+    #[inline(always)]
+    pub fn check_not_paused(&self) -> Result<(), IngestPaused> {
+        if self.ingest_clock.is_paused() {
+            return Err(IngestPaused);
+        }
+        Ok(())
+    }
+
+    /// Lag behind the expected size and the remaining time at the throughput measured
+    /// so far (bytes over active ingest time up to `now_ms`).
+    pub fn ingest_stats(&self, now_ms: f64) -> IngestStats {
+        let active_ms = self.ingest_clock.active_ms(now_ms);
+        let bytes_per_ms = if active_ms > 0.0 {
+            self.total_bytes_indexed as f64 / active_ms
+        } else {
+            0.0
+        };
+        let lag = self
+            .expected_size
+            .map(|expected| expected.saturating_sub(self.total_bytes_indexed));
+        let eta_ms = match lag {
+            _ if self.complete => Some(0.0),
+            Some(0) => Some(0.0),
+            Some(lag) if bytes_per_ms > 0.0 => Some(lag as f64 / bytes_per_ms),
+            _ => None,
+        };
+        IngestStats {
+            indexed_bytes: self.total_bytes_indexed,
+            expected_bytes: self.expected_size.map(|n| n as f64),
+            lag_bytes: lag.map(|n| n as f64),
+            active_ms,
+            bytes_per_ms,
+            eta_ms,
+            paused: self.ingest_clock.is_paused(),
+        }
+    }
+
+    /// Line whose byte range contains file offset `offset`. Offsets at or past
+    /// `total_bytes_indexed` clamp to the last line, offsets of evicted lines to the first
+    /// retained one. `None` when no lines are indexed.
+    #[inline(always)]
+    pub fn line_at_offset(&self, offset: u64) -> Option<usize> {
+        // The answer lies between the last block entry at or before `offset` and the
+        // next entry's line, so only that block's offsets are searched.
+        let block = self
+            .block_offsets
+            .partition_point(|&(start, _)| start <= offset);
+        let first = self.offsets.first_line();
+        let lo = block
+            .checked_sub(1)
+            .map_or(first, |b| self.block_offsets[b].1 as usize);
+        let hi = self
+            .block_offsets
+            .get(block)
+            .map_or(self.offsets.len(), |&(_, line)| line as usize);
+        let line = self
+            .offsets
+            .partition_point_in(lo, hi, |s| s <= offset)
+            .checked_sub(1)?;
+        Some(line.max(first))
+    }
+
+    /// Line and column of file offset `offset`, clamped to `total_bytes_indexed`. The
+    /// column is in bytes; with `chars` it counts characters as the current decode mode
+    /// renders them, when the bytes from the line start are resident (otherwise bytes,
+    /// and `chars` is false in the result). `None` when no lines are indexed.
+    pub fn position_at_byte(&self, offset: u64, chars: bool) -> Option<BytePosition> {
+        let offset = offset.min(self.total_bytes_indexed);
+        let line = self.line_at_offset(offset)?;
+        let line_start = self.offsets.get(line)?;
+        let mut position = BytePosition {
+            line: line as u64,
+            column: offset - line_start,
+            chars: false,
+        };
+        if chars && offset as usize <= self.buffer.len() {
+            let prefix = &self.buffer[line_start as usize..offset as usize];
+            if let Some(text) = decode_line(prefix, self.decode_config) {
+                position.column = text.chars().count() as u64;
+                position.chars = true;
+            }
+        }
+        Some(position)
+    }
+
+    /// File offset where line `line` starts, or `None` past the last line.
+    #[inline(always)]
+    pub fn offset_of_line(&self, line: usize) -> Option<u64> {
+        self.offsets.get(line)
+    }
+
+    /// Number of line starts in each of `buckets` equal byte spans of
+    /// `[0, total_bytes_indexed)`, for an activity minimap. The counts sum to `line_count`.
+    /// One binary search per bucket boundary; the offsets are not walked.
+    pub fn line_density(&self, buckets: usize) -> Vec<u32> {
+        if buckets == 0 {
+            return Vec::new();
+        }
+        let total = self.total_bytes_indexed as u128;
+        let buckets_u128 = buckets as u128;
+        // Bucket b starts at the first offset x with x * buckets / total >= b.
+        let first_line_at = |b: usize| -> usize {
+            if b == 0 {
+                return 0;
+            }
+            let start = (b as u128 * total).div_ceil(buckets_u128) as u64;
+            self.offsets.partition_point(|o| o < start)
+        };
+        let mut counts = Vec::with_capacity(buckets);
+        let mut from = 0;
+        for b in 0..buckets {
+            let to = if b + 1 == buckets {
+                self.offsets.len()
+            } else {
+                first_line_at(b + 1)
+            };
+            counts.push((to - from) as u32);
+            from = to;
+        }
+        counts
+    }
+
+    /// (start, end) byte ranges for lines [start, end). get_lines uses this to slice
     /// the buffer; valid once the full file has been streamed (buffer accumulates chunks).
     pub fn get_line_ranges(&self, start: usize, end: usize) -> Vec<(u64, u64)> {
+        let boundaries = self.get_line_boundaries(start, end);
+        boundaries
+            .windows(2)
+            .map(|pair| (pair[0], pair[1]))
+            .collect()
+    }
+
+    /// `get_line_ranges` without clamping: errors unless [start, end) are existing lines.
+    pub fn get_line_ranges_checked(
+        &self,
+        start: usize,
+        end: usize,
+    ) -> Result<Vec<(u64, u64)>, RangeError> {
+        check_line_range(start, end, self.offsets.first_line(), self.line_count())?;
+        Ok(self.get_line_ranges(start, end))
+    }
+
+    /// Same ranges as `get_line_ranges`, interleaved as [start0, end0, start1, end1, ...]
+    /// so the FFI layer can hand them to JS as a single `Float64Array`.
+    pub fn get_line_ranges_flat(
+        &self,
+        start: usize,
+        end: usize,
+    ) -> Result<Vec<f64>, PrecisionLoss> {
+        let boundaries = self.get_line_boundaries(start, end);
+        let mut flat = Vec::with_capacity(boundaries.len().saturating_sub(1) * 2);
+        for pair in boundaries.windows(2) {
+            flat.push(to_f64(pair[0])?);
+            flat.push(to_f64(pair[1])?);
+        }
+        Ok(flat)
+    }
+
+    /// Same ranges as `get_line_ranges`, interleaved as [start0, end0, start1, end1, ...]
+    /// without the f64 conversion, for the 64-bit (`BigUint64Array`) FFI variant.
+    pub fn get_line_ranges_flat_u64(&self, start: usize, end: usize) -> Vec<u64> {
+        let boundaries = self.get_line_boundaries(start, end);
+        let mut flat = Vec::with_capacity(boundaries.len().saturating_sub(1) * 2);
+        for pair in boundaries.windows(2) {
+            flat.extend_from_slice(pair);
+        }
+        flat
+    }
+
+    /// Start offsets of up to `count` lines from `start`, straight from the index (no end
+    /// offsets). Lines past the last are dropped and evicted lines skipped.
+    pub fn offsets_batch(&self, start: usize, count: usize) -> Vec<u64> {
+        let end = start.saturating_add(count).min(self.offsets.len());
+        self.offsets.range(start.min(end), end).collect()
+    }
+
+    /// Every `step`-th line, `0, step, 2 * step, ...` below `line_count`, for a sampled
+    /// preview; lines evicted by retention are left out. Empty for a step of 0.
+    pub fn sample_line_indices(&self, step: usize) -> Vec<u32> {
+        if step == 0 {
+            return Vec::new();
+        }
+        let first = self.offsets.first_line().div_ceil(step) * step;
+        (first..self.offsets.len())
+            .step_by(step)
+            .map(|line| line as u32)
+            .collect()
+    }
+
+    /// Boundaries for lines in [start, end): `n + 1` offsets where line `start + k` runs
+    /// from `boundaries[k]` to `boundaries[k + 1]`. Consecutive lines share a boundary, so
+    /// this is half the payload of the range pairs. Empty when the range is empty. Lines
+    /// evicted by retention are skipped, so the boundaries start at the first retained one.
+    pub fn get_line_boundaries(&self, start: usize, end: usize) -> Vec<u64> {
         let offsets = self.offsets();
         let end = end.min(offsets.len());
-        let start = start.min(end);
+        let start = start.max(offsets.first_line()).min(end);
         if start >= end {
             return Vec::new();
         }
-        let mut ranges = Vec::with_capacity(end - start);
-        for i in start..end {
-            let line_start = offsets[i];
-            let line_end = offsets.get(i + 1).copied().unwrap_or(self.total_bytes_indexed);
-            ranges.push((line_start, line_end));
+        let mut boundaries = Vec::with_capacity(end - start + 1);
+        boundaries.extend(offsets.range(start, end));
+        boundaries.push(offsets.get(end).unwrap_or(self.total_bytes_indexed));
+        boundaries
+    }
+
+    /// Byte length of each line in [start, end), clamped like `get_line_boundaries`: the
+    /// distance to the next line start (terminator included), or to the end of the indexed
+    /// data for the last line. Saturates at `u32::MAX` for lines of 4 GiB or more.
+    pub fn line_lengths(&self, start: usize, end: usize) -> Vec<u32> {
+        let boundaries = self.get_line_boundaries(start, end);
+        boundaries
+            .windows(2)
+            .map(|pair| u32::try_from(pair[1] - pair[0]).unwrap_or(u32::MAX))
+            .collect()
+    }
+
+    /// Byte range [start, end) of line `line`, or `None` past the last line.
+    #[inline(always)]
+    pub fn line_range(&self, line: usize) -> Option<(u64, u64)> {
+        let start = self.offsets.get(line)?;
+        let end = self
+            .offsets
+            .get(line + 1)
+            .unwrap_or(self.total_bytes_indexed);
+        Some((start, end))
+    }
+
+    /// Ranges for an arbitrary list of lines, interleaved [start0, end0, start1, ...] in the
+    /// order given. Indices past the last line are skipped.
+    pub fn get_ranges_for_lines(&self, lines: &[u32]) -> Result<Vec<f64>, PrecisionLoss> {
+        let mut flat = Vec::with_capacity(lines.len() * 2);
+        for range in lines.iter().filter_map(|&i| self.line_range(i as usize)) {
+            flat.push(to_f64(range.0)?);
+            flat.push(to_f64(range.1)?);
+        }
+        Ok(flat)
+    }
+
+    /// Plans a window fetch for lines [start, end) (clamped like `get_line_ranges`). An empty
+    /// range yields an empty plan at offset 0.
+    pub fn plan_window(&self, start: usize, end: usize) -> WindowPlan {
+        let start = start.max(self.offsets.first_line());
+        let boundaries = self.get_line_boundaries(start, end);
+        let file_start = boundaries.first().copied().unwrap_or(0);
+        let file_end = boundaries.last().copied().unwrap_or(0);
+        WindowPlan {
+            start_line: start as u64,
+            file_start,
+            file_end,
+            line_ends: boundaries.iter().skip(1).map(|&b| b - file_start).collect(),
+        }
+    }
+
+    /// Bytes currently allocated by the engine's growable storage (offsets, chunk buffer,
+    /// decode scratch, optional ingest indexes). Capacity-based, so it includes reallocation
+    /// slack.
+    pub fn heap_bytes(&self) -> usize {
+        self.offsets_bytes()
+            + self.buffer.capacity()
+            + self.decode_scratch.capacity()
+            + self.ci_index_bytes()
+            + self.top_lines_bytes()
+            + self.previews_bytes()
+            + self.numeric_extractor_bytes()
+            + self.counters_bytes()
+            + self.printability.heap_bytes()
+            + self.live_bytes()
+            + self.match_set_bytes()
+    }
+
+    /// Stream filter matches and unseen-match lists, with their partial lines.
+    fn match_set_bytes(&self) -> usize {
+        let filter = self.stream_filter.as_ref();
+        let unseen: usize = self.watched.values().map(UnseenMatches::heap_bytes).sum();
+        filter.map_or(0, StreamFilter::heap_bytes) + unseen
+    }
+
+    fn live_bytes(&self) -> usize {
+        self.live.as_ref().map_or(0, LiveWindow::heap_bytes)
+    }
+
+    /// Fine and coarse line index.
+    fn offsets_bytes(&self) -> usize {
+        self.offsets.heap_bytes()
+            + self.block_offsets.capacity() * std::mem::size_of::<(u64, u64)>()
+    }
+
+    fn ci_index_bytes(&self) -> usize {
+        self.ci_index.as_ref().map_or(0, CaseIndex::heap_bytes)
+    }
+
+    fn top_lines_bytes(&self) -> usize {
+        self.top_lines.as_ref().map_or(0, TopLines::heap_bytes)
+    }
+
+    fn previews_bytes(&self) -> usize {
+        self.previews.as_ref().map_or(0, LinePreviews::heap_bytes)
+    }
+
+    fn counters_bytes(&self) -> usize {
+        self.counters
+            .as_ref()
+            .map_or(0, PatternCounters::heap_bytes)
+    }
+
+    fn numeric_extractor_bytes(&self) -> usize {
+        self.numeric_extractors
+            .iter()
+            .map(NumericExtractor::heap_bytes)
+            .sum()
+    }
+
+    /// Snapshot of the engine's allocations for diagnostics.
+    pub fn memory_stats(&self) -> MemoryStats {
+        MemoryStats {
+            line_count: self.line_count() as u64,
+            offsets_bytes: self.offsets_bytes() as u64,
+            offsets_wide: self.offsets.is_wide(),
+            buffer_capacity: self.buffer.capacity() as u64,
+            buffer_high_water: self.buffer_high_water as u64,
+            decode_scratch_bytes: self.decode_scratch.capacity() as u64,
+            ci_index_bytes: self.ci_index_bytes() as u64,
+            top_lines_bytes: self.top_lines_bytes() as u64,
+            previews_bytes: self.previews_bytes() as u64,
+            numeric_extractor_bytes: self.numeric_extractor_bytes() as u64,
+            counters_bytes: self.counters_bytes() as u64,
+            printability_bytes: self.printability.heap_bytes() as u64,
+            live_bytes: self.live_bytes() as u64,
+            match_set_bytes: self.match_set_bytes() as u64,
+            total_bytes: self.heap_bytes() as u64,
+            compact_before_bytes: self.last_compact.0 as u64,
+            compact_after_bytes: self.last_compact.1 as u64,
+            search_handles: self.searches.len() as u32,
+            decode_cache_lines: self.decode_cache.len() as u32,
+        }
+    }
+
+    /// Releases spare capacity without touching indexed data: shrinks offsets, match sets
+    /// and the ingest-time features' line lists to fit, and frees the idle chunk buffer and
+    /// decode scratch (they regrow on demand). Capacity left over from a bigger file after
+    /// `clear_data` goes too. Returns the estimated number of bytes freed. A lighter
+    /// alternative to `clear` for long sessions.
+    pub fn compact(&mut self) -> usize {
+        let before = self.heap_bytes();
+        self.offsets.shrink_to_fit();
+        self.block_offsets.shrink_to_fit();
+        self.buffer.shrink_to_fit();
+        self.decode_scratch = Vec::new();
+        for observer in self.observers() {
+            observer.shrink_to_fit();
         }
-        ranges
+        for unseen in self.watched.values_mut() {
+            unseen.shrink_to_fit();
+        }
+        self.printability.shrink_to_fit();
+        let after = self.heap_bytes();
+        self.last_compact = (before, after);
+        let freed = before.saturating_sub(after);
+        log::debug!("compact freed {freed} bytes");
+        freed
     }
 
-    /// Clears the index and buffer, and resets streaming state. Call between file
-    /// sessions to avoid memory leaks.
+    /// Clears the index and buffer, and resets streaming state and configuration. Call
+    /// between file sessions to avoid memory leaks.
     pub fn clear(&mut self) {
+        self.clear_data();
+        self.buffer = Vec::new();
+        self.chunk_buffer_cap = DEFAULT_CHUNK_BUFFER_CAP;
+        self.buffer_high_water = 0;
+        self.decode_config = DecodeConfig::default();
+        self.decode_scratch = Vec::new();
+        self.decode_cache = DecodeCache::default();
+        self.searches.clear();
+        self.watched.clear();
+        self.record_size = None;
+        self.nul_runs = None;
+        self.header_skip = 0;
+        self.stream_filter = None;
+        self.ci_index = None;
+        self.top_lines = None;
+        self.previews = None;
+        self.numeric_extractors.clear();
+        self.counter_patterns.clear();
+        self.counters = None;
+        self.extra_observers.clear();
+        self.live_retained_bytes = DEFAULT_LIVE_RETAINED_BYTES;
+        self.retention = RetentionPolicy::default();
+    }
+
+    /// Like `clear`, but keeps configuration (decode settings and cache size, chunk buffer
+    /// capacity and cap, record size, the NUL-run threshold, header length, live window size
+    /// and retention caps, compiled searches, the stream filter regex, whether the case
+    /// index, top-lines sketch and line previews are enabled, numeric extractor and counter
+    /// patterns), for switching between files of the same kind.
+    pub fn clear_data(&mut self) {
         self.buffer.clear();
+        self.grant = None;
         self.offsets.clear();
+        self.block_offsets = Vec::new();
+        self.line_stats.reset();
         self.total_bytes_indexed = 0;
         self.last_chunk_ended_with_newline = true;
+        self.complete = false;
+        self.expected_size = None;
+        self.ingest_clock = IngestClock::default();
+        self.last_search = SearchStats::default();
+        self.cancelled_ops.clear();
+        self.exports.clear();
+        self.live = None;
+        if let Some(runs) = &mut self.nul_runs {
+            runs.reset();
+        }
+        self.decode_cache.invalidate();
+        for observer in self.observers() {
+            observer.reset();
+        }
+        self.filter_first_unseen = 0;
+        for unseen in self.watched.values_mut() {
+            unseen.reset();
+        }
+        self.printability.reset();
     }
 
     /// Returns a slice of the internal buffer for the given byte range.
@@ -148,3 +1550,543 @@ impl Default for LogEngine {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::XorShift;
+
+    fn engine_with(offsets: &[u64], total: u64) -> LogEngine {
+        let mut engine = LogEngine::new();
+        engine.append_offsets(offsets);
+        engine.advance_after_chunk(total as usize, true);
+        engine
+    }
+
+    #[test]
+    fn line_lengths_run_to_the_next_start_or_the_end() {
+        // "abc\n", "defg\n", "\n", "tail" (unterminated).
+        let engine = engine_with(&[0, 4, 9, 10], 14);
+        assert_eq!(engine.line_lengths(0, 4), [4, 5, 1, 4]);
+        assert_eq!(engine.line_lengths(3, 100), [4]);
+        assert!(engine.line_lengths(2, 2).is_empty());
+        assert!(engine.line_lengths(5, 9).is_empty());
+    }
+
+    #[test]
+    fn checked_ranges_reject_instead_of_clamping() {
+        let engine = engine_with(&[0, 4, 9], 12);
+        assert_eq!(engine.get_line_ranges_checked(1, 1), Ok(vec![]));
+        assert_eq!(
+            engine.get_line_ranges_checked(1, 3),
+            Ok(vec![(4, 9), (9, 12)])
+        );
+        assert_eq!(
+            engine.get_line_ranges_checked(0, 4),
+            Err(RangeError::PastEnd {
+                end: 4,
+                line_count: 3
+            })
+        );
+        assert_eq!(
+            engine.get_line_ranges_checked(2, 1),
+            Err(RangeError::Reversed { start: 2, end: 1 })
+        );
+        // The clamping variant stays lenient.
+        assert!(engine.get_line_ranges(1_000_000, 2_000_000).is_empty());
+        assert!(engine.get_line_ranges(2, 1).is_empty());
+    }
+
+    #[test]
+    fn sampled_lines_step_through_the_index() {
+        let starts: Vec<u64> = (0..25).map(|i| i * 10).collect();
+        let mut engine = engine_with(&starts, 250);
+        assert_eq!(engine.sample_line_indices(10), [0, 10, 20]);
+        assert_eq!(engine.sample_line_indices(1).len(), 25);
+        assert_eq!(engine.sample_line_indices(24), [0, 24]);
+        assert_eq!(engine.sample_line_indices(25), [0]);
+        assert!(engine.sample_line_indices(0).is_empty());
+        // Samples stay on multiples of the step after eviction.
+        engine.evict_lines(12);
+        assert_eq!(engine.sample_line_indices(5), [15, 20]);
+        let ranges = engine
+            .get_ranges_for_lines(&engine.sample_line_indices(10))
+            .unwrap();
+        assert_eq!(ranges, [200.0, 210.0]);
+        assert!(LogEngine::new().sample_line_indices(3).is_empty());
+    }
+
+    #[test]
+    fn offsets_batch_windows_the_raw_starts() {
+        let engine = engine_with(&[0, 7, 20, 5_000_000_000, 5_000_000_009], 5_000_000_015);
+        assert_eq!(engine.offsets_batch(0, 2), [0, 7]);
+        assert_eq!(engine.offsets_batch(2, 2), [20, 5_000_000_000]);
+        assert_eq!(engine.offsets_batch(3, 100), [5_000_000_000, 5_000_000_009]);
+        assert!(engine.offsets_batch(5, 10).is_empty());
+        assert!(engine.offsets_batch(9, 10).is_empty());
+        assert!(engine.offsets_batch(1, 0).is_empty());
+        // Batches tile the index in order.
+        let tiled: Vec<u64> = (0..5)
+            .step_by(2)
+            .flat_map(|s| engine.offsets_batch(s, 2))
+            .collect();
+        assert_eq!(tiled, engine.offsets().iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn write_region_capacity_covers_request() {
+        let mut engine = LogEngine::new();
+        for size in [0, 1, 100, 4096, 3] {
+            let region = engine.get_write_region(size);
+            let capacity = region.capacity as usize;
+            assert_ne!(region.ptr, 0);
+            assert!(capacity >= size);
+            // The whole reported capacity is accepted as a chunk.
+            assert_eq!(engine.append_chunk(capacity).len(), capacity);
+            engine.discard_buffer_after_indexing();
+        }
+    }
+
+    #[test]
+    fn decode_scratch_capacity_stabilizes() {
+        let mut engine = LogEngine::new();
+        let window = b"line one\nline two\n".repeat(64);
+        engine.decode_scratch(window.len()).copy_from_slice(&window);
+        let capacity = engine.decode_scratch_capacity();
+        for len in [window.len(), 10, window.len() / 2, window.len()] {
+            let scratch = engine.decode_scratch(len);
+            scratch.copy_from_slice(&window[..len]);
+            assert_eq!(scratch, &window[..len]);
+            assert_eq!(engine.decode_scratch_capacity(), capacity);
+        }
+        engine.clear();
+        assert_eq!(engine.decode_scratch_capacity(), 0);
+    }
+
+    #[test]
+    fn write_grants_are_exact_and_single_use() {
+        let mut engine = LogEngine::new();
+        assert_eq!(engine.take_grant(0), Err(GrantError::Missing));
+        engine.get_buffer_pointer(100);
+        assert_eq!(engine.granted_size(), 100);
+        // A smaller chunk is accepted; submitting again needs a new grant.
+        assert_eq!(engine.take_grant(60), Ok(()));
+        assert_eq!(engine.granted_size(), 0);
+        assert_eq!(engine.take_grant(60), Err(GrantError::Missing));
+
+        engine.get_buffer_pointer(100);
+        assert_eq!(
+            engine.take_grant(101),
+            Err(GrantError::Exceeded {
+                len: 101,
+                granted: 100
+            })
+        );
+        assert_eq!(engine.take_grant(100), Err(GrantError::Missing));
+
+        // Discarding the buffer or clearing the data revokes an unused grant.
+        engine.get_buffer_pointer(100);
+        engine.clear_data();
+        assert_eq!(engine.take_grant(10), Err(GrantError::Missing));
+        engine.get_buffer_pointer(100);
+        engine.discard_buffer_after_indexing();
+        assert_eq!(engine.take_grant(10), Err(GrantError::Missing));
+
+        // get_write_region grants its whole reported capacity.
+        let capacity = engine.get_write_region(10).capacity as usize;
+        assert_eq!(engine.granted_size(), capacity);
+        assert_eq!(engine.take_grant(capacity), Ok(()));
+    }
+
+    #[test]
+    fn chunk_buffer_capacity_stabilizes() {
+        let mut engine = LogEngine::new();
+        let mut offsets = Vec::new();
+        for chunk in 0..100u64 {
+            // Chunk sizes vary as the last chunk of a slice or a live append would.
+            let len = 4096 - (chunk as usize % 7) * 100;
+            engine.get_buffer_pointer(4096);
+            engine.append_chunk(len);
+            offsets.push(chunk * 4096);
+            engine.append_offsets(&offsets[offsets.len() - 1..]);
+            engine.advance_after_chunk(len, true);
+            engine.discard_buffer_after_indexing();
+            assert!(engine.buffer_capacity() >= 4096);
+            assert_eq!(engine.memory_stats().buffer_high_water, 4096);
+        }
+        let capacity = engine.buffer_capacity();
+        engine.get_buffer_pointer(1000);
+        assert_eq!(engine.buffer_capacity(), capacity);
+
+        // A chunk past the cap is freed once discarded, and the high-water mark keeps it.
+        engine.set_chunk_buffer_cap(8192);
+        engine.get_buffer_pointer(10_000);
+        engine.discard_buffer_after_indexing();
+        assert_eq!(engine.buffer_capacity(), 0);
+        assert!(engine.memory_stats().buffer_high_water >= 10_000);
+        engine.clear();
+        assert_eq!(engine.buffer_capacity(), 0);
+        assert_eq!(engine.memory_stats().buffer_high_water, 0);
+    }
+
+    #[test]
+    fn compact_frees_capacity_without_changing_queries() {
+        let mut engine = LogEngine::new();
+        for chunk in 0..100u64 {
+            let starts: Vec<u64> = (0..7).map(|i| chunk * 70 + i * 10).collect();
+            engine.append_offsets(&starts);
+        }
+        engine.advance_after_chunk(7000, true);
+        engine.decode_scratch(4096);
+        let ranges = engine.get_line_ranges(0, engine.line_count());
+        let capacity = engine.offsets().capacity();
+        assert!(capacity > engine.line_count());
+
+        let freed = engine.compact();
+        assert!(freed >= 4096 + (capacity - engine.line_count()) * 4);
+        assert_eq!(engine.offsets().capacity(), engine.line_count());
+        assert_eq!(engine.get_line_ranges(0, engine.line_count()), ranges);
+        assert_eq!(engine.compact(), 0);
+    }
+
+    #[test]
+    fn search_handles_are_tracked_and_invalidated_by_clear() {
+        let compile = || CompiledSearch::compile("x", 0).unwrap();
+        let mut engine = LogEngine::new();
+        let a = engine.add_search(compile());
+        let b = engine.add_search(compile());
+        assert_ne!(a, b);
+        assert_eq!(engine.memory_stats().search_handles, 2);
+        assert!(engine.drop_search(a));
+        assert!(!engine.drop_search(a));
+        assert!(engine.compiled_search(a).is_none());
+        assert_eq!(engine.memory_stats().search_handles, 1);
+
+        engine.clear_data();
+        assert!(engine.compiled_search(b).is_some());
+        engine.clear();
+        assert!(engine.compiled_search(b).is_none());
+        assert_eq!(engine.memory_stats().search_handles, 0);
+        // Handles are not reused after clear.
+        let c = engine.add_search(compile());
+        assert!(c > b);
+    }
+
+    #[test]
+    fn profile_survives_clear_until_reset() {
+        use crate::core::profile::now_ms;
+        let mut engine = LogEngine::new();
+        engine.record_profile(Category::Scan, now_ms());
+        engine.record_profile(Category::Scan, now_ms());
+        engine.clear();
+        assert_eq!(engine.profile().calls(Category::Scan), 2);
+        engine.reset_profile();
+        assert_eq!(engine.profile().calls(Category::Scan), 0);
+    }
+
+    #[test]
+    fn index_progress_fraction() {
+        let mut engine = LogEngine::new();
+        assert_eq!(engine.index_progress(), 0.0);
+        engine.set_expected_size(200);
+        assert_eq!(engine.index_progress(), 0.0);
+        engine.advance_after_chunk(50, true);
+        assert_eq!(engine.index_progress(), 0.25);
+        engine.advance_after_chunk(150, true);
+        assert_eq!(engine.index_progress(), 1.0);
+        // Larger than announced: capped.
+        engine.advance_after_chunk(100, true);
+        assert_eq!(engine.index_progress(), 1.0);
+
+        engine.set_expected_size(0);
+        assert_eq!(engine.index_progress(), 0.0);
+        engine.mark_complete();
+        assert_eq!(engine.index_progress(), 1.0);
+
+        engine.clear_data();
+        assert_eq!(engine.index_progress(), 0.0);
+    }
+
+    #[test]
+    fn clear_data_keeps_config_but_clear_does_not() {
+        let mut engine = engine_with(&[0, 4], 8);
+        engine.set_ascii_mode(true);
+        engine.set_utf8_mode(Utf8Mode::Strict);
+        engine.mark_complete();
+        let config = engine.decode_config();
+
+        engine.clear_data();
+        assert_eq!(engine.line_count(), 0);
+        assert_eq!(engine.total_bytes_indexed(), 0);
+        assert!(engine.last_chunk_ended_with_newline());
+        assert!(!engine.is_complete());
+        assert_eq!(engine.decode_config(), config);
+
+        engine.clear();
+        assert_eq!(engine.decode_config(), DecodeConfig::default());
+    }
+
+    #[test]
+    fn ranges_for_unsorted_lines_keep_input_order() {
+        let engine = engine_with(&[0, 6, 12, 20], 25);
+        assert_eq!(
+            engine.get_ranges_for_lines(&[3, 0, 2, 9, 0]).unwrap(),
+            [20.0, 25.0, 0.0, 6.0, 12.0, 20.0, 0.0, 6.0]
+        );
+        for line in 0..4 {
+            let nested = engine.get_line_ranges(line, line + 1);
+            assert_eq!(engine.line_range(line), Some(nested[0]));
+        }
+        assert!(engine.get_ranges_for_lines(&[4, 100]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn block_index_lookup_matches_plain_binary_search() {
+        let mut rng = XorShift::new(0x2545_F491_4F6C_DD1D);
+        // Mostly short lines with some spanning several blocks, appended in batches.
+        let mut engine = LogEngine::new();
+        let mut all = Vec::new();
+        let mut pos = 0;
+        for _ in 0..40 {
+            let batch: Vec<u64> = (0..1 + rng.below(500))
+                .map(|_| {
+                    let start = pos;
+                    pos += match rng.below(50) {
+                        0 => 1 + rng.below(3 * BLOCK_INDEX_BYTES),
+                        _ => 1 + rng.below(4000),
+                    };
+                    start
+                })
+                .collect();
+            engine.append_offsets(&batch);
+            all.extend(batch);
+        }
+        engine.advance_after_chunk(pos as usize, true);
+        assert!(engine.block_offsets.len() > 10);
+        let plain = LineOffsets::from(&all[..]);
+        let mut probes: Vec<u64> = (0..5000).map(|_| rng.below(pos + 100)).collect();
+        probes.extend(all.iter().flat_map(|&s| [s, s.saturating_sub(1)]));
+        probes.extend(engine.block_offsets.iter().map(|&(start, _)| start));
+        for offset in probes {
+            assert_eq!(
+                engine.line_at_offset(offset),
+                plain.line_containing(offset),
+                "offset {offset}"
+            );
+        }
+        engine.clear_data();
+        assert!(engine.block_offsets.is_empty());
+    }
+
+    #[test]
+    fn line_at_offset_and_back() {
+        let empty = LogEngine::new();
+        assert_eq!(empty.line_at_offset(0), None);
+        assert_eq!(empty.offset_of_line(0), None);
+        let engine = engine_with(&[0, 6, 12], 16);
+        for line in 0..3 {
+            let start = engine.offset_of_line(line).unwrap();
+            assert_eq!(engine.line_at_offset(start), Some(line));
+        }
+        assert_eq!(engine.line_at_offset(11), Some(1));
+        assert_eq!(engine.line_at_offset(15), Some(2));
+        assert_eq!(engine.line_at_offset(16), Some(2));
+        assert_eq!(engine.line_at_offset(1 << 40), Some(2));
+        assert_eq!(engine.offset_of_line(3), None);
+    }
+
+    #[test]
+    fn position_at_byte_clamps_and_counts_chars() {
+        assert_eq!(LogEngine::new().position_at_byte(0, false), None);
+        let text = "ab\nçé x\nend".as_bytes();
+        let mut engine = engine_with(&[0, 3, 10], text.len() as u64);
+        let at = |engine: &LogEngine, offset, chars| {
+            let p = engine.position_at_byte(offset, chars).unwrap();
+            (p.line, p.column, p.chars)
+        };
+        assert_eq!(at(&engine, 0, false), (0, 0, false));
+        assert_eq!(at(&engine, 3, false), (1, 0, false));
+        assert_eq!(at(&engine, 8, false), (1, 5, false));
+        assert_eq!(at(&engine, 13, false), (2, 3, false));
+        assert_eq!(at(&engine, 1 << 40, false), (2, 3, false));
+        // Not resident: byte columns even when characters are asked for.
+        assert_eq!(at(&engine, 8, true), (1, 5, false));
+        engine.buffer = text.to_vec();
+        assert_eq!(at(&engine, 8, true), (1, 3, true));
+        // Mid-character: the cut sequence is not counted.
+        assert_eq!(at(&engine, 4, true), (1, 0, true));
+        assert_eq!(at(&engine, 1 << 40, true), (2, 3, true));
+    }
+
+    #[test]
+    fn needle_longer_than_every_line_cannot_match() {
+        let mut engine = LogEngine::new();
+        assert!(engine.needle_exceeds_lines(b"x"));
+        assert!(!engine.needle_exceeds_lines(b""));
+        engine.append_offsets(&[0, 4, 12]);
+        engine.advance_after_chunk(15, true);
+        // Lines are 3, 7 and 3 bytes long, terminators excluded.
+        assert!(!engine.needle_exceeds_lines(b"1234567"));
+        assert!(!engine.needle_exceeds_lines(b"1234567\n"));
+        assert!(engine.needle_exceeds_lines(b"12345678"));
+    }
+
+    #[test]
+    fn complete_flag_transitions() {
+        let mut engine = LogEngine::new();
+        assert!(!engine.is_complete());
+        engine.append_offsets(&[0, 4]);
+        engine.advance_after_chunk(6, false);
+        assert!(!engine.is_complete());
+        engine.mark_complete();
+        assert!(engine.is_complete());
+        // More data after completion (live append) means the tail may still change.
+        engine.advance_after_chunk(2, true);
+        assert!(!engine.is_complete());
+        engine.mark_complete();
+        engine.advance_to(12, true);
+        assert!(!engine.is_complete());
+        engine.mark_complete();
+        engine.clear();
+        assert!(!engine.is_complete());
+    }
+
+    #[test]
+    fn cancel_flags_are_per_operation_and_consumed() {
+        let mut engine = LogEngine::new();
+        engine.request_cancel(7);
+        assert!(!engine.take_cancel(3));
+        assert!(engine.take_cancel(7));
+        assert!(!engine.take_cancel(7));
+        engine.request_cancel(9);
+        engine.clear();
+        assert!(!engine.take_cancel(9));
+    }
+
+    #[test]
+    fn flat_ranges_match_nested() {
+        let engine = engine_with(&[0, 6, 12, 20], 25);
+        for (start, end) in [(0, 4), (1, 3), (2, 10), (3, 4), (4, 4), (3, 1)] {
+            let nested = engine.get_line_ranges(start, end);
+            let expected: Vec<f64> = nested
+                .iter()
+                .flat_map(|&(s, e)| [s as f64, e as f64])
+                .collect();
+            assert_eq!(engine.get_line_ranges_flat(start, end).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn flat_u64_ranges_match_nested() {
+        let big = u32::MAX as u64 * 3;
+        let engine = engine_with(&[0, 6, big], big + 10);
+        let expected: Vec<u64> = engine
+            .get_line_ranges(0, 3)
+            .iter()
+            .flat_map(|&(s, e)| [s, e])
+            .collect();
+        assert_eq!(engine.get_line_ranges_flat_u64(0, 3), expected);
+    }
+
+    #[test]
+    fn window_plan_reproduces_line_ranges() {
+        let engine = engine_with(&[0, 6, 12, 20], 25);
+        let plan = engine.plan_window(1, 4);
+        assert_eq!((plan.file_start, plan.file_end), (6, 25));
+        let mut prev = 0;
+        let rebuilt: Vec<(u64, u64)> = plan
+            .line_ends
+            .iter()
+            .map(|&end| {
+                let range = (plan.file_start + prev, plan.file_start + end);
+                prev = end;
+                range
+            })
+            .collect();
+        assert_eq!(rebuilt, engine.get_line_ranges(1, 4));
+        assert_eq!(plan.blob_len(), 19);
+        assert!(engine.plan_window(4, 9).line_ends.is_empty());
+    }
+
+    #[test]
+    fn short_blob_fails_plan_check() {
+        use crate::decode::lines::{check_blob_len, DecodeError};
+        let engine = engine_with(&[0, 6, 12, 20], 25);
+        let plan = engine.plan_window(0, 2);
+        assert_eq!(check_blob_len(plan.blob_len(), 12), Ok(()));
+        assert_eq!(
+            check_blob_len(plan.blob_len(), 11),
+            Err(DecodeError::BlobLengthMismatch {
+                expected: 12,
+                actual: 11
+            })
+        );
+    }
+
+    #[test]
+    fn boundaries_match_nested() {
+        let engine = engine_with(&[0, 6, 12, 20], 25);
+        for (start, end) in [(0, 4), (1, 3), (2, 10), (3, 4), (4, 4), (3, 1)] {
+            let nested = engine.get_line_ranges(start, end);
+            let boundaries = engine.get_line_boundaries(start, end);
+            let derived: Vec<(u64, u64)> = boundaries.windows(2).map(|w| (w[0], w[1])).collect();
+            assert_eq!(derived, nested);
+            assert_eq!(boundaries.is_empty(), nested.is_empty());
+        }
+    }
+
+    #[test]
+    fn decode_cache_hits_match_fresh_decodes() {
+        let text = b"alpha\nb\xc3\xa9ta\ngamma\ndelta";
+        let mut engine = engine_with(&[0, 6, 12, 18], text.len() as u64);
+        let decode = |engine: &mut LogEngine, start: usize, end: usize| {
+            let plan = engine.plan_window(start, end);
+            let blob = &text[plan.file_start as usize..plan.file_end as usize];
+            engine.decode_scratch(blob.len()).copy_from_slice(blob);
+            let ends: Vec<u32> = plan.line_ends.iter().map(|&e| e as u32).collect();
+            engine.decode_scratch_cached(blob.len(), &ends, plan.start_line)
+        };
+        engine.set_decode_cache_size(3);
+        let fresh = decode(&mut engine, 0, 3);
+        assert_eq!(fresh[1].as_deref(), Some("béta\n"));
+        assert_eq!(engine.cached_lines(0, 3), Some(fresh.clone()));
+        // A second decode is served from the cache with identical strings.
+        assert_eq!(decode(&mut engine, 0, 3), fresh);
+
+        // Line 3 evicts the least recently used line (0).
+        decode(&mut engine, 3, 4);
+        assert_eq!(engine.cached_lines(0, 1), None);
+        assert_eq!(engine.cached_lines(1, 4).map(|l| l.len()), Some(3));
+
+        // Appended data may extend the last line, so it is dropped; settings changes and
+        // clear_data drop everything.
+        engine.append_offsets(&[]);
+        assert_eq!(engine.cached_lines(3, 4), None);
+        assert!(engine.cached_lines(1, 3).is_some());
+        engine.set_ascii_mode(true);
+        assert_eq!(engine.cached_lines(1, 2), None);
+        decode(&mut engine, 1, 2);
+        engine.clear_data();
+        assert_eq!(engine.memory_stats().decode_cache_lines, 0);
+        assert!(engine.decode_cache_enabled());
+        engine.clear();
+        assert!(!engine.decode_cache_enabled());
+    }
+
+    #[test]
+    fn line_density_sums_to_line_count() {
+        // Starts 0..=9 are 10 bytes apart; the last one sits at the very end.
+        let starts: Vec<u64> = (0..10).map(|i| i * 10).collect();
+        let engine = engine_with(&starts, 90);
+        assert_eq!(engine.line_density(3), [3, 3, 4]);
+        assert_eq!(engine.line_density(9), [1, 1, 1, 1, 1, 1, 1, 1, 2]);
+        for buckets in [1, 2, 7, 10, 100, 1000] {
+            let counts = engine.line_density(buckets);
+            assert_eq!(counts.len(), buckets);
+            let sum: u32 = counts.iter().sum();
+            assert_eq!(sum as usize, engine.line_count(), "{buckets} buckets");
+        }
+        assert!(engine.line_density(0).is_empty());
+        assert_eq!(LogEngine::new().line_density(2), [0, 0]);
+    }
+}
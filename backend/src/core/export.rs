@@ -0,0 +1,210 @@
+//! Line exports: a set of lines turned into ascending file byte ranges, handed out a
+//! budget at a time so JS can read them from the `File` and append them to a `Blob`
+//! without holding the whole export in memory. Consecutive lines are coalesced into one
+//! range, so a contiguous block costs one read.
+
+use super::offsets::LineOffsets;
+
+#[derive(Debug)]
+pub struct LineExport {
+    /// Runs of consecutive lines [first, end), ascending and never adjacent.
+    runs: Vec<(u64, u64)>,
+    /// Run being emitted and its first line not emitted yet.
+    run: usize,
+    line: u64,
+    /// End of the indexed data when the export began. Lines are cut here, so data indexed
+    /// later (a live append growing the last line) does not change the export.
+    file_end: u64,
+    total_lines: u64,
+    total_bytes: u64,
+    emitted_lines: u64,
+    emitted_bytes: u64,
+}
+
+impl LineExport {
+    /// Export of `lines` (any order, repeats allowed); lines past the last one are dropped.
+    pub fn new(mut lines: Vec<u64>, offsets: &LineOffsets, file_end: u64) -> Self {
+        lines.retain(|&l| l < offsets.len() as u64);
+        lines.sort_unstable();
+        lines.dedup();
+        let mut runs: Vec<(u64, u64)> = Vec::new();
+        for line in lines {
+            match runs.last_mut() {
+                Some(run) if run.1 == line => run.1 += 1,
+                _ => runs.push((line, line + 1)),
+            }
+        }
+        let mut export = Self {
+            line: runs.first().map_or(0, |r| r.0),
+            runs,
+            run: 0,
+            file_end,
+            total_lines: 0,
+            total_bytes: 0,
+            emitted_lines: 0,
+            emitted_bytes: 0,
+        };
+        for &(first, end) in &export.runs {
+            export.total_lines += end - first;
+            export.total_bytes += export.boundary(offsets, end) - export.boundary(offsets, first);
+        }
+        export
+    }
+
+    /// File offset where `line` starts (the end of the data for the line past the last).
+    #[inline(always)]
+    fn boundary(&self, offsets: &LineOffsets, line: u64) -> u64 {
+        offsets
+            .get(line as usize)
+            .unwrap_or(self.file_end)
+            .min(self.file_end)
+    }
+
+    /// Next ranges, ascending, totalling at most `max_bytes` unless a single line is
+    /// longer (it is then emitted alone). Ranges end on line boundaries, terminators
+    /// included. Empty once everything has been emitted.
+    pub fn next_ranges(&mut self, offsets: &LineOffsets, max_bytes: u64) -> Vec<(u64, u64)> {
+        let mut ranges = Vec::new();
+        let mut budget = max_bytes;
+        while let Some(&(_, end)) = self.runs.get(self.run) {
+            let start = self.boundary(offsets, self.line);
+            let cut = if self.boundary(offsets, end) - start <= budget {
+                end
+            } else {
+                // Last line boundary within the budget: first k with boundary(k) > limit.
+                let limit = start + budget;
+                let (mut lo, mut hi) = (self.line + 1, end);
+                while lo < hi {
+                    let mid = lo + (hi - lo) / 2;
+                    if self.boundary(offsets, mid) <= limit {
+                        lo = mid + 1;
+                    } else {
+                        hi = mid;
+                    }
+                }
+                match lo - 1 {
+                    k if k > self.line => k,
+                    _ if ranges.is_empty() => self.line + 1,
+                    _ => break,
+                }
+            };
+            let stop = self.boundary(offsets, cut);
+            if stop > start {
+                ranges.push((start, stop));
+            }
+            budget = budget.saturating_sub(stop - start);
+            self.emitted_lines += cut - self.line;
+            self.emitted_bytes += stop - start;
+            if cut == end {
+                self.run += 1;
+                self.line = self.runs.get(self.run).map_or(end, |r| r.0);
+            } else {
+                self.line = cut;
+                break;
+            }
+        }
+        ranges
+    }
+
+    /// (total lines, total bytes, lines emitted, bytes emitted).
+    #[inline(always)]
+    pub fn progress(&self) -> (u64, u64, u64, u64) {
+        (
+            self.total_lines,
+            self.total_bytes,
+            self.emitted_lines,
+            self.emitted_bytes,
+        )
+    }
+
+    #[inline(always)]
+    pub fn is_done(&self) -> bool {
+        self.run >= self.runs.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::XorShift;
+
+    /// Offsets of lines with the given lengths, and the end of the data.
+    fn offsets(lengths: &[u64]) -> (LineOffsets, u64) {
+        let mut offsets = LineOffsets::new();
+        let mut starts = Vec::new();
+        let mut pos = 0;
+        for len in lengths {
+            starts.push(pos);
+            pos += len;
+        }
+        offsets.extend_from_slice(&starts);
+        (offsets, pos)
+    }
+
+    #[test]
+    fn adjacent_lines_coalesce_into_one_range() {
+        // Line i spans [10 * i, 10 * i + 10).
+        let (offsets, end) = offsets(&[10; 8]);
+        let mut export = LineExport::new(vec![5, 1, 2, 3, 7, 2], &offsets, end);
+        assert_eq!(export.progress(), (5, 50, 0, 0));
+        assert_eq!(
+            export.next_ranges(&offsets, u64::MAX),
+            [(10, 40), (50, 60), (70, 80)]
+        );
+        assert!(export.is_done());
+        assert_eq!(export.progress(), (5, 50, 5, 50));
+        assert!(export.next_ranges(&offsets, u64::MAX).is_empty());
+    }
+
+    #[test]
+    fn single_line_export_includes_unterminated_last_line() {
+        let (offsets, end) = offsets(&[4, 6, 3]);
+        let mut export = LineExport::new(vec![2], &offsets, end);
+        // A budget smaller than the line still emits it whole.
+        assert_eq!(export.next_ranges(&offsets, 1), [(10, 13)]);
+        assert!(export.is_done());
+        let mut past_end = LineExport::new(vec![3, 9], &offsets, end);
+        assert!(past_end.is_done());
+        assert_eq!(past_end.progress(), (0, 0, 0, 0));
+        assert!(past_end.next_ranges(&offsets, 100).is_empty());
+    }
+
+    #[test]
+    fn resumes_across_many_calls() {
+        let mut rng = XorShift::new(0x9E37_79B9_7F4A_7C15);
+        let lengths: Vec<u64> = (0..2000).map(|_| 1 + rng.below(200)).collect();
+        let (offsets, end) = offsets(&lengths);
+        let lines: Vec<u64> = (0..2000).filter(|_| rng.below(3) > 0).collect();
+        let mut export = LineExport::new(lines.clone(), &offsets, end);
+        let mut ranges = Vec::new();
+        let mut calls = 0;
+        loop {
+            let budget = 1 + rng.below(1000);
+            let batch = export.next_ranges(&offsets, budget);
+            if batch.is_empty() {
+                break;
+            }
+            let bytes: u64 = batch.iter().map(|r| r.1 - r.0).sum();
+            assert!(bytes <= budget || batch.len() == 1, "{bytes} > {budget}");
+            ranges.extend(batch);
+            calls += 1;
+        }
+        assert!(calls > 50);
+        assert!(ranges.windows(2).all(|w| w[0].1 <= w[1].0));
+        // The ranges cover exactly the chosen lines, whole.
+        let covered: Vec<u64> = (0..2000u64)
+            .filter(|&l| {
+                let start = offsets.get(l as usize).unwrap();
+                ranges.iter().any(|r| r.0 <= start && start < r.1)
+            })
+            .collect();
+        assert_eq!(covered, lines);
+        assert!(ranges.iter().all(|r| {
+            offsets.iter().chain([end]).any(|b| b == r.0)
+                && offsets.iter().chain([end]).any(|b| b == r.1)
+        }));
+        let (total_lines, total_bytes, emitted_lines, emitted_bytes) = export.progress();
+        assert_eq!((emitted_lines, emitted_bytes), (total_lines, total_bytes));
+        assert_eq!(total_lines, lines.len() as u64);
+    }
+}
@@ -0,0 +1,167 @@
+//! Numbers crossing the FFI. JS numbers are f64, exact for integers only up to 2^53 − 1
+//! (`Number.MAX_SAFE_INTEGER`). An offset past that (tera-scale concatenated streams, or
+//! offsets scaled by merged or decompressed views) would be rounded silently by `as f64`,
+//! so exports convert u64 values through `to_f64`. Sizes and offsets coming from JS are
+//! taken as f64 and checked by `byte_len` or `file_bytes`, since a `usize` parameter wraps
+//! what JS passes (-1 becomes 4294967295 on wasm32) and `as u64` turns NaN and negative
+//! numbers into 0.
+
+use std::fmt;
+
+/// Largest integer JS represents exactly (`Number.MAX_SAFE_INTEGER`).
+pub const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// A value that would not survive the conversion to a JS number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrecisionLoss {
+    pub value: u64,
+}
+
+impl fmt::Display for PrecisionLoss {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is past 2^53 - 1 and cannot be passed to JS exactly",
+            self.value
+        )
+    }
+}
+
+impl std::error::Error for PrecisionLoss {}
+
+/// `value` as an f64, or an error if the f64 would not be exact.
+#[inline(always)]
+pub fn checked_f64(value: u64) -> Result<f64, PrecisionLoss> {
+    if value > MAX_SAFE_INTEGER {
+        return Err(PrecisionLoss { value });
+    }
+    Ok(value as f64)
+}
+
+/// `checked_f64` for values handed to JS (offsets, lengths, counts), which should never
+/// get that large: debug builds assert, release builds return the error for the export to
+/// throw.
+#[inline(always)]
+pub fn to_f64(value: u64) -> Result<f64, PrecisionLoss> {
+    debug_assert!(
+        value <= MAX_SAFE_INTEGER,
+        "{value} is past 2^53 - 1 and cannot be passed to JS exactly"
+    );
+    checked_f64(value)
+}
+
+/// `to_f64` for every value, failing on the first that is too large.
+pub fn to_f64_all(values: impl IntoIterator<Item = u64>) -> Result<Vec<f64>, PrecisionLoss> {
+    values.into_iter().map(to_f64).collect()
+}
+
+/// A size or offset argument from JS that is not a byte count.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InvalidSize {
+    pub name: &'static str,
+    pub value: f64,
+    /// Largest value the argument accepts.
+    pub max: u64,
+}
+
+impl fmt::Display for InvalidSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} = {} is not a byte count (must be a whole number from 0 to {})",
+            self.name, self.value, self.max
+        )
+    }
+}
+
+impl std::error::Error for InvalidSize {}
+
+/// Converts JS size argument `name` to a byte count. Negative, fractional and non-finite
+/// values, and values past `usize::MAX`, are errors.
+pub fn byte_len(name: &'static str, value: f64) -> Result<usize, InvalidSize> {
+    let whole = value.is_finite() && value >= 0.0 && value.fract() == 0.0;
+    // `usize::MAX + 1` is a power of two, exact as an f64 (`usize::MAX` is not on 64-bit).
+    if whole && value < usize::MAX as f64 + 1.0 {
+        return Ok(value as usize);
+    }
+    let max = usize::MAX as u64;
+    Err(InvalidSize { name, value, max })
+}
+
+/// Converts JS argument `name`, a file offset or a byte count over the file, to a u64.
+/// Unlike `byte_len` it may pass 4 GiB on wasm32; the same values are errors, and values
+/// past 2^53 - 1.
+pub fn file_bytes(name: &'static str, value: f64) -> Result<u64, InvalidSize> {
+    let whole = value.is_finite() && value >= 0.0 && value.fract() == 0.0;
+    if whole && value <= MAX_SAFE_INTEGER as f64 {
+        return Ok(value as u64);
+    }
+    let max = MAX_SAFE_INTEGER;
+    Err(InvalidSize { name, value, max })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversion_is_exact_up_to_max_safe_integer() {
+        let max = (1u64 << 53) - 1;
+        assert_eq!(checked_f64(max), Ok(9_007_199_254_740_991.0));
+        assert_eq!(to_f64(max), Ok(9_007_199_254_740_991.0));
+        // 2^53 itself is an f64, but so is the rounded 2^53 + 1: neither is safe.
+        assert_eq!(checked_f64(1 << 53), Err(PrecisionLoss { value: 1 << 53 }));
+        assert_eq!(
+            checked_f64((1 << 53) + 1),
+            Err(PrecisionLoss {
+                value: (1 << 53) + 1
+            })
+        );
+        assert_eq!(to_f64_all([0, 1, max]), Ok(vec![0.0, 1.0, max as f64]));
+        assert!(to_f64_all([]).unwrap().is_empty());
+    }
+
+    #[test]
+    #[cfg_attr(debug_assertions, should_panic(expected = "past 2^53 - 1"))]
+    fn unsafe_values_assert_in_debug_and_fail_in_release() {
+        assert_eq!(
+            to_f64_all([1, (1 << 53) + 1, 2]),
+            Err(PrecisionLoss {
+                value: (1 << 53) + 1
+            })
+        );
+    }
+
+    #[test]
+    fn sizes_from_js_are_validated() {
+        assert_eq!(byte_len("size", 0.0), Ok(0));
+        assert_eq!(byte_len("size", 65536.0), Ok(65536));
+        for bad in [-1.0, 0.5, f64::NAN, f64::INFINITY, 1e30] {
+            assert_eq!(
+                byte_len("chunk_len", bad).map_err(|e| e.name),
+                Err("chunk_len"),
+                "{bad}"
+            );
+        }
+        assert!(byte_len("size", -1.0)
+            .unwrap_err()
+            .to_string()
+            .starts_with("size = -1 is not a byte count"));
+    }
+
+    #[test]
+    fn file_offsets_from_js_are_validated_up_to_max_safe_integer() {
+        let max = MAX_SAFE_INTEGER as f64;
+        assert_eq!(file_bytes("base_offset", 0.0), Ok(0));
+        assert_eq!(file_bytes("base_offset", 8e9), Ok(8_000_000_000));
+        assert_eq!(file_bytes("base_offset", max), Ok(MAX_SAFE_INTEGER));
+        for bad in [-1.0, 0.5, f64::NAN, f64::NEG_INFINITY, max + 1.0] {
+            let err = file_bytes("base_offset", bad).unwrap_err();
+            assert_eq!(
+                (err.name, err.max),
+                ("base_offset", MAX_SAFE_INTEGER),
+                "{bad}"
+            );
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Ingest pacing: JS can pause file ingestion (e.g. while the tab is hidden or the user
+//! is scrolling) and resume it later. The clock measures active ingest time, paused spans
+//! excluded, so throughput and the remaining-time estimate are not skewed by the pause.
+//!
+//! Write grants: `get_buffer_pointer(size)` grants JS exactly `size` bytes for the next
+//! submission, which consumes the grant, so a stale pointer cannot be submitted twice.
+
+use std::fmt;
+
+/// A file chunk submitted while ingestion is paused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IngestPaused;
+
+impl fmt::Display for IngestPaused {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ingestion is paused; call resume_ingest() before indexing more chunks"
+        )
+    }
+}
+
+impl std::error::Error for IngestPaused {}
+
+/// A file chunk submitted without a matching write grant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrantError {
+    /// No grant is open: `get_buffer_pointer` was not called since the last submission
+    /// or `clear_data`.
+    Missing,
+    /// The chunk is longer than the grant.
+    Exceeded { len: usize, granted: usize },
+}
+
+impl fmt::Display for GrantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GrantError::Missing => write!(
+                f,
+                "no write grant; call get_buffer_pointer(size) before submitting each chunk"
+            ),
+            GrantError::Exceeded { len, granted } => write!(
+                f,
+                "chunk of {len} bytes exceeds the {granted} bytes granted by get_buffer_pointer"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GrantError {}
+
+/// Wall-clock time spent ingesting, from the first indexed chunk, minus paused spans.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct IngestClock {
+    /// When the first chunk was indexed.
+    started_ms: Option<f64>,
+    /// When the current pause began, while paused.
+    paused_since: Option<f64>,
+    /// Total length of finished pauses after `started_ms`.
+    paused_ms: f64,
+}
+
+impl IngestClock {
+    /// Starts the clock on the first indexed chunk; later calls do nothing.
+    #[inline(always)]
+    pub fn start(&mut self, now_ms: f64) {
+        self.started_ms.get_or_insert(now_ms);
+    }
+
+    /// Returns false if already paused.
+    pub fn pause(&mut self, now_ms: f64) -> bool {
+        if self.paused_since.is_some() {
+            return false;
+        }
+        self.paused_since = Some(now_ms);
+        true
+    }
+
+    /// Returns false if not paused.
+    pub fn resume(&mut self, now_ms: f64) -> bool {
+        let Some(since) = self.paused_since.take() else {
+            return false;
+        };
+        if let Some(started) = self.started_ms {
+            // Time paused before the first chunk is not ingest time anyway.
+            self.paused_ms += now_ms - since.max(started);
+        }
+        true
+    }
+
+    #[inline(always)]
+    pub fn is_paused(&self) -> bool {
+        self.paused_since.is_some()
+    }
+
+    /// Active ingest time up to `now_ms`; 0 before the first chunk.
+    pub fn active_ms(&self, now_ms: f64) -> f64 {
+        let Some(started) = self.started_ms else {
+            return 0.0;
+        };
+        let end = self.paused_since.map_or(now_ms, |since| since.max(started));
+        (end - started - self.paused_ms).max(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paused_spans_are_not_active_time() {
+        let mut clock = IngestClock::default();
+        assert_eq!(clock.active_ms(50.0), 0.0);
+        // A pause before the first chunk costs nothing.
+        assert!(clock.pause(5.0));
+        assert!(!clock.pause(6.0));
+        assert!(clock.resume(8.0));
+        assert!(!clock.resume(9.0));
+        clock.start(10.0);
+        assert_eq!(clock.active_ms(30.0), 20.0);
+        clock.start(25.0);
+        assert!(clock.pause(40.0));
+        assert!(clock.is_paused());
+        assert_eq!(clock.active_ms(100.0), 30.0);
+        assert!(clock.resume(100.0));
+        assert_eq!(clock.active_ms(110.0), 40.0);
+    }
+}
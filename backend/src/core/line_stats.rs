@@ -0,0 +1,118 @@
+//! Line length distribution gathered while indexing: a log2-bucketed histogram and the
+//! longest lines, so the UI can decide on word wrap or warn about minified content
+//! without reading the file again. Lengths are in bytes, excluding the `\n` terminator.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Histogram buckets: bucket 0 holds empty lines, bucket `i` lengths in
+/// `[2^(i-1), 2^i)`.
+pub const LENGTH_BUCKETS: usize = 65;
+
+/// How many of the longest lines are tracked; `longest(k)` is capped at this.
+pub const TRACKED_LONGEST: usize = 100;
+
+#[derive(Debug)]
+pub struct LineLengthStats {
+    histogram: [u64; LENGTH_BUCKETS],
+    /// Min-heap of the longest lines as `(length, Reverse(line))`, so among equal lengths
+    /// the later line is evicted first.
+    longest: BinaryHeap<Reverse<(u64, Reverse<u64>)>>,
+}
+
+impl Default for LineLengthStats {
+    fn default() -> Self {
+        Self {
+            histogram: [0; LENGTH_BUCKETS],
+            longest: BinaryHeap::new(),
+        }
+    }
+}
+
+#[inline(always)]
+fn bucket(len: u64) -> usize {
+    (u64::BITS - len.leading_zeros()) as usize
+}
+
+impl LineLengthStats {
+    /// Records complete line `line` of `len` bytes.
+    pub fn record(&mut self, line: u64, len: u64) {
+        self.histogram[bucket(len)] += 1;
+        let entry = Reverse((len, Reverse(line)));
+        if self.longest.len() < TRACKED_LONGEST {
+            self.longest.push(entry);
+        } else if self.longest.peek().is_some_and(|min| entry < *min) {
+            self.longest.pop();
+            self.longest.push(entry);
+        }
+    }
+
+    /// Counts per bucket, trailing empty buckets trimmed. `pending` is the last line when
+    /// it has no terminator yet (`(line, len)`), counted without being recorded.
+    pub fn histogram(&self, pending: Option<(u64, u64)>) -> Vec<u64> {
+        let mut counts = self.histogram.to_vec();
+        if let Some((_, len)) = pending {
+            counts[bucket(len)] += 1;
+        }
+        let used = counts.iter().rposition(|&c| c > 0).map_or(0, |i| i + 1);
+        counts.truncate(used);
+        counts
+    }
+
+    /// Up to `k` longest lines as `(line, len)`, longest first; equal lengths in line
+    /// order. `pending` as for `histogram`.
+    pub fn longest(&self, k: usize, pending: Option<(u64, u64)>) -> Vec<(u64, u64)> {
+        let mut lines: Vec<(u64, u64)> = self
+            .longest
+            .iter()
+            .map(|Reverse((len, Reverse(line)))| (*line, *len))
+            .chain(pending)
+            .collect();
+        lines.sort_unstable_by_key(|&(line, len)| (Reverse(len), line));
+        lines.truncate(k.min(TRACKED_LONGEST));
+        lines
+    }
+
+    /// Stops tracking lines before `line` as longest (they were evicted). The histogram
+    /// keeps counting them.
+    pub fn evict_before(&mut self, line: u64) {
+        self.longest.retain(|Reverse((_, Reverse(l)))| *l >= line);
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_are_log2() {
+        let mut stats = LineLengthStats::default();
+        for len in [0, 1, 2, 3, 4, 7, 8, 1000] {
+            stats.record(0, len);
+        }
+        // 0 | 1 | 2-3 | 4-7 | 8-15 | ... | 512-1023
+        assert_eq!(stats.histogram(None), [1, 1, 2, 2, 1, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(
+            stats.histogram(Some((9, 1))),
+            [1, 2, 2, 2, 1, 0, 0, 0, 0, 0, 1]
+        );
+        assert!(LineLengthStats::default().histogram(None).is_empty());
+        assert_eq!(bucket(u64::MAX), LENGTH_BUCKETS - 1);
+    }
+
+    #[test]
+    fn keeps_the_longest_lines() {
+        let mut stats = LineLengthStats::default();
+        for line in 0..1000 {
+            stats.record(line, line % 300);
+        }
+        let top = stats.longest(4, None);
+        assert_eq!(top, [(299, 299), (599, 299), (899, 299), (298, 298)]);
+        assert_eq!(stats.longest(1000, None).len(), TRACKED_LONGEST);
+        assert_eq!(stats.longest(2, Some((1000, 5000)))[0], (1000, 5000));
+    }
+}
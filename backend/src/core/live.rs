@@ -0,0 +1,166 @@
+//! Live streams (WebSocket, SSE): text appended from JS instead of read from a file.
+//! There is no file to read line bytes back from, so the most recent bytes are retained
+//! here for display. The window is trimmed from the front in bulk once it reaches twice
+//! its target size, so each appended byte is moved at most once.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+/// Default target size of the retained window.
+pub const DEFAULT_LIVE_RETAINED_BYTES: usize = 16 * 1024 * 1024;
+
+/// `append_live` after file chunks were indexed, or file chunks after `append_live`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MixedIngest {
+    /// True when live text was rejected because file data is indexed.
+    pub live_rejected: bool,
+}
+
+impl fmt::Display for MixedIngest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.live_rejected {
+            write!(
+                f,
+                "file data is already indexed; call clear_data() before append_live"
+            )
+        } else {
+            write!(
+                f,
+                "this session is a live stream; call clear_data() before indexing a file"
+            )
+        }
+    }
+}
+
+impl std::error::Error for MixedIngest {}
+
+/// The most recent bytes of a live stream.
+#[derive(Debug)]
+pub struct LiveWindow {
+    bytes: Vec<u8>,
+    /// Stream offset of `bytes[0]`.
+    start: u64,
+    /// At least this many of the latest bytes are kept (all of them while fewer).
+    target: usize,
+    /// Arrival time of each retained line, oldest first, while an age-based retention
+    /// cap is set; empty otherwise.
+    arrivals: VecDeque<f64>,
+}
+
+impl LiveWindow {
+    pub fn new(target: usize) -> Self {
+        Self {
+            bytes: Vec::new(),
+            start: 0,
+            target,
+            arrivals: VecDeque::new(),
+        }
+    }
+
+    /// Appends the next bytes of the stream, evicting the oldest once the window holds
+    /// twice `target`.
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+        if self.bytes.len() > self.target.saturating_mul(2) {
+            let evict = self.bytes.len() - self.target;
+            self.bytes.drain(..evict);
+            self.start += evict as u64;
+        }
+    }
+
+    /// Records that `lines` new lines started at `now_ms`.
+    pub fn push_arrivals(&mut self, lines: usize, now_ms: f64) {
+        self.arrivals.extend(std::iter::repeat_n(now_ms, lines));
+    }
+
+    #[inline(always)]
+    pub fn arrivals(&self) -> &VecDeque<f64> {
+        &self.arrivals
+    }
+
+    /// Starts tracking arrivals: the `retained` lines without a time yet count as
+    /// arriving at `now_ms`.
+    pub fn backfill_arrivals(&mut self, retained: usize, now_ms: f64) {
+        while self.arrivals.len() < retained {
+            self.arrivals.push_front(now_ms);
+        }
+    }
+
+    /// Stops tracking arrivals.
+    pub fn clear_arrivals(&mut self) {
+        self.arrivals = VecDeque::new();
+    }
+
+    /// Forgets the oldest `lines` lines, whose bytes end before stream offset `first_kept`.
+    pub fn evict_lines(&mut self, lines: usize, first_kept: u64) {
+        self.arrivals.drain(..lines.min(self.arrivals.len()));
+        if first_kept > self.start {
+            let evict = ((first_kept - self.start) as usize).min(self.bytes.len());
+            self.bytes.drain(..evict);
+            self.start += evict as u64;
+        }
+    }
+
+    /// Changes the target size; the window shrinks on the next push if it is now too big.
+    #[inline(always)]
+    pub fn set_target(&mut self, target: usize) {
+        self.target = target;
+    }
+
+    /// Stream offset of the oldest retained byte.
+    #[inline(always)]
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// The retained bytes, from stream offset `start()` on.
+    #[inline(always)]
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Bytes [from, to) of the stream, or `None` if any of them were evicted or not
+    /// appended yet.
+    pub fn slice(&self, from: u64, to: u64) -> Option<&[u8]> {
+        let end = self.start + self.bytes.len() as u64;
+        if from < self.start || to > end || from > to {
+            return None;
+        }
+        Some(&self.bytes[(from - self.start) as usize..(to - self.start) as usize])
+    }
+
+    pub fn heap_bytes(&self) -> usize {
+        self.bytes.capacity() + self.arrivals.capacity() * std::mem::size_of::<f64>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sustained_appends_evict_oldest_bytes() {
+        let mut window = LiveWindow::new(100);
+        let mut stream = Vec::new();
+        for i in 0..1000 {
+            let line = format!("event {i}\n");
+            window.push(line.as_bytes());
+            stream.extend_from_slice(line.as_bytes());
+            let end = stream.len() as u64;
+            let retained = end - window.start();
+            assert!(
+                retained >= 100.min(end) && retained <= 200,
+                "{retained} bytes"
+            );
+            // Retained bytes are exactly the stream's tail.
+            assert_eq!(
+                window.slice(window.start(), end).unwrap(),
+                &stream[window.start() as usize..]
+            );
+        }
+        assert!(window.start() > 0);
+        assert_eq!(window.slice(0, 10), None);
+        assert_eq!(window.slice(window.start(), stream.len() as u64 + 1), None);
+        assert!(window.heap_bytes() >= 100);
+    }
+}
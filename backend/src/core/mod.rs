@@ -1 +1,12 @@
+pub mod bounds;
 pub mod engine;
+pub mod export;
+pub mod ffi_num;
+pub mod ingest;
+pub mod line_stats;
+pub mod live;
+pub mod offsets;
+pub mod printability;
+pub mod profile;
+pub mod retention;
+pub mod types;
@@ -0,0 +1,397 @@
+//! Line-offset storage. Keeps offsets as `u32` while every value fits (files under 4GB),
+//! halving index memory, and transparently widens to `u64` once an offset exceeds the limit.
+//! Lines can be evicted from the front (live retention); indices stay absolute, so line `i`
+//! keeps its number and evicted lines simply have no offset.
+
+use std::fmt;
+
+/// Largest offset stored in the compact representation.
+const NARROW_LIMIT: u64 = u32::MAX as u64;
+
+/// Line-start offsets, stored narrow (`u32`) or wide (`u64`). Values are always read back
+/// as `u64`; the representation is an internal detail.
+#[derive(Debug, Clone)]
+pub struct LineOffsets {
+    repr: Repr,
+    /// Lines evicted from the front; line `i` is stored at `i - first`.
+    first: usize,
+    /// Largest value the narrow representation accepts. `NARROW_LIMIT` outside tests.
+    narrow_limit: u64,
+}
+
+#[derive(Debug, Clone)]
+enum Repr {
+    Narrow(Vec<u32>),
+    Wide(Vec<u64>),
+}
+
+impl LineOffsets {
+    pub fn new() -> Self {
+        Self::with_narrow_limit(NARROW_LIMIT)
+    }
+
+    /// Storage that upgrades to `u64` once a value exceeds `narrow_limit` (clamped to
+    /// `u32::MAX`). Lets tests cross the boundary without 4GB of input.
+    pub fn with_narrow_limit(narrow_limit: u64) -> Self {
+        Self {
+            repr: Repr::Narrow(Vec::new()),
+            first: 0,
+            narrow_limit: narrow_limit.min(NARROW_LIMIT),
+        }
+    }
+
+    /// Number of lines, evicted ones included (one past the last line's index).
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.first + self.stored()
+    }
+
+    /// Number of offsets held: the lines not evicted.
+    #[inline(always)]
+    pub fn stored(&self) -> usize {
+        match &self.repr {
+            Repr::Narrow(v) => v.len(),
+            Repr::Wide(v) => v.len(),
+        }
+    }
+
+    /// Index of the first line that still has an offset (the number of evicted lines).
+    #[inline(always)]
+    pub fn first_line(&self) -> usize {
+        self.first
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// True once the storage has been upgraded to `u64`.
+    #[inline(always)]
+    pub fn is_wide(&self) -> bool {
+        matches!(self.repr, Repr::Wide(_))
+    }
+
+    #[inline(always)]
+    pub fn get(&self, i: usize) -> Option<u64> {
+        let i = i.checked_sub(self.first)?;
+        match &self.repr {
+            Repr::Narrow(v) => v.get(i).map(|&o| o as u64),
+            Repr::Wide(v) => v.get(i).copied(),
+        }
+    }
+
+    /// Iterates offsets in order, widened to `u64`.
+    #[cfg(test)]
+    #[inline(always)]
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        self.range(self.first, self.len())
+    }
+
+    /// Offsets in [start, end), widened to `u64`; evicted lines are skipped. Panics if
+    /// `end` is past the last line.
+    pub fn range(&self, start: usize, end: usize) -> impl Iterator<Item = u64> + '_ {
+        let (start, end) = self.stored_range(start, end);
+        let (narrow, wide) = match &self.repr {
+            Repr::Narrow(v) => (&v[start..end], &[][..]),
+            Repr::Wide(v) => (&[][..], &v[start..end]),
+        };
+        narrow.iter().map(|&o| o as u64).chain(wide.iter().copied())
+    }
+
+    /// Index of the first offset for which `pred` is false (offsets are ascending). Never
+    /// below `first_line()`.
+    #[inline(always)]
+    pub fn partition_point(&self, mut pred: impl FnMut(u64) -> bool) -> usize {
+        self.first
+            + match &self.repr {
+                Repr::Narrow(v) => v.partition_point(|&o| pred(o as u64)),
+                Repr::Wide(v) => v.partition_point(|&o| pred(o)),
+            }
+    }
+
+    /// `partition_point` over offsets [start, end) only (evicted lines skipped); returns
+    /// an absolute index. Panics if `end` is past the last line.
+    #[inline(always)]
+    pub fn partition_point_in(
+        &self,
+        start: usize,
+        end: usize,
+        mut pred: impl FnMut(u64) -> bool,
+    ) -> usize {
+        let (start, end) = self.stored_range(start, end);
+        self.first
+            + start
+            + match &self.repr {
+                Repr::Narrow(v) => v[start..end].partition_point(|&o| pred(o as u64)),
+                Repr::Wide(v) => v[start..end].partition_point(|&o| pred(o)),
+            }
+    }
+
+    /// Storage indices for lines [start, end), clamped to the retained lines.
+    #[inline(always)]
+    fn stored_range(&self, start: usize, end: usize) -> (usize, usize) {
+        let end = end.saturating_sub(self.first);
+        (start.saturating_sub(self.first).min(end), end)
+    }
+
+    /// Index of the line containing file offset `pos`: the last line whose start is
+    /// `<= pos`. Offsets past the last start map to the last line, and offsets before the
+    /// first retained line to that line; `None` when no line is retained.
+    #[inline(always)]
+    pub fn line_containing(&self, pos: u64) -> Option<usize> {
+        if self.stored() == 0 {
+            return None;
+        }
+        let line = self.partition_point(|s| s <= pos).checked_sub(1);
+        Some(line.map_or(self.first, |line| line.max(self.first)))
+    }
+
+    /// Appends offsets, upgrading to `u64` storage first if any value is too large.
+    pub fn extend_from_slice(&mut self, new_offsets: &[u64]) {
+        if let Repr::Narrow(v) = &mut self.repr {
+            // Offsets are ascending, so only the last one needs checking.
+            match new_offsets.last() {
+                Some(&last) if last > self.narrow_limit => self.widen(),
+                _ => {
+                    v.extend(new_offsets.iter().map(|&o| o as u32));
+                    return;
+                }
+            }
+        }
+        if let Repr::Wide(v) = &mut self.repr {
+            v.extend_from_slice(new_offsets);
+        }
+    }
+
+    /// Reserves room for `lines` lines in total (evicted ones included) so a long index
+    /// grows without repeated reallocation. Only a hint: returns false, reserving nothing,
+    /// when the allocation fails.
+    pub fn reserve_lines(&mut self, lines: usize) -> bool {
+        let additional = lines.saturating_sub(self.len());
+        match &mut self.repr {
+            Repr::Narrow(v) => v.try_reserve(additional).is_ok(),
+            Repr::Wide(v) => v.try_reserve(additional).is_ok(),
+        }
+    }
+
+    /// Number of offsets the current allocation can hold.
+    #[inline(always)]
+    pub fn capacity(&self) -> usize {
+        match &self.repr {
+            Repr::Narrow(v) => v.capacity(),
+            Repr::Wide(v) => v.capacity(),
+        }
+    }
+
+    /// Bytes allocated for offsets (capacity times the current element size).
+    #[inline(always)]
+    pub fn heap_bytes(&self) -> usize {
+        match &self.repr {
+            Repr::Narrow(v) => v.capacity() * std::mem::size_of::<u32>(),
+            Repr::Wide(v) => v.capacity() * std::mem::size_of::<u64>(),
+        }
+    }
+
+    /// Releases spare capacity.
+    pub fn shrink_to_fit(&mut self) {
+        match &mut self.repr {
+            Repr::Narrow(v) => v.shrink_to_fit(),
+            Repr::Wide(v) => v.shrink_to_fit(),
+        }
+    }
+
+    /// Drops the offsets of the oldest `count` retained lines (at most all of them). The
+    /// remaining lines keep their indices.
+    pub fn evict_front(&mut self, count: usize) {
+        let count = count.min(self.stored());
+        match &mut self.repr {
+            Repr::Narrow(v) => drop(v.drain(..count)),
+            Repr::Wide(v) => drop(v.drain(..count)),
+        }
+        self.first += count;
+    }
+
+    /// Removes all offsets and returns to the compact representation.
+    pub fn clear(&mut self) {
+        self.repr = Repr::Narrow(Vec::new());
+        self.first = 0;
+    }
+
+    fn widen(&mut self) {
+        if let Repr::Narrow(v) = &self.repr {
+            log::debug!("line offsets widened from u32 to u64 at {} lines", v.len());
+            // Keep any capacity reserved by `reserve_lines`.
+            let mut wide = Vec::with_capacity(v.capacity());
+            wide.extend(v.iter().map(|&o| o as u64));
+            self.repr = Repr::Wide(wide);
+        }
+    }
+}
+
+impl Default for LineOffsets {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A line start that is not after the one before it. Appending it would give a line a
+/// negative length and break every range query, so it is rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OffsetOrderError {
+    /// Line index the start would have had.
+    pub index: usize,
+    pub offset: u64,
+    pub prev: u64,
+}
+
+impl fmt::Display for OffsetOrderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "start of line {} = {} is not after the previous line start {}",
+            self.index, self.offset, self.prev
+        )
+    }
+}
+
+impl std::error::Error for OffsetOrderError {}
+
+/// Checks that `new` line starts, to be appended after `prev` (the current last start)
+/// as lines `first_index..`, are strictly ascending.
+pub fn check_ascending(
+    prev: Option<u64>,
+    new: &[u64],
+    first_index: usize,
+) -> Result<(), OffsetOrderError> {
+    let mut prev = prev;
+    for (index, &offset) in (first_index..).zip(new) {
+        if let Some(prev) = prev.filter(|&p| offset <= p) {
+            return Err(OffsetOrderError {
+                index,
+                offset,
+                prev,
+            });
+        }
+        prev = Some(offset);
+    }
+    Ok(())
+}
+
+impl From<&[u64]> for LineOffsets {
+    fn from(offsets: &[u64]) -> Self {
+        let mut store = Self::new();
+        store.extend_from_slice(offsets);
+        store
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stays_narrow_below_limit() {
+        let mut offsets = LineOffsets::new();
+        offsets.extend_from_slice(&[0, 10, u32::MAX as u64]);
+        assert!(!offsets.is_wide());
+        assert_eq!(offsets.iter().collect::<Vec<_>>(), [0, 10, u32::MAX as u64]);
+    }
+
+    #[test]
+    fn line_containing_at_boundaries() {
+        for limit in [u32::MAX as u64, 0] {
+            // limit 0 forces wide storage so both representations are covered.
+            let mut offsets = LineOffsets::with_narrow_limit(limit);
+            assert_eq!(offsets.line_containing(0), None);
+            offsets.extend_from_slice(&[0, 6, 12]);
+            assert_eq!(offsets.line_containing(0), Some(0));
+            assert_eq!(offsets.line_containing(5), Some(0));
+            assert_eq!(offsets.line_containing(6), Some(1));
+            assert_eq!(offsets.line_containing(11), Some(1));
+            assert_eq!(offsets.line_containing(12), Some(2));
+            assert_eq!(offsets.line_containing(u64::MAX), Some(2));
+        }
+    }
+
+    #[test]
+    fn unsorted_starts_are_rejected_with_their_index() {
+        assert_eq!(check_ascending(None, &[0, 4, 9], 0), Ok(()));
+        assert_eq!(check_ascending(Some(9), &[], 3), Ok(()));
+        assert_eq!(
+            check_ascending(None, &[0, 8, 4, 12], 0),
+            Err(OffsetOrderError {
+                index: 2,
+                offset: 4,
+                prev: 8
+            })
+        );
+        // Equal starts (a zero-length line with no terminator) are rejected too.
+        assert_eq!(
+            check_ascending(Some(10), &[10], 5),
+            Err(OffsetOrderError {
+                index: 5,
+                offset: 10,
+                prev: 10
+            })
+        );
+        assert!(check_ascending(None, &[3, 1], 0)
+            .unwrap_err()
+            .to_string()
+            .contains("line 1 = 1"));
+    }
+
+    #[test]
+    fn widens_when_crossing_limit() {
+        let mut offsets = LineOffsets::with_narrow_limit(100);
+        offsets.extend_from_slice(&[0, 40, 100]);
+        assert!(!offsets.is_wide());
+        offsets.extend_from_slice(&[150, 5_000_000_000]);
+        assert!(offsets.is_wide());
+        assert_eq!(
+            offsets.iter().collect::<Vec<_>>(),
+            [0, 40, 100, 150, 5_000_000_000]
+        );
+        assert_eq!(offsets.get(1), Some(40));
+        assert_eq!(offsets.get(4), Some(5_000_000_000));
+        assert_eq!(offsets.partition_point(|o| o <= 120), 3);
+        offsets.clear();
+        assert!(!offsets.is_wide());
+        assert!(offsets.is_empty());
+    }
+
+    #[test]
+    fn evicted_lines_keep_absolute_indices() {
+        for limit in [u32::MAX as u64, 0] {
+            let mut offsets = LineOffsets::with_narrow_limit(limit);
+            offsets.extend_from_slice(&[0, 6, 12, 20, 31]);
+            offsets.evict_front(2);
+            assert_eq!((offsets.first_line(), offsets.len()), (2, 5));
+            assert_eq!(offsets.get(1), None);
+            assert_eq!(offsets.get(2), Some(12));
+            assert_eq!(offsets.range(0, 4).collect::<Vec<_>>(), [12, 20]);
+            assert_eq!(offsets.partition_point(|o| o < 20), 3);
+            assert_eq!(offsets.partition_point_in(0, 5, |o| o <= 25), 4);
+            // Offsets before the first retained line map to it.
+            assert_eq!(offsets.line_containing(3), Some(2));
+            assert_eq!(offsets.line_containing(25), Some(3));
+            offsets.extend_from_slice(&[40]);
+            assert_eq!(offsets.get(5), Some(40));
+            offsets.evict_front(10);
+            assert_eq!((offsets.first_line(), offsets.len()), (6, 6));
+            assert_eq!(offsets.line_containing(50), None);
+            offsets.clear();
+            assert_eq!((offsets.first_line(), offsets.len()), (0, 0));
+        }
+    }
+
+    #[test]
+    fn real_limit_widens_past_4gb() {
+        let mut offsets = LineOffsets::new();
+        offsets.extend_from_slice(&[u32::MAX as u64]);
+        assert!(!offsets.is_wide());
+        offsets.extend_from_slice(&[u32::MAX as u64 + 1]);
+        assert!(offsets.is_wide());
+        assert_eq!(offsets.get(1), Some(u32::MAX as u64 + 1));
+    }
+}
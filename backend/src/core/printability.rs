@@ -0,0 +1,164 @@
+//! Printability profile: per `BLOCK_BYTES` block of the file, how many bytes are text
+//! (printable ASCII, tab/CR/LF, or part of a complete UTF-8 sequence), so the viewer can
+//! gray out binary regions. One table lookup per byte plus a little UTF-8 state.
+
+/// Bytes per block. A 10 GB file needs ~160k blocks (640 KB of counts).
+pub const BLOCK_BYTES: u64 = 64 * 1024;
+
+const BINARY: u8 = 0;
+const TEXT: u8 = 1;
+// Lead bytes are tagged with their sequence length (2..=4).
+const CONTINUATION: u8 = 5;
+
+/// Byte class per byte value: `BINARY`, `TEXT`, `CONTINUATION` or a lead byte's
+/// sequence length.
+static CLASS: [u8; 256] = {
+    let mut table = [BINARY; 256];
+    let mut b = 0;
+    while b < 256 {
+        table[b] = match b as u8 {
+            b'\t' | b'\n' | b'\r' | 0x20..=0x7E => TEXT,
+            0x80..=0xBF => CONTINUATION,
+            0xC2..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF4 => 4,
+            _ => BINARY,
+        };
+        b += 1;
+    }
+    table
+};
+
+#[derive(Debug, Default)]
+pub struct Printability {
+    /// Text bytes per block; the last entry is the block being filled.
+    text: Vec<u32>,
+    /// Bytes seen so far.
+    total: u64,
+    /// Continuation bytes still expected by the current UTF-8 sequence.
+    pending: u8,
+    /// Length of the current UTF-8 sequence; its bytes count once it completes.
+    seq_len: u8,
+}
+
+impl Printability {
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Scores the next bytes of the stream.
+    pub fn feed(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            let room = BLOCK_BYTES - self.total % BLOCK_BYTES;
+            if room == BLOCK_BYTES {
+                self.text.push(0);
+            }
+            let (block, rest) = bytes.split_at(bytes.len().min(room as usize));
+            let text = self.score(block);
+            *self.text.last_mut().expect("block pushed above") += text;
+            self.total += block.len() as u64;
+            bytes = rest;
+        }
+    }
+
+    /// Text bytes in `bytes`. A UTF-8 sequence counts in the block where it completes.
+    #[inline(always)]
+    fn score(&mut self, bytes: &[u8]) -> u32 {
+        let mut text = 0u32;
+        for &b in bytes {
+            match CLASS[b as usize] {
+                CONTINUATION if self.pending > 0 => {
+                    self.pending -= 1;
+                    if self.pending == 0 {
+                        text += self.seq_len as u32;
+                    }
+                }
+                class => {
+                    self.pending = 0;
+                    match class {
+                        TEXT => text += 1,
+                        BINARY | CONTINUATION => {}
+                        len => {
+                            self.pending = len - 1;
+                            self.seq_len = len;
+                        }
+                    }
+                }
+            }
+        }
+        text
+    }
+
+    /// Share of text bytes in each of `buckets` equal byte spans of the stream (0 to 1),
+    /// from the blocks each span touches. Empty until a byte has been fed.
+    pub fn profile(&self, buckets: usize) -> Vec<f64> {
+        if self.total == 0 {
+            return Vec::new();
+        }
+        let total = self.total as u128;
+        (0..buckets as u128)
+            .map(|b| {
+                let lo = (b * total / buckets as u128) as u64;
+                let hi = (((b + 1) * total).div_ceil(buckets as u128) as u64).max(lo + 1);
+                let first = (lo / BLOCK_BYTES) as usize;
+                let last = ((hi - 1) / BLOCK_BYTES) as usize;
+                let text: u64 = self.text[first..=last].iter().map(|&t| t as u64).sum();
+                let end = ((last as u64 + 1) * BLOCK_BYTES).min(self.total);
+                text as f64 / (end - first as u64 * BLOCK_BYTES) as f64
+            })
+            .collect()
+    }
+
+    /// Releases spare capacity of the block list.
+    pub fn shrink_to_fit(&mut self) {
+        self.text.shrink_to_fit();
+    }
+
+    pub fn heap_bytes(&self) -> usize {
+        self.text.capacity() * std::mem::size_of::<u32>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::XorShift;
+
+    #[test]
+    fn half_text_half_random_profile() {
+        let mut rng = XorShift::new(0x2545_F491_4F6C_DD1D);
+        let blocks = 8;
+        let half = (blocks / 2 * BLOCK_BYTES) as usize;
+        let mut data = "grüße, log line\n".repeat(half / 16 + 1).into_bytes();
+        data.truncate(half);
+        data.extend((0..half).map(|_| rng.next_u64() as u8));
+        let mut p = Printability::default();
+        for chunk in data.chunks(10_007) {
+            p.feed(chunk);
+        }
+        let profile = p.profile(blocks as usize);
+        for (i, &score) in profile.iter().enumerate() {
+            if i < 4 {
+                assert!(score > 0.99, "text bucket {i}: {score}");
+            } else {
+                assert!(score < 0.6, "binary bucket {i}: {score}");
+            }
+        }
+        // Coarser and finer resampling keep the shape.
+        let coarse = p.profile(2);
+        assert!(coarse[0] > 0.99 && coarse[1] < 0.6);
+        let fine = p.profile(64);
+        assert!(fine[..32].iter().all(|&s| s > 0.99));
+        assert!(fine[32..].iter().all(|&s| s < 0.6));
+    }
+
+    #[test]
+    fn utf8_sequences_count_only_when_complete() {
+        let mut p = Printability::default();
+        // "é" split across feeds, then a lone lead byte, a stray continuation and a NUL.
+        p.feed(b"a\xC3");
+        p.feed(b"\xA9\xE2b\x80\x00");
+        assert_eq!(p.profile(1), [4.0 / 7.0]);
+        assert_eq!(Printability::default().profile(3), Vec::<f64>::new());
+    }
+}
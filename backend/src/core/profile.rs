@@ -0,0 +1,154 @@
+//! Lightweight per-subsystem profiler. Callers take a start time with `now_ms()` and hand
+//! it back with a fixed `Category` when the scope ends; the engine accumulates totals and
+//! call counts in flat arrays, so recording is two adds and no formatting or allocation.
+
+/// Subsystems timed by the profiler. The discriminant indexes `Profile`'s arrays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    /// Newline scanning while indexing chunks or regions.
+    Scan,
+    /// Substring search (`search`, `search_limit`, `search_async` batches).
+    Search,
+    /// Regex rule classification.
+    Classify,
+    /// Decoding window blobs to strings (including rendering options).
+    Decode,
+    /// Content-line filtering (blank/comment lines).
+    Filter,
+    /// Printability scoring of indexed bytes.
+    Printability,
+}
+
+impl Category {
+    pub const ALL: [Category; 6] = [
+        Category::Scan,
+        Category::Search,
+        Category::Classify,
+        Category::Decode,
+        Category::Filter,
+        Category::Printability,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Scan => "scan",
+            Category::Search => "search",
+            Category::Classify => "classify",
+            Category::Decode => "decode",
+            Category::Filter => "filter",
+            Category::Printability => "printability",
+        }
+    }
+}
+
+use super::types::ProfileEntry;
+
+const CATEGORIES: usize = Category::ALL.len();
+
+/// Accumulated time (ms) and call count per category.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Profile {
+    total_ms: [f64; CATEGORIES],
+    calls: [u64; CATEGORIES],
+}
+
+impl Profile {
+    /// Adds the time since `started_ms` (from `now_ms`) to `category`.
+    #[inline(always)]
+    pub fn record(&mut self, category: Category, started_ms: f64) {
+        self.add(category, now_ms() - started_ms);
+    }
+
+    #[inline(always)]
+    pub fn add(&mut self, category: Category, elapsed_ms: f64) {
+        self.total_ms[category as usize] += elapsed_ms;
+        self.calls[category as usize] += 1;
+    }
+
+    pub fn total_ms(&self, category: Category) -> f64 {
+        self.total_ms[category as usize]
+    }
+
+    pub fn calls(&self, category: Category) -> u64 {
+        self.calls[category as usize]
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// One entry per category, in `Category::ALL` order, for `get_profile`.
+    pub fn entries(&self) -> Vec<ProfileEntry> {
+        Category::ALL
+            .into_iter()
+            .map(|category| ProfileEntry {
+                category: category.name().to_string(),
+                total_ms: self.total_ms(category),
+                calls: self.calls(category),
+            })
+            .collect()
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = performance, js_name = now)]
+    fn performance_now() -> f64;
+}
+
+/// Monotonic clock in milliseconds: `performance.now()` on wasm, `Instant` natively.
+#[cfg(target_arch = "wasm32")]
+#[inline(always)]
+pub fn now_ms() -> f64 {
+    performance_now()
+}
+
+/// Monotonic clock in milliseconds: `performance.now()` on wasm, `Instant` natively.
+#[cfg(not(target_arch = "wasm32"))]
+#[inline(always)]
+pub fn now_ms() -> f64 {
+    use once_cell::sync::Lazy;
+    use std::time::Instant;
+    static ORIGIN: Lazy<Instant> = Lazy::new(Instant::now);
+    ORIGIN.elapsed().as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn categories_accumulate_and_reset() {
+        let mut profile = Profile::default();
+        let started = now_ms();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        profile.record(Category::Scan, started);
+        profile.record(Category::Scan, now_ms());
+        profile.add(Category::Search, 1.5);
+
+        assert_eq!(profile.calls(Category::Scan), 2);
+        assert!(profile.total_ms(Category::Scan) >= 2.0);
+        assert_eq!(profile.calls(Category::Search), 1);
+        assert_eq!(profile.total_ms(Category::Search), 1.5);
+        assert_eq!(profile.calls(Category::Decode), 0);
+
+        let entries = profile.entries();
+        assert_eq!(entries.len(), Category::ALL.len());
+        assert_eq!(entries[1].category, "search");
+        assert_eq!((entries[1].total_ms, entries[1].calls), (1.5, 1));
+
+        profile.reset();
+        for category in Category::ALL {
+            assert_eq!(profile.calls(category), 0);
+            assert_eq!(profile.total_ms(category), 0.0);
+        }
+    }
+
+    #[test]
+    fn category_indices_match_all() {
+        for (i, category) in Category::ALL.into_iter().enumerate() {
+            assert_eq!(category as usize, i);
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! Retention for live sessions: endless tailing would otherwise grow the line index (and
+//! every per-line structure fed while indexing) forever, even though content bytes are
+//! discarded. After each append the oldest lines beyond a count cap, or older than an age
+//! cap, are evicted. Line numbers stay absolute, so evicted lines just stop existing.
+
+use std::collections::VecDeque;
+
+/// Caps on the retained lines of a live session; 0 disables a cap.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RetentionPolicy {
+    pub max_lines: u64,
+    /// Lines whose first byte arrived longer ago than this are evicted.
+    pub max_age_ms: f64,
+}
+
+impl RetentionPolicy {
+    /// How many of the `retained` lines to evict at `now_ms`, oldest first. `arrivals`
+    /// holds the arrival time of each retained line, oldest first, while an age cap is
+    /// set (it is empty otherwise). The last line is never evicted, as it may still be
+    /// growing.
+    pub fn evictions(&self, retained: usize, arrivals: &VecDeque<f64>, now_ms: f64) -> usize {
+        let over_count = match self.max_lines {
+            0 => 0,
+            max => retained.saturating_sub(max as usize),
+        };
+        let too_old = if self.max_age_ms > 0.0 {
+            let cutoff = now_ms - self.max_age_ms;
+            arrivals.partition_point(|&t| t < cutoff)
+        } else {
+            0
+        };
+        over_count.max(too_old).min(retained.saturating_sub(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caps_combine_and_spare_the_last_line() {
+        let arrivals: VecDeque<f64> = [0.0, 10.0, 20.0, 30.0, 40.0].into();
+        let policy = |max_lines, max_age_ms| RetentionPolicy {
+            max_lines,
+            max_age_ms,
+        };
+        assert_eq!(policy(0, 0.0).evictions(5, &arrivals, 100.0), 0);
+        assert_eq!(policy(3, 0.0).evictions(5, &VecDeque::new(), 100.0), 2);
+        // Older than 45 ms at t = 60: lines that arrived before 15.
+        assert_eq!(policy(0, 45.0).evictions(5, &arrivals, 60.0), 2);
+        assert_eq!(policy(4, 45.0).evictions(5, &arrivals, 60.0), 2);
+        assert_eq!(policy(1, 1.0).evictions(5, &arrivals, 1000.0), 4);
+        assert_eq!(policy(1, 0.0).evictions(0, &VecDeque::new(), 0.0), 0);
+    }
+}
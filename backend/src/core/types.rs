@@ -0,0 +1,415 @@
+//! Structured values returned to JS through `serde-wasm-bindgen`.
+//!
+//! Fields that can exceed 2^32 (byte offsets, byte counts) are `u64` in Rust and serialize
+//! as JS numbers (f64), which are exact up to 2^53; past that serialization fails (see
+//! `ffi_num`). Hot-path results (line ranges, match
+//! lists, line ends) stay on typed arrays and are not part of these structs.
+
+use serde::ser::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::ffi_num::to_f64;
+
+/// Serializes a `u64` as an f64 JS number, failing if it would not be exact.
+fn as_f64<S: Serializer>(value: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(to_f64(*value).map_err(S::Error::custom)?)
+}
+
+/// Reads back a `u64` serialized by `as_f64`.
+fn from_f64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    f64::deserialize(deserializer).map(|v| v as u64)
+}
+
+/// Result of `get_write_region`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WriteRegion {
+    /// Address in WASM linear memory (always below 2^32 on wasm32).
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub ptr: u64,
+    /// Bytes JS may write at `ptr`. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub capacity: u64,
+}
+
+/// Everything JS needs to fetch and decode a window of lines: one contiguous file byte
+/// range and the line ends relative to its start (the `line_ends` for decoding).
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct WindowPlan {
+    /// First line of the window. Lets `decode_lines_from_blob` use the decode cache. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub start_line: u64,
+    /// File offset of the first byte to read. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub file_start: u64,
+    /// File offset one past the last byte to read. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub file_end: u64,
+    /// End of each line relative to `file_start` (exclusive). Attached to the JS object as
+    /// a `Uint32Array` by the FFI layer, so it is not serialized here.
+    #[serde(skip)]
+    pub line_ends: Vec<u64>,
+}
+
+impl WindowPlan {
+    /// Number of bytes JS must read for this plan.
+    #[inline(always)]
+    pub fn blob_len(&self) -> u64 {
+        self.file_end - self.file_start
+    }
+}
+
+/// Result of `position_at_byte`: the line containing a file offset and the column of the
+/// offset within it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BytePosition {
+    /// f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub line: u64,
+    /// Bytes (or characters, when `chars`) from the line start. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub column: u64,
+    /// True when `column` counts decoded characters rather than bytes.
+    pub chars: bool,
+}
+
+/// Telemetry from the most recent `search()` call.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct SearchStats {
+    /// Bytes of resident content the search scanned. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub bytes_scanned: u64,
+    /// Wall time of the search in milliseconds (`performance.now()` based).
+    pub duration_ms: f64,
+}
+
+/// Result of `search_limit`: at most `max` matching line indices, plus whether more exist.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LimitedSearch {
+    pub lines: Vec<u32>,
+    /// True when scanning stopped with another matching line still ahead.
+    pub truncated: bool,
+}
+
+/// Result of `search_summary`: matching lines and total occurrences, from one pass.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchSummary {
+    /// Lines containing the needle. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub line_count: u64,
+    /// Non-overlapping occurrences of the needle in those lines. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub occurrence_count: u64,
+}
+
+/// One line of `search_with_positions`: byte offsets of the hits within the line.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineMatchPositions {
+    pub line: u32,
+    pub positions: Vec<u32>,
+    /// True when the line has more hits than `max_positions_per_line`.
+    pub truncated: bool,
+}
+
+/// One line of `render_window`: its number, its text, and where the needle matched.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenderedLine {
+    /// Line number, counted from `first_line_index`. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub n: u64,
+    /// The decoded line, terminator removed.
+    pub text: String,
+    /// `[start, end)` of each match in `text`, in characters.
+    pub matches: Vec<[u32; 2]>,
+}
+
+/// One category of `get_profile()`: accumulated time and number of timed calls.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProfileEntry {
+    pub category: String,
+    pub total_ms: f64,
+    /// f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub calls: u64,
+}
+
+/// One entry of `get_top_lines()`: a frequent line's estimated count. The true count lies
+/// in `[count - error, count]`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TopLine {
+    /// A line with this content, to fetch via `get_line_byte_ranges`. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub line: u64,
+    /// f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub count: u64,
+    /// f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub error: u64,
+}
+
+/// One entry of `get_line_previews()`: the start of a line, kept while indexing.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinePreview {
+    /// f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub line: u64,
+    /// The kept bytes, decoded (terminator removed).
+    pub text: String,
+    /// The line is longer than `text`.
+    pub truncated: bool,
+}
+
+/// One token of `token_frequencies()` and how often it occurs in the window.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenCount {
+    /// ASCII-lowercased token; non-UTF-8 bytes are replaced with U+FFFD.
+    pub token: String,
+    /// f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub count: u64,
+}
+
+/// Result of `token_frequencies()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenFrequencies {
+    /// Highest count first; ties in byte order.
+    pub tokens: Vec<TokenCount>,
+    /// The window had more distinct tokens than are tracked; rarer ones may be missing.
+    pub overflowed: bool,
+}
+
+/// Result of `get_numeric_stats`. Every figure but the counts is `None` (`undefined`)
+/// until a value has been parsed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NumericStats {
+    /// Values parsed. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub count: u64,
+    /// Matching lines whose group was missing or not a finite number. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub skipped: u64,
+    pub sum: f64,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub mean: Option<f64>,
+    pub p50: Option<f64>,
+    pub p95: Option<f64>,
+    pub p99: Option<f64>,
+    /// False once the quantiles come from a sample rather than every value.
+    pub exact_quantiles: bool,
+}
+
+/// Share of the sampled lines in each format (0 to 1, summing to 1 once any line is
+/// sampled).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct FormatScores {
+    pub json: f64,
+    pub access_log: f64,
+    pub syslog: f64,
+    pub logfmt: f64,
+    pub plain: f64,
+}
+
+/// Result of `get_detected_format`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DetectedFormat {
+    /// `"json"`, `"access_log"`, `"syslog"`, `"logfmt"`, `"plain"`, or `"unknown"` before
+    /// any line is sampled.
+    pub format: String,
+    /// Share of the sampled lines in `format`; below 1 for a mixed file.
+    pub confidence: f64,
+    pub sampled_lines: u32,
+    pub scores: FormatScores,
+}
+
+/// Result of `export_stats`: size of an export and how much of it `export_next_ranges`
+/// has handed out, for a progress bar.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportStats {
+    /// Lines in the export. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub total_lines: u64,
+    /// Bytes in the export, terminators included. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub total_bytes: u64,
+    /// Lines covered by the ranges returned so far. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub emitted_lines: u64,
+    /// Bytes covered by the ranges returned so far. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub emitted_bytes: u64,
+    /// True once every range has been returned.
+    pub done: bool,
+}
+
+/// Result of `boundary_state`: the streaming state the next chunk continues from, for
+/// diagnosing indexing issues.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BoundaryState {
+    /// Bytes indexed so far (file offset where the next chunk starts). f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub total_bytes: u64,
+    /// True if the last chunk ended with `\n`, so the next chunk starts a new line.
+    pub last_chunk_ended_with_newline: bool,
+    /// Line starts recorded so far. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub line_count: u64,
+    /// Bytes held in the engine buffer (0 between chunks, as it is discarded).
+    pub buffer_len: usize,
+    pub buffer_capacity: usize,
+}
+
+/// Result of `get_ingest_stats`: how far file ingestion is behind the announced size and
+/// how long the rest should take at the throughput measured so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct IngestStats {
+    /// Bytes indexed so far. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub indexed_bytes: u64,
+    /// Size from `set_expected_total_bytes`, or `None` if none was announced.
+    pub expected_bytes: Option<f64>,
+    /// Expected minus indexed bytes (0 once past the expected size); `None` without an
+    /// expected size.
+    pub lag_bytes: Option<f64>,
+    /// Time since the first chunk, paused spans excluded.
+    pub active_ms: f64,
+    /// Indexed bytes over `active_ms`; 0 before the first chunk.
+    pub bytes_per_ms: f64,
+    /// `lag_bytes / bytes_per_ms`; 0 once complete, `None` while unknown.
+    pub eta_ms: Option<f64>,
+    /// True between `pause_ingest` and `resume_ingest`.
+    pub paused: bool,
+}
+
+/// Memory held by the engine, returned by `get_memory_stats`. All sizes are capacity-based
+/// byte counts (they include reallocation slack).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryStats {
+    /// Number of indexed lines. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub line_count: u64,
+    /// Bytes allocated for line offsets and the coarse block index over them. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub offsets_bytes: u64,
+    /// True once offsets have been widened from `u32` to `u64` storage.
+    pub offsets_wide: bool,
+    /// Capacity of the chunk buffer. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub buffer_capacity: u64,
+    /// Largest chunk buffer capacity since the last `clear`. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub buffer_high_water: u64,
+    /// Capacity of the decode scratch buffer. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub decode_scratch_bytes: u64,
+    /// Bytes held by the case-insensitive shadow index (0 unless enabled). f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub ci_index_bytes: u64,
+    /// Approximate bytes held by the top-lines sketch (0 unless enabled). f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub top_lines_bytes: u64,
+    /// Bytes held by the line preview arena (0 unless enabled). f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub previews_bytes: u64,
+    /// Approximate bytes held by numeric extractors (line tails and samples). f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub numeric_extractor_bytes: u64,
+    /// Approximate bytes held by the pattern counters' automaton and state. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub counters_bytes: u64,
+    /// Bytes held by the printability profile (4 per 64 KiB indexed). f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub printability_bytes: u64,
+    /// Bytes held by the live stream's retained window and line arrival times (0 outside
+    /// live sessions). f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub live_bytes: u64,
+    /// Bytes held by the stream filter's matches and the unseen-match lists. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub match_set_bytes: u64,
+    /// Sum of the above allocations. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub total_bytes: u64,
+    /// `total_bytes` just before the last `trim_memory()` (0 until it first runs). f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub compact_before_bytes: u64,
+    /// `total_bytes` right after the last `trim_memory()`. f64.
+    #[serde(serialize_with = "as_f64", deserialize_with = "from_f64")]
+    pub compact_after_bytes: u64,
+    /// Live handles from `compile_search` (not included in `total_bytes`).
+    pub search_handles: u32,
+    /// Lines held by the decode cache (not included in `total_bytes`).
+    pub decode_cache_lines: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<T: Serialize + for<'de> Deserialize<'de>>(value: &T) -> T {
+        serde_json::from_str(&serde_json::to_string(value).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn large_offsets_serialize_as_numbers() {
+        let plan = WindowPlan {
+            start_line: 12,
+            file_start: 5_000_000_000,
+            file_end: 5_000_000_100,
+            line_ends: vec![40, 100],
+        };
+        let json = serde_json::to_value(&plan).unwrap();
+        assert_eq!(json["file_start"].as_f64(), Some(5_000_000_000.0));
+        assert!(json.get("line_ends").is_none());
+        let back = round_trip(&plan);
+        assert_eq!(
+            (back.file_start, back.file_end),
+            (plan.file_start, plan.file_end)
+        );
+        assert!(back.line_ends.is_empty());
+    }
+
+    #[test]
+    fn stats_round_trip() {
+        let search = SearchStats {
+            bytes_scanned: 1 << 40,
+            duration_ms: 12.5,
+        };
+        assert_eq!(round_trip(&search), search);
+        let memory = MemoryStats {
+            line_count: 3,
+            offsets_bytes: 16,
+            offsets_wide: true,
+            buffer_capacity: 0,
+            buffer_high_water: 1 << 20,
+            decode_scratch_bytes: 4096,
+            ci_index_bytes: 0,
+            top_lines_bytes: 0,
+            previews_bytes: 0,
+            numeric_extractor_bytes: 0,
+            counters_bytes: 0,
+            printability_bytes: 0,
+            live_bytes: 0,
+            match_set_bytes: 0,
+            total_bytes: 4112,
+            compact_before_bytes: 8192,
+            compact_after_bytes: 4112,
+            search_handles: 2,
+            decode_cache_lines: 64,
+        };
+        assert_eq!(round_trip(&memory), memory);
+        let region = WriteRegion {
+            ptr: 1024,
+            capacity: 65536,
+        };
+        assert_eq!(round_trip(&region), region);
+        let export = ExportStats {
+            total_lines: 80_000,
+            total_bytes: 5 << 30,
+            emitted_lines: 100,
+            emitted_bytes: 6400,
+            done: false,
+        };
+        assert_eq!(round_trip(&export), export);
+    }
+}
@@ -0,0 +1,149 @@
+//! Bounded LRU cache of decoded lines keyed by line index, so scrolling back over
+//! recently viewed lines skips the fetch and decode. Disabled (capacity 0) by default.
+
+use std::collections::{BTreeMap, HashMap};
+
+/// A decoded line as `decode_line` returns it (`None` = rejected in strict mode).
+pub type DecodedLine = Option<String>;
+
+#[derive(Debug, Default)]
+pub struct DecodeCache {
+    capacity: usize,
+    /// Line index -> (decoded line, last-use stamp).
+    entries: HashMap<u64, (DecodedLine, u64)>,
+    /// Last-use stamp -> line index; the first entry is the least recently used.
+    by_age: BTreeMap<u64, u64>,
+    clock: u64,
+}
+
+impl DecodeCache {
+    /// Sets the maximum number of cached lines, evicting the oldest beyond it. 0 disables
+    /// the cache and frees it.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        if capacity == 0 {
+            *self = Self::default();
+        }
+        while self.entries.len() > self.capacity {
+            self.evict_oldest();
+        }
+    }
+
+    #[inline(always)]
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Returns the cached line and marks it most recently used.
+    pub fn get(&mut self, line: u64) -> Option<&DecodedLine> {
+        let stamp = self.tick();
+        let (_, used) = self.entries.get_mut(&line)?;
+        self.by_age.remove(used);
+        *used = stamp;
+        self.by_age.insert(stamp, line);
+        self.entries.get(&line).map(|(value, _)| value)
+    }
+
+    /// Caches `value` for `line`, evicting the least recently used line when full.
+    pub fn insert(&mut self, line: u64, value: DecodedLine) {
+        if !self.is_enabled() {
+            return;
+        }
+        let stamp = self.tick();
+        if let Some((_, used)) = self.entries.insert(line, (value, stamp)) {
+            self.by_age.remove(&used);
+        }
+        self.by_age.insert(stamp, line);
+        while self.entries.len() > self.capacity {
+            self.evict_oldest();
+        }
+    }
+
+    /// Drops every entry (the capacity is kept), e.g. when decode settings change.
+    pub fn invalidate(&mut self) {
+        self.entries.clear();
+        self.by_age.clear();
+    }
+
+    /// Drops lines `first_line` and later, whose bytes may still change while the file
+    /// is being appended to.
+    pub fn forget_from(&mut self, first_line: u64) {
+        if self.entries.is_empty() {
+            return;
+        }
+        self.entries.retain(|&line, _| line < first_line);
+        self.by_age.retain(|_, &mut line| line < first_line);
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn evict_oldest(&mut self) {
+        if let Some((_, line)) = self.by_age.pop_first() {
+            self.entries.remove(&line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(s: &str) -> DecodedLine {
+        Some(s.to_string())
+    }
+
+    #[test]
+    fn hits_return_the_cached_line() {
+        let mut cache = DecodeCache::default();
+        cache.insert(1, line("ignored"));
+        assert_eq!(cache.len(), 0);
+        cache.set_capacity(4);
+        cache.insert(7, line("héllo"));
+        cache.insert(8, None);
+        assert_eq!(cache.get(7), Some(&line("héllo")));
+        assert_eq!(cache.get(8), Some(&None));
+        assert_eq!(cache.get(9), None);
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = DecodeCache::default();
+        cache.set_capacity(2);
+        cache.insert(0, line("a"));
+        cache.insert(1, line("b"));
+        // Touch 0, so 1 is now the oldest.
+        assert!(cache.get(0).is_some());
+        cache.insert(2, line("c"));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(1), None);
+        assert_eq!(cache.get(0), Some(&line("a")));
+        assert_eq!(cache.get(2), Some(&line("c")));
+
+        // Re-inserting a line replaces it without growing the cache.
+        cache.insert(2, line("c2"));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(2), Some(&line("c2")));
+
+        cache.insert(5, line("tail"));
+        cache.forget_from(3);
+        assert_eq!(cache.get(5), None);
+        assert_eq!(cache.len(), 1);
+        cache.insert(0, line("a"));
+
+        // Shrinking keeps the most recently used lines.
+        cache.set_capacity(1);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(0), Some(&line("a")));
+        cache.set_capacity(0);
+        assert_eq!(cache.len(), 0);
+        assert!(!cache.is_enabled());
+    }
+}
@@ -0,0 +1,762 @@
+//! Line decoding for the UI. Turns raw line byte slices (read by JS from the file) into
+//! strings, without splitting multi-byte characters at chunk/blob boundaries.
+
+use std::fmt;
+
+/// Invalid `line_ends` passed to a checked decode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// `line_ends[index]` is smaller than the end before it.
+    NonMonotonic { index: usize, end: u64, prev: u64 },
+    /// `line_ends[index]` points past the end of the blob.
+    OutOfBounds {
+        index: usize,
+        end: u64,
+        blob_len: usize,
+    },
+    /// The blob is not the length its window plan says JS should have read.
+    BlobLengthMismatch { expected: u64, actual: usize },
+    /// `indices[index]` names a line the window does not have.
+    LineOutOfRange {
+        index: usize,
+        line: u32,
+        line_count: usize,
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::NonMonotonic { index, end, prev } => write!(
+                f,
+                "line_ends[{index}] = {end} is smaller than the previous end {prev}"
+            ),
+            DecodeError::OutOfBounds {
+                index,
+                end,
+                blob_len,
+            } => write!(
+                f,
+                "line_ends[{index}] = {end} exceeds blob length {blob_len}"
+            ),
+            DecodeError::BlobLengthMismatch { expected, actual } => write!(
+                f,
+                "blob has {actual} bytes but the window plan covers {expected}"
+            ),
+            DecodeError::LineOutOfRange {
+                index,
+                line,
+                line_count,
+            } => write!(
+                f,
+                "indices[{index}] = {line} is past the window's {line_count} lines"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// How invalid UTF-8 inside a line is decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Utf8Mode {
+    /// Trims an incomplete multi-byte sequence at the very end of the line (a chunk or
+    /// blob cut); invalid bytes anywhere else become U+FFFD and decoding continues.
+    #[default]
+    TrimTrailingOnly,
+    /// Every invalid sequence, including a cut one at the end, becomes U+FFFD.
+    Replace,
+    /// A line containing any invalid UTF-8 is reported as an error instead of decoded.
+    Strict,
+    /// Invalid bytes (including a cut sequence at the end) are dropped; the valid text
+    /// around them is kept.
+    Skip,
+}
+
+impl Utf8Mode {
+    /// Maps the FFI code (0 = TrimTrailingOnly, 1 = Replace, 2 = Strict, 3 = Skip) to a mode.
+    pub fn from_code(code: u32) -> Option<Self> {
+        match code {
+            0 => Some(Utf8Mode::TrimTrailingOnly),
+            1 => Some(Utf8Mode::Replace),
+            2 => Some(Utf8Mode::Strict),
+            3 => Some(Utf8Mode::Skip),
+            _ => None,
+        }
+    }
+}
+
+/// What the line-array decode exports do with each line's terminator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEndings {
+    /// Lines keep their terminator as read.
+    #[default]
+    Keep,
+    /// Each line drops its own terminator, `\n` or `\r\n`, so files mixing both never
+    /// leave a stray `\r` in a decoded line.
+    Auto,
+}
+
+impl LineEndings {
+    /// Maps the FFI code (0 = Keep, 1 = Auto) to a mode.
+    pub fn from_code(code: u32) -> Option<Self> {
+        match code {
+            0 => Some(LineEndings::Keep),
+            1 => Some(LineEndings::Auto),
+            _ => None,
+        }
+    }
+}
+
+/// Engine-wide text decoding settings applied by every decode export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DecodeConfig {
+    /// Decode bytes as Latin-1 without UTF-8 validation (see `decode_latin1_line_slice`).
+    /// Takes precedence over `utf8_mode`.
+    pub ascii_mode: bool,
+    pub utf8_mode: Utf8Mode,
+    pub line_endings: LineEndings,
+    /// Minimum length of a skipped NUL run (0: runs are not skipped). A line that is such
+    /// a run decodes to a placeholder in windows (see `skipped_run_placeholder`).
+    pub skip_nul_runs: u64,
+}
+
+/// Splits `blob` into line slices at the 64-bit relative `line_ends` (line i is
+/// `blob[line_ends[i - 1]..line_ends[i]]`). Ends must be non-decreasing and within the
+/// blob; values that do not fit (e.g. above `u32::MAX` for a small blob) are errors,
+/// never truncated or wrapped.
+pub fn checked_line_slices<'a>(
+    blob: &'a [u8],
+    line_ends: &[u64],
+) -> Result<Vec<&'a [u8]>, DecodeError> {
+    let mut slices = Vec::with_capacity(line_ends.len());
+    let mut start = 0usize;
+    let mut prev = 0u64;
+    for (index, &end) in line_ends.iter().enumerate() {
+        if end < prev {
+            return Err(DecodeError::NonMonotonic { index, end, prev });
+        }
+        let end_usize = usize::try_from(end)
+            .ok()
+            .filter(|&e| e <= blob.len())
+            .ok_or(DecodeError::OutOfBounds {
+                index,
+                end,
+                blob_len: blob.len(),
+            })?;
+        slices.push(&blob[start..end_usize]);
+        start = end_usize;
+        prev = end;
+    }
+    Ok(slices)
+}
+
+/// Checks that a blob read for a window plan has exactly the planned length, so a short
+/// or long read errors instead of decoding shifted lines.
+pub fn check_blob_len(expected: u64, actual: usize) -> Result<(), DecodeError> {
+    if expected == actual as u64 {
+        Ok(())
+    } else {
+        Err(DecodeError::BlobLengthMismatch { expected, actual })
+    }
+}
+
+/// Validates 32-bit relative `line_ends` for a blob of `blob_len` bytes: ends must be
+/// non-decreasing and within the blob. Equal ends (zero-length lines) are allowed.
+pub fn check_line_ends(line_ends: &[u32], blob_len: usize) -> Result<(), DecodeError> {
+    let mut prev = 0u32;
+    for (index, &end) in line_ends.iter().enumerate() {
+        if end < prev {
+            return Err(DecodeError::NonMonotonic {
+                index,
+                end: end as u64,
+                prev: prev as u64,
+            });
+        }
+        if end as usize > blob_len {
+            return Err(DecodeError::OutOfBounds {
+                index,
+                end: end as u64,
+                blob_len,
+            });
+        }
+        prev = end;
+    }
+    Ok(())
+}
+
+/// Splits `blob` at the 32-bit relative `line_ends` used by `decode_lines_from_blob`.
+/// Ends past the blob are clamped to its length (the last line takes the remainder).
+pub fn clamped_line_slices<'a>(blob: &'a [u8], line_ends: &[u32]) -> Vec<&'a [u8]> {
+    let mut slices = Vec::with_capacity(line_ends.len());
+    let mut start = 0usize;
+    for &end in line_ends {
+        let end = (end as usize).min(blob.len());
+        let begin = start.min(end);
+        slices.push(&blob[begin..end]);
+        start = end;
+    }
+    slices
+}
+
+/// Concatenates lines `indices` of a window (same `blob`/`line_ends` contract as
+/// `clamped_line_slices`), in the order given and repeats included, each with its own
+/// terminator replaced by `line_ending`. Fails on an index past the last line.
+pub fn assemble_lines(
+    blob: &[u8],
+    line_ends: &[u32],
+    indices: &[u32],
+    line_ending: &[u8],
+) -> Result<Vec<u8>, DecodeError> {
+    let slices = clamped_line_slices(blob, line_ends);
+    let mut out = Vec::new();
+    for (index, &line) in indices.iter().enumerate() {
+        let slice = slices
+            .get(line as usize)
+            .ok_or(DecodeError::LineOutOfRange {
+                index,
+                line,
+                line_count: slices.len(),
+            })?;
+        out.extend_from_slice(strip_line_terminator(slice));
+        out.extend_from_slice(line_ending);
+    }
+    Ok(out)
+}
+
+/// Strips one trailing line terminator (`\n` or `\r\n`) from a line slice.
+#[inline(always)]
+pub fn strip_line_terminator(slice: &[u8]) -> &[u8] {
+    let slice = slice.strip_suffix(b"\n").unwrap_or(slice);
+    slice.strip_suffix(b"\r").unwrap_or(slice)
+}
+
+/// Marker shown instead of a line that is a skipped NUL run, so a window overlapping a
+/// skip region shows where bytes were left out. `None` for any other line.
+pub fn skipped_run_placeholder(slice: &[u8], config: DecodeConfig) -> Option<String> {
+    let run = strip_line_terminator(slice);
+    let skipped = config.skip_nul_runs > 0
+        && run.len() as u64 >= config.skip_nul_runs
+        && run.iter().all(|&b| b == 0);
+    skipped.then(|| format!("\u{27E8}{} NUL bytes skipped\u{27E9}", run.len()))
+}
+
+/// Separator `decode_range_joined` uses when JS passes none.
+pub const DEFAULT_LINE_SEPARATOR: &str = "\n";
+
+/// Decodes a whole window into one string: each line has its terminator removed, is
+/// decoded on its own (so UTF-8 trimming applies at every line end, not only at the blob
+/// end), and lines are joined with `separator`.
+pub fn decode_window_joined(
+    blob: &[u8],
+    line_ends: &[u32],
+    separator: &str,
+    config: DecodeConfig,
+) -> String {
+    let mut out = String::with_capacity(blob.len());
+    for (i, slice) in clamped_line_slices(blob, line_ends).into_iter().enumerate() {
+        if i > 0 {
+            out.push_str(separator);
+        }
+        match skipped_run_placeholder(slice, config) {
+            Some(placeholder) => out.push_str(&placeholder),
+            None => out.push_str(&decode_line_slice(strip_line_terminator(slice), config)),
+        }
+    }
+    out
+}
+
+/// Rewrites every `\r\n` and lone `\r` in `text` as `\n`, so a joined selection uses one
+/// line ending whatever the source mixed (CRLF files, old Mac `\r` files, stray CRs).
+pub fn normalize_newlines(text: String) -> String {
+    if !text.contains('\r') {
+        return text;
+    }
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Decodes every line of `blob` delimited by `line_ends`, validating the ends first
+/// (see `checked_line_slices`). `None` marks a line rejected in `Utf8Mode::Strict`.
+pub fn decode_lines_checked(
+    blob: &[u8],
+    line_ends: &[u64],
+    config: DecodeConfig,
+) -> Result<Vec<Option<String>>, DecodeError> {
+    Ok(checked_line_slices(blob, line_ends)?
+        .into_iter()
+        .map(|slice| decode_window_line(slice, config))
+        .collect())
+}
+
+/// Decodes a single line slice to String. Trims a trailing incomplete UTF-8 sequence
+/// (e.g. when a chunk cut a multi-byte character in the middle) to avoid a replacement
+/// character there; invalid bytes elsewhere in the line become U+FFFD.
+pub fn decode_utf8_line_slice(slice: &[u8]) -> String {
+    let slice = &slice[..slice.len() - incomplete_suffix_len(slice)];
+    String::from_utf8_lossy(slice).into_owned()
+}
+
+/// Decodes the first bytes of a longer line. A multi-byte character cut at the end is
+/// dropped in every UTF-8 mode, so the cut never shows as U+FFFD.
+pub fn decode_line_prefix(prefix: &[u8], config: DecodeConfig) -> String {
+    let cut = if config.ascii_mode {
+        0
+    } else {
+        incomplete_suffix_len(prefix)
+    };
+    decode_line_slice(&prefix[..prefix.len() - cut], config)
+}
+
+/// Preview text appended when `decode_preview` truncates a line.
+pub const PREVIEW_ELLIPSIS: char = '…';
+
+/// Decodes a line for a preview: terminator removed and, past `max_chars` characters,
+/// truncated to at most `max_chars` including a trailing `…`. The cut moves back so it
+/// does not split common grapheme clusters (a base and its combining marks, variation
+/// selectors or skin-tone modifiers, or a ZWJ emoji sequence). Full segmentation is out
+/// of scope; rarer clusters (e.g. Hangul jamo, flags) may still be split.
+pub fn decode_preview(slice: &[u8], max_chars: usize, config: DecodeConfig) -> String {
+    let text = decode_line_slice(strip_line_terminator(slice), config);
+    let Some((cut, _)) = text.char_indices().nth(max_chars) else {
+        return text;
+    };
+    if max_chars == 0 {
+        return String::new();
+    }
+    // Keep room for the ellipsis, then back off to a cluster boundary.
+    let mut cut = text[..cut]
+        .char_indices()
+        .nth(max_chars - 1)
+        .map_or(cut, |(i, _)| i);
+    while cut > 0 {
+        let prev = text[..cut].chars().next_back();
+        let next = text[cut..].chars().next();
+        if !next.is_some_and(extends_cluster) && prev != Some('\u{200D}') {
+            break;
+        }
+        cut -= prev.map_or(0, char::len_utf8);
+    }
+    let mut preview = String::with_capacity(cut + PREVIEW_ELLIPSIS.len_utf8());
+    preview.push_str(&text[..cut]);
+    preview.push(PREVIEW_ELLIPSIS);
+    preview
+}
+
+/// True for characters that attach to the one before: combining marks, variation
+/// selectors, ZWJ and emoji skin-tone modifiers.
+#[inline(always)]
+fn extends_cluster(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{200D}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{FE20}'..='\u{FE2F}'
+        | '\u{1F3FB}'..='\u{1F3FF}'
+        | '\u{E0100}'..='\u{E01EF}')
+}
+
+/// Length of an incomplete multi-byte sequence at the very end of `slice` (0 if none).
+fn incomplete_suffix_len(slice: &[u8]) -> usize {
+    // A sequence is at most 4 bytes, so a cut one starts within the last 3.
+    let tail_start = slice.len().saturating_sub(3);
+    for start in (tail_start..slice.len()).rev() {
+        if slice[start] & 0xC0 != 0x80 {
+            return match std::str::from_utf8(&slice[start..]) {
+                Err(e) if e.valid_up_to() == 0 && e.error_len().is_none() => slice.len() - start,
+                _ => 0,
+            };
+        }
+    }
+    0
+}
+
+/// Decodes a line slice without UTF-8 validation, treating every byte as Latin-1.
+/// ASCII input decodes exactly as `decode_utf8_line_slice` would; any byte >= 0x80 maps
+/// to the code point of the same value, so multi-byte UTF-8 sequences become mojibake
+/// (lossy by design: this is only for logs known to be ASCII).
+pub fn decode_latin1_line_slice(slice: &[u8]) -> String {
+    if slice.is_ascii() {
+        // SAFETY: every byte is < 0x80, which is valid single-byte UTF-8.
+        return unsafe { String::from_utf8_unchecked(slice.to_vec()) };
+    }
+    slice.iter().map(|&b| b as char).collect()
+}
+
+/// Decodes a line slice using the engine's decode settings. Returns `None` only in
+/// `Utf8Mode::Strict` when the line is not valid UTF-8.
+#[inline(always)]
+pub fn decode_line(slice: &[u8], config: DecodeConfig) -> Option<String> {
+    if config.ascii_mode {
+        return Some(decode_latin1_line_slice(slice));
+    }
+    match config.utf8_mode {
+        Utf8Mode::TrimTrailingOnly => Some(decode_utf8_line_slice(slice)),
+        Utf8Mode::Replace => Some(String::from_utf8_lossy(slice).into_owned()),
+        Utf8Mode::Strict => std::str::from_utf8(slice).ok().map(str::to_owned),
+        Utf8Mode::Skip => Some(slice.utf8_chunks().map(|chunk| chunk.valid()).collect()),
+    }
+}
+
+/// Decodes a whole line slice from a window, terminator included, for the line-array
+/// exports: in `LineEndings::Auto` the terminator is removed first. A skipped NUL run
+/// decodes to its placeholder.
+#[inline(always)]
+pub fn decode_window_line(slice: &[u8], config: DecodeConfig) -> Option<String> {
+    if let Some(placeholder) = skipped_run_placeholder(slice, config) {
+        return Some(placeholder);
+    }
+    match config.line_endings {
+        LineEndings::Keep => decode_line(slice, config),
+        LineEndings::Auto => decode_line(strip_line_terminator(slice), config),
+    }
+}
+
+/// Like `decode_line`, for outputs that need a string for every line (joined windows):
+/// a line rejected in `Utf8Mode::Strict` falls back to U+FFFD replacement.
+#[inline(always)]
+pub fn decode_line_slice(slice: &[u8], config: DecodeConfig) -> String {
+    decode_line(slice, config).unwrap_or_else(|| String::from_utf8_lossy(slice).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UTF8: DecodeConfig = DecodeConfig {
+        ascii_mode: false,
+        utf8_mode: Utf8Mode::TrimTrailingOnly,
+        line_endings: LineEndings::Keep,
+        skip_nul_runs: 0,
+    };
+    const ASCII: DecodeConfig = DecodeConfig {
+        ascii_mode: true,
+        utf8_mode: Utf8Mode::TrimTrailingOnly,
+        line_endings: LineEndings::Keep,
+        skip_nul_runs: 0,
+    };
+
+    fn with_mode(utf8_mode: Utf8Mode) -> DecodeConfig {
+        DecodeConfig {
+            ascii_mode: false,
+            utf8_mode,
+            line_endings: LineEndings::Keep,
+            skip_nul_runs: 0,
+        }
+    }
+
+    #[test]
+    fn ascii_mode_matches_utf8_for_ascii() {
+        for line in [&b""[..], b"plain line", b"\tlevel=INFO code=200\r"] {
+            assert_eq!(
+                decode_line_slice(line, ASCII),
+                decode_line_slice(line, UTF8)
+            );
+        }
+    }
+
+    #[test]
+    fn ascii_mode_maps_high_bytes_to_latin1() {
+        // "é" in UTF-8 is C3 A9; in ASCII mode each byte becomes its own char.
+        let line = "café".as_bytes();
+        assert_eq!(decode_line_slice(line, UTF8), "café");
+        assert_eq!(decode_line_slice(line, ASCII), "cafÃ©");
+        // Truncated multi-byte is kept (not trimmed) in ASCII mode.
+        assert_eq!(
+            decode_line_slice(&[b'a', 0xE2, 0x82], ASCII),
+            "a\u{e2}\u{82}"
+        );
+        assert_eq!(decode_line_slice(&[b'a', 0xE2, 0x82], UTF8), "a");
+    }
+
+    #[test]
+    fn invalid_byte_mid_line() {
+        let line = b"ab\xFFcd";
+        assert_eq!(
+            decode_line(line, with_mode(Utf8Mode::TrimTrailingOnly)).unwrap(),
+            "ab\u{FFFD}cd"
+        );
+        assert_eq!(
+            decode_line(line, with_mode(Utf8Mode::Replace)).unwrap(),
+            "ab\u{FFFD}cd"
+        );
+        assert_eq!(decode_line(line, with_mode(Utf8Mode::Strict)), None);
+        assert_eq!(
+            decode_line(line, with_mode(Utf8Mode::Skip)).unwrap(),
+            "abcd"
+        );
+    }
+
+    #[test]
+    fn cut_multibyte_at_end() {
+        // "€" is E2 82 AC; the slice ends after two of its bytes.
+        let line = b"cost \xE2\x82";
+        assert_eq!(
+            decode_line(line, with_mode(Utf8Mode::TrimTrailingOnly)).unwrap(),
+            "cost "
+        );
+        assert_eq!(
+            decode_line(line, with_mode(Utf8Mode::Replace)).unwrap(),
+            "cost \u{FFFD}"
+        );
+        assert_eq!(decode_line(line, with_mode(Utf8Mode::Strict)), None);
+        assert_eq!(
+            decode_line(line, with_mode(Utf8Mode::Skip)).unwrap(),
+            "cost "
+        );
+    }
+
+    #[test]
+    fn invalid_mid_line_and_cut_at_end() {
+        let line = b"a\xFFb \xE2\x82";
+        assert_eq!(
+            decode_line(line, with_mode(Utf8Mode::TrimTrailingOnly)).unwrap(),
+            "a\u{FFFD}b "
+        );
+        assert_eq!(
+            decode_line(line, with_mode(Utf8Mode::Replace)).unwrap(),
+            "a\u{FFFD}b \u{FFFD}"
+        );
+        assert_eq!(decode_line(line, with_mode(Utf8Mode::Strict)), None);
+        assert_eq!(decode_line(line, with_mode(Utf8Mode::Skip)).unwrap(), "ab ");
+        // Valid lines decode the same in every mode.
+        for mode in [
+            Utf8Mode::TrimTrailingOnly,
+            Utf8Mode::Replace,
+            Utf8Mode::Strict,
+            Utf8Mode::Skip,
+        ] {
+            assert_eq!(
+                decode_line("ok €".as_bytes(), with_mode(mode)).unwrap(),
+                "ok €"
+            );
+        }
+    }
+
+    #[test]
+    fn checked_decode_splits_at_64bit_ends() {
+        let blob = b"ab\ncd\n\nx";
+        let lines = decode_lines_checked(blob, &[3, 6, 6, 7, 8], UTF8).unwrap();
+        let lines: Vec<String> = lines.into_iter().map(Option::unwrap).collect();
+        assert_eq!(lines, ["ab\n", "cd\n", "", "\n", "x"]);
+    }
+
+    #[test]
+    fn checked_decode_rejects_ends_beyond_u32() {
+        let blob = b"tiny";
+        let huge = u32::MAX as u64 + 2;
+        // Wrapping to u32 would give 1, a valid end; it must be an error instead.
+        assert_eq!(
+            decode_lines_checked(blob, &[huge], UTF8),
+            Err(DecodeError::OutOfBounds {
+                index: 0,
+                end: huge,
+                blob_len: 4
+            })
+        );
+        assert!(decode_lines_checked(blob, &[2, u64::MAX], UTF8).is_err());
+    }
+
+    #[test]
+    fn checked_decode_rejects_non_monotonic_ends() {
+        assert_eq!(
+            decode_lines_checked(b"abcdef", &[2, 4, 3], UTF8),
+            Err(DecodeError::NonMonotonic {
+                index: 2,
+                end: 3,
+                prev: 4
+            })
+        );
+    }
+
+    #[test]
+    fn auto_line_endings_strip_each_lines_own_terminator() {
+        // LF, CRLF, an empty CRLF line, a CR inside a line, and an unterminated last line.
+        let blob = b"unix\ndos\r\n\r\nin\rside\nlast";
+        let ends = [5, 10, 12, 20, 24];
+        let auto = DecodeConfig {
+            line_endings: LineEndings::Auto,
+            ..UTF8
+        };
+        let lines: Vec<String> = decode_lines_checked(blob, &ends, auto)
+            .unwrap()
+            .into_iter()
+            .map(Option::unwrap)
+            .collect();
+        assert_eq!(lines, ["unix", "dos", "", "in\rside", "last"]);
+        let kept = decode_lines_checked(blob, &ends, UTF8).unwrap();
+        assert_eq!(kept[1].as_deref(), Some("dos\r\n"));
+        assert_eq!(LineEndings::from_code(1), Some(LineEndings::Auto));
+        assert_eq!(LineEndings::from_code(2), None);
+    }
+
+    #[test]
+    fn line_ends_are_checked_for_order_and_bounds() {
+        // Zero-length lines, including at the start and end, are fine.
+        assert_eq!(check_line_ends(&[0, 3, 3, 6, 6], 6), Ok(()));
+        assert_eq!(check_line_ends(&[], 0), Ok(()));
+        assert_eq!(
+            check_line_ends(&[2, 4, 3], 6),
+            Err(DecodeError::NonMonotonic {
+                index: 2,
+                end: 3,
+                prev: 4
+            })
+        );
+        // A last end past the blob is an error, not an empty or clamped tail line.
+        assert_eq!(
+            check_line_ends(&[2, 6, 7], 6),
+            Err(DecodeError::OutOfBounds {
+                index: 2,
+                end: 7,
+                blob_len: 6
+            })
+        );
+        assert_eq!(
+            check_line_ends(&[u32::MAX], 6),
+            Err(DecodeError::OutOfBounds {
+                index: 0,
+                end: u32::MAX as u64,
+                blob_len: 6
+            })
+        );
+    }
+
+    #[test]
+    fn joined_window_strips_crlf_and_lf() {
+        let blob = b"one\r\ntwo\nthree\r\n";
+        assert_eq!(
+            decode_window_joined(blob, &[5, 9, 16], "\n", UTF8),
+            "one\ntwo\nthree"
+        );
+        assert_eq!(
+            decode_window_joined(blob, &[5, 9, 16], " | ", UTF8),
+            "one | two | three"
+        );
+    }
+
+    #[test]
+    fn joined_window_keeps_empty_lines() {
+        let blob = b"a\n\n\r\nb";
+        assert_eq!(
+            decode_window_joined(blob, &[2, 3, 5, 6], ",", UTF8),
+            "a,,,b"
+        );
+        assert_eq!(decode_window_joined(b"", &[], ",", UTF8), "");
+    }
+
+    #[test]
+    fn joined_range_reproduces_original_text() {
+        let text = "first line\nzweite Zeile: grüße\n\n第三行 ✓\nlast";
+        let ends: Vec<u32> = text
+            .match_indices('\n')
+            .map(|(i, _)| i as u32 + 1)
+            .chain([text.len() as u32])
+            .collect();
+        let joined = decode_window_joined(text.as_bytes(), &ends, DEFAULT_LINE_SEPARATOR, UTF8);
+        assert_eq!(joined, text);
+        // A sub-range (lines 1..3) rejoins to the matching slice of the original.
+        let sub = &text.as_bytes()[ends[0] as usize..ends[2] as usize];
+        let sub_ends: Vec<u32> = ends[1..3].iter().map(|e| e - ends[0]).collect();
+        assert_eq!(
+            decode_window_joined(sub, &sub_ends, DEFAULT_LINE_SEPARATOR, UTF8),
+            "zweite Zeile: grüße\n"
+        );
+    }
+
+    #[test]
+    fn joined_window_trims_utf8_per_line() {
+        // First line ends with a cut 3-byte char (E2 82 of "€") before its newline.
+        let blob = [b'x', 0xE2, 0x82, b'\n', b'y', b'\n'];
+        assert_eq!(decode_window_joined(&blob, &[4, 6], "\n", UTF8), "x\ny");
+    }
+
+    #[test]
+    fn normalized_join_mixes_crlf_lf_and_cr() {
+        // CRLF, LF, a line holding old-Mac `\r` breaks, then an unterminated last line.
+        let blob = b"one\r\ntwo\nthree\rfour\r\r\nfive";
+        let ends = [5, 9, 22, 26];
+        let joined = decode_window_joined(blob, &ends, "\r\n", UTF8);
+        assert_eq!(joined, "one\r\ntwo\r\nthree\rfour\r\r\nfive");
+        assert_eq!(normalize_newlines(joined), "one\ntwo\nthree\nfour\n\nfive");
+        assert_eq!(normalize_newlines("no cr\n".to_string()), "no cr\n");
+    }
+
+    #[test]
+    fn preview_truncates_on_char_and_cluster_boundaries() {
+        // Short lines and lines of exactly `max_chars` are kept whole.
+        assert_eq!(decode_preview(b"short\r\n", 10, UTF8), "short");
+        assert_eq!(decode_preview("grüße".as_bytes(), 5, UTF8), "grüße");
+        // Multi-byte characters right at the cut.
+        assert_eq!(
+            decode_preview("日本語のログ行".as_bytes(), 4, UTF8),
+            "日本語…"
+        );
+        assert_eq!(decode_preview("ab€€€".as_bytes(), 4, UTF8), "ab€…");
+        // "é" as e + U+0301 is not split from its accent: the cut moves before the "e".
+        assert_eq!(
+            decode_preview("cafe\u{301} au lait".as_bytes(), 5, UTF8),
+            "caf…"
+        );
+        // A ZWJ family emoji and a skin-toned thumb stay whole or go entirely.
+        let family = "👨\u{200D}👩\u{200D}👧";
+        let line = format!("ok {family} done");
+        assert_eq!(decode_preview(line.as_bytes(), 5, UTF8), "ok …");
+        assert_eq!(
+            decode_preview(line.as_bytes(), 9, UTF8),
+            format!("ok {family}…")
+        );
+        assert_eq!(decode_preview("x👍🏽y".as_bytes(), 3, UTF8), "x…");
+        // Degenerate limits.
+        assert_eq!(decode_preview(b"abc", 1, UTF8), "…");
+        assert_eq!(decode_preview(b"abc", 0, UTF8), "");
+        assert_eq!(decode_preview(b"", 0, UTF8), "");
+    }
+
+    #[test]
+    fn assembles_chosen_lines_in_order_with_crlf() {
+        let blob = b"first\nsecond\r\nthird\nlast";
+        let ends = [6, 14, 20, 24];
+        assert_eq!(
+            assemble_lines(blob, &ends, &[3, 0, 1, 0], b"\r\n").unwrap(),
+            b"last\r\nfirst\r\nsecond\r\nfirst\r\n"
+        );
+        assert_eq!(assemble_lines(blob, &ends, &[], b"\n").unwrap(), b"");
+        assert_eq!(
+            assemble_lines(blob, &ends, &[2, 4], b"\n"),
+            Err(DecodeError::LineOutOfRange {
+                index: 1,
+                line: 4,
+                line_count: 4
+            })
+        );
+    }
+
+    #[test]
+    #[ignore = "benchmark; run with --release -- --ignored --nocapture"]
+    fn bench_ascii_mode_decode() {
+        use std::time::Instant;
+        let line =
+            b"2024-01-01T00:00:00Z INFO request handled path=/api/v1/items status=200 took=3ms";
+        let iterations = 200_000;
+        let time = |config: DecodeConfig| {
+            let start = Instant::now();
+            let mut total = 0usize;
+            for _ in 0..iterations {
+                total += decode_line_slice(std::hint::black_box(line), config).len();
+            }
+            (start.elapsed(), total)
+        };
+        let (utf8, a) = time(UTF8);
+        let (ascii, b) = time(ASCII);
+        assert_eq!(a, b);
+        println!(
+            "utf8: {:?}, ascii: {:?}, speedup: {:.2}x",
+            utf8,
+            ascii,
+            utf8.as_secs_f64() / ascii.as_secs_f64()
+        );
+    }
+}
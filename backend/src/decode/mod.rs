@@ -0,0 +1,4 @@
+pub mod cache;
+pub mod lines;
+pub mod render;
+pub mod selection;
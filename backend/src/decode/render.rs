@@ -0,0 +1,221 @@
+//! Presentation transforms applied to decoded lines before they reach the DOM: control
+//! character escaping, tab expansion, soft-wrap row counting, and match highlighting.
+//! Operate on `&str`
+//! (after UTF-8 decoding).
+
+use unicode_width::UnicodeWidthChar;
+
+/// Flag: replace control characters with a visible form (see `ESCAPE_CONTROL_HEX`).
+pub const ESCAPE_CONTROL: u32 = 1 << 0;
+/// Flag: escape control characters as `\xNN` instead of Unicode control pictures
+/// (U+2400 block). Implies `ESCAPE_CONTROL`.
+pub const ESCAPE_CONTROL_HEX: u32 = 1 << 1;
+/// Flag: expand `\t` with spaces to the next multiple of the tab width.
+pub const EXPAND_TABS: u32 = 1 << 2;
+
+/// How escaped control characters are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlEscape {
+    /// ␇-style control pictures (one column each).
+    Picture,
+    /// `\x07`-style hex escapes.
+    Hex,
+}
+
+/// Combined render options, built from the FFI flag bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RenderOptions {
+    pub escape: Option<ControlEscape>,
+    /// Tab stop width in columns; `None` (or a zero width) leaves tabs as-is.
+    pub tab_width: Option<usize>,
+}
+
+impl RenderOptions {
+    pub fn from_flags(flags: u32, tab_width: u32) -> Self {
+        let escape = if flags & ESCAPE_CONTROL_HEX != 0 {
+            Some(ControlEscape::Hex)
+        } else if flags & ESCAPE_CONTROL != 0 {
+            Some(ControlEscape::Picture)
+        } else {
+            None
+        };
+        let tab_width = (flags & EXPAND_TABS != 0 && tab_width > 0).then_some(tab_width as usize);
+        Self { escape, tab_width }
+    }
+
+    #[inline(always)]
+    pub fn is_noop(&self) -> bool {
+        self.escape.is_none() && self.tab_width.is_none()
+    }
+}
+
+/// True for characters that `ESCAPE_CONTROL` rewrites: C0 controls other than tab and the
+/// line terminators (`\n`, `\r`), plus DEL.
+#[inline(always)]
+fn is_escaped_control(c: char) -> bool {
+    (c < ' ' && !matches!(c, '\t' | '\n' | '\r')) || c == '\x7f'
+}
+
+/// Applies `options` to one decoded line. Tab stops are computed from the column in
+/// Unicode scalar values of the output so far (escapes count as the characters they emit).
+pub fn render_line(line: &str, options: RenderOptions) -> String {
+    if options.is_noop() {
+        return line.to_owned();
+    }
+    let mut out = String::with_capacity(line.len());
+    let mut column = 0usize;
+    for c in line.chars() {
+        match c {
+            '\t' if options.tab_width.is_some() => {
+                let width = options.tab_width.unwrap_or(1);
+                let spaces = width - column % width;
+                out.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            }
+            c if is_escaped_control(c) && options.escape.is_some() => match options.escape {
+                Some(ControlEscape::Hex) => {
+                    out.push_str(&format!("\\x{:02x}", c as u32));
+                    column += 4;
+                }
+                _ => {
+                    out.push(control_picture(c));
+                    column += 1;
+                }
+            },
+            c => {
+                out.push(c);
+                column += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Unicode control picture for a C0 control or DEL (U+2400..U+2421).
+#[inline(always)]
+fn control_picture(c: char) -> char {
+    if c == '\x7f' {
+        '\u{2421}'
+    } else {
+        char::from_u32(0x2400 + c as u32).unwrap_or(c)
+    }
+}
+
+/// Number of display rows `line` occupies when soft-wrapped at `columns`. Each char is
+/// one column, or with `wide_chars` its East Asian display width (2 for CJK/emoji, 0 for
+/// combining marks); a wide char that does not fit at the end of a row moves to the next.
+/// An empty line is one row; `columns == 0` disables wrapping (always one row).
+pub fn wrapped_rows(line: &str, columns: usize, wide_chars: bool) -> u32 {
+    if columns == 0 {
+        return 1;
+    }
+    let mut rows = 1u32;
+    let mut column = 0usize;
+    for c in line.chars() {
+        let width = if wide_chars {
+            c.width().unwrap_or(0)
+        } else {
+            1
+        };
+        if column + width > columns && column > 0 {
+            rows += 1;
+            column = 0;
+        }
+        column += width;
+    }
+    rows
+}
+
+/// `[start, end)` ranges of the non-overlapping occurrences of `needle` in `line`, in
+/// characters (Unicode scalar values, like `position_at_byte` columns). An empty needle
+/// matches nothing.
+pub fn match_char_ranges(line: &str, needle: &str) -> Vec<[u32; 2]> {
+    let mut ranges = Vec::new();
+    if needle.is_empty() {
+        return ranges;
+    }
+    let needle_chars = needle.chars().count() as u32;
+    let (mut byte, mut chars) = (0, 0u32);
+    for (at, _) in line.match_indices(needle) {
+        chars += line[byte..at].chars().count() as u32;
+        byte = at;
+        ranges.push([chars, chars + needle_chars]);
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tabs(width: u32) -> RenderOptions {
+        RenderOptions::from_flags(EXPAND_TABS, width)
+    }
+
+    #[test]
+    fn tabs_after_multibyte_chars_count_scalars() {
+        // "é" is 2 bytes but one column: "é\t" reaches column 4 with 3 spaces.
+        assert_eq!(render_line("é\tx", tabs(4)), "é   x");
+        assert_eq!(render_line("日本\tx", tabs(4)), "日本  x");
+    }
+
+    #[test]
+    fn consecutive_tabs() {
+        assert_eq!(render_line("\t\tx", tabs(4)), "        x");
+        assert_eq!(render_line("ab\t\tc", tabs(4)), "ab      c");
+        // Without the flag (or with width 0) tabs are untouched.
+        assert_eq!(render_line("a\tb", RenderOptions::from_flags(0, 4)), "a\tb");
+        assert_eq!(render_line("a\tb", tabs(0)), "a\tb");
+    }
+
+    #[test]
+    fn escapes_del_and_controls() {
+        let pictures = RenderOptions::from_flags(ESCAPE_CONTROL, 0);
+        assert_eq!(
+            render_line("a\x7fb\x07\n", pictures),
+            "a\u{2421}b\u{2407}\n"
+        );
+        let hex = RenderOptions::from_flags(ESCAPE_CONTROL_HEX, 0);
+        assert_eq!(render_line("a\x7fb\x0b", hex), "a\\x7fb\\x0b");
+    }
+
+    #[test]
+    fn wrapped_rows_for_long_lines() {
+        assert_eq!(wrapped_rows("", 10, false), 1);
+        assert_eq!(wrapped_rows("0123456789", 10, false), 1);
+        assert_eq!(wrapped_rows("0123456789a", 10, false), 2);
+        assert_eq!(wrapped_rows(&"x".repeat(35), 10, false), 4);
+        assert_eq!(wrapped_rows("anything", 0, false), 1);
+    }
+
+    #[test]
+    fn wrapped_rows_count_chars_not_bytes() {
+        // 5 chars, 15 bytes: one row at 5 columns without the wide flag.
+        assert_eq!(wrapped_rows("日本語日本", 5, false), 1);
+        // Wide: 10 columns. Rows hold 2 chars (4 cols); the third would overflow to col 6.
+        assert_eq!(wrapped_rows("日本語日本", 5, true), 3);
+        assert_eq!(wrapped_rows("éééé", 2, true), 2);
+        // A wide char never straddles rows: "a日" at 2 columns needs two rows.
+        assert_eq!(wrapped_rows("a日", 2, true), 2);
+    }
+
+    #[test]
+    fn escapes_shift_tab_stops() {
+        let both = RenderOptions::from_flags(ESCAPE_CONTROL_HEX | EXPAND_TABS, 8);
+        // "\x07" occupies 4 columns, so the tab pads to column 8 with 4 spaces.
+        assert_eq!(render_line("\x07\tz", both), "\\x07    z");
+    }
+
+    #[test]
+    fn match_ranges_count_characters() {
+        assert_eq!(
+            match_char_ranges("é err, ошибка err", "err"),
+            [[2, 5], [14, 17]]
+        );
+        // Non-overlapping, left to right.
+        assert_eq!(match_char_ranges("aaaa", "aa"), [[0, 2], [2, 4]]);
+        assert_eq!(match_char_ranges("日本語", "本"), [[1, 2]]);
+        assert!(match_char_ranges("line", "").is_empty());
+        assert!(match_char_ranges("line", "x").is_empty());
+    }
+}
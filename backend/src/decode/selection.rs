@@ -0,0 +1,179 @@
+//! Copy formatting for a selected window: decoded lines joined with `\n`, optionally
+//! numbered (right-aligned to the widest number), stripped of ANSI escape sequences and
+//! of trailing whitespace. For pasting a selection into a bug report.
+
+use std::borrow::Cow;
+use std::fmt::Write;
+
+use super::lines::{clamped_line_slices, decode_line_slice, strip_line_terminator, DecodeConfig};
+
+/// Flag: prefix each line with its number and `: `.
+pub const NUMBER_LINES: u32 = 1 << 0;
+/// Flag: remove ANSI escape sequences (colors, cursor movement, OSC titles).
+pub const STRIP_ANSI: u32 = 1 << 1;
+/// Flag: remove trailing whitespace (after ANSI stripping, so a reset code after the
+/// text does not keep the spaces before it).
+pub const TRIM_TRAILING_WHITESPACE: u32 = 1 << 2;
+
+/// Formats the window lines delimited by `line_ends` (same contract as
+/// `decode_window_joined`); `first_line` is the number printed for the first line. With
+/// `NUMBER_LINES`, an empty line is printed as its number and `:` alone.
+pub fn format_selection(
+    blob: &[u8],
+    line_ends: &[u32],
+    first_line: u64,
+    flags: u32,
+    config: DecodeConfig,
+) -> String {
+    let slices = clamped_line_slices(blob, line_ends);
+    let last = first_line + slices.len().saturating_sub(1) as u64;
+    let width = last.to_string().len();
+    let mut out = String::with_capacity(blob.len() + slices.len() * (width + 2));
+    for (i, slice) in slices.into_iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        let line = decode_line_slice(strip_line_terminator(slice), config);
+        let line = if flags & STRIP_ANSI != 0 {
+            strip_ansi(&line)
+        } else {
+            Cow::Borrowed(line.as_str())
+        };
+        let text = if flags & TRIM_TRAILING_WHITESPACE != 0 {
+            line.trim_end()
+        } else {
+            &line
+        };
+        if flags & NUMBER_LINES != 0 {
+            let _ = write!(out, "{:>width$}:", first_line + i as u64);
+            if !text.is_empty() {
+                out.push(' ');
+            }
+        }
+        out.push_str(text);
+    }
+    out
+}
+
+/// Removes ANSI escape sequences: CSI (`ESC [` params, intermediates, final byte), OSC
+/// (`ESC ]` up to BEL or `ESC \`), and other `ESC` sequences (intermediate bytes plus
+/// one final character). A sequence cut off by the end of the line is removed up to there.
+pub fn strip_ansi(line: &str) -> Cow<'_, str> {
+    if !line.contains('\x1b') {
+        return Cow::Borrowed(line);
+    }
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('[') => {
+                // Parameter and intermediate bytes, then one final byte in @..=~.
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // `ESC ( B` and the like: intermediate bytes, then one final byte.
+            Some(' '..='/') => {
+                for c in chars.by_ref() {
+                    if !(' '..='/').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Cow::Owned(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::lines::{LineEndings, Utf8Mode};
+
+    const UTF8: DecodeConfig = DecodeConfig {
+        ascii_mode: false,
+        utf8_mode: Utf8Mode::TrimTrailingOnly,
+        line_endings: LineEndings::Keep,
+        skip_nul_runs: 0,
+    };
+
+    /// `line_ends` for `text` split after each `\n`, plus the unterminated rest.
+    fn ends(text: &str) -> Vec<u32> {
+        let mut ends: Vec<u32> = text
+            .match_indices('\n')
+            .map(|(i, _)| i as u32 + 1)
+            .collect();
+        if !text.ends_with('\n') {
+            ends.push(text.len() as u32);
+        }
+        ends
+    }
+
+    fn format(text: &str, first_line: u64, flags: u32) -> String {
+        format_selection(text.as_bytes(), &ends(text), first_line, flags, UTF8)
+    }
+
+    #[test]
+    fn number_width_follows_the_largest_number() {
+        let text = "a\nb\r\nc\nd\n";
+        assert_eq!(
+            format(text, 97, NUMBER_LINES),
+            " 97: a\n 98: b\n 99: c\n100: d"
+        );
+        assert_eq!(format("a\nb\n", 98, NUMBER_LINES), "98: a\n99: b");
+        assert_eq!(format("x", 0, NUMBER_LINES), "0: x");
+        assert_eq!(format(text, 1, 0), "a\nb\nc\nd");
+    }
+
+    #[test]
+    fn empty_lines_get_a_bare_number() {
+        let text = "first\n\n   \nlast";
+        assert_eq!(
+            format(text, 9, NUMBER_LINES),
+            " 9: first\n10:\n11:    \n12: last"
+        );
+        assert_eq!(
+            format(text, 9, NUMBER_LINES | TRIM_TRAILING_WHITESPACE),
+            " 9: first\n10:\n11:\n12: last"
+        );
+        assert_eq!(format_selection(b"", &[], 1, NUMBER_LINES, UTF8), "");
+    }
+
+    #[test]
+    fn ansi_strip_runs_before_trimming() {
+        let text = "\x1b[31mERROR\x1b[0m disk full  \x1b[0m\n\x1b]0;title\x07ok\t\n\x1b(Bplain";
+        assert_eq!(
+            format(text, 1, STRIP_ANSI | TRIM_TRAILING_WHITESPACE),
+            "ERROR disk full\nok\nplain"
+        );
+        // Trimming alone stops at the escape sequence.
+        assert_eq!(
+            format(text, 1, TRIM_TRAILING_WHITESPACE).lines().next(),
+            Some("\x1b[31mERROR\x1b[0m disk full  \x1b[0m")
+        );
+        assert_eq!(
+            format(text, 1, STRIP_ANSI | NUMBER_LINES),
+            "1: ERROR disk full  \n2: ok\t\n3: plain"
+        );
+        assert_eq!(strip_ansi("cut \x1b[3"), "cut ");
+        assert_eq!(strip_ansi("osc \x1b]8;;x\x1b\\link"), "osc link");
+    }
+}
@@ -0,0 +1,147 @@
+//! Diagnostics: routes the `log` facade to the JS console and installs the panic hook.
+//!
+//! Logging is off (level 0) until JS calls `init_diagnostics`. The `log` macros check the
+//! global max level before formatting anything, so at level 0 a log call on the hot path
+//! is one atomic load and a branch: no strings are built.
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+#[wasm_bindgen::prelude::wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = console, js_name = error)]
+    fn console_error(s: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = warn)]
+    fn console_warn(s: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = log)]
+    fn console_log(s: &str);
+}
+
+/// Maps the numeric level JS passes to a filter: 0 off, 1 error, 2 warn, 3 info,
+/// 4 debug, 5 or more trace.
+pub fn level_filter(level: u32) -> LevelFilter {
+    match level {
+        0 => LevelFilter::Off,
+        1 => LevelFilter::Error,
+        2 => LevelFilter::Warn,
+        3 => LevelFilter::Info,
+        4 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// Writes records to `console.error` / `console.warn` / `console.log` by severity.
+struct ConsoleLogger;
+
+impl Log for ConsoleLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!("[wasm-log-explorer {}] {}", record.level(), record.args());
+        match record.level() {
+            Level::Error => console_error(&line),
+            Level::Warn => console_warn(&line),
+            _ => console_log(&line),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: ConsoleLogger = ConsoleLogger;
+
+/// Installs the console logger and panic hook (both once) and sets the log level.
+/// Later calls only change the level.
+pub fn init(level: u32) {
+    console_error_panic_hook::set_once();
+    // Fails only if a logger is already installed, which is fine on repeat calls.
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(level_filter(level));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    use crate::core::offsets::LineOffsets;
+    use crate::indexer::scanner::scan_chunk;
+
+    /// Collects formatted records so tests can inspect what the engine logged.
+    struct CapturingLogger(Mutex<Vec<(Level, String)>>);
+
+    impl Log for CapturingLogger {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.level() <= log::max_level()
+        }
+
+        fn log(&self, record: &Record) {
+            if self.enabled(record.metadata()) {
+                let text = record.args().to_string();
+                self.0.lock().unwrap().push((record.level(), text));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static CAPTURE: CapturingLogger = CapturingLogger(Mutex::new(Vec::new()));
+
+    /// Counts how often it is formatted, to prove disabled log calls build no strings.
+    struct Counted<'a>(&'a AtomicUsize);
+
+    impl fmt::Display for Counted<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            f.write_str("counted")
+        }
+    }
+
+    #[test]
+    fn level_filter_mapping() {
+        assert_eq!(level_filter(0), LevelFilter::Off);
+        assert_eq!(level_filter(2), LevelFilter::Warn);
+        assert_eq!(level_filter(4), LevelFilter::Debug);
+        assert_eq!(level_filter(99), LevelFilter::Trace);
+    }
+
+    // The logger and max level are process-global, so all plumbing checks share one test.
+    #[test]
+    fn logger_plumbing_and_level_zero_is_free() {
+        log::set_logger(&CAPTURE).expect("no other logger in tests");
+        let formatted = AtomicUsize::new(0);
+
+        log::set_max_level(level_filter(0));
+        let chunk = b"line\n".repeat(200_000);
+        let mut starts = Vec::new();
+        for _ in 0..1000 {
+            log::debug!("{}", Counted(&formatted));
+        }
+        scan_chunk(&chunk, 0, &mut starts, true);
+        let mut offsets = LineOffsets::with_narrow_limit(10);
+        offsets.extend_from_slice(&starts);
+        assert_eq!(formatted.load(Ordering::Relaxed), 0);
+        assert!(CAPTURE.0.lock().unwrap().is_empty());
+
+        log::set_max_level(level_filter(2));
+        log::debug!("{}", Counted(&formatted));
+        log::warn!("{}", Counted(&formatted));
+        assert_eq!(formatted.load(Ordering::Relaxed), 1);
+
+        log::set_max_level(level_filter(4));
+        let mut offsets = LineOffsets::with_narrow_limit(10);
+        offsets.extend_from_slice(&[0, 5, 20]);
+        let captured = std::mem::take(&mut *CAPTURE.0.lock().unwrap());
+        log::set_max_level(LevelFilter::Off);
+        assert_eq!(captured[0], (Level::Warn, "counted".to_string()));
+        assert!(captured[1..]
+            .iter()
+            .any(|(level, text)| *level == Level::Debug && text.contains("u64")));
+    }
+}
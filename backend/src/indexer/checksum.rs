@@ -0,0 +1,53 @@
+//! Chunk integrity check: CRC32 (IEEE, as computed by zlib and most JS CRC libraries)
+//! over the bytes JS wrote, verified before they are indexed.
+
+use std::fmt;
+
+/// The CRC32 JS supplied does not match the bytes in the buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChecksumMismatch {
+    pub expected: u32,
+    pub actual: u32,
+}
+
+impl fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "chunk CRC32 mismatch: expected {:08x}, computed {:08x}",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for ChecksumMismatch {}
+
+/// Checks `bytes` against `expected` CRC32.
+pub fn verify_crc32(bytes: &[u8], expected: u32) -> Result<(), ChecksumMismatch> {
+    let actual = crc32fast::hash(bytes);
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(ChecksumMismatch { expected, actual })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_crc_values() {
+        // Standard check value for CRC-32/ISO-HDLC.
+        assert_eq!(verify_crc32(b"123456789", 0xCBF4_3926), Ok(()));
+        assert_eq!(verify_crc32(b"", 0), Ok(()));
+    }
+
+    #[test]
+    fn corrupted_bytes_are_reported() {
+        let err = verify_crc32(b"123456788", 0xCBF4_3926).unwrap_err();
+        assert_eq!(err.expected, 0xCBF4_3926);
+        assert_ne!(err.actual, err.expected);
+        assert!(err.to_string().contains("cbf43926"));
+    }
+}
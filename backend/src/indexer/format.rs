@@ -0,0 +1,230 @@
+//! Log format detection: the first `SAMPLE_LINES` complete, non-blank lines of a file are
+//! classified as they are indexed (JSON lines, nginx/Apache access log, syslog, logfmt, or
+//! plain text), and the dominant format is reported with its share of the sample. Lines
+//! split across chunks are reassembled first, so only whole lines are scored.
+
+use memchr::memchr_iter;
+use once_cell::sync::Lazy;
+use regex::bytes::Regex;
+
+use crate::decode::lines::strip_line_terminator;
+
+/// Lines sampled per file. Detection is finished (and costs nothing) after these.
+pub const SAMPLE_LINES: usize = 200;
+
+/// Longest line kept for classification; the rest of a longer line is ignored, so a
+/// giant first line cannot make the detector buffer the file.
+const MAX_SAMPLED_LINE: usize = 16 * 1024;
+
+/// Common/combined access log: `host ident user [time] "request" status size`, optionally
+/// followed by `"referer" "user-agent"`.
+static ACCESS_LOG: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"^\S+ \S+ \S+ \[[^\]]+\] "[^"]*" \d{3} (?:\d+|-)(?: "[^"]*" "[^"]*")?"#)
+        .expect("valid access log regex")
+});
+
+/// RFC 5424 (`<PRI>VERSION `) or RFC 3164 (`[<PRI>]Mmm dd hh:mm:ss host tag`).
+static SYSLOG: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?:<\d{1,3}>\d |(?:<\d{1,3}>)?[A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2} \S+ \S)")
+        .expect("valid syslog regex")
+});
+
+/// One logfmt `key=value` pair, with an optional quoted value.
+static LOGFMT_PAIR: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?:^|\s)[A-Za-z_][\w.\-]*=(?:"(?:[^"\\]|\\.)*"|\S*)"#)
+        .expect("valid logfmt regex")
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Json,
+    AccessLog,
+    Syslog,
+    Logfmt,
+    Plain,
+}
+
+impl LogFormat {
+    /// All formats, in the order they are tried (most specific first).
+    pub const ALL: [LogFormat; 5] = [
+        LogFormat::Json,
+        LogFormat::AccessLog,
+        LogFormat::Syslog,
+        LogFormat::Logfmt,
+        LogFormat::Plain,
+    ];
+
+    /// Name reported to JS.
+    pub fn name(self) -> &'static str {
+        match self {
+            LogFormat::Json => "json",
+            LogFormat::AccessLog => "access_log",
+            LogFormat::Syslog => "syslog",
+            LogFormat::Logfmt => "logfmt",
+            LogFormat::Plain => "plain",
+        }
+    }
+
+    /// The first format that accepts `line` (terminator stripped, not blank).
+    pub fn classify(line: &[u8]) -> LogFormat {
+        let trimmed = line.trim_ascii();
+        if trimmed.starts_with(b"{") && trimmed.ends_with(b"}") {
+            return LogFormat::Json;
+        }
+        if ACCESS_LOG.is_match(line) {
+            return LogFormat::AccessLog;
+        }
+        if SYSLOG.is_match(line) {
+            return LogFormat::Syslog;
+        }
+        // Logfmt when there are at least two pairs and they make up most of the line.
+        let (pairs, covered) = LOGFMT_PAIR
+            .find_iter(trimmed)
+            .fold((0, 0), |(n, len), m| (n + 1, len + m.len()));
+        if pairs >= 2 && covered * 2 >= trimmed.len() {
+            return LogFormat::Logfmt;
+        }
+        LogFormat::Plain
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FormatDetector {
+    /// Lines classified per format, indexed like `LogFormat::ALL`.
+    counts: [u32; 5],
+    /// Bytes of the current line seen so far (it started in an earlier chunk).
+    tail: Vec<u8>,
+}
+
+impl FormatDetector {
+    /// Drops the sample (for a new file).
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    #[inline(always)]
+    fn sampled(&self) -> usize {
+        self.counts.iter().map(|&c| c as usize).sum()
+    }
+
+    #[inline(always)]
+    pub fn is_done(&self) -> bool {
+        self.sampled() >= SAMPLE_LINES
+    }
+
+    /// Consumes the next bytes of the stream, classifying every line they complete until
+    /// the sample is full.
+    pub fn feed(&mut self, chunk: &[u8]) {
+        if self.is_done() {
+            return;
+        }
+        let mut start = 0;
+        for nl in memchr_iter(b'\n', chunk) {
+            let piece = &chunk[start..=nl];
+            if self.tail.is_empty() {
+                record(&mut self.counts, piece);
+            } else {
+                self.keep(piece);
+                record(&mut self.counts, &self.tail);
+                self.tail.clear();
+            }
+            start = nl + 1;
+            if self.is_done() {
+                self.tail = Vec::new();
+                return;
+            }
+        }
+        self.keep(&chunk[start..]);
+    }
+
+    fn keep(&mut self, bytes: &[u8]) {
+        let room = MAX_SAMPLED_LINE.saturating_sub(self.tail.len());
+        self.tail.extend_from_slice(&bytes[..bytes.len().min(room)]);
+    }
+
+    /// Lines sampled per format, indexed like `LogFormat::ALL`. With `include_tail` (the
+    /// stream has ended), a final line without a terminator is counted too.
+    pub fn counts(&self, include_tail: bool) -> [u32; 5] {
+        let mut counts = self.counts;
+        if include_tail && !self.is_done() {
+            record(&mut counts, &self.tail);
+        }
+        counts
+    }
+}
+
+/// Classifies one line into `counts`, ignoring blank lines.
+fn record(counts: &mut [u32; 5], line: &[u8]) {
+    let line = strip_line_terminator(line);
+    if line.trim_ascii().is_empty() {
+        return;
+    }
+    let format = LogFormat::classify(line);
+    let slot = LogFormat::ALL.iter().position(|&f| f == format);
+    counts[slot.expect("every format is in ALL")] += 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JSON: &str = r#"{"ts":"2024-05-01T10:00:00Z","level":"info","msg":"started"}"#;
+    const ACCESS: &str = r#"203.0.113.9 - frank [10/Oct/2000:13:55:36 -0700] "GET /a.gif HTTP/1.0" 200 2326 "http://x/" "Mozilla/5.0""#;
+    const SYSLOG_3164: &str = "Oct 11 22:14:15 mymachine su: 'su root' failed for lonvick";
+    const SYSLOG_5424: &str =
+        "<165>1 2003-10-11T22:14:15.003Z host.example.com evntslog - ID47 - msg";
+    const LOGFMT: &str = r#"ts=2024-05-01T10:00:00Z level=warn msg="disk almost full" pct=91"#;
+    const PLAIN: &str = "2024-05-01 10:00:00 INFO Server listening on port 8080";
+
+    #[test]
+    fn fixture_lines_classify_as_their_format() {
+        assert_eq!(LogFormat::classify(JSON.as_bytes()), LogFormat::Json);
+        assert_eq!(LogFormat::classify(ACCESS.as_bytes()), LogFormat::AccessLog);
+        assert_eq!(
+            LogFormat::classify(br#"::1 - - [10/Oct/2000:13:55:36 +0000] "GET / HTTP/1.1" 304 -"#),
+            LogFormat::AccessLog
+        );
+        assert_eq!(
+            LogFormat::classify(SYSLOG_3164.as_bytes()),
+            LogFormat::Syslog
+        );
+        assert_eq!(
+            LogFormat::classify(SYSLOG_5424.as_bytes()),
+            LogFormat::Syslog
+        );
+        assert_eq!(LogFormat::classify(LOGFMT.as_bytes()), LogFormat::Logfmt);
+        assert_eq!(LogFormat::classify(PLAIN.as_bytes()), LogFormat::Plain);
+        // A stray `key=value` does not make a plain line logfmt.
+        assert_eq!(
+            LogFormat::classify(b"Retrying connection to the primary database, attempt=3"),
+            LogFormat::Plain
+        );
+    }
+
+    #[test]
+    fn mixed_file_reports_every_format_split_across_chunks() {
+        let mut text = String::new();
+        for i in 0..30 {
+            text.push_str(if i % 3 == 0 { LOGFMT } else { JSON });
+            text.push_str(if i % 2 == 0 { "\n" } else { "\r\n\n" });
+        }
+        text.push_str(PLAIN);
+        let mut detector = FormatDetector::default();
+        for chunk in text.as_bytes().chunks(37) {
+            detector.feed(chunk);
+        }
+        assert_eq!(detector.counts(false), [20, 0, 0, 10, 0]);
+        assert_eq!(detector.counts(true), [20, 0, 0, 10, 1]);
+    }
+
+    #[test]
+    fn sampling_stops_after_sample_lines() {
+        let mut detector = FormatDetector::default();
+        let lines = format!("{JSON}\n").repeat(SAMPLE_LINES + 50);
+        detector.feed(lines.as_bytes());
+        detector.feed(ACCESS.as_bytes());
+        assert!(detector.is_done());
+        assert_eq!(detector.counts(true), [SAMPLE_LINES as u32, 0, 0, 0, 0]);
+        assert!(detector.tail.capacity() == 0);
+    }
+}
@@ -1 +1,8 @@
+pub mod checksum;
+pub mod format;
+pub mod nul_runs;
+pub mod numeric;
+pub mod observer;
+pub mod previews;
 pub mod scanner;
+pub mod top_lines;
@@ -0,0 +1,206 @@
+//! NUL-run skipping for files recovered from crashed systems, which hold long runs of
+//! NUL bytes between valid regions. Without it each run is part of one colossal line.
+//! With it, a run of at least `min_run` NULs becomes a skip region and a line of its own:
+//! the line before it ends where the run starts, and the next line starts at the first
+//! byte after it (a `\n` right after the run terminates the run's line instead). Offsets
+//! stay real file offsets.
+//!
+//! Ingest-time line features count lines by `\n`, so the scanner also builds the bytes
+//! they are fed: each skip region is replaced by line breaks matching the inserted line
+//! starts (its own line is empty there). A run is only known to qualify once `min_run`
+//! NULs have arrived, so NULs at a chunk end are held back until then.
+
+use memchr::memchr2;
+
+#[derive(Debug, Clone)]
+pub struct NulRuns {
+    min_run: u64,
+    /// The NUL run the stream currently ends in.
+    open: Option<OpenRun>,
+    /// Start of the current line.
+    line_start: u64,
+    /// Skip regions `[start, end)`, ascending. The last one may still grow while open.
+    regions: Vec<(u64, u64)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct OpenRun {
+    start: u64,
+    /// Reached `min_run` bytes: a skip region (the last in `regions`).
+    qualified: bool,
+}
+
+impl NulRuns {
+    /// Skips runs of at least `min_run` (non-zero) NUL bytes.
+    pub fn new(min_run: u64) -> Self {
+        Self {
+            min_run,
+            open: None,
+            line_start: 0,
+            regions: Vec::new(),
+        }
+    }
+
+    /// Forgets all regions and stream state (for a new file).
+    pub fn reset(&mut self) {
+        *self = Self::new(self.min_run);
+    }
+
+    #[inline(always)]
+    pub fn regions(&self) -> &[(u64, u64)] {
+        &self.regions
+    }
+
+    /// True if a line starting at `start` follows a skip region or is one, so the line
+    /// before it has no terminator.
+    pub fn starts_unterminated(&self, start: u64) -> bool {
+        let i = self.regions.partition_point(|&(s, _)| s < start);
+        self.regions.get(i).is_some_and(|&(s, _)| s == start)
+            || (i > 0 && self.regions[i - 1].1 == start)
+    }
+
+    /// `scan_chunk` with NUL runs skipped: pushes the line starts of `chunk` (file bytes
+    /// from `base`) and appends the bytes ingest-time features should see to `view`.
+    /// A start inserted at a run that began in an earlier chunk is below `base` but still
+    /// after every start pushed before. Returns true if the chunk ends with a newline.
+    pub fn scan(
+        &mut self,
+        chunk: &[u8],
+        base: u64,
+        line_starts: &mut Vec<u64>,
+        starts_new_line: bool,
+        view: &mut Vec<u8>,
+    ) -> bool {
+        let mut want_start = starts_new_line;
+        let mut i = 0;
+        while i < chunk.len() {
+            if let Some(mut run) = self.open {
+                let zeros = chunk[i..].iter().take_while(|&&b| b == 0).count();
+                i += zeros;
+                let end = base + i as u64;
+                if run.qualified {
+                    self.regions.last_mut().expect("qualified run").1 = end;
+                } else if end - run.start >= self.min_run {
+                    run.qualified = true;
+                    if run.start != self.line_start {
+                        line_starts.push(run.start);
+                        self.line_start = run.start;
+                        view.push(b'\n');
+                    }
+                    self.regions.push((run.start, end));
+                }
+                if i == chunk.len() {
+                    self.open = Some(run);
+                    break;
+                }
+                self.open = None;
+                if !run.qualified {
+                    view.resize(view.len() + (end - run.start) as usize, 0);
+                } else if chunk[i] != b'\n' {
+                    line_starts.push(end);
+                    self.line_start = end;
+                    view.push(b'\n');
+                }
+                continue;
+            }
+            if want_start {
+                line_starts.push(base + i as u64);
+                self.line_start = base + i as u64;
+                want_start = false;
+            }
+            match memchr2(b'\n', 0, &chunk[i..]) {
+                None => {
+                    view.extend_from_slice(&chunk[i..]);
+                    i = chunk.len();
+                }
+                Some(j) if chunk[i + j] == b'\n' => {
+                    view.extend_from_slice(&chunk[i..=i + j]);
+                    i += j + 1;
+                    want_start = true;
+                }
+                Some(j) => {
+                    view.extend_from_slice(&chunk[i..i + j]);
+                    i += j;
+                    self.open = Some(OpenRun {
+                        start: base + i as u64,
+                        qualified: false,
+                    });
+                }
+            }
+        }
+        if chunk.is_empty() {
+            return starts_new_line;
+        }
+        want_start
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Scans `pieces` in order, returning the line starts and the feature view.
+    fn scan_all(runs: &mut NulRuns, pieces: &[&[u8]]) -> (Vec<u64>, Vec<u8>) {
+        let (mut starts, mut view) = (Vec::new(), Vec::new());
+        let (mut base, mut new_line) = (0, true);
+        for piece in pieces {
+            new_line = runs.scan(piece, base, &mut starts, new_line, &mut view);
+            base += piece.len() as u64;
+        }
+        (starts, view)
+    }
+
+    #[test]
+    fn runs_become_their_own_lines_across_chunk_boundaries() {
+        let text = b"ab\0\0\0\0cd\nx\0\0\0\0\ny\0z";
+        let mut runs = NulRuns::new(3);
+        let (starts, view) = scan_all(&mut runs, &[text]);
+        // "ab", run, "cd\n", "x", run + its "\n", "y\0z" (a short run kept verbatim).
+        assert_eq!(starts, [0, 2, 6, 9, 10, 15]);
+        assert_eq!(runs.regions(), [(2, 6), (10, 14)]);
+        assert_eq!(view, b"ab\n\ncd\nx\n\ny\0z");
+        assert_eq!(
+            view.iter().filter(|&&b| b == b'\n').count() + 1,
+            starts.len()
+        );
+        assert!(runs.starts_unterminated(2) && runs.starts_unterminated(6));
+        assert!(!runs.starts_unterminated(9) && !runs.starts_unterminated(15));
+
+        // Every split point, including inside the runs, gives the same result.
+        for split in 0..=text.len() {
+            let (head, tail) = text.split_at(split);
+            let mut split_runs = NulRuns::new(3);
+            assert_eq!(
+                scan_all(&mut split_runs, &[head, tail]),
+                (starts.clone(), view.clone()),
+                "split {split}"
+            );
+            assert_eq!(split_runs.regions(), runs.regions());
+        }
+    }
+
+    #[test]
+    fn short_runs_and_leading_runs() {
+        let mut runs = NulRuns::new(4);
+        // Three NULs split over three chunks stay verbatim.
+        let (starts, view) = scan_all(&mut runs, &[b"a\0", b"\0", b"\0b\n"]);
+        assert_eq!(starts, [0]);
+        assert_eq!(view, b"a\0\0\0b\n");
+        assert!(runs.regions().is_empty());
+
+        // A file beginning with NULs: the run is line 0, no line before it.
+        let mut runs = NulRuns::new(4);
+        let (starts, view) = scan_all(&mut runs, &[b"\0\0", b"\0\0\0log\n"]);
+        assert_eq!(starts, [0, 5]);
+        assert_eq!(runs.regions(), [(0, 5)]);
+        assert_eq!(view, b"\nlog\n");
+        // A run still open at the end of the stream keeps growing.
+        let (starts, _) = scan_all(&mut NulRuns::new(2), &[b"\0\0\0"]);
+        assert_eq!(starts, [0]);
+        // Reset keeps the threshold.
+        runs.reset();
+        assert!(runs.regions().is_empty());
+        scan_all(&mut runs, &[b"\0\0\0x\0\0\0\0"]);
+        assert_eq!(runs.regions(), [(4, 8)]);
+    }
+}
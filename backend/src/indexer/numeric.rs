@@ -0,0 +1,254 @@
+//! Numeric extractors: a regex capture group (e.g. `took (\d+)ms`) parsed as f64 on every
+//! line during ingest, summarised as count/sum/min/max plus a reservoir sample for
+//! quantiles. Lines split across chunks are buffered until their end by a `ChunkMatcher`,
+//! as in the stream filter.
+
+use regex::bytes::Regex;
+
+use crate::decode::lines::strip_line_terminator;
+use crate::search::matcher::ChunkMatcher;
+
+/// Values kept for quantiles. Up to this many values the quantiles are exact; past it they
+/// come from a uniform sample, with a rank error of about `1 / sqrt(RESERVOIR_SIZE)`
+/// (~1.6%).
+pub const RESERVOIR_SIZE: usize = 4096;
+
+/// Running figures for one extractor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumericSummary {
+    /// Values parsed.
+    pub count: u64,
+    /// Lines that matched but whose group was missing or not a finite number.
+    pub skipped: u64,
+    pub sum: f64,
+    /// `None` until a value is parsed, like `max`.
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Uniform sample of the values (all of them while `count <= RESERVOIR_SIZE`).
+    reservoir: Vec<f64>,
+    /// xorshift64 state for reservoir replacement; fixed seed so results are repeatable.
+    rng: u64,
+}
+
+impl Default for NumericSummary {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            skipped: 0,
+            sum: 0.0,
+            min: None,
+            max: None,
+            reservoir: Vec::new(),
+            rng: 0x2545_F491_4F6C_DD1D,
+        }
+    }
+}
+
+impl NumericSummary {
+    fn add(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.min = Some(self.min.map_or(value, |m| m.min(value)));
+        self.max = Some(self.max.map_or(value, |m| m.max(value)));
+        if self.reservoir.len() < RESERVOIR_SIZE {
+            self.reservoir.push(value);
+            return;
+        }
+        // Algorithm R: the new value replaces a random slot with probability k / count.
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        let slot = self.rng % self.count;
+        if (slot as usize) < RESERVOIR_SIZE {
+            self.reservoir[slot as usize] = value;
+        }
+    }
+
+    #[inline(always)]
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+
+    /// True while the quantiles are computed from every value.
+    #[inline(always)]
+    pub fn exact_quantiles(&self) -> bool {
+        self.count as usize <= RESERVOIR_SIZE
+    }
+
+    /// Nearest-rank quantiles for each `q` in [0, 1], `None` when no values were parsed.
+    pub fn quantiles(&self, qs: &[f64]) -> Vec<Option<f64>> {
+        let mut sorted = self.reservoir.clone();
+        sorted.sort_unstable_by(f64::total_cmp);
+        qs.iter()
+            .map(|&q| {
+                let rank = (q.clamp(0.0, 1.0) * sorted.len() as f64).ceil() as usize;
+                sorted.get(rank.saturating_sub(1)).copied()
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub struct NumericExtractor {
+    regex: Regex,
+    group: usize,
+    /// The line in progress; a line started before the extractor was registered is skipped.
+    matcher: ChunkMatcher,
+    summary: NumericSummary,
+}
+
+impl NumericExtractor {
+    /// `mid_line` means the stream is inside a line already; that line is skipped because
+    /// its beginning was never seen.
+    pub fn new(regex: Regex, group: usize, mid_line: bool) -> Self {
+        Self {
+            regex,
+            group,
+            matcher: ChunkMatcher::new(0, mid_line),
+            summary: NumericSummary::default(),
+        }
+    }
+
+    /// Drops everything extracted so far, keeping the pattern (for a new file).
+    pub fn reset(&mut self) {
+        self.matcher.reset();
+        self.summary = NumericSummary::default();
+    }
+
+    /// Consumes the next bytes of the stream, extracting from every line they complete.
+    pub fn feed(&mut self, chunk: &[u8]) {
+        let (regex, group, summary) = (&self.regex, self.group, &mut self.summary);
+        self.matcher
+            .feed_text(regex, chunk, |m| extract(regex, group, m.text, summary));
+    }
+
+    /// Figures so far. With `include_tail` (the stream has ended), the final line without
+    /// a terminator is included; otherwise it is still incomplete.
+    pub fn summary(&self, include_tail: bool) -> NumericSummary {
+        let mut summary = self.summary.clone();
+        if let Some((_, line)) = self.matcher.pending().filter(|_| include_tail) {
+            extract(&self.regex, self.group, line, &mut summary);
+        }
+        summary
+    }
+
+    /// Heap bytes held (line tail and reservoir).
+    pub fn heap_bytes(&self) -> usize {
+        self.matcher.heap_bytes() + self.summary.reservoir.capacity() * std::mem::size_of::<f64>()
+    }
+}
+
+fn extract(regex: &Regex, group: usize, line: &[u8], summary: &mut NumericSummary) {
+    let Some(captures) = regex.captures(strip_line_terminator(line)) else {
+        return;
+    };
+    let value = captures
+        .get(group)
+        .and_then(|m| std::str::from_utf8(m.as_bytes()).ok())
+        .and_then(|text| text.trim().parse::<f64>().ok())
+        .filter(|v| v.is_finite());
+    match value {
+        Some(value) => summary.add(value),
+        None => summary.skipped += 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::XorShift;
+
+    fn extractor(pattern: &str, group: usize) -> NumericExtractor {
+        NumericExtractor::new(Regex::new(pattern).unwrap(), group, false)
+    }
+
+    #[test]
+    fn integer_values_with_units_stripped() {
+        let mut e = extractor(r"took (\d+)ms", 1);
+        e.feed(b"GET /a took 12ms\nstartup\nGET /b took 3");
+        e.feed(b"0ms\r\nGET /c took 7ms");
+        let s = e.summary(false);
+        assert_eq!(
+            (s.count, s.sum, s.min, s.max),
+            (2, 42.0, Some(12.0), Some(30.0))
+        );
+        let s = e.summary(true);
+        assert_eq!((s.count, s.sum, s.min), (3, 49.0, Some(7.0)));
+        assert_eq!(s.mean(), Some(49.0 / 3.0));
+        assert!(s.exact_quantiles());
+        assert_eq!(
+            s.quantiles(&[0.0, 0.5, 1.0]),
+            [Some(7.0), Some(12.0), Some(30.0)]
+        );
+    }
+
+    #[test]
+    fn unparseable_and_missing_groups_are_skipped() {
+        let mut e = extractor(r"v=(\S+)|none", 1);
+        e.feed(b"v=1.5\nv=abc\nnone\nv=NaN\nv=-2e3\nother\n");
+        let s = e.summary(true);
+        assert_eq!((s.count, s.skipped), (2, 3));
+        assert_eq!((s.min, s.max), (Some(-2000.0), Some(1.5)));
+        let empty = extractor("x", 0).summary(true);
+        assert_eq!((empty.mean(), empty.quantiles(&[0.5])), (None, vec![None]));
+    }
+
+    #[test]
+    fn sampled_quantiles_stay_within_tolerance() {
+        // A shuffled permutation of 1..=n: the exact q-quantile is q * n.
+        let n = 100_000u64;
+        let mut e = extractor(r"(\d+)", 1);
+        let mut text = Vec::new();
+        for i in 0..n {
+            // 7919 is coprime with n, so this visits every value once in scrambled order.
+            text.extend_from_slice(format!("{}\n", (i * 7919) % n + 1).as_bytes());
+        }
+        for chunk in text.chunks(4093) {
+            e.feed(chunk);
+        }
+        let s = e.summary(true);
+        assert_eq!(s.count, n);
+        assert_eq!((s.min, s.max), (Some(1.0), Some(n as f64)));
+        assert!(!s.exact_quantiles());
+        let tolerance = 0.03 * n as f64;
+        for (q, got) in [0.5, 0.95, 0.99]
+            .into_iter()
+            .zip(s.quantiles(&[0.5, 0.95, 0.99]))
+        {
+            let got = got.unwrap();
+            assert!((got - q * n as f64).abs() <= tolerance, "p{q}: {got}");
+        }
+    }
+    #[test]
+    fn random_splits_extract_like_per_line_parsing() {
+        let regex = Regex::new(r"v=(\d*)x").unwrap();
+        let mut rng = XorShift::new(0xC2B2_AE3D_27D4_EB4F);
+        for _ in 0..300 {
+            let text: Vec<u8> = (0..rng.below(100))
+                .map(|_| b"v=19x\n"[rng.below(6) as usize])
+                .collect();
+            let mid_line = rng.below(2) == 1;
+            // Reference: each line parsed on its own; the unterminated last one only with
+            // `include_tail`.
+            let (mut complete, mut all) = (NumericSummary::default(), NumericSummary::default());
+            for (i, line) in text.split_inclusive(|&b| b == b'\n').enumerate() {
+                if i > 0 || !mid_line {
+                    extract(&regex, 1, line, &mut all);
+                    if line.ends_with(b"\n") {
+                        extract(&regex, 1, line, &mut complete);
+                    }
+                }
+            }
+            let mut e = NumericExtractor::new(regex.clone(), 1, mid_line);
+            let mut rest = &text[..];
+            while !rest.is_empty() {
+                let (head, tail) = rest.split_at(1 + rng.below(rest.len() as u64) as usize);
+                e.feed(head);
+                rest = tail;
+            }
+            let text = String::from_utf8_lossy(&text);
+            assert_eq!(e.summary(false), complete, "{text:?}");
+            assert_eq!(e.summary(true), all, "{text:?}");
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Ingest pipeline: the features computed while a chunk is resident. After the scanner
+//! has found a chunk's line starts, the engine hands the chunk to each `ChunkObserver` in
+//! turn, so analytics that need the bytes add an observer instead of a pass of their own
+//! in `index_chunk`.
+//!
+//! The built-in observers split lines on `\n`, which gives the same lines the scanner
+//! found, rather than using `new_line_starts`, which do not line up with a feature view.
+//! Those that match patterns (counters, stream filter, numeric extractors) and the engine's
+//! unseen-match trackers carry bytes between chunks with a `ChunkMatcher` fed through
+//! `feed_text`; the others keep their own partial-line state. Fixed-size records are not
+//! observed at all.
+
+use crate::indexer::format::FormatDetector;
+use crate::indexer::numeric::NumericExtractor;
+use crate::indexer::previews::LinePreviews;
+use crate::indexer::top_lines::TopLines;
+use crate::search::ci_index::CaseIndex;
+use crate::search::counters::PatternCounters;
+use crate::search::stream::StreamFilter;
+
+/// A feature fed every indexed chunk, in file order.
+pub trait ChunkObserver {
+    /// Sees the bytes of one ingest call, starting at file offset `base_offset`, and the
+    /// line starts the scanner found in them (ascending, possibly empty). Header bytes are
+    /// not included. With NUL runs skipped, `chunk` is the scanner's feature view (each
+    /// skip region replaced by line breaks), so its bytes past the first region no longer
+    /// sit at `base_offset + i`.
+    fn observe(&mut self, chunk: &[u8], base_offset: u64, new_line_starts: &[u64]);
+
+    /// Forgets everything observed, keeping configuration (for a new file).
+    fn reset(&mut self);
+
+    /// Releases spare capacity, keeping everything observed (for `compact`).
+    fn shrink_to_fit(&mut self) {}
+}
+
+impl ChunkObserver for FormatDetector {
+    fn observe(&mut self, chunk: &[u8], _base_offset: u64, _new_line_starts: &[u64]) {
+        self.feed(chunk);
+    }
+
+    fn reset(&mut self) {
+        self.reset();
+    }
+}
+
+impl ChunkObserver for PatternCounters {
+    fn observe(&mut self, chunk: &[u8], _base_offset: u64, _new_line_starts: &[u64]) {
+        self.feed(chunk);
+    }
+
+    fn reset(&mut self) {
+        self.reset();
+    }
+}
+
+impl ChunkObserver for StreamFilter {
+    fn observe(&mut self, chunk: &[u8], _base_offset: u64, _new_line_starts: &[u64]) {
+        self.feed(chunk);
+    }
+
+    fn reset(&mut self) {
+        self.reset();
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.shrink_to_fit();
+    }
+}
+
+impl ChunkObserver for CaseIndex {
+    fn observe(&mut self, chunk: &[u8], _base_offset: u64, _new_line_starts: &[u64]) {
+        self.feed(chunk);
+    }
+
+    fn reset(&mut self) {
+        self.reset();
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.shrink_to_fit();
+    }
+}
+
+impl ChunkObserver for TopLines {
+    fn observe(&mut self, chunk: &[u8], _base_offset: u64, _new_line_starts: &[u64]) {
+        self.feed(chunk);
+    }
+
+    fn reset(&mut self) {
+        self.reset();
+    }
+}
+
+impl ChunkObserver for LinePreviews {
+    fn observe(&mut self, chunk: &[u8], _base_offset: u64, _new_line_starts: &[u64]) {
+        self.feed(chunk);
+    }
+
+    fn reset(&mut self) {
+        self.reset();
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.shrink_to_fit();
+    }
+}
+
+impl ChunkObserver for NumericExtractor {
+    fn observe(&mut self, chunk: &[u8], _base_offset: u64, _new_line_starts: &[u64]) {
+        self.feed(chunk);
+    }
+
+    fn reset(&mut self) {
+        self.reset();
+    }
+}
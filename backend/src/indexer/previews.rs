@@ -0,0 +1,209 @@
+//! First-bytes preview of every line, kept while indexing so a scrolled-to list can paint
+//! at once while the full lines are read from the file. The first `max_bytes` of each
+//! line (terminator excluded) go back to back into one arena, with a `u32` end per line.
+
+use memchr::memchr_iter;
+
+/// Flag bit of an `ends` entry: the line is longer than its preview.
+const TRUNCATED: u32 = 1 << 31;
+
+/// Largest arena; later lines keep an empty preview (flagged truncated).
+const MAX_ARENA: usize = (TRUNCATED - 1) as usize;
+
+#[derive(Debug)]
+pub struct LinePreviews {
+    max_bytes: usize,
+    /// Global index of the line `ends[0]` describes.
+    first_line: u64,
+    /// Previews of the lines from `first_line` on, the current line's last.
+    arena: Vec<u8>,
+    /// End in `arena` of each complete line's preview, or'ed with `TRUNCATED`.
+    ends: Vec<u32>,
+    /// Bytes of the current line seen so far.
+    line_len: usize,
+    /// A `\r` held back until it is known not to precede the `\n`.
+    pending_cr: bool,
+    /// The current line started before previews were enabled; it gets none.
+    skip_line: bool,
+}
+
+impl LinePreviews {
+    /// Keeps the first `max_bytes` of every line from line `first_line` on (`mid_line`:
+    /// that line is already in progress and is skipped).
+    pub fn new(max_bytes: usize, first_line: u64, mid_line: bool) -> Self {
+        Self {
+            max_bytes,
+            first_line: first_line + mid_line as u64,
+            arena: Vec::new(),
+            ends: Vec::new(),
+            line_len: 0,
+            pending_cr: false,
+            skip_line: mid_line,
+        }
+    }
+
+    /// Forgets all previews and restarts at line 0, keeping the length.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.max_bytes, 0, false);
+    }
+
+    /// Drops the previews of lines before `line` (evicted by retention).
+    pub fn evict_before(&mut self, line: u64) {
+        let evicted = line
+            .saturating_sub(self.first_line)
+            .min(self.ends.len() as u64) as usize;
+        if evicted == 0 {
+            return;
+        }
+        let cut = self.ends[evicted - 1] & !TRUNCATED;
+        self.arena.drain(..cut as usize);
+        self.ends.drain(..evicted);
+        for end in &mut self.ends {
+            *end -= cut;
+        }
+        self.first_line += evicted as u64;
+    }
+
+    /// Consumes the next bytes of the stream, keeping the start of every line.
+    pub fn feed(&mut self, chunk: &[u8]) {
+        let mut start = 0;
+        for nl in memchr_iter(b'\n', chunk) {
+            let body = &chunk[start..nl];
+            // A `\r` right before the `\n` is part of the terminator.
+            match body.split_last() {
+                Some((b'\r', head)) => self.keep(head),
+                Some(_) => self.keep(body),
+                None => {}
+            }
+            self.end_line();
+            start = nl + 1;
+        }
+        let tail = &chunk[start..];
+        match tail.split_last() {
+            Some((b'\r', head)) => {
+                self.keep(head);
+                self.pending_cr = true;
+            }
+            Some(_) => self.keep(tail),
+            None => {}
+        }
+    }
+
+    /// Previews of lines `[start, end)` that have one, as `(line, bytes, truncated)`. The
+    /// current line is included with the bytes seen so far (a held-back `\r` left out).
+    pub fn previews(&self, start: u64, end: u64) -> Vec<(u64, &[u8], bool)> {
+        let current = !self.skip_line && (self.line_len > 0 || self.pending_cr);
+        let last = self.first_line + self.ends.len() as u64 + current as u64;
+        (start.max(self.first_line)..end.min(last))
+            .map(|line| {
+                let i = (line - self.first_line) as usize;
+                let from = i.checked_sub(1).map_or(0, |j| self.ends[j] & !TRUNCATED) as usize;
+                match self.ends.get(i) {
+                    Some(&end) => {
+                        let to = (end & !TRUNCATED) as usize;
+                        (line, &self.arena[from..to], end & TRUNCATED != 0)
+                    }
+                    None => {
+                        let kept = &self.arena[from..];
+                        (line, kept, self.line_len > kept.len())
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Releases spare capacity of the arena and the line ends.
+    pub fn shrink_to_fit(&mut self) {
+        self.arena.shrink_to_fit();
+        self.ends.shrink_to_fit();
+    }
+
+    pub fn heap_bytes(&self) -> usize {
+        self.arena.capacity() + self.ends.capacity() * std::mem::size_of::<u32>()
+    }
+
+    fn keep(&mut self, bytes: &[u8]) {
+        if std::mem::take(&mut self.pending_cr) {
+            self.keep(b"\r");
+        }
+        if !self.skip_line {
+            let room = self.max_bytes.saturating_sub(self.line_len);
+            let room = room.min(MAX_ARENA.saturating_sub(self.arena.len()));
+            self.arena
+                .extend_from_slice(&bytes[..bytes.len().min(room)]);
+        }
+        self.line_len = self.line_len.saturating_add(bytes.len());
+    }
+
+    fn end_line(&mut self) {
+        if !self.skip_line {
+            let start = self.ends.last().map_or(0, |&end| end & !TRUNCATED) as usize;
+            let truncated = self.line_len > self.arena.len() - start;
+            self.ends
+                .push(self.arena.len() as u32 | if truncated { TRUNCATED } else { 0 });
+        }
+        self.line_len = 0;
+        self.pending_cr = false;
+        self.skip_line = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fed(max_bytes: usize, pieces: &[&[u8]]) -> LinePreviews {
+        let mut previews = LinePreviews::new(max_bytes, 0, false);
+        for piece in pieces {
+            previews.feed(piece);
+        }
+        previews
+    }
+
+    #[test]
+    fn keeps_the_start_of_each_line_across_chunks() {
+        let text: &[u8] = b"short\r\nexactly8\nlonger than eight\n\r\nx\ry\ntail of the file";
+        let expected: Vec<(u64, &[u8], bool)> = vec![
+            (0, b"short", false),
+            (1, b"exactly8", false),
+            (2, b"longer t", true),
+            (3, b"", false),
+            (4, b"x\ry", false),
+            (5, b"tail of ", true),
+        ];
+        for split in 0..=text.len() {
+            let (head, tail) = text.split_at(split);
+            let previews = fed(8, &[head, tail]);
+            assert_eq!(previews.previews(0, 10), expected, "split {split}");
+        }
+        let previews = fed(8, &[text]);
+        assert_eq!(previews.previews(2, 4), expected[2..4]);
+        assert!(previews.previews(6, 9).is_empty());
+        // A `\r` held back at a chunk end shows once the line goes on.
+        let previews = fed(8, &[b"a\r", b"b\n"]);
+        assert_eq!(previews.previews(0, 1), [(0, &b"a\rb"[..], false)]);
+    }
+
+    #[test]
+    fn skips_the_line_in_progress_and_evicts_from_the_front() {
+        let mut previews = LinePreviews::new(4, 7, true);
+        previews.feed(b"rest of 7\nline8\nl9\n");
+        assert_eq!(
+            previews.previews(0, 10),
+            [(8, &b"line"[..], true), (9, &b"l9"[..], false)]
+        );
+        previews.evict_before(9);
+        assert_eq!(previews.previews(0, 10), [(9, &b"l9"[..], false)]);
+        assert_eq!(previews.arena, b"l9");
+        previews.reset();
+        previews.feed(b"abcdef");
+        assert_eq!(previews.previews(0, 1), [(0, &b"abcd"[..], true)]);
+    }
+
+    #[test]
+    fn memory_is_the_arena_and_one_end_per_line() {
+        let mut previews = fed(3, &[b"aaaaa\nbb\nc\n".repeat(100).as_slice()]);
+        previews.shrink_to_fit();
+        assert_eq!(previews.heap_bytes(), 600 + 300 * 4);
+    }
+}
@@ -15,8 +15,12 @@ use memchr::memchr_iter;
 /// * `chunk_starts_new_line` - If true, the first byte of `chunk` is the start of a line
 ///   (previous chunk ended with a newline). Pushes `base_offset` as first line start when true.
 ///
+/// A start after a newline at the very end of `chunk` is not pushed: it is deferred to the
+/// next non-empty chunk, so a file ending in `\n` has no empty line after it.
+///
 /// # Returns
-/// `true` if `chunk` ends with a newline (so the next chunk starts a new line).
+/// `true` if `chunk` ends with a newline (so the next chunk starts a new line). An empty
+/// chunk leaves the state unchanged.
 #[inline(always)]
 pub fn scan_chunk(
     chunk: &[u8],
@@ -25,17 +29,19 @@ pub fn scan_chunk(
     chunk_starts_new_line: bool,
 ) -> bool {
     if chunk.is_empty() {
-        return true;
+        return chunk_starts_new_line;
     }
 
     if chunk_starts_new_line {
         line_starts.push(base_offset);
     }
 
-    let base = base_offset as u64;
-
+    let last = chunk.len() - 1;
     for pos in memchr_iter(b'\n', chunk) {
-        let off = base + (pos as u64);
+        if pos == last {
+            break;
+        }
+        let off = base_offset + (pos as u64);
         // Line start after this newline is the next byte. Handles both \n and \r\n.
         line_starts.push(off + 1);
     }
@@ -44,6 +50,34 @@ pub fn scan_chunk(
     chunk.last() == Some(&b'\n')
 }
 
+/// Fixed-size record counterpart of `scan_chunk`, for logs without delimiters: every
+/// `record_size` bytes of the file form one record and newlines are ignored. Pushes the
+/// start of each record that begins inside the chunk (file offsets that are multiples of
+/// `record_size`). A record split across chunks continues from its partial byte count,
+/// `base_offset % record_size`.
+///
+/// # Returns
+/// `true` if the chunk ends exactly on a record boundary.
+pub fn scan_fixed_records(
+    chunk_len: usize,
+    base_offset: u64,
+    record_size: u64,
+    line_starts: &mut Vec<u64>,
+) -> bool {
+    let end = base_offset + chunk_len as u64;
+    let partial = base_offset % record_size;
+    let mut start = if partial == 0 {
+        base_offset
+    } else {
+        base_offset + (record_size - partial)
+    };
+    while start < end {
+        line_starts.push(start);
+        start += record_size;
+    }
+    end.is_multiple_of(record_size)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -54,7 +88,7 @@ mod tests {
         let mut starts = Vec::new();
         let ends = scan_chunk(chunk, 0, &mut starts, true);
         assert!(ends);
-        assert_eq!(starts, [0, 2, 4, 6]);
+        assert_eq!(starts, [0, 2, 4]);
     }
 
     #[test]
@@ -63,7 +97,23 @@ mod tests {
         let mut starts = Vec::new();
         let ends = scan_chunk(chunk, 0, &mut starts, true);
         assert!(ends);
-        assert_eq!(starts, [0, 3, 6]);
+        assert_eq!(starts, [0, 3]);
+    }
+
+    #[test]
+    fn trailing_newline_start_is_deferred_to_the_next_chunk() {
+        let mut starts = Vec::new();
+        let mut ends = scan_chunk(b"a\n", 0, &mut starts, true);
+        assert_eq!(starts, [0]);
+        // Empty chunks keep the boundary state either way.
+        assert!(scan_chunk(b"", 2, &mut starts, ends));
+        assert!(!scan_chunk(b"", 2, &mut starts, false));
+        ends = scan_chunk(b"\nb\n", 2, &mut starts, ends);
+        assert!(ends);
+        // Line 1 is empty; no start after the final newline and no duplicate at 2.
+        assert_eq!(starts, [0, 2, 3]);
+        assert!(!scan_chunk(b"c", 5, &mut starts, ends));
+        assert_eq!(starts, [0, 2, 3, 5]);
     }
 
     #[test]
@@ -75,4 +125,22 @@ mod tests {
         assert!(!ends);
         assert_eq!(starts, [18]); // line start after \n (base 10 + 7 + 1)
     }
+
+    #[test]
+    fn fixed_records_span_chunks() {
+        // 10-byte records fed in 7-byte chunks: boundaries rarely line up.
+        let mut starts = Vec::new();
+        let mut ends = Vec::new();
+        for base in (0..45).step_by(7) {
+            let len = 7.min(45 - base);
+            ends.push(scan_fixed_records(len, base as u64, 10, &mut starts));
+        }
+        assert_eq!(starts, [0, 10, 20, 30, 40]);
+        // Chunks end at 7, 14, ..., 42, 45: never on a multiple of 10.
+        assert_eq!(ends, [false; 7]);
+        // Newlines and chunk contents play no part.
+        let mut starts = Vec::new();
+        assert!(scan_fixed_records(20, 0, 4, &mut starts));
+        assert_eq!(starts, [0, 4, 8, 12, 16]);
+    }
 }
@@ -0,0 +1,229 @@
+//! Most frequent lines, for "what are we logging millions of times?". A space-saving
+//! sketch over per-line hashes, fed while indexing: memory is bounded by the number of
+//! counters however large the file is, and each counter keeps one line index so JS can
+//! fetch a representative through the normal range/decode flow.
+
+use std::collections::{BTreeSet, HashMap};
+
+use memchr::memchr_iter;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// A tracked line: approximate count and how much of it may be overestimated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeavyHitter {
+    /// A line with this content (the one seen when the counter was claimed).
+    pub line: u64,
+    /// Estimated occurrences; never less than the true count.
+    pub count: u64,
+    /// Upper bound on the overestimate: the true count is at least `count - error`.
+    pub error: u64,
+}
+
+#[derive(Debug)]
+pub struct TopLines {
+    capacity: usize,
+    /// Bytes at the start of each line left out of its hash (e.g. a timestamp prefix).
+    skip_bytes: usize,
+    counters: HashMap<u64, HeavyHitter>,
+    /// `(count, hash)` of every counter, so the smallest one is found in O(log n).
+    by_count: BTreeSet<(u64, u64)>,
+    /// Hash state of the current line.
+    hash: u64,
+    /// Bytes of the current line seen so far.
+    line_len: usize,
+    /// A `\r` held back until it is known not to precede the `\n`.
+    pending_cr: bool,
+    /// Global index of the current line.
+    line: u64,
+    /// The current line started before the sketch was enabled; it is not counted.
+    skip_line: bool,
+}
+
+impl TopLines {
+    /// Tracks up to `capacity` distinct lines starting at line `first_line` (`mid_line`:
+    /// that line is already in progress and is skipped). Estimates for the top `k` are
+    /// good when `capacity` is several times `k`.
+    pub fn new(capacity: usize, skip_bytes: usize, first_line: u64, mid_line: bool) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            skip_bytes,
+            counters: HashMap::new(),
+            by_count: BTreeSet::new(),
+            hash: FNV_OFFSET,
+            line_len: 0,
+            pending_cr: false,
+            line: first_line,
+            skip_line: mid_line,
+        }
+    }
+
+    /// Forgets all counts and restarts at line 0, keeping the settings.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.capacity, self.skip_bytes, 0, false);
+    }
+
+    /// Consumes the next bytes of the stream, counting every line they complete.
+    pub fn feed(&mut self, chunk: &[u8]) {
+        let mut start = 0;
+        for nl in memchr_iter(b'\n', chunk) {
+            let body = &chunk[start..nl];
+            // A `\r` right before the `\n` is part of the terminator.
+            match body.split_last() {
+                Some((b'\r', head)) => self.hash_bytes(head),
+                Some(_) => self.hash_bytes(body),
+                None => {}
+            }
+            self.end_line();
+            start = nl + 1;
+        }
+        let tail = &chunk[start..];
+        match tail.split_last() {
+            Some((b'\r', head)) => {
+                self.hash_bytes(head);
+                self.pending_cr = true;
+            }
+            Some(_) => self.hash_bytes(tail),
+            None => {}
+        }
+    }
+
+    /// Up to `k` most frequent lines, highest count first; ties in line order.
+    pub fn top(&self, k: usize) -> Vec<HeavyHitter> {
+        let mut hitters: Vec<HeavyHitter> = self.counters.values().copied().collect();
+        hitters.sort_unstable_by_key(|h| (std::cmp::Reverse(h.count), h.line));
+        hitters.truncate(k);
+        hitters
+    }
+
+    /// Approximate heap use, for memory stats.
+    pub fn heap_bytes(&self) -> usize {
+        let entry = std::mem::size_of::<(u64, HeavyHitter)>() + std::mem::size_of::<(u64, u64)>();
+        self.counters.capacity() * entry
+    }
+
+    fn hash_bytes(&mut self, bytes: &[u8]) {
+        if std::mem::take(&mut self.pending_cr) {
+            self.hash_byte(b'\r');
+        }
+        for &b in bytes {
+            self.hash_byte(b);
+        }
+    }
+
+    #[inline(always)]
+    fn hash_byte(&mut self, b: u8) {
+        if self.line_len >= self.skip_bytes {
+            self.hash = (self.hash ^ b as u64).wrapping_mul(FNV_PRIME);
+        }
+        self.line_len += 1;
+    }
+
+    fn end_line(&mut self) {
+        if !self.skip_line {
+            self.count(self.hash, self.line);
+        }
+        self.hash = FNV_OFFSET;
+        self.line_len = 0;
+        self.pending_cr = false;
+        self.skip_line = false;
+        self.line += 1;
+    }
+
+    /// Space-saving update: bump a tracked line, or take over the smallest counter.
+    fn count(&mut self, hash: u64, line: u64) {
+        if let Some(hitter) = self.counters.get_mut(&hash) {
+            self.by_count.remove(&(hitter.count, hash));
+            hitter.count += 1;
+            self.by_count.insert((hitter.count, hash));
+            return;
+        }
+        let mut hitter = HeavyHitter {
+            line,
+            count: 1,
+            error: 0,
+        };
+        if self.counters.len() == self.capacity {
+            if let Some((min, evicted)) = self.by_count.pop_first() {
+                self.counters.remove(&evicted);
+                hitter.count = min + 1;
+                hitter.error = min;
+            }
+        }
+        self.counters.insert(hash, hitter);
+        self.by_count.insert((hitter.count, hash));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_heavy_hitters_in_noisy_stream() {
+        // 3 frequent messages among 2000 distinct one-off lines.
+        let mut text = Vec::new();
+        let mut kinds = Vec::new();
+        for i in 0..5000u32 {
+            let kind = match i % 10 {
+                0 | 3 | 6 => Some(0),
+                1 | 7 => Some(1),
+                4 => Some(2),
+                _ => None,
+            };
+            let line = match kind {
+                Some(f) => format!("12:00:{i:05} frequent {f}\r\n"),
+                None => format!("12:00:{i:05} unique {i}\n"),
+            };
+            text.extend_from_slice(line.as_bytes());
+            kinds.push(kind);
+        }
+        let mut sketch = TopLines::new(50, "12:00:00000 ".len(), 0, false);
+        for chunk in text.chunks(97) {
+            sketch.feed(chunk);
+        }
+        let top = sketch.top(3);
+        for (kind, (hitter, true_count)) in top.iter().zip([1500, 1000, 500]).enumerate() {
+            assert!(hitter.count >= true_count, "{hitter:?}");
+            assert!(hitter.count - hitter.error <= true_count, "{hitter:?}");
+            // The representative is a line with that message.
+            assert_eq!(kinds[hitter.line as usize], Some(kind), "{hitter:?}");
+        }
+        assert!(sketch.counters.len() <= 50);
+    }
+
+    #[test]
+    fn skipped_prefix_and_crlf_do_not_split_counts() {
+        let mut sketch = TopLines::new(8, 3, 0, false);
+        sketch.feed(b"01 hello\r\n02 hel");
+        sketch.feed(b"lo\n03 hello\r");
+        sketch.feed(b"\n04 bye\n");
+        assert_eq!(
+            sketch.top(2),
+            [
+                HeavyHitter {
+                    line: 0,
+                    count: 3,
+                    error: 0
+                },
+                HeavyHitter {
+                    line: 3,
+                    count: 1,
+                    error: 0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn enabled_mid_line_skips_that_line() {
+        let mut sketch = TopLines::new(4, 0, 10, true);
+        sketch.feed(b"same\nsame\n");
+        assert_eq!(sketch.top(1)[0].line, 11);
+        assert_eq!(sketch.top(1)[0].count, 1);
+        sketch.reset();
+        sketch.feed(b"same\n");
+        assert_eq!(sketch.top(1)[0].line, 0);
+    }
+}
@@ -2,51 +2,708 @@
 //! line count, get lines, and explicit clear.
 
 use once_cell::sync::Lazy;
+use serde::Serialize;
 use std::sync::RwLock;
 use wasm_bindgen::prelude::*;
 
 mod core;
+mod decode;
+mod diagnostics;
 mod indexer;
 mod search;
+#[cfg(test)]
+mod test_util;
 
+use core::bounds::line_index;
 use core::engine::LogEngine;
-use indexer::scanner::scan_chunk;
-use search::matcher::match_lines;
+use core::ffi_num::{byte_len, file_bytes, to_f64, to_f64_all, PrecisionLoss};
+use core::live::MixedIngest;
+use core::offsets::{LineOffsets, OffsetOrderError};
+use core::profile::{now_ms, Category};
+use core::retention::RetentionPolicy;
+use core::types::{
+    LimitedSearch, LineMatchPositions, NumericStats, RenderedLine, SearchStats, SearchSummary,
+    TokenCount, TokenFrequencies, TopLine,
+};
+use decode::lines::{
+    check_blob_len, check_line_ends, clamped_line_slices, decode_line_slice, decode_lines_checked,
+    decode_window_line, skipped_run_placeholder, strip_line_terminator, DecodeConfig, LineEndings,
+    Utf8Mode, DEFAULT_LINE_SEPARATOR,
+};
+use decode::render::{match_char_ranges, render_line, wrapped_rows, RenderOptions};
+use indexer::checksum::{verify_crc32, ChecksumMismatch};
+use indexer::nul_runs::NulRuns;
+use indexer::scanner::{scan_chunk, scan_fixed_records};
+use search::compiled::CompiledSearch;
+use search::content::ContentFilter;
+use search::matcher::{
+    any_line_matches, count_lines_limit, count_occurrences, first_matching_line, match_lines_any,
+    match_lines_byte_class, match_lines_chunked, match_lines_limit, match_page, match_positions,
+    match_start_lines, ChunkedSearch,
+};
+use search::rules::RuleSet;
+
+#[wasm_bindgen]
+extern "C" {
+    /// High-resolution timer, available on both window and worker globals.
+    #[wasm_bindgen(js_namespace = performance, js_name = now)]
+    fn performance_now() -> f64;
+
+    /// Macrotask scheduling, so async exports let queued messages (e.g. cancel) run.
+    #[wasm_bindgen(js_name = setTimeout)]
+    fn set_timeout(handler: &js_sys::Function, ms: i32);
+}
+
+/// Lines scanned per batch by async exports before yielding to the event loop.
+const ASYNC_BATCH_LINES: usize = 64 * 1024;
+
+/// Chunk size `search()` feeds the retained window to its `ChunkMatcher` in.
+const SEARCH_CHUNK_BYTES: usize = 1024 * 1024;
+
+/// Precise TypeScript types for the exports marked `skip_typescript` (which would
+/// otherwise be typed `any`). Emitted into the generated package's index.d.ts.
+#[wasm_bindgen(typescript_custom_section)]
+const TS_DECLARATIONS: &str = include_str!("bindings.d.ts");
 
 /// Global engine instance. Single-threaded WASM implies one active log session.
 static ENGINE: Lazy<RwLock<LogEngine>> = Lazy::new(|| RwLock::new(LogEngine::new()));
 
+/// TLSF allocator (`alloc-tlsf` feature): bounded fragmentation, so memory freed by
+/// `clear()` and `trim_memory()` is reusable by the next file's large arrays.
+#[cfg(feature = "alloc-tlsf")]
+#[global_allocator]
+static ALLOCATOR: rlsf::GlobalTlsf = rlsf::GlobalTlsf::new();
+
+/// Turns on diagnostics: installs a panic hook that prints the Rust panic message (instead
+/// of only "unreachable executed") and routes engine logs to the console at `level`
+/// (0 off, 1 error, 2 warn, 3 info, 4 debug, 5 trace). Safe to call again to change level.
+#[wasm_bindgen]
+pub fn init_diagnostics(level: u32) {
+    diagnostics::init(level);
+}
+
 /// Returns a pointer to the write region for the next chunk. JS should write up to
 /// `size` bytes there, then call `index_chunk(chunk_len)` with the actual length.
 ///
+/// Grants exactly `size` bytes (see `get_granted_size`) to the next `index_chunk`,
+/// `index_chunk_checked` or `index_chunks`, which throw for a longer submission or without
+/// a grant; each submission consumes it, as does `clear_data()`.
+///
 /// **Important:** Do not cache this pointer in JS. Call `get_buffer_pointer(size)` immediately
 /// before each chunk write; if the buffer is reallocated (e.g. by `reserve`), a previously
 /// obtained pointer becomes invalid.
+/// Throws if `size` is not a byte count.
 #[wasm_bindgen]
-pub fn get_buffer_pointer(size: usize) -> *mut u8 {
-    ENGINE
+pub fn get_buffer_pointer(size: f64) -> Result<*mut u8, JsError> {
+    let size = byte_len("size", size).map_err(js_error)?;
+    Ok(ENGINE
         .write()
         .expect("engine lock")
-        .get_buffer_pointer(size)
+        .get_buffer_pointer(size))
+}
+
+/// Like `get_buffer_pointer`, but returns `{ ptr, capacity }` where `capacity` (>= `size`)
+/// is the number of bytes JS may actually write at `ptr` before calling `index_chunk`, and
+/// grants all of them. Lets JS opportunistically write a larger chunk in one go.
+///
+/// **Important:** The same rule as `get_buffer_pointer` applies: do not cache `ptr`.
+/// Throws if `size` is not a byte count.
+#[wasm_bindgen(skip_typescript)]
+pub fn get_write_region(size: f64) -> Result<JsValue, JsError> {
+    let size = byte_len("size", size).map_err(js_error)?;
+    let region = ENGINE.write().expect("engine lock").get_write_region(size);
+    to_js(&region)
+}
+
+/// Bytes the next chunk submission may contain: the grant from the last
+/// `get_buffer_pointer` or `get_write_region`, or 0 once it was used or revoked.
+#[wasm_bindgen]
+pub fn get_granted_size() -> Result<f64, JsError> {
+    let granted = ENGINE.read().expect("engine lock").granted_size();
+    to_f64(granted as u64).map_err(js_error)
+}
+
+/// Converts a `core::types` value to a plain JS object.
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsError> {
+    serde_wasm_bindgen::to_value(value).map_err(js_error)
+}
+
+/// Converts an engine error into a `JsError`, logging it as a warning first.
+fn js_error(e: impl std::fmt::Display) -> JsError {
+    log::warn!("{e}");
+    JsError::new(&e.to_string())
 }
 
 /// Indexes the chunk of length `chunk_len` that JS wrote into the buffer. Scans for
 /// newlines and appends line-start offsets. Handles lines split across chunk boundaries.
 /// Buffer content is discarded after indexing so only offsets are kept (avoids 10GB in WASM).
+/// Throws (discarding the chunk) while paused (see `pause_ingest`), in a live session
+/// (see `append_live`), if `chunk_len` is not a byte count, and if it is not covered by a
+/// write grant (see `get_buffer_pointer`).
 #[wasm_bindgen]
-pub fn index_chunk(chunk_len: usize) {
+pub fn index_chunk(chunk_len: f64) -> Result<(), JsValue> {
     let mut engine = ENGINE.write().expect("engine lock");
-    let base = engine.total_bytes_indexed();
-    let starts_new_line = engine.last_chunk_ended_with_newline();
-    let (line_starts, ends_with_newline) = {
-        let chunk = engine.append_chunk(chunk_len);
-        let mut line_starts = Vec::new();
-        let ends = scan_chunk(chunk, base, &mut line_starts, starts_new_line);
-        (line_starts, ends)
+    let checked = check_file_ingest(&engine)
+        .and_then(|()| byte_len("chunk_len", chunk_len).map_err(|e| js_error(e).into()))
+        .and_then(|len| check_grant(&mut engine, len));
+    if let Ok(chunk_len) = checked {
+        index_written_chunks(&mut engine, &[chunk_len]);
+    }
+    engine.discard_buffer_after_indexing();
+    checked.map(drop)
+}
+
+/// Guard for the file ingest exports. While paused, throws an `Error` whose `name` is
+/// `"Paused"`, so JS can tell it from other failures and resubmit the chunk after
+/// `resume_ingest()`; in a live session, throws a plain error.
+fn check_file_ingest(engine: &LogEngine) -> Result<(), JsValue> {
+    if let Err(e) = engine.check_not_paused() {
+        let err = js_sys::Error::new(&e.to_string());
+        err.set_name("Paused");
+        return Err(err.into());
+    }
+    engine.check_file_ingest().map_err(|e| js_error(e).into())
+}
+
+/// Consumes the write grant for a submission of `len` bytes, returning `len`.
+fn check_grant(engine: &mut LogEngine, len: usize) -> Result<usize, JsValue> {
+    engine.take_grant(len).map_err(js_error)?;
+    Ok(len)
+}
+
+/// Like `index_chunk`, but first verifies the CRC32 (IEEE) of the `chunk_len` bytes JS
+/// wrote against `expected_crc`. On mismatch throws, discards the bytes and leaves the
+/// index unchanged, so JS can re-read and resubmit the chunk (after a new
+/// `get_buffer_pointer`). Throws while paused, in a live session, if `chunk_len` is not a
+/// byte count, and without a write grant covering it.
+#[wasm_bindgen]
+pub fn index_chunk_checked(chunk_len: f64, expected_crc: u32) -> Result<(), JsValue> {
+    let mut engine = ENGINE.write().expect("engine lock");
+    let checked = check_file_ingest(&engine)
+        .and_then(|()| byte_len("chunk_len", chunk_len).map_err(|e| js_error(e).into()))
+        .and_then(|len| check_grant(&mut engine, len));
+    let chunk_len = match checked {
+        Ok(chunk_len) => chunk_len,
+        Err(e) => {
+            engine.discard_buffer_after_indexing();
+            return Err(e);
+        }
     };
-    engine.append_offsets(&line_starts);
-    engine.advance_after_chunk(chunk_len, ends_with_newline);
+    index_written_checked(&mut engine, chunk_len, expected_crc).map_err(|e| js_error(e).into())
+}
+
+/// Verifies and indexes one written chunk; discards the buffer either way.
+fn index_written_checked(
+    engine: &mut LogEngine,
+    chunk_len: usize,
+    expected_crc: u32,
+) -> Result<(), ChecksumMismatch> {
+    let verified = verify_crc32(engine.append_chunk(chunk_len), expected_crc);
+    engine.unappend_chunk(chunk_len);
+    if verified.is_ok() {
+        index_written_chunks(engine, &[chunk_len]);
+    }
+    engine.discard_buffer_after_indexing();
+    verified
+}
+
+/// Copy-in alternative to the pointer protocol: copies `data` into the engine buffer
+/// (reusing its capacity) and indexes it exactly like `get_buffer_pointer` + `index_chunk`.
+/// Costs one copy, but there is no pointer for JS to mishandle. Throws while paused and in
+/// a live session.
+#[wasm_bindgen]
+pub fn index_bytes(data: &js_sys::Uint8Array) -> Result<(), JsValue> {
+    let len = data.length() as usize;
+    let mut engine = ENGINE.write().expect("engine lock");
+    check_file_ingest(&engine)?;
+    let ptr = engine.get_buffer_pointer(len);
+    // SAFETY: get_buffer_pointer reserved `len` writable bytes at `ptr`.
+    unsafe { data.raw_copy_to_ptr(ptr) };
+    index_written_chunks(&mut engine, &[len]);
+    engine.discard_buffer_after_indexing();
+    Ok(())
+}
+
+/// `index_bytes` for a string (UTF-8 bytes). Meant for small fixtures; for a stream of
+/// text to view without a backing file, use `append_live`. Throws while paused and in a
+/// live session.
+#[wasm_bindgen]
+pub fn index_text(s: &str) -> Result<(), JsValue> {
+    let mut engine = ENGINE.write().expect("engine lock");
+    check_file_ingest(&engine)?;
+    index_copied(&mut engine, s.as_bytes());
+    Ok(())
+}
+
+/// Appends text from a live source (WebSocket, SSE) to the stream and returns the new
+/// line count. Lines are indexed and fed to every ingest-time feature like file chunks; a
+/// final line without a newline is completed by later appends. Because there is no file
+/// to read back from, the most recent bytes are kept (see `set_live_retention`) and read
+/// with `read_live`. Throws if file data was indexed in this session (`clear_data()`
+/// first); file ingestion throws once a session is live.
+#[wasm_bindgen]
+pub fn append_live(text: &str) -> Result<u32, JsError> {
+    let mut engine = ENGINE.write().expect("engine lock");
+    append_live_bytes(&mut engine, text.as_bytes())
+        .map(|lines| lines as u32)
+        .map_err(js_error)
+}
+
+/// Indexes `bytes` as the next part of a live stream and retains them for display.
+fn append_live_bytes(engine: &mut LogEngine, bytes: &[u8]) -> Result<usize, MixedIngest> {
+    append_live_at(engine, bytes, now_ms())
+}
+
+/// `append_live_bytes` with the arrival time given, for age-based retention.
+fn append_live_at(engine: &mut LogEngine, bytes: &[u8], now_ms: f64) -> Result<usize, MixedIngest> {
+    engine.begin_live()?;
+    let lines_before = engine.line_count();
+    index_copied(engine, bytes);
+    let new_lines = engine.line_count() - lines_before;
+    engine.retain_live(bytes, new_lines, now_ms);
+    Ok(engine.line_count())
+}
+
+/// Bytes of live lines [start_line, end_line) from the retained window, to decode with
+/// `plan_window` + `decode_lines_from_blob` as if read from a file. Throws outside a live
+/// session, when part of the range has been evicted (see `live_first_line`) and for a
+/// bound that is not a line index.
+#[wasm_bindgen]
+pub fn read_live(start_line: f64, end_line: f64) -> Result<Vec<u8>, JsError> {
+    let start_line = line_index("start_line", start_line).map_err(js_error)?;
+    let end_line = line_index("end_line", end_line).map_err(js_error)?;
+    let engine = ENGINE.read().expect("engine lock");
+    let first = engine
+        .live_first_line()
+        .ok_or_else(|| js_error("not a live session"))?;
+    engine
+        .read_live(start_line, end_line)
+        .map(<[u8]>::to_vec)
+        .ok_or_else(|| js_error(format!("lines before {first} are no longer retained")))
+}
+
+/// First line of a live stream still fully retained for `read_live`, or -1 outside a live
+/// session.
+#[wasm_bindgen]
+pub fn live_first_line() -> Result<f64, JsError> {
+    ENGINE
+        .read()
+        .expect("engine lock")
+        .live_first_line()
+        .map_or(Ok(-1.0), |line| to_f64(line as u64).map_err(js_error))
+}
+
+/// Keeps at least the latest `bytes` (and at most twice that) of a live stream for
+/// `read_live`; default 16 MiB. Throws unless `bytes` is a byte count. Kept by
+/// `clear_data()`, reset by `clear()`.
+#[wasm_bindgen]
+pub fn set_live_retention(bytes: f64) -> Result<(), JsError> {
+    let bytes = byte_len("bytes", bytes).map_err(js_error)?;
+    ENGINE
+        .write()
+        .expect("engine lock")
+        .set_live_retained_bytes(bytes);
+    Ok(())
+}
+
+/// Caps the history of a live session: after each `append_live`, the oldest lines beyond
+/// `max_lines`, or that arrived more than `max_age_ms` ago, are evicted from the index,
+/// the retained window and every per-line structure (0 disables a cap). The last line is
+/// always kept. Line numbers stay absolute: evicted lines simply stop existing, so ranges
+/// and plans start at the first retained line (`retention_evictions()`). Aggregates (length
+/// histogram, counters, top lines, numeric stats) still include evicted lines. No effect
+/// on file sessions. Kept by `clear_data()`, reset by `clear()`. Throws if `max_age_ms` is
+/// negative or NaN.
+#[wasm_bindgen]
+pub fn set_retention(max_lines: u32, max_age_ms: f64) -> Result<(), JsError> {
+    if max_age_ms.is_nan() || max_age_ms < 0.0 {
+        return Err(js_error(format!(
+            "max_age_ms = {max_age_ms} is not a duration (must be a number from 0)"
+        )));
+    }
+    ENGINE
+        .write()
+        .expect("engine lock")
+        .set_retention(RetentionPolicy {
+            max_lines: max_lines as u64,
+            max_age_ms,
+        });
+    Ok(())
+}
+
+/// Lines evicted by retention in this session, which is also the index of the first
+/// retained line; for "older history was dropped" in the UI.
+#[wasm_bindgen]
+pub fn retention_evictions() -> Result<f64, JsError> {
+    let evicted = ENGINE.read().expect("engine lock").retention_evictions();
+    to_f64(evicted).map_err(js_error)
+}
+
+/// Copies `bytes` into the engine buffer and indexes them as one chunk, then discards.
+fn index_copied(engine: &mut LogEngine, bytes: &[u8]) {
+    let ptr = engine.get_buffer_pointer(bytes.len());
+    // SAFETY: get_buffer_pointer reserved `bytes.len()` writable bytes at `ptr`.
+    unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, bytes.len()) };
+    index_written_chunks(engine, &[bytes.len()]);
+    engine.discard_buffer_after_indexing();
+}
+
+/// Batched `index_chunk`: JS writes several consecutive chunks back-to-back at the pointer
+/// from a single `get_buffer_pointer(total)`, then passes their lengths here. Each piece is
+/// scanned in order with boundary state carried between them, and the buffer is discarded
+/// once at the end. Resulting offsets are identical to calling `index_chunk` per piece.
+/// Throws (discarding the chunks) while paused, in a live session, and unless the grant of
+/// that `get_buffer_pointer` covers the total.
+#[wasm_bindgen]
+pub fn index_chunks(chunk_lens: &js_sys::Uint32Array) -> Result<(), JsValue> {
+    let lens: Vec<usize> = chunk_lens
+        .to_vec()
+        .into_iter()
+        .map(|n| n as usize)
+        .collect();
+    let mut engine = ENGINE.write().expect("engine lock");
+    let checked = check_file_ingest(&engine)
+        .and_then(|()| {
+            let total = lens
+                .iter()
+                .fold(0usize, |total, &len| total.saturating_add(len));
+            check_grant(&mut engine, total)
+        })
+        .map(drop);
+    if checked.is_ok() {
+        index_written_chunks(&mut engine, &lens);
+    }
     engine.discard_buffer_after_indexing();
+    checked
+}
+
+/// Appends the pieces JS wrote into the buffer (lengths `lens`, back-to-back) and scans
+/// each as its own chunk, continuing boundary state across them. Leaves the buffer intact.
+fn index_written_chunks(engine: &mut LogEngine, lens: &[usize]) {
+    let started = now_ms();
+    let total: usize = lens.iter().sum();
+    let mut base = engine.total_bytes_indexed();
+    let mut starts_new_line = engine.last_chunk_ended_with_newline();
+    let layout = ScanLayout::of(engine);
+    let header_bytes = layout.header_bytes(base, total);
+    let observed_from = base + header_bytes as u64;
+    let mut nul_runs = engine.take_nul_runs();
+    let mut view = Vec::new();
+    let mut line_starts = Vec::new();
+    let mut piece = engine.append_chunk(total);
+    for &len in lens {
+        let (chunk, rest) = piece.split_at(len);
+        starts_new_line = scan_piece(
+            chunk,
+            base,
+            &mut line_starts,
+            starts_new_line,
+            layout,
+            nul_runs.as_mut(),
+            &mut view,
+        );
+        base += len as u64;
+        piece = rest;
+    }
+    log::debug!("indexed {total} bytes, {} new lines", line_starts.len());
+    let skipping_nul_runs = nul_runs.is_some();
+    engine.restore_nul_runs(nul_runs);
+    engine.append_offsets(&line_starts);
+    engine.advance_after_chunk(total, starts_new_line);
+    engine.record_profile(Category::Scan, started);
+    let started = now_ms();
+    if skipping_nul_runs {
+        engine.observe_chunk(&view, observed_from, &line_starts);
+    } else {
+        engine.observe_appended(total - header_bytes, observed_from, &line_starts);
+    }
+    engine.record_profile(Category::Filter, started);
+    let started = now_ms();
+    engine.score_appended(total);
+    engine.record_profile(Category::Printability, started);
+}
+
+/// How the engine maps file bytes to lines, read once per ingest call.
+#[derive(Debug, Clone, Copy)]
+struct ScanLayout {
+    /// Fixed record size (see `set_fixed_record_size`), or `None` for lines.
+    record_size: Option<u64>,
+    /// Leading header bytes that belong to no line (see `set_header_skip`).
+    header: u64,
+}
+
+impl ScanLayout {
+    fn of(engine: &LogEngine) -> Self {
+        Self {
+            record_size: engine.record_size(),
+            header: engine.header_skip(),
+        }
+    }
+
+    /// How many of the `len` bytes from file offset `base` are header.
+    #[inline(always)]
+    fn header_bytes(&self, base: u64, len: usize) -> usize {
+        self.header.saturating_sub(base).min(len as u64) as usize
+    }
+}
+
+/// Scans one chunk for line starts, or for record starts in fixed-size record mode, after
+/// skipping any header bytes in it. With NUL runs skipped, the bytes ingest-time features
+/// should see are appended to `view`.
+fn scan_piece(
+    chunk: &[u8],
+    base: u64,
+    line_starts: &mut Vec<u64>,
+    starts_new_line: bool,
+    layout: ScanLayout,
+    nul_runs: Option<&mut NulRuns>,
+    view: &mut Vec<u8>,
+) -> bool {
+    let skip = layout.header_bytes(base, chunk.len());
+    let (chunk, base) = (&chunk[skip..], base + skip as u64);
+    if chunk.is_empty() {
+        // Still in the header: the first line starts right after it.
+        return starts_new_line;
+    }
+    match (layout.record_size, nul_runs) {
+        (Some(size), _) => {
+            // Records are counted from the end of the header.
+            let first = line_starts.len();
+            let ends = scan_fixed_records(chunk.len(), base - layout.header, size, line_starts);
+            line_starts[first..]
+                .iter_mut()
+                .for_each(|start| *start += layout.header);
+            ends
+        }
+        (None, Some(runs)) => runs.scan(chunk, base, line_starts, starts_new_line, view),
+        (None, None) => scan_chunk(chunk, base, line_starts, starts_new_line),
+    }
+}
+
+/// Indexes a region of WASM linear memory that JS filled directly (e.g. a view backed by
+/// a SharedArrayBuffer), without copying it into the engine buffer. The region is treated
+/// as the next part of the logical file starting at file offset `base_offset`; line
+/// boundaries continue from the previous chunk/region, so regions should be fed in order.
+///
+/// # Safety
+/// `ptr..ptr + len` must lie inside this module's linear memory, be initialized, and not be
+/// written by another thread while this call runs. The region is only read during the call
+/// and is not retained. Throws while paused and in a live session, unless `len` and
+/// `base_offset` are byte counts, and when `base_offset` would put a line start at or
+/// before an indexed one (the region is then not indexed).
+#[wasm_bindgen]
+pub unsafe fn index_shared_region(
+    ptr: *const u8,
+    len: f64,
+    base_offset: f64,
+) -> Result<(), JsValue> {
+    let len = byte_len("len", len).map_err(js_error)?;
+    let base_offset = file_bytes("base_offset", base_offset).map_err(js_error)?;
+    let region = std::slice::from_raw_parts(ptr, len);
+    let mut engine = ENGINE.write().expect("engine lock");
+    check_file_ingest(&engine)?;
+    index_region(&mut engine, region, base_offset).map_err(|e| js_error(e).into())
+}
+
+/// Scans `region` (file bytes starting at `base_offset`) and appends its line starts,
+/// continuing the engine's boundary state. Does not touch the engine buffer. Fails,
+/// indexing nothing, when `base_offset` would put a line start at or before the last one.
+fn index_region(
+    engine: &mut LogEngine,
+    region: &[u8],
+    base_offset: u64,
+) -> Result<(), OffsetOrderError> {
+    let started = now_ms();
+    let starts_new_line = engine.last_chunk_ended_with_newline();
+    let layout = ScanLayout::of(engine);
+    let header_bytes = layout.header_bytes(base_offset, region.len());
+    // Scanned with a copy of the NUL-run state, so a rejected region leaves it untouched.
+    let previous_runs = engine.take_nul_runs();
+    let mut nul_runs = previous_runs.clone();
+    let mut view = Vec::new();
+    let mut line_starts = Vec::new();
+    let ends_with_newline = scan_piece(
+        region,
+        base_offset,
+        &mut line_starts,
+        starts_new_line,
+        layout,
+        nul_runs.as_mut(),
+        &mut view,
+    );
+    let skipping = nul_runs.is_some();
+    engine.restore_nul_runs(nul_runs);
+    if let Err(e) = engine.append_offsets_checked(&line_starts) {
+        engine.restore_nul_runs(previous_runs);
+        return Err(e);
+    }
+    engine.advance_to(base_offset + region.len() as u64, ends_with_newline);
+    engine.record_profile(Category::Scan, started);
+    let started = now_ms();
+    let observed = if skipping {
+        &view[..]
+    } else {
+        &region[header_bytes..]
+    };
+    engine.observe_chunk(observed, base_offset + header_bytes as u64, &line_starts);
+    engine.record_profile(Category::Filter, started);
+    let started = now_ms();
+    engine.score_bytes(region);
+    engine.record_profile(Category::Printability, started);
+    Ok(())
+}
+
+/// Indexes the file as fixed-size records of `size` bytes instead of newline-delimited
+/// lines: every `size` bytes form one "line", whatever they contain, and records may span
+/// chunks. 0 switches back to lines. Must be set before the first chunk (throws
+/// otherwise). Ingest-time features that look for newlines (stream filter, case index, top
+/// lines, numeric extractors, format detection, pattern counters) are not fed in this
+/// mode. Kept by `clear_data()`, reset by `clear()`.
+#[wasm_bindgen]
+pub fn set_fixed_record_size(size: u32) -> Result<(), JsError> {
+    let mut engine = ENGINE.write().expect("engine lock");
+    if engine.total_bytes_indexed() > 0 {
+        return Err(js_error("set the record size before indexing any data"));
+    }
+    if size > 0 && engine.decode_config().skip_nul_runs > 0 {
+        return Err(js_error(
+            "fixed-size records cannot be combined with NUL-run skipping",
+        ));
+    }
+    engine.set_record_size((size > 0).then_some(size as u64));
+    Ok(())
+}
+
+/// Treats the first `bytes` bytes of the file as a binary header (a capture file's global
+/// header, a container's preamble) that is not part of any line: line 0 and every offset
+/// start after it, and ingest-time features never see it. The header may span any number
+/// of chunks; chunks keep their real file offsets. In fixed-size record mode records are
+/// counted from the end of the header. 0 switches it off. Must be set before the first
+/// chunk (throws otherwise). Kept by `clear_data()`, reset by `clear()`.
+#[wasm_bindgen]
+pub fn set_header_skip(bytes: f64) -> Result<(), JsError> {
+    let bytes = byte_len("bytes", bytes).map_err(js_error)?;
+    let mut engine = ENGINE.write().expect("engine lock");
+    if engine.total_bytes_indexed() > 0 {
+        return Err(js_error("set the header length before indexing any data"));
+    }
+    engine.set_header_skip(bytes as u64);
+    Ok(())
+}
+
+/// Skips runs of at least `min_run_len` NUL bytes, as found in files recovered from crashed
+/// systems: each run becomes a skip region (see `get_skip_regions`) on a line of its own,
+/// the line before it ends where the run starts, and the next line starts at the first byte
+/// after it. Shorter runs are kept verbatim. Decoded windows show a skipped run as a
+/// placeholder marker, and ingest-time features see it as an empty line. 0 switches
+/// skipping off. Must be set before the first chunk and cannot be combined with fixed-size
+/// records (throws otherwise). Kept by `clear_data()`, reset by `clear()`.
+#[wasm_bindgen]
+pub fn skip_nul_runs(min_run_len: u32) -> Result<(), JsError> {
+    let mut engine = ENGINE.write().expect("engine lock");
+    if engine.total_bytes_indexed() > 0 {
+        return Err(js_error("set NUL-run skipping before indexing any data"));
+    }
+    if min_run_len > 0 && engine.record_size().is_some() {
+        return Err(js_error(
+            "NUL runs cannot be skipped in fixed-size record mode",
+        ));
+    }
+    engine.set_skip_nul_runs((min_run_len > 0).then_some(min_run_len as u64));
+    Ok(())
+}
+
+/// Skipped NUL runs as interleaved `[start, end)` file offsets, ascending: `[start0, end0,
+/// start1, end1, ...]`. Empty when skipping is off. The last region may still grow while
+/// the file ends in a run.
+#[wasm_bindgen]
+pub fn get_skip_regions() -> Result<Vec<f64>, JsError> {
+    let engine = ENGINE.read().expect("engine lock");
+    let regions = engine.skip_regions().iter();
+    to_f64_all(regions.flat_map(|&(start, end)| [start, end])).map_err(js_error)
+}
+
+/// Call after the final chunk has been indexed. Until then the last line's end offset and
+/// the line count may still grow; `is_complete()` lets JS tell "loading" from "done".
+#[wasm_bindgen]
+pub fn mark_complete() {
+    ENGINE.write().expect("engine lock").mark_complete();
+}
+
+/// True after `mark_complete()`, until more data is indexed or `clear()` is called.
+#[wasm_bindgen]
+pub fn is_complete() -> bool {
+    ENGINE.read().expect("engine lock").is_complete()
+}
+
+/// Announces the total size of the file about to be streamed, for `index_progress()`.
+/// Throws unless `bytes` is a byte count.
+#[wasm_bindgen]
+pub fn set_expected_size(bytes: f64) -> Result<(), JsError> {
+    let bytes = file_bytes("bytes", bytes).map_err(js_error)?;
+    ENGINE
+        .write()
+        .expect("engine lock")
+        .set_expected_size(bytes);
+    Ok(())
+}
+
+/// Reserves index capacity for about `expected` lines before a long index (e.g. the file
+/// size divided by the mean line length of a sample), so the line index does not keep
+/// reallocating as it grows. A hint: indexing is unchanged, and it may grow past it.
+/// Returns false if the memory could not be reserved. Throws if `expected` is negative or
+/// not a number.
+#[wasm_bindgen]
+pub fn reserve_lines(expected: f64) -> Result<bool, JsError> {
+    let expected = line_index("expected", expected).map_err(js_error)?;
+    Ok(ENGINE.write().expect("engine lock").reserve_lines(expected))
+}
+
+/// Same as `set_expected_size`; named for `get_ingest_stats`, which reports the lag
+/// behind this size.
+#[wasm_bindgen]
+pub fn set_expected_total_bytes(bytes: f64) -> Result<(), JsError> {
+    set_expected_size(bytes)
+}
+
+/// Pauses file ingestion: until `resume_ingest()`, the file ingest exports (`index_chunk`
+/// and friends) throw an `Error` named `"Paused"` and discard the submitted bytes, leaving
+/// the index and the line-boundary state as they were. Returns false if already paused.
+/// Paused time does not count towards the throughput in `get_ingest_stats`. Reset by
+/// `clear()` / `clear_data()`.
+#[wasm_bindgen]
+pub fn pause_ingest() -> bool {
+    ENGINE.write().expect("engine lock").pause_ingest()
+}
+
+/// Resumes file ingestion; the next chunk continues exactly where the last accepted one
+/// ended, even mid-line. Returns false if ingestion was not paused.
+#[wasm_bindgen]
+pub fn resume_ingest() -> bool {
+    ENGINE.write().expect("engine lock").resume_ingest()
+}
+
+/// Read-only diagnostic: `{ total_bytes, last_chunk_ended_with_newline, line_count,
+/// buffer_len, buffer_capacity }`, the state the next chunk continues from.
+#[wasm_bindgen(skip_typescript)]
+pub fn boundary_state() -> Result<JsValue, JsError> {
+    to_js(&ENGINE.read().expect("engine lock").boundary_state())
+}
+
+/// Ingest lag and estimated time to completion: bytes indexed, the expected size and how
+/// far behind it ingestion is, the throughput over active (unpaused) time since the first
+/// chunk, and the remaining bytes at that rate. See `IngestStats`.
+#[wasm_bindgen(skip_typescript)]
+pub fn get_ingest_stats() -> Result<JsValue, JsError> {
+    to_js(&ENGINE.read().expect("engine lock").ingest_stats(now_ms()))
+}
+
+/// Indexed bytes divided by the size from `set_expected_size`, clamped to [0, 1]: a file
+/// that grows past the announced size reports 1.0. Returns 1.0 after `mark_complete()`, and
+/// 0.0 when no size was announced. Reset by `clear()` / `clear_data()`.
+#[wasm_bindgen]
+pub fn index_progress() -> f64 {
+    ENGINE.read().expect("engine lock").index_progress()
 }
 
 /// Returns the number of lines indexed so far.
@@ -55,74 +712,2549 @@ pub fn get_line_count() -> usize {
     ENGINE.read().expect("engine lock").line_count()
 }
 
-/// Returns byte ranges (file offsets) for lines [start, end). JS must read the file
-/// for these ranges and call `decode_lines_from_blob` to get strings.
+/// Printability per equal byte span: splits `[0, indexed bytes)` into `buckets` spans and
+/// returns the share of text bytes in each (0 to 1; printable ASCII, tab/CR/LF and
+/// complete UTF-8 sequences) as a `Float64Array`, so binary regions can be grayed out.
+/// Scored per 64 KiB block while indexing, so a span reports the blocks it touches.
+/// Empty before any data is indexed.
+#[wasm_bindgen]
+pub fn get_printability_profile(buckets: u32) -> Vec<f64> {
+    ENGINE
+        .read()
+        .expect("engine lock")
+        .printability_profile(buckets as usize)
+}
+
+/// Line starts per equal byte span: splits `[0, indexed bytes)` into `buckets` spans and
+/// returns how many lines start in each, as a `Uint32Array` (for a minimap heatmap).
+/// Computed from the offsets alone; the counts sum to `get_line_count()`.
+#[wasm_bindgen]
+pub fn line_density(buckets: u32) -> Vec<u32> {
+    ENGINE
+        .read()
+        .expect("engine lock")
+        .line_density(buckets as usize)
+}
+
+/// Line length distribution as a `Float64Array`: element `i` counts lines whose length
+/// in bytes (without the `\n`) is in `[2^(i-1), 2^i)`, element 0 counts empty lines.
+/// Trailing empty buckets are omitted. Includes the last line even without a terminator.
+#[wasm_bindgen]
+pub fn get_length_histogram() -> Result<Vec<f64>, JsError> {
+    let engine = ENGINE.read().expect("engine lock");
+    to_f64_all(engine.length_histogram()).map_err(js_error)
+}
+
+/// The `k` longest lines (at most 100) as a `Float64Array` of interleaved
+/// `[line, length]` pairs, longest first; ties in line order. Lengths as in
+/// `get_length_histogram`.
+#[wasm_bindgen]
+pub fn get_longest_lines(k: u32) -> Result<Vec<f64>, JsError> {
+    let engine = ENGINE.read().expect("engine lock");
+    let longest = engine.longest_lines(k as usize).into_iter();
+    to_f64_all(longest.flat_map(|(line, len)| [line, len])).map_err(js_error)
+}
+
+/// Returns the line whose byte range contains `byte_offset` (e.g. a minimap click).
+/// Offsets at or past the indexed size clamp to the last line. Throws if no lines are
+/// indexed or the offset is not a byte count.
+#[wasm_bindgen]
+pub fn line_at_offset(byte_offset: f64) -> Result<u32, JsError> {
+    let byte_offset = file_bytes("byte_offset", byte_offset).map_err(js_error)?;
+    ENGINE
+        .read()
+        .expect("engine lock")
+        .line_at_offset(byte_offset)
+        .map(|line| line as u32)
+        .ok_or_else(|| js_error("no lines indexed"))
+}
+
+/// Line and column of file offset `byte_offset` (e.g. "error at byte 12345"), as `{ line,
+/// column, chars }`. Offsets past the indexed size clamp to the end. `column` is in bytes
+/// from the line start; with `char_column` it counts decoded characters instead when the
+/// line is resident (`chars` reports which). Throws if no lines are indexed or the offset
+/// is not a byte count.
+#[wasm_bindgen(skip_typescript)]
+pub fn position_at_byte(byte_offset: f64, char_column: bool) -> Result<JsValue, JsError> {
+    let byte_offset = file_bytes("byte_offset", byte_offset).map_err(js_error)?;
+    let position = ENGINE
+        .read()
+        .expect("engine lock")
+        .position_at_byte(byte_offset, char_column)
+        .ok_or_else(|| js_error("no lines indexed"))?;
+    to_js(&position)
+}
+
+/// Returns the file offset where `line` starts. Throws if `line` is past the last line or
+/// not a line index.
 #[wasm_bindgen]
-pub fn get_line_byte_ranges(start: usize, end: usize) -> JsValue {
+pub fn offset_of_line(line: f64) -> Result<f64, JsError> {
+    let line = line_index("line", line).map_err(js_error)?;
+    ENGINE
+        .read()
+        .expect("engine lock")
+        .offset_of_line(line)
+        .ok_or_else(|| js_error(format!("line {line} out of range")))
+        .and_then(|offset| to_f64(offset).map_err(js_error))
+}
+
+/// Returns byte ranges (file offsets) for lines [start, end). JS must read the file
+/// for these ranges and call `decode_lines_from_blob` to get strings. The range is clamped
+/// to the index (past the end or `start > end` gives fewer or no ranges); throws only for
+/// a bound that is not a line index (negative, NaN or above 2^53) rather than wrapping it.
+#[wasm_bindgen(skip_typescript)]
+pub fn get_line_byte_ranges(start: f64, end: f64) -> Result<JsValue, JsError> {
+    let start = line_index("start", start).map_err(js_error)?;
+    let end = line_index("end", end).map_err(js_error)?;
     let engine = ENGINE.read().expect("engine lock");
-    let ranges = engine.get_line_ranges(start, end);
+    ranges_to_js(engine.get_line_ranges(start, end)).map_err(js_error)
+}
+
+/// Strict `get_line_byte_ranges`: throws, naming the bad bound and the line count, unless
+/// `start <= end <= line count` (and `start` is not evicted), instead of clamping. Use it
+/// where an empty result would hide a frontend bug.
+#[wasm_bindgen(skip_typescript)]
+pub fn get_line_byte_ranges_checked(start: f64, end: f64) -> Result<JsValue, JsError> {
+    let start = line_index("start", start).map_err(js_error)?;
+    let end = line_index("end", end).map_err(js_error)?;
+    let engine = ENGINE.read().expect("engine lock");
+    let ranges = engine
+        .get_line_ranges_checked(start, end)
+        .map_err(js_error)?;
+    ranges_to_js(ranges).map_err(js_error)
+}
+
+/// `[[start, end], ...]` pairs for JS.
+fn ranges_to_js(ranges: Vec<(u64, u64)>) -> Result<JsValue, PrecisionLoss> {
     let arr = js_sys::Array::new();
     for (s, e) in ranges {
         let pair = js_sys::Array::new();
-        pair.push(&JsValue::from(s as f64));
-        pair.push(&JsValue::from(e as f64));
+        pair.push(&JsValue::from(to_f64(s)?));
+        pair.push(&JsValue::from(to_f64(e)?));
         arr.push(&pair.into());
     }
-    arr.into()
+    Ok(arr.into())
+}
+
+/// Same ranges as `get_line_byte_ranges`, returned as one `Float64Array` of interleaved
+/// [start0, end0, start1, end1, ...] offsets (single boundary crossing, no per-line arrays).
+/// Throws for a bound that is not a line index.
+#[wasm_bindgen]
+pub fn get_line_byte_ranges_flat(start: f64, end: f64) -> Result<Vec<f64>, JsError> {
+    let start = line_index("start", start).map_err(js_error)?;
+    let end = line_index("end", end).map_err(js_error)?;
+    ENGINE
+        .read()
+        .expect("engine lock")
+        .get_line_ranges_flat(start, end)
+        .map_err(js_error)
+}
+
+/// Every `step`-th line index (`0, step, 2 * step, ...` below the line count) as a
+/// `Uint32Array`, for a scrubbable preview: pass it to `get_ranges_for_lines` to fetch the
+/// sampled lines. Empty for a step of 0; throws if `step` is negative or not a number.
+#[wasm_bindgen]
+pub fn sample_line_indices(step: f64) -> Result<Vec<u32>, JsError> {
+    let step = line_index("step", step).map_err(js_error)?;
+    Ok(ENGINE
+        .read()
+        .expect("engine lock")
+        .sample_line_indices(step))
+}
+
+/// Byte ranges for an arbitrary set of lines (e.g. bookmarks) in one call: a
+/// `Float64Array` of interleaved [start, end) pairs in the order of `indices`. Indices past
+/// the last line are skipped, so filter them out first if positions must line up.
+#[wasm_bindgen]
+pub fn get_ranges_for_lines(indices: &js_sys::Uint32Array) -> Result<Vec<f64>, JsError> {
+    ENGINE
+        .read()
+        .expect("engine lock")
+        .get_ranges_for_lines(&indices.to_vec())
+        .map_err(js_error)
+}
+
+/// Returns `n + 1` boundary offsets for lines [start, end) as a `Float64Array`: line
+/// `start + k` spans [b[k], b[k + 1]). Half the payload of `get_line_byte_ranges_flat`.
+/// Throws for a bound that is not a line index.
+#[wasm_bindgen]
+pub fn get_line_boundaries(start: f64, end: f64) -> Result<Vec<f64>, JsError> {
+    let start = line_index("start", start).map_err(js_error)?;
+    let end = line_index("end", end).map_err(js_error)?;
+    let boundaries = ENGINE
+        .read()
+        .expect("engine lock")
+        .get_line_boundaries(start, end);
+    to_f64_all(boundaries).map_err(js_error)
+}
+
+/// Byte length of each line in [start, end) as a `Uint32Array`, clamped like
+/// `get_line_boundaries`: the terminator is included, and the last line runs to the end of
+/// the indexed data. Lines of 4 GiB or more read as `2^32 - 1`. Throws for a bound that is
+/// not a line index.
+#[wasm_bindgen]
+pub fn line_lengths(start: f64, end: f64) -> Result<Vec<u32>, JsError> {
+    let start = line_index("start", start).map_err(js_error)?;
+    let end = line_index("end", end).map_err(js_error)?;
+    Ok(ENGINE.read().expect("engine lock").line_lengths(start, end))
 }
 
 /// Decodes lines from a contiguous blob and relative line boundaries. UTF-8 safe:
 /// avoids splitting multi-byte characters at blob boundaries.
 /// `line_ends` — end offset of each line within `blob` (exclusive), so line i = blob[prev_end..line_ends[i]].
-#[wasm_bindgen]
-pub fn decode_lines_from_blob(blob: &js_sys::Uint8Array, line_ends: &js_sys::Uint32Array) -> JsValue {
-    let blob = blob.to_vec();
+/// Ends must be non-decreasing and within `blob` (equal ends give empty lines); otherwise
+/// throws naming the offending index.
+/// If the `plan` returned by `plan_window` is passed, throws when `blob` is not exactly the
+/// planned byte range (or the plan's offsets are invalid) instead of decoding shifted
+/// lines, and uses the decode cache (see `set_decode_cache_size`) for the planned lines.
+#[wasm_bindgen(skip_typescript)]
+pub fn decode_lines_from_blob(
+    blob: &js_sys::Uint8Array,
+    line_ends: &js_sys::Uint32Array,
+    plan: Option<js_sys::Object>,
+) -> Result<JsValue, JsError> {
+    let mut start_line = None;
+    if let Some(plan) = plan {
+        let field = |key: &str| {
+            js_sys::Reflect::get(&plan, &key.into())
+                .ok()
+                .and_then(|v| v.as_f64())
+                .ok_or_else(|| js_error(format!("window plan is missing `{key}`")))
+        };
+        let file_start = file_bytes("file_start", field("file_start")?).map_err(js_error)?;
+        let file_end = file_bytes("file_end", field("file_end")?).map_err(js_error)?;
+        let expected = file_end.checked_sub(file_start).ok_or_else(|| {
+            js_error(format!(
+                "window plan ends at {file_end}, before its start {file_start}"
+            ))
+        })?;
+        check_blob_len(expected, blob.length() as usize).map_err(js_error)?;
+        start_line = match field("start_line") {
+            Ok(line) => Some(line_index("start_line", line).map_err(js_error)? as u64),
+            Err(_) => None,
+        };
+    }
     let line_ends: Vec<u32> = line_ends.to_vec();
+    check_line_ends(&line_ends, blob.length() as usize).map_err(js_error)?;
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
     let arr = js_sys::Array::new();
-    let mut start = 0usize;
-    for &end in &line_ends {
-        let end = end as usize;
-        let slice = if end <= blob.len() {
-            &blob[start..end]
-        } else {
-            &blob[start..]
-        };
-        let s = decode_utf8_line_slice(slice);
-        arr.push(&JsValue::from(s));
-        start = end;
+    match start_line.filter(|_| engine.decode_cache_enabled()) {
+        Some(start_line) => {
+            copy_blob_to_scratch(&mut engine, blob);
+            let blob_len = blob.length() as usize;
+            for line in engine.decode_scratch_cached(blob_len, &line_ends, start_line) {
+                arr.push(&line_to_js(line));
+            }
+        }
+        None => {
+            let config = engine.decode_config();
+            let blob = copy_blob_to_scratch(&mut engine, blob);
+            for slice in clamped_line_slices(blob, &line_ends) {
+                arr.push(&line_to_js(decode_window_line(slice, config)));
+            }
+        }
     }
-    arr.into()
+    engine.record_profile(Category::Decode, started);
+    Ok(arr.into())
 }
 
-/// Decodes a single line slice to String. Trims trailing incomplete UTF-8 (e.g. when a
-/// chunk cut a multi-byte character in the middle) to avoid replacement characters.
-fn decode_utf8_line_slice(slice: &[u8]) -> String {
-    let valid_len = match std::str::from_utf8(slice) {
-        Ok(_) => slice.len(),
-        Err(e) => e.valid_up_to(),
+/// Lines [start_line, end_line) straight from the decode cache, as an array like
+/// `decode_lines_from_blob` returns, or `undefined` if any of them is not cached (then
+/// fetch the window as usual). Lets JS skip the file read when scrolling back. Throws for
+/// a bound that is not a line index.
+#[wasm_bindgen(skip_typescript)]
+pub fn get_cached_lines(start_line: f64, end_line: f64) -> Result<JsValue, JsError> {
+    let start_line = line_index("start_line", start_line).map_err(js_error)? as u64;
+    let end_line = line_index("end_line", end_line).map_err(js_error)? as u64;
+    let mut engine = ENGINE.write().expect("engine lock");
+    let Some(lines) = engine.cached_lines(start_line, end_line) else {
+        return Ok(JsValue::UNDEFINED);
     };
-    String::from_utf8_lossy(&slice[..valid_len]).into_owned()
+    let arr = js_sys::Array::new();
+    for line in lines {
+        arr.push(&line_to_js(line));
+    }
+    Ok(arr.into())
 }
 
-/// Clears the engine state (buffer and index). Call between file sessions to free memory.
+/// Keeps up to `lines` recently decoded lines (by line index) so windows decoded through
+/// `decode_lines_from_blob` with a plan, or read with `get_cached_lines`, skip decoding.
+/// 0 (the default) disables and frees the cache. Changing decode settings, `clear_data()`
+/// and appended data invalidate entries; `clear()` disables the cache.
 #[wasm_bindgen]
-pub fn clear() {
-    ENGINE.write().expect("engine lock").clear();
+pub fn set_decode_cache_size(lines: u32) {
+    ENGINE
+        .write()
+        .expect("engine lock")
+        .set_decode_cache_size(lines as usize);
 }
 
-/// Searches for `needle` (raw bytes) in all lines. Returns line indices (u32).
-/// Note: Buffer is cleared after each index_chunk, so this only sees in-memory content.
-/// For full-file search, use a separate flow (e.g. search per chunk during ingest).
-#[wasm_bindgen]
-pub fn search(needle: &js_sys::Uint8Array) -> JsValue {
-    let needle = needle.to_vec();
-    let engine = ENGINE.read().expect("engine lock");
-    let buf = engine.buffer_slice(0, engine.buffer_len() as u64);
-    let offsets = engine.offsets();
-    let indices = match_lines(buf, offsets, &needle);
-    let arr = js_sys::Array::new();
-    for i in indices {
-        arr.push(&JsValue::from(i as u32));
+/// Plans a window fetch for lines [start_line, end_line) in one call. Returns
+/// `{ start_line, file_start, file_end, line_ends }`: JS reads file bytes [file_start,
+/// file_end) and passes them with `line_ends` (a `Uint32Array`, unchanged) and optionally
+/// the plan itself to `decode_lines_from_blob`, which checks the blob length against
+/// `file_start`/`file_end` and keys the decode cache on `start_line`.
+#[wasm_bindgen(skip_typescript)]
+pub fn plan_window(start_line: f64, end_line: f64) -> Result<JsValue, JsError> {
+    let start_line = line_index("start_line", start_line).map_err(js_error)?;
+    let end_line = line_index("end_line", end_line).map_err(js_error)?;
+    let plan = ENGINE
+        .read()
+        .expect("engine lock")
+        .plan_window(start_line, end_line);
+    if plan.blob_len() > u32::MAX as u64 {
+        return Err(js_error(format!(
+            "window of {} bytes is too large for 32-bit line ends",
+            plan.blob_len()
+        )));
+    }
+    let line_ends: Vec<u32> = plan.line_ends.iter().map(|&e| e as u32).collect();
+    let obj = to_js(&plan)?;
+    // Line ends go out as a typed array so they can be passed to decode unchanged.
+    let _ = js_sys::Reflect::set(
+        &obj,
+        &"line_ends".into(),
+        &js_sys::Uint32Array::from(line_ends.as_slice()).into(),
+    );
+    Ok(obj)
+}
+
+/// Like `decode_lines_from_blob`, with presentation options applied to each decoded line.
+/// `flags` combine 1 = escape control characters as control pictures (␇), 2 = escape them
+/// as `\xNN` instead, 4 = expand tabs to `tab_width` columns (counted in Unicode scalar
+/// values). Tab and line terminators are never escaped.
+#[wasm_bindgen(skip_typescript)]
+pub fn decode_lines_opts(
+    blob: &js_sys::Uint8Array,
+    line_ends: &js_sys::Uint32Array,
+    flags: u32,
+    tab_width: u32,
+) -> JsValue {
+    let options = RenderOptions::from_flags(flags, tab_width);
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
+    let config = engine.decode_config();
+    let blob = copy_blob_to_scratch(&mut engine, blob);
+    let line_ends: Vec<u32> = line_ends.to_vec();
+    let arr = js_sys::Array::new();
+    for slice in clamped_line_slices(blob, &line_ends) {
+        let line = decode_window_line(slice, config).map(|s| render_line(&s, options));
+        arr.push(&line_to_js(line));
+    }
+    engine.record_profile(Category::Decode, started);
+    arr.into()
+}
+
+/// Decodes a window (same `blob`/`line_ends` contract as `decode_lines_from_blob`) and
+/// finds `needle` in each line, so the visible lines render with highlights in one call.
+/// Returns `{ n, text, matches }` per line: `n` counts from `first_line_index`, `text` has
+/// its terminator removed, and `matches` holds the `[start, end)` character ranges of the
+/// non-overlapping matches. Throws if `first_line_index` is not a line index.
+#[wasm_bindgen(skip_typescript)]
+pub fn render_window(
+    blob: &js_sys::Uint8Array,
+    line_ends: &js_sys::Uint32Array,
+    needle: &str,
+    first_line_index: f64,
+) -> Result<JsValue, JsError> {
+    let first_line = line_index("first_line_index", first_line_index).map_err(js_error)?;
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
+    let config = engine.decode_config();
+    let blob = copy_blob_to_scratch(&mut engine, blob);
+    let line_ends: Vec<u32> = line_ends.to_vec();
+    let lines = render_window_lines(blob, &line_ends, needle, first_line as u64, config);
+    engine.record_profile(Category::Decode, started);
+    to_js(&lines)
+}
+
+/// The lines of `render_window`. Every line gets a string: a line rejected in strict UTF-8
+/// mode falls back to U+FFFD replacement, and a skipped NUL run shows its placeholder.
+fn render_window_lines(
+    blob: &[u8],
+    line_ends: &[u32],
+    needle: &str,
+    first_line: u64,
+    config: DecodeConfig,
+) -> Vec<RenderedLine> {
+    clamped_line_slices(blob, line_ends)
+        .into_iter()
+        .zip(first_line..)
+        .map(|(slice, n)| {
+            let text = skipped_run_placeholder(slice, config)
+                .unwrap_or_else(|| decode_line_slice(strip_line_terminator(slice), config));
+            let matches = match_char_ranges(&text, needle);
+            RenderedLine { n, text, matches }
+        })
+        .collect()
+}
+
+/// Decodes each line of a window (same `blob`/`line_ends` contract as
+/// `decode_lines_from_blob`) and returns how many display rows it occupies when soft-wrapped
+/// at `columns` character columns, as a `Uint32Array`. With `wide_chars`, CJK and other
+/// wide characters count as 2 columns. Terminators do not count; empty lines are one row.
+#[wasm_bindgen]
+pub fn wrapped_row_counts(
+    blob: &js_sys::Uint8Array,
+    line_ends: &js_sys::Uint32Array,
+    columns: u32,
+    wide_chars: bool,
+) -> Vec<u32> {
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
+    let config = engine.decode_config();
+    let blob = copy_blob_to_scratch(&mut engine, blob);
+    let line_ends: Vec<u32> = line_ends.to_vec();
+    let rows = clamped_line_slices(blob, &line_ends)
+        .into_iter()
+        .map(|slice| {
+            let line = decode_line_slice(strip_line_terminator(slice), config);
+            wrapped_rows(&line, columns as usize, wide_chars)
+        })
+        .collect();
+    engine.record_profile(Category::Decode, started);
+    rows
+}
+
+/// A decoded line for JS: the string, or `null` for a line rejected in strict UTF-8 mode.
+fn line_to_js(line: Option<String>) -> JsValue {
+    line.map(JsValue::from).unwrap_or(JsValue::NULL)
+}
+
+/// Copies a JS window blob into the engine's reusable decode scratch (no per-call
+/// allocation proportional to the window) and returns the copied bytes.
+fn copy_blob_to_scratch<'a>(engine: &'a mut LogEngine, blob: &js_sys::Uint8Array) -> &'a [u8] {
+    let scratch = engine.decode_scratch(blob.length() as usize);
+    blob.copy_to(scratch);
+    scratch
+}
+
+/// End offset of a window blob of `len` bytes read from the file at `blob_offset`.
+fn blob_end(blob_offset: u64, len: u32) -> Result<u64, JsError> {
+    blob_offset.checked_add(u64::from(len)).ok_or_else(|| {
+        js_error(format!(
+            "blob at {blob_offset} of {len} bytes ends past 2^64"
+        ))
+    })
+}
+
+/// Decodes a whole window (same `blob`/`line_ends` contract as `decode_lines_from_blob`)
+/// into a single string: line terminators are removed and lines joined with `separator`.
+/// One JS string per window instead of one per line.
+#[wasm_bindgen]
+pub fn decode_window_joined(
+    blob: &js_sys::Uint8Array,
+    line_ends: &js_sys::Uint32Array,
+    separator: &str,
+) -> String {
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
+    let config = engine.decode_config();
+    let blob = copy_blob_to_scratch(&mut engine, blob);
+    let line_ends: Vec<u32> = line_ends.to_vec();
+    let joined = decode::lines::decode_window_joined(blob, &line_ends, separator, config);
+    engine.record_profile(Category::Decode, started);
+    joined
+}
+
+/// Text of a contiguous line range as one string, for "copy selection": same as
+/// `decode_window_joined`, with `join_with` defaulting to `"\n"` so a selection of
+/// `\n`-terminated lines rejoins to the original text (minus the final terminator).
+/// With `normalize_newlines`, every `\r\n` or lone `\r` left in the result (inside
+/// lines or in `join_with`) becomes `\n`, so CRLF and `\r` sources copy as `\n` text.
+#[wasm_bindgen]
+pub fn decode_range_joined(
+    blob: &js_sys::Uint8Array,
+    line_ends: &js_sys::Uint32Array,
+    join_with: Option<String>,
+    normalize_newlines: Option<bool>,
+) -> String {
+    let separator = join_with.as_deref().unwrap_or(DEFAULT_LINE_SEPARATOR);
+    let joined = decode_window_joined(blob, line_ends, separator);
+    if normalize_newlines.unwrap_or(false) {
+        decode::lines::normalize_newlines(joined)
+    } else {
+        joined
+    }
+}
+
+/// One line's bytes decoded for a preview (terminator removed, current decode settings),
+/// truncated past `max_chars` characters to at most `max_chars` ending in `…`. The cut
+/// falls on a character boundary and avoids splitting common grapheme clusters (accents,
+/// emoji modifiers, ZWJ sequences).
+#[wasm_bindgen]
+pub fn decode_preview(bytes: &[u8], max_chars: u32) -> String {
+    let config = ENGINE.read().expect("engine lock").decode_config();
+    decode::lines::decode_preview(bytes, max_chars as usize, config)
+}
+
+/// A window selection formatted for pasting into a bug report (same `blob`/`line_ends`
+/// contract as `decode_window_joined`), lines joined with `\n`. `options` bits: 1 =
+/// prefix each line with its number (`first_line_number` for the first line) right-aligned
+/// to the widest one, then `: `; 2 = strip ANSI escape sequences; 4 = strip trailing
+/// whitespace (after ANSI stripping). Uses the current UTF-8 decode settings. Throws if
+/// `first_line_number` is not a line index.
+#[wasm_bindgen]
+pub fn format_selection(
+    blob: &js_sys::Uint8Array,
+    line_ends: &js_sys::Uint32Array,
+    first_line_number: f64,
+    options: u32,
+) -> Result<String, JsError> {
+    let first_line_number =
+        line_index("first_line_number", first_line_number).map_err(js_error)? as u64;
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
+    let config = engine.decode_config();
+    let blob = copy_blob_to_scratch(&mut engine, blob);
+    let line_ends: Vec<u32> = line_ends.to_vec();
+    let formatted =
+        decode::selection::format_selection(blob, &line_ends, first_line_number, options, config);
+    engine.record_profile(Category::Decode, started);
+    Ok(formatted)
+}
+
+/// Bytes of the window lines at `indices` (relative to the window, in the order given,
+/// repeats allowed), each terminated by `line_ending` instead of its original terminator:
+/// e.g. a filtered view to save as a file. `blob`/`line_ends` are as for
+/// `decode_lines_from_blob`. Throws on an index past the last line.
+#[wasm_bindgen]
+pub fn assemble_lines(
+    blob: &js_sys::Uint8Array,
+    line_ends: &js_sys::Uint32Array,
+    indices: &js_sys::Uint32Array,
+    line_ending: &str,
+) -> Result<Vec<u8>, JsError> {
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
+    let blob = copy_blob_to_scratch(&mut engine, blob);
+    let bytes = decode::lines::assemble_lines(
+        blob,
+        &line_ends.to_vec(),
+        &indices.to_vec(),
+        line_ending.as_bytes(),
+    )
+    .map_err(js_error)?;
+    engine.record_profile(Category::Decode, started);
+    Ok(bytes)
+}
+
+/// 64-bit-clean variant of `get_line_byte_ranges_flat`: interleaved [start, end) offsets
+/// as a `BigUint64Array`, exact at any file size. Throws for a bound that is not a line
+/// index.
+#[wasm_bindgen]
+pub fn get_line_byte_ranges_big(start: f64, end: f64) -> Result<Vec<u64>, JsError> {
+    let start = line_index("start", start).map_err(js_error)?;
+    let end = line_index("end", end).map_err(js_error)?;
+    Ok(ENGINE
+        .read()
+        .expect("engine lock")
+        .get_line_ranges_flat_u64(start, end))
+}
+
+/// Start offsets of lines [start, start + count) as a `BigUint64Array`, straight from the
+/// index without the range-pair expansion of `get_line_byte_ranges`, for pulling starts in
+/// order in bounded batches (e.g. to join with metadata keyed by line number). Shorter at
+/// the end of the index; empty past it. Throws if `start` or `count` is negative or not a
+/// number.
+#[wasm_bindgen]
+pub fn offsets_batch(start: f64, count: f64) -> Result<Vec<u64>, JsError> {
+    let start = line_index("start", start).map_err(js_error)?;
+    let count = line_index("count", count).map_err(js_error)?;
+    Ok(ENGINE
+        .read()
+        .expect("engine lock")
+        .offsets_batch(start, count))
+}
+
+/// 64-bit-clean variant of `decode_lines_from_blob` taking `line_ends` as a
+/// `BigUint64Array`. Ends must be non-decreasing and within `blob`; otherwise throws
+/// instead of clamping or wrapping.
+#[wasm_bindgen(skip_typescript)]
+pub fn decode_lines_from_blob_big(
+    blob: &js_sys::Uint8Array,
+    line_ends: &js_sys::BigUint64Array,
+) -> Result<JsValue, JsError> {
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
+    let config = engine.decode_config();
+    let blob = copy_blob_to_scratch(&mut engine, blob);
+    let line_ends: Vec<u64> = line_ends.to_vec();
+    let lines = decode_lines_checked(blob, &line_ends, config).map_err(js_error)?;
+    engine.record_profile(Category::Decode, started);
+    let arr = js_sys::Array::new();
+    for line in lines {
+        arr.push(&line_to_js(line));
+    }
+    Ok(arr.into())
+}
+
+/// Enables the ASCII fast path: decoding skips UTF-8 validation and maps each byte to
+/// one char (Latin-1). Non-ASCII bytes decode lossily (UTF-8 sequences become mojibake).
+/// Search is byte-level in both modes, so its results are unaffected. Reset by `clear()`.
+#[wasm_bindgen]
+pub fn set_ascii_mode(enabled: bool) {
+    ENGINE.write().expect("engine lock").set_ascii_mode(enabled);
+}
+
+/// Sets how decode exports handle invalid UTF-8: 0 = trim only a cut multi-byte sequence
+/// at the line end and replace other invalid bytes with U+FFFD (default), 1 = replace
+/// everything with U+FFFD, 2 = strict (invalid lines decode to `null` in line arrays;
+/// joined-string exports fall back to replacement), 3 = skip (drop invalid bytes, keep the
+/// rest of the line). Reset by `clear()`.
+#[wasm_bindgen(skip_typescript)]
+pub fn set_invalid_utf8_mode(mode: u32) -> Result<(), JsError> {
+    let mode = Utf8Mode::from_code(mode)
+        .ok_or_else(|| js_error(format!("unknown invalid-UTF-8 mode {mode}")))?;
+    ENGINE.write().expect("engine lock").set_utf8_mode(mode);
+    Ok(())
+}
+
+/// Sets what the line-array decode exports (`decode_lines_from_blob`, its `_big` variant
+/// and `decode_lines_opts`) do with terminators: 0 = keep them (default), 1 = auto (each
+/// line drops its own `\n` or `\r\n`, so a file mixing both never leaves a stray `\r`).
+/// Line starts and `line_ends` are unaffected. Reset by `clear()`.
+#[wasm_bindgen(skip_typescript)]
+pub fn set_line_ending_mode(mode: u32) -> Result<(), JsError> {
+    let mode = LineEndings::from_code(mode)
+        .ok_or_else(|| js_error(format!("unknown line-ending mode {mode}")))?;
+    ENGINE.write().expect("engine lock").set_line_endings(mode);
+    Ok(())
+}
+
+/// Caps the capacity the chunk buffer keeps between chunks (default 64 MiB). The buffer is
+/// reused rather than freed after each chunk; one grown past `bytes` by an unusually large
+/// chunk is freed once that chunk is indexed. `trim_memory()` and `clear()` free it
+/// regardless.
+/// Kept by `clear_data()`, reset by `clear()`.
+#[wasm_bindgen]
+pub fn set_chunk_buffer_cap(bytes: f64) -> Result<(), JsError> {
+    let bytes = byte_len("bytes", bytes).map_err(js_error)?;
+    ENGINE
+        .write()
+        .expect("engine lock")
+        .set_chunk_buffer_cap(bytes);
+    Ok(())
+}
+
+/// Releases spare memory (offset and match-set slack, capacity left from a bigger file after
+/// `clear_data()`, idle chunk buffer, decode scratch) while keeping the index and settings.
+/// Returns the estimated bytes freed; `get_memory_stats()` reports the totals before and
+/// after. Cheap enough for idle callbacks.
+#[wasm_bindgen]
+pub fn trim_memory() -> Result<f64, JsError> {
+    let freed = ENGINE.write().expect("engine lock").compact();
+    to_f64(freed as u64).map_err(js_error)
+}
+
+/// Same as `trim_memory`, under the name it was first exported as; kept so existing
+/// callers keep working. New code should call `trim_memory`.
+#[wasm_bindgen]
+pub fn compact() -> Result<f64, JsError> {
+    trim_memory()
+}
+
+/// Clears the engine state (buffer and index). Call between file sessions to free memory.
+#[wasm_bindgen]
+pub fn clear() {
+    ENGINE.write().expect("engine lock").clear();
+}
+
+/// Like `clear`, but keeps every setting so the next file of the same kind does not need
+/// to be set up again: decode settings (ASCII, invalid-UTF-8 and line-ending modes, NUL-run
+/// skipping, decode cache size), record size, header length, chunk buffer cap, live window
+/// size and retention, compiled searches, the stream filter regex, enabled ingest features
+/// (case index, top lines, line previews) and numeric extractor and counter patterns.
+/// Indexed data, results and counts are dropped.
+#[wasm_bindgen]
+pub fn clear_data() {
+    ENGINE.write().expect("engine lock").clear_data();
+}
+
+/// Searches for `needle` (raw bytes) in all lines. Returns line indices (u32).
+/// Note: Buffer is cleared after each index_chunk, so this only sees in-memory content:
+/// the retained window of a live stream, or a buffer that was not discarded.
+/// For full-file search, use a separate flow (e.g. search per chunk during ingest).
+#[wasm_bindgen(skip_typescript)]
+pub fn search(needle: &js_sys::Uint8Array) -> JsValue {
+    search_needle(&needle.to_vec())
+}
+
+/// `search` for a typed needle with C-style escapes (`\n`, `\t`, `\r`, `\xNN`, `\\`)
+/// turned into the bytes they name, e.g. `\t` to find a literal tab. Other characters are
+/// searched as UTF-8. Throws on a malformed escape.
+#[wasm_bindgen(skip_typescript)]
+pub fn search_escaped(pattern: &str) -> Result<JsValue, JsError> {
+    let needle = search::escape::unescape_needle(pattern).map_err(js_error)?;
+    Ok(search_needle(&needle))
+}
+
+/// Searches the resident bytes as one stream, ignoring line boundaries, so `needle` may
+/// contain newlines (e.g. an exception line followed by `"\n\tat "`). Returns the lines
+/// where matches start, ascending and each once, as a `Uint32Array`. Like `search`, only
+/// sees in-memory content.
+#[wasm_bindgen]
+pub fn search_multiline(needle: &[u8]) -> Vec<u32> {
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
+    let buf = engine.buffer_slice(0, engine.buffer_len() as u64);
+    let lines = match_start_lines(buf, engine.offsets(), needle);
+    engine.record_profile(Category::Search, started);
+    lines.into_iter().map(|i| i as u32).collect()
+}
+
+/// "Smart" search over resident lines [start_line, end_line): each line is decoded (with
+/// the current decode settings) and NFC-normalized, as is `needle`, before comparing, so a
+/// precomposed `é` matches `e` + a combining accent and vice versa. Much slower than byte
+/// search, hence the range; returns matching line indices as a `Uint32Array`. Throws for a
+/// bound that is not a line index.
+#[wasm_bindgen]
+pub fn search_normalized(
+    needle: &str,
+    start_line: f64,
+    end_line: f64,
+) -> Result<Vec<u32>, JsError> {
+    let start_line = line_index("start_line", start_line).map_err(js_error)?;
+    let end_line = line_index("end_line", end_line).map_err(js_error)?;
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
+    let buf = engine.buffer_slice(0, engine.buffer_len() as u64);
+    let lines = search::normalized::normalized_matches(
+        buf,
+        engine.offsets(),
+        start_line,
+        end_line,
+        needle,
+        engine.decode_config(),
+    );
+    engine.record_profile(Category::Search, started);
+    Ok(lines.into_iter().map(|i| i as u32).collect())
+}
+
+/// Runs `search` for `needle`, recording its stats and profile time.
+fn search_needle(needle: &[u8]) -> JsValue {
+    let indices = search_timed(needle);
+    let arr = js_sys::Array::new();
+    for i in indices {
+        arr.push(&JsValue::from(i as u32));
+    }
+    arr.into()
+}
+
+/// Runs `search_resident` on the engine, recording its profile and `search_stats()`.
+fn search_timed(needle: &[u8]) -> Vec<u64> {
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = performance_now();
+    let profile_started = now_ms();
+    let indices = search_resident(&mut engine, needle);
+    engine.record_profile(Category::Search, profile_started);
+    let stats = SearchStats {
+        duration_ms: performance_now() - started,
+        ..engine.last_search_stats()
+    };
+    engine.record_search(stats);
+    indices
+}
+
+/// `search` returning its lines packed (delta + varint, see `search::packed`) in a
+/// `Uint8Array`, about a byte per line for dense hits: much cheaper to `postMessage` to
+/// another worker than an array of numbers. Unpack with `search_results_decode`.
+#[wasm_bindgen]
+pub fn search_export(needle: &[u8]) -> Vec<u8> {
+    search::packed::encode_lines(&search_timed(needle))
+}
+
+/// Unpacks a `search_export` buffer into its line indices, as a `Float64Array`. Throws
+/// on a truncated or corrupt buffer.
+#[wasm_bindgen]
+pub fn search_results_decode(packed: &[u8]) -> Result<Vec<f64>, JsError> {
+    let lines = search::packed::decode_lines(packed).map_err(js_error)?;
+    to_f64_all(lines).map_err(js_error)
+}
+
+/// Like `search`, but stops scanning once `max` matching lines are found. Returns
+/// `{ lines, truncated }`; `truncated` is true when another matching line exists. Updates
+/// `search_stats()` with the bytes actually scanned.
+#[wasm_bindgen(skip_typescript)]
+pub fn search_limit(needle: &js_sys::Uint8Array, max: u32) -> Result<JsValue, JsError> {
+    let needle = needle.to_vec();
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = performance_now();
+    let profile_started = now_ms();
+    let limited = {
+        let buf = engine.buffer_slice(0, engine.buffer_len() as u64);
+        match_lines_limit(buf, engine.offsets(), &needle, max as usize)
+    };
+    engine.record_profile(Category::Search, profile_started);
+    engine.record_search(SearchStats {
+        bytes_scanned: limited.bytes_scanned,
+        duration_ms: performance_now() - started,
+    });
+    to_js(&LimitedSearch {
+        lines: limited.lines.into_iter().map(|i| i as u32).collect(),
+        truncated: limited.truncated,
+    })
+}
+
+/// Like `search`, with the byte offset of every hit inside its line, as `[{ line,
+/// positions, truncated }]`. Each line reports at most `max_positions_per_line` hits
+/// (`truncated` marks lines with more), keeping the payload bounded on lines with
+/// thousands of matches. Hits do not overlap; an empty needle matches nothing.
+#[wasm_bindgen(skip_typescript)]
+pub fn search_with_positions(
+    needle: &js_sys::Uint8Array,
+    max_positions_per_line: u32,
+) -> Result<JsValue, JsError> {
+    let needle = needle.to_vec();
+    let max_per_line = max_positions_per_line as usize;
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
+    let lines = {
+        let buf = engine.buffer_slice(0, engine.buffer_len() as u64);
+        match_positions(buf, engine.offsets(), &needle, max_per_line)
+    };
+    engine.record_profile(Category::Search, started);
+    let lines: Vec<LineMatchPositions> = lines
+        .into_iter()
+        .map(|l| LineMatchPositions {
+            line: l.line as u32,
+            positions: l.positions,
+            truncated: l.truncated,
+        })
+        .collect();
+    to_js(&lines)
+}
+
+/// Lines containing any of `needles` (`Uint8Array[]`), as a `Uint32Array`. Strictly
+/// ascending: a line matched by several needles appears once. Throws if an element is not
+/// a `Uint8Array`.
+#[wasm_bindgen(skip_typescript)]
+pub fn search_any(needles: &js_sys::Array) -> Result<Vec<u32>, JsError> {
+    let mut owned = Vec::with_capacity(needles.length() as usize);
+    for (i, needle) in needles.iter().enumerate() {
+        let needle = needle
+            .dyn_into::<js_sys::Uint8Array>()
+            .map_err(|_| js_error(format!("needle {i} is not a Uint8Array")))?;
+        owned.push(needle.to_vec());
+    }
+    let needles: Vec<&[u8]> = owned.iter().map(Vec::as_slice).collect();
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
+    let lines = {
+        let buf = engine.buffer_slice(0, engine.buffer_len() as u64);
+        match_lines_any(buf, engine.offsets(), &needles)
+    };
+    engine.record_profile(Category::Search, started);
+    Ok(lines.into_iter().map(|i| i as u32).collect())
+}
+
+/// Indices of the `region_size`-byte file regions containing the start of a line that
+/// matches `needle`, as a `Float64Array` (ascending, deduplicated), so JS can fetch only
+/// those regions of a paged file. Throws unless `region_size` is a positive integer.
+#[wasm_bindgen]
+pub fn match_regions(needle: &[u8], region_size: f64) -> Result<Vec<f64>, JsError> {
+    let region_size = file_bytes("region_size", region_size).map_err(js_error)?;
+    if region_size == 0 {
+        return Err(js_error("region_size must be positive"));
+    }
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
+    let regions = {
+        let buf = engine.buffer_slice(0, engine.buffer_len() as u64);
+        search::matcher::match_regions(buf, engine.offsets(), needle, region_size)
+    };
+    engine.record_profile(Category::Search, started);
+    to_f64_all(regions).map_err(js_error)
+}
+
+/// Lines containing any byte listed in `bytes` (a character class, e.g. 0x80..=0xFF for
+/// non-ASCII lines), as a `Uint32Array`. Line terminators are not matched.
+#[wasm_bindgen]
+pub fn search_byte_class(bytes: &[u8]) -> Vec<u32> {
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
+    let lines = {
+        let buf = engine.buffer_slice(0, engine.buffer_len() as u64);
+        match_lines_byte_class(buf, engine.offsets(), bytes)
+    };
+    engine.record_profile(Category::Search, started);
+    lines.into_iter().map(|i| i as u32).collect()
+}
+
+/// Compiles `pattern` once and returns a handle for `run_compiled`. `flags`: 0 = literal
+/// substring, 1 = regex, 2 = glob (`*`, `?`), plus 4 = case-insensitive, 8 = whole words
+/// (ASCII word characters), 16 = whole words with Unicode letters and digits as word
+/// characters. All kinds match anywhere in a line. Throws on an invalid regex. Free with
+/// `drop_search`; `clear()` invalidates every handle.
+#[wasm_bindgen]
+pub fn compile_search(pattern: &str, flags: u32) -> Result<u32, JsError> {
+    let search = CompiledSearch::compile(pattern, flags).map_err(js_error)?;
+    Ok(ENGINE.write().expect("engine lock").add_search(search))
+}
+
+/// Runs a compiled search over resident lines [start_line, end_line) and returns the
+/// matching line indices as a `Uint32Array`. Case-insensitive literal searches use the
+/// shadow index from `enable_ci_index()` when present. Throws for an unknown or dropped
+/// handle and for a bound that is not a line index.
+#[wasm_bindgen]
+pub fn run_compiled(handle: u32, start_line: f64, end_line: f64) -> Result<Vec<u32>, JsError> {
+    let start_line = line_index("start_line", start_line).map_err(js_error)?;
+    let end_line = line_index("end_line", end_line).map_err(js_error)?;
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
+    let search = engine
+        .compiled_search(handle)
+        .ok_or_else(|| js_error(format!("unknown search handle {handle}")))?;
+    let buf = engine.buffer_slice(0, engine.buffer_len() as u64);
+    let offsets = engine.offsets();
+    let lines = search.matching_lines(buf, offsets, start_line, end_line, engine.ci_index());
+    engine.record_profile(Category::Search, started);
+    Ok(lines.into_iter().map(|i| i as u32).collect())
+}
+
+/// Cmd-G navigation: the first resident line after `after_line` matching compiled search
+/// `handle`, without materializing all matches. Pass -1 to start from the top. With `wrap`,
+/// continues from the top; returns -1 when there is no match. Throws for an unknown handle.
+#[wasm_bindgen]
+pub fn find_next_match(handle: u32, after_line: i32, wrap: bool) -> Result<i32, JsError> {
+    let after = usize::try_from(after_line).ok();
+    navigate(handle, |search, buf, offsets| {
+        search.next_match(buf, offsets, after, wrap)
+    })
+}
+
+/// Like `find_next_match`, scanning backwards from the line before `before_line` (pass -1
+/// to start from the last line). With `wrap`, continues from the bottom.
+#[wasm_bindgen]
+pub fn find_prev_match(handle: u32, before_line: i32, wrap: bool) -> Result<i32, JsError> {
+    let before = usize::try_from(before_line).ok();
+    navigate(handle, |search, buf, offsets| {
+        search.prev_match(buf, offsets, before, wrap)
+    })
+}
+
+/// Runs a navigation step with compiled search `handle`; `None` becomes -1.
+fn navigate(
+    handle: u32,
+    step: impl FnOnce(&CompiledSearch, &[u8], &LineOffsets) -> Option<u64>,
+) -> Result<i32, JsError> {
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
+    let search = engine
+        .compiled_search(handle)
+        .ok_or_else(|| js_error(format!("unknown search handle {handle}")))?;
+    let buf = engine.buffer_slice(0, engine.buffer_len() as u64);
+    let line = step(search, buf, engine.offsets());
+    engine.record_profile(Category::Search, started);
+    Ok(line.map_or(-1, |l| l as i32))
+}
+
+/// Frees a compiled search and its unseen counter (see `mark_seen`). Throws for an
+/// unknown or already dropped handle.
+#[wasm_bindgen]
+pub fn drop_search(handle: u32) -> Result<(), JsError> {
+    if ENGINE.write().expect("engine lock").drop_search(handle) {
+        Ok(())
+    } else {
+        Err(js_error(format!("unknown search handle {handle}")))
+    }
+}
+
+/// Opt-in: builds a per-line fingerprint of lowercased bytes while indexing (8 bytes per
+/// line, reported as `ci_index_bytes` in `get_memory_stats()`), letting case-insensitive
+/// literal searches in `run_compiled` skip lines that cannot match. Results are unchanged.
+/// Enable before the first chunk; earlier lines are simply not pruned. `clear()` turns it
+/// off; `clear_data()` keeps it on.
+#[wasm_bindgen]
+pub fn enable_ci_index() {
+    ENGINE.write().expect("engine lock").enable_ci_index();
+}
+
+/// Opt-in: counts the most frequent lines while indexing with a space-saving sketch of
+/// `capacity` counters (memory is bounded by `capacity`, not file size; use several times
+/// the `k` you will ask for). The first `skip_bytes` of each line (e.g. a timestamp) are
+/// ignored when comparing lines. Enable before the first chunk; calling it again restarts
+/// the counts. `clear()` turns it off; `clear_data()` keeps it on.
+#[wasm_bindgen]
+pub fn enable_top_lines(capacity: u32, skip_bytes: u32) {
+    ENGINE
+        .write()
+        .expect("engine lock")
+        .enable_top_lines(capacity as usize, skip_bytes as usize);
+}
+
+/// The `k` most frequent lines counted so far, as `[{ line, count, error }]`, highest
+/// count first. `line` is one line with that content; the true count is between
+/// `count - error` and `count`. An unterminated last line is not counted. Throws unless
+/// `enable_top_lines` was called.
+#[wasm_bindgen(skip_typescript)]
+pub fn get_top_lines(k: u32) -> Result<JsValue, JsError> {
+    let top = ENGINE
+        .read()
+        .expect("engine lock")
+        .top_lines(k as usize)
+        .ok_or_else(|| js_error("top lines are not enabled"))?;
+    let top: Vec<TopLine> = top
+        .into_iter()
+        .map(|h| TopLine {
+            line: h.line,
+            count: h.count,
+            error: h.error,
+        })
+        .collect();
+    to_js(&top)
+}
+
+/// Opt-in: keeps the first `max_bytes` of every line while indexing (one arena plus 4
+/// bytes per line, reported as `previews_bytes` in `get_memory_stats()`), so a list can
+/// paint from `get_line_previews` before the full lines are read from the file. Enable
+/// before the first chunk; calling it again restarts with the new length. Fixed-size
+/// records get no previews. `clear()` turns it off; `clear_data()` keeps it on.
+#[wasm_bindgen]
+pub fn enable_line_previews(max_bytes: f64) -> Result<(), JsError> {
+    let max_bytes = byte_len("max_bytes", max_bytes).map_err(js_error)?;
+    ENGINE
+        .write()
+        .expect("engine lock")
+        .enable_line_previews(max_bytes);
+    Ok(())
+}
+
+/// Previews of lines [start, end) as `[{ line, text, truncated }]`, decoded with the
+/// current settings (terminator removed). A character cut by the `max_bytes` limit is
+/// dropped rather than shown as U+FFFD; `truncated` marks lines longer than their preview.
+/// Lines indexed before `enable_line_previews` or evicted by retention are left out.
+/// Throws unless previews are enabled, and for a bound that is not a line index.
+#[wasm_bindgen(skip_typescript)]
+pub fn get_line_previews(start: f64, end: f64) -> Result<JsValue, JsError> {
+    let start = line_index("start", start).map_err(js_error)?;
+    let end = line_index("end", end).map_err(js_error)?;
+    let previews = ENGINE
+        .read()
+        .expect("engine lock")
+        .line_previews(start, end)
+        .ok_or_else(|| js_error("line previews are not enabled"))?;
+    to_js(&previews)
+}
+
+/// The `top_k` most frequent tokens in lines [start_line, end_line), as `{ tokens: [{
+/// token, count }], overflowed }`. `blob` holds the window bytes read from the file
+/// starting at byte `blob_offset` and must cover every line in the range. Tokens are split
+/// on ASCII whitespace and punctuation (`_` is kept) and ASCII-lowercased; tokens shorter
+/// than `min_len` bytes, and all-digit tokens when `skip_numbers` is set, are ignored.
+#[wasm_bindgen(skip_typescript)]
+pub fn token_frequencies(
+    start_line: f64,
+    end_line: f64,
+    blob: &js_sys::Uint8Array,
+    blob_offset: f64,
+    top_k: u32,
+    min_len: u32,
+    skip_numbers: bool,
+) -> Result<JsValue, JsError> {
+    let start_line = line_index("start_line", start_line).map_err(js_error)?;
+    let end_line = line_index("end_line", end_line).map_err(js_error)?;
+    let blob_offset = file_bytes("blob_offset", blob_offset).map_err(js_error)?;
+    let blob_end = blob_end(blob_offset, blob.length())?;
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
+    let boundaries = engine.get_line_boundaries(start_line, end_line);
+    let (from, to) = match (boundaries.first(), boundaries.last()) {
+        (Some(&from), Some(&to)) => (from, to),
+        _ => (blob_offset, blob_offset),
+    };
+    if from < blob_offset || to > blob_end {
+        return Err(js_error(format!(
+            "blob covers bytes {blob_offset}..{blob_end} but the lines span {from}..{to}"
+        )));
+    }
+    let blob = copy_blob_to_scratch(&mut engine, blob);
+    let window = &blob[(from - blob_offset) as usize..(to - blob_offset) as usize];
+    let counts =
+        search::tokens::token_frequencies(window, top_k as usize, min_len as usize, skip_numbers);
+    engine.record_profile(Category::Search, started);
+    to_js(&TokenFrequencies {
+        tokens: counts
+            .top
+            .into_iter()
+            .map(|(token, count)| TokenCount {
+                token: String::from_utf8_lossy(&token).into_owned(),
+                count,
+            })
+            .collect(),
+        overflowed: counts.overflowed,
+    })
+}
+
+/// Metadata of matches [start, end) of `lines` (a match set held by JS, e.g. search
+/// results) as a string in `format`: `"json"` gives an array of `{ line, offset, text }`
+/// objects, `"csv"` a `line,offset,text` header plus one quoted-as-needed row per match.
+/// `start`/`end` are positions in `lines` (clamped), so a large set can be exported in
+/// pages. `text` is filled for lines wholly inside `blob` (window bytes read from the file
+/// starting at byte `blob_offset`) and is `null` (empty in CSV) otherwise. Throws on an
+/// unknown format, a line past the last one, a negative position or a `blob_offset` that
+/// is not a file offset.
+#[wasm_bindgen]
+pub fn export_matches_meta(
+    format: &str,
+    lines: &js_sys::Uint32Array,
+    start: f64,
+    end: f64,
+    blob: Option<js_sys::Uint8Array>,
+    blob_offset: Option<f64>,
+) -> Result<String, JsError> {
+    let format = search::report::ReportFormat::parse(format)
+        .ok_or_else(|| js_error(format!("unknown export format {format:?}")))?;
+    let mut engine = ENGINE.write().expect("engine lock");
+    let config = engine.decode_config();
+    let lines = lines.to_vec();
+    let end = line_index("end", end).map_err(js_error)?.min(lines.len());
+    let start = line_index("start", start).map_err(js_error)?.min(end);
+    let mut ranges = Vec::with_capacity(end - start);
+    for &line in &lines[start..end] {
+        let range = engine
+            .line_range(line_index("line", f64::from(line)).map_err(js_error)?)
+            .ok_or_else(|| js_error(format!("line {line} is past the last line")))?;
+        ranges.push((line, range));
+    }
+    let blob_offset = match blob_offset {
+        Some(blob_offset) => file_bytes("blob_offset", blob_offset).map_err(js_error)?,
+        None => 0,
+    };
+    let blob_end = blob_end(blob_offset, blob.as_ref().map_or(0, |blob| blob.length()))?;
+    let blob = match &blob {
+        Some(blob) => copy_blob_to_scratch(&mut engine, blob),
+        None => &[],
+    };
+    let rows: Vec<search::report::MatchRow> = ranges
+        .into_iter()
+        .map(|(line, (from, to))| search::report::MatchRow {
+            line: line as u64,
+            offset: from,
+            text: (from >= blob_offset && to <= blob_end).then(|| {
+                let slice = &blob[(from - blob_offset) as usize..(to - blob_offset) as usize];
+                decode_line_slice(strip_line_terminator(slice), config)
+            }),
+        })
+        .collect();
+    Ok(search::report::format_rows(&rows, format))
+}
+
+/// Installs a regex that every line is tested against as it is indexed, so a filtered view
+/// is ready when ingestion ends even though chunk bytes are discarded. Set it before the
+/// first chunk; a line already in progress when it is set is skipped. Lines split across
+/// chunks are buffered until complete. Pass `null` to remove the filter. Either resets
+/// its unseen counter (see `mark_seen`). Kept by `clear_data()`, removed by `clear()`.
+/// Throws on an invalid regex.
+#[wasm_bindgen]
+pub fn set_stream_filter_regex(pattern: Option<String>) -> Result<(), JsError> {
+    let regex = pattern
+        .map(|p| regex::bytes::Regex::new(&p))
+        .transpose()
+        .map_err(|e| js_error(format!("invalid stream filter regex: {e}")))?;
+    ENGINE
+        .write()
+        .expect("engine lock")
+        .set_stream_filter(regex);
+    Ok(())
+}
+
+/// Line indices matched by the stream filter so far, as a `Uint32Array`. The last line
+/// (without a trailing newline) is only included after `mark_complete()`. Throws if no
+/// filter is set.
+#[wasm_bindgen]
+pub fn get_stream_filter_results() -> Result<Vec<u32>, JsError> {
+    let engine = ENGINE.read().expect("engine lock");
+    let lines = engine
+        .stream_filter_results()
+        .ok_or_else(|| js_error("no stream filter set"))?;
+    Ok(lines.into_iter().map(|i| i as u32).collect())
+}
+
+/// Tail mode: marks matches up to and including `up_to_line` as seen, so
+/// `get_unseen_count(id)` counts only matches after it. `id` is 0 for the stream filter or
+/// a `compile_search` handle; the first call for a search starts testing it against every
+/// line indexed (or appended with `append_live`) from then on. The baseline only moves
+/// forward. Throws for an unknown handle, for 0 when no stream filter is set, and if
+/// `up_to_line` is not a line index.
+#[wasm_bindgen]
+pub fn mark_seen(id: u32, up_to_line: f64) -> Result<(), JsError> {
+    let up_to_line = line_index("up_to_line", up_to_line).map_err(js_error)? as u64;
+    let mut engine = ENGINE.write().expect("engine lock");
+    if engine.mark_seen(id, up_to_line) {
+        Ok(())
+    } else {
+        Err(js_error(format!("no stream filter or search with id {id}")))
+    }
+}
+
+/// Matches of `id` (see `mark_seen`) after its seen baseline: "12 new errors since you
+/// scrolled up". 0 for a search never passed to `mark_seen`. The counters are kept in the
+/// engine, so recomputing a filtered view does not reset them; replacing or removing the
+/// stream filter (`set_stream_filter_regex`) or dropping a search (`drop_search`) does.
+/// Throws like `mark_seen`.
+#[wasm_bindgen]
+pub fn get_unseen_count(id: u32) -> Result<f64, JsError> {
+    let engine = ENGINE.read().expect("engine lock");
+    engine
+        .unseen_count(id)
+        .ok_or_else(|| js_error(format!("no stream filter or search with id {id}")))
+        .and_then(|count| to_f64(count as u64).map_err(js_error))
+}
+
+/// Starts exporting a set of lines to a file and returns a token for
+/// `export_next_ranges`. `source` is `"filter"` (the stream filter's matches so far;
+/// `lines` must be omitted), or `"search"` / `"bookmarks"` with the line indices in
+/// `lines` (any order, repeats allowed; indices past the last line are dropped). The
+/// export covers the data indexed so far. Dropped by `clear_data()`; free it earlier with
+/// `export_end`. Throws on an unknown source, missing or unexpected `lines`, or
+/// `"filter"` without a stream filter.
+#[wasm_bindgen]
+pub fn export_begin(source: &str, lines: Option<js_sys::Uint32Array>) -> Result<u32, JsError> {
+    let mut engine = ENGINE.write().expect("engine lock");
+    let lines = match (source, lines) {
+        ("filter", None) => engine
+            .stream_filter_results()
+            .ok_or_else(|| js_error("no stream filter set"))?,
+        ("filter", Some(_)) => return Err(js_error("source \"filter\" takes no lines")),
+        ("search" | "bookmarks", Some(lines)) => {
+            lines.to_vec().into_iter().map(u64::from).collect()
+        }
+        ("search" | "bookmarks", None) => {
+            return Err(js_error(format!(
+                "source {source:?} needs the lines to export"
+            )))
+        }
+        _ => return Err(js_error(format!("unknown export source {source:?}"))),
+    };
+    Ok(engine.begin_export(lines))
+}
+
+/// Next file byte ranges of export `token` as a `Float64Array` of interleaved
+/// [start, end) pairs, in ascending order and totalling at most `max_bytes` (a single
+/// longer line is returned alone). Consecutive lines are coalesced into one range, and
+/// ranges cover whole lines including terminators, so reading them from the `File` in
+/// order and appending to a `Blob` reproduces the lines. Empty once exhausted. Throws
+/// for an unknown token and unless `max_bytes` is a byte count.
+#[wasm_bindgen]
+pub fn export_next_ranges(token: u32, max_bytes: f64) -> Result<Vec<f64>, JsError> {
+    let max_bytes = file_bytes("max_bytes", max_bytes).map_err(js_error)?;
+    let ranges = ENGINE
+        .write()
+        .expect("engine lock")
+        .export_next_ranges(token, max_bytes)
+        .ok_or_else(|| js_error(format!("unknown export token {token}")))?;
+    to_f64_all(ranges.into_iter().flat_map(|(start, end)| [start, end])).map_err(js_error)
+}
+
+/// Size of export `token` and progress so far: `{ total_lines, total_bytes,
+/// emitted_lines, emitted_bytes, done }`. Throws for an unknown token.
+#[wasm_bindgen(skip_typescript)]
+pub fn export_stats(token: u32) -> Result<JsValue, JsError> {
+    let stats = ENGINE
+        .read()
+        .expect("engine lock")
+        .export_stats(token)
+        .ok_or_else(|| js_error(format!("unknown export token {token}")))?;
+    to_js(&stats)
+}
+
+/// Frees export `token`. Throws if it is unknown or already ended.
+#[wasm_bindgen]
+pub fn export_end(token: u32) -> Result<(), JsError> {
+    if ENGINE.write().expect("engine lock").end_export(token) {
+        Ok(())
+    } else {
+        Err(js_error(format!("unknown export token {token}")))
+    }
+}
+
+/// Registers a numeric extractor and returns its id for `get_numeric_stats`: on every
+/// line indexed from now on, capture group `group` of `pattern` (e.g. 1 in `took (\d+)ms`)
+/// is parsed as a number. Register before the first chunk; a line already in progress is
+/// skipped. Kept (with counts reset) by `clear_data()`, removed by `clear()`. Throws on an
+/// invalid regex or a group the pattern does not have.
+#[wasm_bindgen]
+pub fn register_numeric_extractor(pattern: &str, group: u32) -> Result<u32, JsError> {
+    let regex = regex::bytes::Regex::new(pattern)
+        .map_err(|e| js_error(format!("invalid numeric extractor regex: {e}")))?;
+    if group as usize >= regex.captures_len() {
+        return Err(js_error(format!(
+            "pattern has no capture group {group} (it has {})",
+            regex.captures_len() - 1
+        )));
+    }
+    Ok(ENGINE
+        .write()
+        .expect("engine lock")
+        .add_numeric_extractor(regex, group as usize))
+}
+
+/// Figures for numeric extractor `id` so far: `{ count, skipped, sum, min, max, mean, p50,
+/// p95, p99, exact_quantiles }`. `skipped` counts matching lines whose value did not parse.
+/// Quantiles are exact up to 4096 values, then estimated from a uniform sample
+/// (`exact_quantiles` is false). The last line is only included after `mark_complete()`.
+/// Throws for an unknown id.
+#[wasm_bindgen(skip_typescript)]
+pub fn get_numeric_stats(id: u32) -> Result<JsValue, JsError> {
+    let summary = ENGINE
+        .read()
+        .expect("engine lock")
+        .numeric_summary(id)
+        .ok_or_else(|| js_error(format!("unknown numeric extractor {id}")))?;
+    let quantiles = summary.quantiles(&[0.5, 0.95, 0.99]);
+    to_js(&NumericStats {
+        count: summary.count,
+        skipped: summary.skipped,
+        sum: summary.sum,
+        min: summary.min,
+        max: summary.max,
+        mean: summary.mean(),
+        p50: quantiles[0],
+        p95: quantiles[1],
+        p99: quantiles[2],
+        exact_quantiles: summary.exact_quantiles(),
+    })
+}
+
+/// The file's format judged from its first 200 complete, non-blank lines as they are
+/// indexed: `{ format, confidence, sampled_lines, scores: { json, access_log, syslog,
+/// logfmt, plain } }`. `format` is the format most sampled lines have (`"unknown"` before
+/// any), `confidence` its share of the sample (below 1 for a mixed file), and `scores`
+/// each format's share. Reset by `clear_data()`.
+#[wasm_bindgen(skip_typescript)]
+pub fn get_detected_format() -> Result<JsValue, JsError> {
+    to_js(&ENGINE.read().expect("engine lock").detected_format())
+}
+
+/// Registers literal patterns (`Uint8Array[]`) to count during ingest and returns their
+/// ids as a `Uint32Array`. All registered patterns are matched in one Aho–Corasick pass
+/// per chunk, counting the lines that contain each (a line counts once per pattern, hits
+/// split across chunks included). Must be called before the first chunk; may be called
+/// several times. Kept (with counts reset) by `clear_data()`, removed by `clear()`.
+/// Throws on an empty pattern, a non-`Uint8Array` element, or once data is indexed.
+#[wasm_bindgen(skip_typescript)]
+pub fn register_counters(patterns: &js_sys::Array) -> Result<Vec<u32>, JsError> {
+    let mut owned = Vec::with_capacity(patterns.length() as usize);
+    for (i, pattern) in patterns.iter().enumerate() {
+        let pattern = pattern
+            .dyn_into::<js_sys::Uint8Array>()
+            .map_err(|_| js_error(format!("pattern {i} is not a Uint8Array")))?;
+        if pattern.length() == 0 {
+            return Err(js_error(format!("pattern {i} is empty")));
+        }
+        owned.push(pattern.to_vec());
+    }
+    let mut engine = ENGINE.write().expect("engine lock");
+    if engine.total_bytes_indexed() > 0 {
+        return Err(js_error("register counters before indexing any data"));
+    }
+    engine
+        .add_counters(owned)
+        .map_err(|e| js_error(format!("cannot build counters: {e}")))
+}
+
+/// Lines counted so far for counter `id` (see `register_counters`). Throws for an
+/// unknown id.
+#[wasm_bindgen]
+pub fn get_counter(id: u32) -> Result<f64, JsError> {
+    ENGINE
+        .read()
+        .expect("engine lock")
+        .counter(id)
+        .ok_or_else(|| js_error(format!("unknown counter {id}")))
+        .and_then(|count| to_f64(count).map_err(js_error))
+}
+
+/// Every counter's line count so far, indexed by id, as a `Float64Array`.
+#[wasm_bindgen]
+pub fn get_all_counters() -> Result<Vec<f64>, JsError> {
+    let engine = ENGINE.read().expect("engine lock");
+    to_f64_all(engine.all_counters().iter().copied()).map_err(js_error)
+}
+
+/// Classifies resident lines against named regex rules in one pass. `rules` is an array of
+/// `{ name, pattern }` (at most 32); each is compiled once. Returns a `Uint32Array` of
+/// interleaved `[line, mask]` pairs for lines matching at least one rule, where bit `i` of
+/// `mask` is set when rule `i` matched. Throws naming the rule if a pattern fails to compile.
+#[wasm_bindgen(skip_typescript)]
+pub fn classify_by_rules(rules: &js_sys::Array) -> Result<Vec<u32>, JsError> {
+    let mut specs = Vec::with_capacity(rules.length() as usize);
+    for (i, rule) in rules.iter().enumerate() {
+        let field = |key: &str| {
+            js_sys::Reflect::get(&rule, &key.into())
+                .ok()
+                .and_then(|v| v.as_string())
+                .ok_or_else(|| js_error(format!("rule {i} is missing a string `{key}`")))
+        };
+        specs.push((field("name")?, field("pattern")?));
+    }
+    let set = RuleSet::compile(&specs).map_err(js_error)?;
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
+    let buf = engine.buffer_slice(0, engine.buffer_len() as u64);
+    let mut out = Vec::new();
+    for (line, mask) in set.classify(buf, engine.offsets()) {
+        out.push(line as u32);
+        out.push(mask);
+    }
+    engine.record_profile(Category::Classify, started);
+    Ok(out)
+}
+
+/// Counts resident lines that are not blank and do not start with any of `prefixes`
+/// (an array of `Uint8Array`, e.g. `#`). With `whitespace_is_blank`, lines of only
+/// whitespace count as blank too; by default only empty lines do.
+#[wasm_bindgen(skip_typescript)]
+pub fn count_content_lines(
+    prefixes: &js_sys::Array,
+    whitespace_is_blank: Option<bool>,
+) -> Result<u32, JsError> {
+    let filter = content_filter(prefixes, whitespace_is_blank)?;
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
+    let buf = engine.buffer_slice(0, engine.buffer_len() as u64);
+    let count = filter.count(buf, engine.offsets()) as u32;
+    engine.record_profile(Category::Filter, started);
+    Ok(count)
+}
+
+/// Indices of the lines `count_content_lines` counts (same arguments), as a `Uint32Array`.
+#[wasm_bindgen(skip_typescript)]
+pub fn content_line_indices(
+    prefixes: &js_sys::Array,
+    whitespace_is_blank: Option<bool>,
+) -> Result<Vec<u32>, JsError> {
+    let filter = content_filter(prefixes, whitespace_is_blank)?;
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
+    let buf = engine.buffer_slice(0, engine.buffer_len() as u64);
+    let indices = filter.line_indices(buf, engine.offsets());
+    engine.record_profile(Category::Filter, started);
+    Ok(indices.into_iter().map(|i| i as u32).collect())
+}
+
+fn content_filter(
+    prefixes: &js_sys::Array,
+    whitespace_is_blank: Option<bool>,
+) -> Result<ContentFilter, JsError> {
+    let mut bytes = Vec::with_capacity(prefixes.length() as usize);
+    for (i, prefix) in prefixes.iter().enumerate() {
+        let prefix = prefix
+            .dyn_into::<js_sys::Uint8Array>()
+            .map_err(|_| js_error(format!("prefix {i} is not a Uint8Array")))?;
+        bytes.push(prefix.to_vec());
+    }
+    let whitespace_is_blank = whitespace_is_blank.unwrap_or(false);
+    Ok(ContentFilter::new(bytes, whitespace_is_blank))
+}
+
+/// One page of `search` results: skips `offset` matches and returns up to `limit` line
+/// indices as a `Uint32Array`. With `reverse`, lines are scanned from the end, so page 0
+/// holds the newest matches in descending order (for tailing). Matches are not stored
+/// between calls: each page rescans, stopping once it is full.
+#[wasm_bindgen]
+pub fn search_page(
+    needle: &js_sys::Uint8Array,
+    offset: u32,
+    limit: u32,
+    reverse: bool,
+) -> Vec<u32> {
+    let needle = needle.to_vec();
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
+    let buf = engine.buffer_slice(0, engine.buffer_len() as u64);
+    let lines = match_page(
+        buf,
+        engine.offsets(),
+        &needle,
+        offset as usize,
+        limit as usize,
+        reverse,
+    );
+    engine.record_profile(Category::Search, started);
+    lines.into_iter().map(|i| i as u32).collect()
+}
+
+/// True when at least one resident line contains `needle`. Stops at the first hit (e.g.
+/// to enable a "next match" button).
+#[wasm_bindgen]
+pub fn search_exists(needle: &js_sys::Uint8Array) -> bool {
+    let needle = needle.to_vec();
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
+    let buf = engine.buffer_slice(0, engine.buffer_len() as u64);
+    let found = any_line_matches(buf, engine.offsets(), &needle);
+    engine.record_profile(Category::Search, started);
+    found
+}
+
+/// Index of the first resident line containing `needle`, or -1 when none does. Stops at
+/// the first hit, so it is the cheapest query for "does this appear at all?" checks that
+/// also want to jump to it.
+#[wasm_bindgen]
+pub fn contains_anywhere(needle: &js_sys::Uint8Array) -> Result<f64, JsError> {
+    let needle = needle.to_vec();
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
+    let buf = engine.buffer_slice(0, engine.buffer_len() as u64);
+    let first = first_matching_line(buf, engine.offsets(), &needle);
+    engine.record_profile(Category::Search, started);
+    first.map_or(Ok(-1.0), to_f64).map_err(js_error)
+}
+
+/// Number of resident lines containing `needle`, counting no further than `limit`: a
+/// result equal to `limit` means "`limit` or more" (render as e.g. "10,000+"). Throws if
+/// `limit` is negative or not a number.
+#[wasm_bindgen]
+pub fn search_count(needle: &js_sys::Uint8Array, limit: f64) -> Result<f64, JsError> {
+    let limit = line_index("limit", limit).map_err(js_error)? as u64;
+    let needle = needle.to_vec();
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
+    let buf = engine.buffer_slice(0, engine.buffer_len() as u64);
+    let count = count_lines_limit(buf, engine.offsets(), &needle, limit);
+    engine.record_profile(Category::Search, started);
+    to_f64(count).map_err(js_error)
+}
+
+/// Both sizes of a search over the resident lines, from one pass: `{ line_count,
+/// occurrence_count }`, the lines containing `needle` and its total non-overlapping
+/// occurrences in them (e.g. "3 lines, 7 matches"). An empty needle matches every line once.
+#[wasm_bindgen(skip_typescript)]
+pub fn search_summary(needle: &[u8]) -> Result<JsValue, JsError> {
+    let mut engine = ENGINE.write().expect("engine lock");
+    let started = now_ms();
+    let buf = engine.buffer_slice(0, engine.buffer_len() as u64);
+    let (line_count, occurrence_count) = count_occurrences(buf, engine.offsets(), needle);
+    engine.record_profile(Category::Search, started);
+    to_js(&SearchSummary {
+        line_count,
+        occurrence_count,
+    })
+}
+
+/// Async `search`: scans resident lines in batches, yielding to the event loop (zero-delay
+/// timeout) between batches so messages such as a cancel request are processed. Resolves to
+/// an array of matching line indices. `op_id` is chosen by JS; `request_cancel(op_id)` makes
+/// the promise reject with an `Error` whose `name` is `"Cancelled"`.
+#[wasm_bindgen(skip_typescript)]
+pub fn search_async(needle: &js_sys::Uint8Array, op_id: u32) -> js_sys::Promise {
+    let needle = needle.to_vec();
+    wasm_bindgen_futures::future_to_promise(async move {
+        let mut search = ChunkedSearch::new(&needle);
+        loop {
+            {
+                let mut engine = ENGINE.write().expect("engine lock");
+                if engine.take_cancel(op_id) {
+                    let err = js_sys::Error::new(&format!("operation {op_id} cancelled"));
+                    err.set_name("Cancelled");
+                    return Err(err.into());
+                }
+                let started = now_ms();
+                let buf = engine.buffer_slice(0, engine.buffer_len() as u64);
+                let done = search.step(buf, engine.offsets(), ASYNC_BATCH_LINES);
+                engine.record_profile(Category::Search, started);
+                if done {
+                    break;
+                }
+            }
+            yield_to_event_loop().await;
+        }
+        let arr = js_sys::Array::new();
+        for i in search.into_matches() {
+            arr.push(&JsValue::from(i as u32));
+        }
+        Ok(arr.into())
+    })
+}
+
+/// Requests cancellation of the async operation `op_id`. It stops at its next batch.
+#[wasm_bindgen]
+pub fn request_cancel(op_id: u32) {
+    ENGINE.write().expect("engine lock").request_cancel(op_id);
+}
+
+/// Resolves after a zero-delay timeout (a macrotask, unlike `Promise.resolve`).
+async fn yield_to_event_loop() {
+    let promise = js_sys::Promise::new(&mut |resolve, _reject| set_timeout(&resolve, 0));
+    let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+}
+
+/// Runs `match_lines_chunked` over the retained window (the live window of a live stream,
+/// else the resident buffer) and records how many bytes were scanned. A needle longer than
+/// every indexed line skips the scan (0 bytes scanned). Timing is left to the caller (it
+/// needs the JS clock).
+fn search_resident(engine: &mut LogEngine, needle: &[u8]) -> Vec<u64> {
+    if engine.needle_exceeds_lines(needle) {
+        engine.record_search(SearchStats::default());
+        return Vec::new();
+    }
+    let (window, start) = engine.retained_window();
+    let indices = match_lines_chunked(window, start, engine.offsets(), needle, SEARCH_CHUNK_BYTES);
+    let bytes_scanned = window.len() as u64;
+    engine.record_search(SearchStats {
+        bytes_scanned,
+        duration_ms: 0.0,
+    });
+    indices
+}
+
+/// Returns `{ bytes_scanned, duration_ms }` for the most recent `search()` call.
+#[wasm_bindgen(skip_typescript)]
+pub fn search_stats() -> Result<JsValue, JsError> {
+    to_js(&ENGINE.read().expect("engine lock").last_search_stats())
+}
+
+/// Returns per-subsystem timings accumulated since start-up or `reset_profile()`: an array
+/// of `{ category, total_ms, calls }` for scan, search, classify, decode, filter and
+/// printability.
+/// Not reset by `clear()`, so it covers a whole session.
+#[wasm_bindgen(skip_typescript)]
+pub fn get_profile() -> Result<JsValue, JsError> {
+    to_js(&ENGINE.read().expect("engine lock").profile().entries())
+}
+
+/// Zeroes the timings reported by `get_profile()`.
+#[wasm_bindgen]
+pub fn reset_profile() {
+    ENGINE.write().expect("engine lock").reset_profile();
+}
+
+/// Returns the engine's current allocations: `{ line_count, offsets_bytes, offsets_wide,
+/// buffer_capacity, buffer_high_water, decode_scratch_bytes, ci_index_bytes,
+/// top_lines_bytes, previews_bytes, numeric_extractor_bytes, counters_bytes,
+/// printability_bytes, live_bytes, match_set_bytes, total_bytes, compact_before_bytes,
+/// compact_after_bytes, search_handles, decode_cache_lines }` (capacity-based byte counts;
+/// `buffer_high_water` is the largest chunk buffer since `clear()`; `compact_before_bytes`
+/// and `compact_after_bytes` are `total_bytes` around the last `trim_memory()`;
+/// `search_handles` is the number of live `compile_search` handles).
+#[wasm_bindgen(skip_typescript)]
+pub fn get_memory_stats() -> Result<JsValue, JsError> {
+    to_js(&ENGINE.read().expect("engine lock").memory_stats())
+}
+
+/// Number of line offsets held (evicted lines excluded). With `offsets_capacity()` this
+/// shows the reallocation slack behind `offsets_bytes` in `get_memory_stats()`.
+#[wasm_bindgen]
+pub fn offsets_len() -> usize {
+    ENGINE.read().expect("engine lock").offsets().stored()
+}
+
+/// Number of line offsets the index can hold before it reallocates.
+#[wasm_bindgen]
+pub fn offsets_capacity() -> usize {
+    ENGINE.read().expect("engine lock").offsets().capacity()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::XorShift;
+    use indexer::observer::ChunkObserver;
+
+    /// Writes `text` through the pointer protocol and indexes it, keeping the bytes
+    /// resident (no discard) so search has content to scan.
+    fn engine_with_resident(text: &[u8]) -> LogEngine {
+        let mut engine = LogEngine::new();
+        let ptr = engine.get_buffer_pointer(text.len());
+        unsafe { std::ptr::copy_nonoverlapping(text.as_ptr(), ptr, text.len()) };
+        index_written_chunks(&mut engine, &[text.len()]);
+        engine
+    }
+
+    /// Writes `pieces` back-to-back through one reserved region and indexes them in one
+    /// batched call, as `index_chunks` does.
+    fn index_batched(engine: &mut LogEngine, pieces: &[&[u8]]) {
+        let total: usize = pieces.iter().map(|p| p.len()).sum();
+        let mut ptr = engine.get_buffer_pointer(total);
+        for piece in pieces {
+            unsafe {
+                std::ptr::copy_nonoverlapping(piece.as_ptr(), ptr, piece.len());
+                ptr = ptr.add(piece.len());
+            }
+        }
+        let lens: Vec<usize> = pieces.iter().map(|p| p.len()).collect();
+        index_written_chunks(engine, &lens);
+        engine.discard_buffer_after_indexing();
+    }
+
+    /// Writes and indexes one piece, as `get_buffer_pointer` + `index_chunk` does.
+    fn index_single(engine: &mut LogEngine, piece: &[u8]) {
+        let ptr = engine.get_buffer_pointer(piece.len());
+        unsafe { std::ptr::copy_nonoverlapping(piece.as_ptr(), ptr, piece.len()) };
+        index_written_chunks(engine, &[piece.len()]);
+        engine.discard_buffer_after_indexing();
+    }
+
+    #[test]
+    fn escaped_needles_match_control_bytes() {
+        let mut engine = engine_with_resident(b"a\tb\nplain\nbell\x07\n\x1b[31mred\r\n");
+        let mut find = |pattern: &str| {
+            let needle = search::escape::unescape_needle(pattern).unwrap();
+            search_resident(&mut engine, &needle)
+        };
+        assert_eq!(find(r"\t"), [0]);
+        assert_eq!(find(r"\n"), [0, 1, 2, 3]);
+        assert_eq!(find(r"\r\n"), [3]);
+        assert_eq!(find(r"l\x07"), [2]);
+        assert_eq!(find(r"\x1B[31m"), [3]);
+        assert_eq!(find(r"\\t"), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn boundary_state_tracks_chunks() {
+        let mut engine = LogEngine::new();
+        index_single(&mut engine, b"one\ntw");
+        let state = engine.boundary_state();
+        assert_eq!(state.total_bytes, 6);
+        assert!(!state.last_chunk_ended_with_newline);
+        assert_eq!(state.line_count, 2);
+        assert_eq!(state.buffer_len, 0);
+        index_single(&mut engine, b"o\nthree");
+        let state = engine.boundary_state();
+        assert_eq!((state.total_bytes, state.line_count), (13, 3));
+        assert!(!state.last_chunk_ended_with_newline);
+        assert!(state.buffer_capacity >= state.buffer_len);
+    }
+
+    #[test]
+    fn pause_mid_line_resumes_where_it_stopped() {
+        let pieces: [&[u8]; 4] = [b"alpha be", b"ta gamma\nde", b"lta\nlast li", b"ne"];
+        let mut unpaused = LogEngine::new();
+        for piece in pieces {
+            index_single(&mut unpaused, piece);
+        }
+
+        let mut engine = LogEngine::new();
+        engine.set_expected_size(40);
+        index_single(&mut engine, pieces[0]);
+        assert!(engine.pause_ingest());
+        assert!(!engine.pause_ingest());
+        // A chunk submitted while paused is written, rejected and discarded.
+        let ptr = engine.get_buffer_pointer(pieces[1].len());
+        unsafe { std::ptr::copy_nonoverlapping(pieces[1].as_ptr(), ptr, pieces[1].len()) };
+        assert_eq!(engine.check_not_paused(), Err(core::ingest::IngestPaused));
+        engine.discard_buffer_after_indexing();
+        let stats = engine.ingest_stats(now_ms());
+        assert!(stats.paused);
+        assert_eq!(stats.indexed_bytes, 8);
+        assert_eq!(stats.lag_bytes, Some(32.0));
+        assert!(engine.resume_ingest());
+        assert!(engine.check_not_paused().is_ok());
+        for piece in &pieces[1..] {
+            index_single(&mut engine, piece);
+        }
+
+        let starts = |e: &LogEngine| e.offsets().iter().collect::<Vec<u64>>();
+        assert_eq!(starts(&engine), starts(&unpaused));
+        assert_eq!(starts(&engine), [0, 17, 23]);
+        assert_eq!(engine.total_bytes_indexed(), unpaused.total_bytes_indexed());
+        let stats = engine.ingest_stats(now_ms());
+        assert!(!stats.paused);
+        assert_eq!(stats.lag_bytes, Some(8.0));
+        assert!(stats.bytes_per_ms > 0.0 || stats.active_ms == 0.0);
+        engine.set_expected_size(32);
+        assert_eq!(engine.ingest_stats(now_ms()).eta_ms, Some(0.0));
+    }
+
+    #[test]
+    fn unseen_counts_only_matches_after_mark_seen() {
+        let mut engine = LogEngine::new();
+        engine.set_stream_filter(Some(regex::bytes::Regex::new("ERROR").unwrap()));
+        let handle = engine.add_search(CompiledSearch::compile("warn", 4).unwrap());
+        append_live_bytes(&mut engine, b"ERROR a\nWARN b\nERR").unwrap();
+        assert_eq!(engine.unseen_count(0), Some(1));
+        assert_eq!(engine.unseen_count(handle), Some(0));
+        assert_eq!(engine.unseen_count(99), None);
+
+        // The user scrolls up after line 1; the split line 2 completes afterwards.
+        assert!(engine.mark_seen(0, 1));
+        assert!(engine.mark_seen(handle, 1));
+        assert!(!engine.mark_seen(99, 1));
+        assert_eq!(engine.unseen_count(0), Some(0));
+        append_live_bytes(&mut engine, b"OR c\nwarn d\nERROR e\nWarn f").unwrap();
+        assert_eq!(engine.unseen_count(0), Some(2));
+        assert_eq!(engine.unseen_count(handle), Some(1));
+        engine.mark_complete();
+        assert_eq!(engine.unseen_count(handle), Some(2));
+
+        assert!(engine.mark_seen(0, 3));
+        assert!(engine.mark_seen(handle, 3));
+        assert_eq!(engine.unseen_count(0), Some(1));
+        assert_eq!(engine.unseen_count(handle), Some(1));
+        // An earlier line does not move the baseline back.
+        assert!(engine.mark_seen(0, 0));
+        assert_eq!(engine.unseen_count(0), Some(1));
+
+        assert!(engine.drop_search(handle));
+        assert_eq!(engine.unseen_count(handle), None);
+        engine.set_stream_filter(None);
+        assert_eq!(engine.unseen_count(0), None);
+        assert!(!engine.mark_seen(0, 0));
+    }
+
+    #[test]
+    fn retention_evicts_lines_older_than_max_age() {
+        let mut engine = LogEngine::new();
+        engine.set_stream_filter(Some(regex::bytes::Regex::new("new|old").unwrap()));
+        engine.set_retention(RetentionPolicy {
+            max_lines: 0,
+            max_age_ms: 100.0,
+        });
+        append_live_at(&mut engine, b"old0\nold1\nmid", 0.0).unwrap();
+        append_live_at(&mut engine, b"2\nnew3\nnew4", 60.0).unwrap();
+        assert_eq!(engine.retention_evictions(), 0);
+        // At t = 150 the lines that started at t = 0 are older than 100 ms.
+        assert_eq!(append_live_at(&mut engine, b"\nnew5", 150.0), Ok(6));
+        assert_eq!(engine.retention_evictions(), 3);
+        assert_eq!(engine.line_count(), 6);
+        assert_eq!(engine.stream_filter_results(), Some(vec![3, 4]));
+
+        // Queries spanning the eviction edge see only the retained lines.
+        assert_eq!(engine.line_range(2), None);
+        assert_eq!(engine.line_range(3), Some((15, 20)));
+        assert_eq!(engine.get_line_boundaries(0, 5), [15, 20, 25]);
+        assert_eq!(engine.plan_window(1, 5).start_line, 3);
+        assert_eq!(engine.line_at_offset(2), Some(3));
+        assert_eq!(engine.line_at_offset(21), Some(4));
+        assert_eq!(engine.live_first_line(), Some(3));
+        assert_eq!(engine.read_live(2, 4), None);
+        assert_eq!(engine.read_live(3, 6).unwrap(), b"new3\nnew4\nnew5");
+        // The last line is kept however old it gets.
+        engine.set_retention(RetentionPolicy {
+            max_lines: 0,
+            max_age_ms: 1.0,
+        });
+        append_live_at(&mut engine, b" tail", 1000.0).unwrap();
+        assert_eq!(engine.retention_evictions(), 5);
+        assert_eq!(engine.read_live(5, 6).unwrap(), b"new5 tail");
+        engine.clear_data();
+        assert_eq!(engine.retention_evictions(), 0);
+    }
+
+    #[test]
+    fn retention_count_cap_crosses_block_index_boundaries() {
+        let mut engine = LogEngine::new();
+        engine.enable_ci_index();
+        engine.set_retention(RetentionPolicy {
+            max_lines: 1000,
+            max_age_ms: 0.0,
+        });
+        // Line i is 99 digits plus `\n`, starting at byte 100 * i; 3.6 MB crosses several
+        // 1 MiB blocks of the coarse index.
+        let lines = 36_000u64;
+        for i in 0..lines {
+            let text = format!("{}{i:099}", if i == 0 { "" } else { "\n" });
+            append_live_at(&mut engine, text.as_bytes(), i as f64).unwrap();
+        }
+        assert!(lines * 100 > 3 * core::engine::BLOCK_INDEX_BYTES);
+        let first = lines - 1000;
+        assert_eq!(engine.retention_evictions(), first);
+        assert_eq!(engine.line_count(), lines as usize);
+        assert_eq!(engine.offsets().range(0, lines as usize).count(), 1000);
+        for line in (first..lines).step_by(37).chain([first, lines - 1]) {
+            let offset = line * 100 + 42;
+            assert_eq!(engine.line_at_offset(offset), Some(line as usize));
+            assert_eq!(engine.line_range(line as usize).unwrap().0, line * 100);
+        }
+        assert_eq!(engine.line_at_offset(5), Some(first as usize));
+        assert_eq!(engine.line_range(first as usize - 1), None);
+        let expected = format!("{:099}\n{:099}", lines - 2, lines - 1);
+        assert_eq!(
+            engine
+                .read_live(lines as usize - 2, lines as usize)
+                .unwrap(),
+            expected.as_bytes()
+        );
+        assert!(engine.memory_stats().live_bytes < 1 << 20);
+    }
+
+    #[test]
+    fn live_appends_merge_partial_lines() {
+        let mut engine = LogEngine::new();
+        engine.add_counters(vec![b"err".to_vec()]).unwrap();
+        assert_eq!(append_live_bytes(&mut engine, b"first li").unwrap(), 1);
+        assert_eq!(append_live_bytes(&mut engine, b"ne\nsecond er").unwrap(), 2);
+        assert_eq!(append_live_bytes(&mut engine, b"ror\nthi").unwrap(), 3);
+        assert_eq!(append_live_bytes(&mut engine, b"rd").unwrap(), 3);
+        assert_eq!(
+            engine.read_live(0, 3).unwrap(),
+            b"first line\nsecond error\nthird"
+        );
+        assert_eq!(engine.read_live(1, 2).unwrap(), b"second error\n");
+        assert_eq!(engine.live_first_line(), Some(0));
+        assert_eq!(engine.counter(0), Some(1));
+        // File chunks cannot join a live session, nor live text a file session.
+        assert!(engine.check_file_ingest().is_err());
+        engine.clear_data();
+        index_single(&mut engine, b"file line\n");
+        assert!(engine.check_file_ingest().is_ok());
+        assert_eq!(
+            append_live_bytes(&mut engine, b"x\n"),
+            Err(MixedIngest {
+                live_rejected: true
+            })
+        );
+        assert_eq!(engine.read_live(0, 1), None);
+    }
+
+    #[test]
+    fn live_window_evicts_under_sustained_appends() {
+        let mut engine = LogEngine::new();
+        engine.set_live_retained_bytes(1000);
+        let mut stream = Vec::new();
+        for i in 0..5000 {
+            let line = format!("event {i} payload {}\n", "x".repeat(i % 37));
+            stream.extend_from_slice(line.as_bytes());
+        }
+        stream.extend_from_slice(b"partial");
+        // Lines arrive split at varying points.
+        let mut rest = &stream[..];
+        let mut size = 1;
+        while !rest.is_empty() {
+            let cut = (size % 61 + 1).min(rest.len());
+            let (piece, tail) = rest.split_at(cut);
+            append_live_bytes(&mut engine, piece).unwrap();
+            rest = tail;
+            size += 7;
+        }
+        let offsets: Vec<u64> = engine.offsets().iter().collect();
+        assert_eq!(offsets.len(), 5001);
+        let first = engine.live_first_line().unwrap();
+        assert!(first > 4900 && first < 5001, "first retained line {first}");
+        assert!(engine.memory_stats().live_bytes < 4000);
+        // Retained lines read back exactly; evicted ones are refused.
+        assert_eq!(
+            engine.read_live(first, 5001).unwrap(),
+            &stream[offsets[first] as usize..]
+        );
+        assert_eq!(engine.read_live(first - 1, 5001), None);
+        assert_eq!(engine.read_live(0, 1), None);
+        // Search scans the retained window: its lines match, evicted ones do not.
+        assert_eq!(search_resident(&mut engine, b"event 4999 "), [4999]);
+        assert_eq!(search_resident(&mut engine, b"partial"), [5000]);
+        assert!(search_resident(&mut engine, b"event 10 ").is_empty());
+        let (window, _) = engine.retained_window();
+        let retained = window.len() as u64;
+        assert_eq!(engine.last_search_stats().bytes_scanned, retained);
+    }
+
+    #[test]
+    fn checked_chunk_rejects_bad_crc_without_indexing() {
+        let mut engine = LogEngine::new();
+        let chunk = b"one\ntwo\n";
+        let crc = crc32fast::hash(chunk);
+
+        let ptr = engine.get_buffer_pointer(chunk.len());
+        unsafe { std::ptr::copy_nonoverlapping(chunk.as_ptr(), ptr, chunk.len()) };
+        let err = index_written_checked(&mut engine, chunk.len(), crc ^ 1).unwrap_err();
+        assert_eq!(err.actual, crc);
+        assert_eq!(engine.line_count(), 0);
+        assert_eq!(engine.total_bytes_indexed(), 0);
+        assert_eq!(engine.buffer_len(), 0);
+
+        let ptr = engine.get_buffer_pointer(chunk.len());
+        unsafe { std::ptr::copy_nonoverlapping(chunk.as_ptr(), ptr, chunk.len()) };
+        index_written_checked(&mut engine, chunk.len(), crc).unwrap();
+        assert_eq!(engine.offsets().iter().collect::<Vec<_>>(), [0, 4]);
+        assert_eq!(engine.total_bytes_indexed(), chunk.len() as u64);
+    }
+
+    #[test]
+    fn copy_in_path_matches_pointer_path() {
+        let text = "alpha\nbeta\r\ngamma δ\n\nlast";
+        for split in 0..=text.len() {
+            let (head, tail) = text.as_bytes().split_at(split);
+            let mut pointer = LogEngine::new();
+            let mut copied = LogEngine::new();
+            for piece in [head, tail] {
+                index_single(&mut pointer, piece);
+                index_copied(&mut copied, piece);
+            }
+            assert_eq!(
+                copied.offsets().iter().collect::<Vec<_>>(),
+                pointer.offsets().iter().collect::<Vec<_>>()
+            );
+            assert_eq!(copied.total_bytes_indexed(), pointer.total_bytes_indexed());
+            assert_eq!(
+                copied.last_chunk_ended_with_newline(),
+                pointer.last_chunk_ended_with_newline()
+            );
+            assert_eq!(copied.buffer_len(), 0);
+        }
+    }
+
+    #[test]
+    fn nul_runs_are_skip_regions_on_lines_of_their_own() {
+        let text = b"\0\0\0\0\0start\nok \0\0 short\nerr\0\0\0\0\0\0after err\n";
+        let mut engine = LogEngine::new();
+        engine.set_skip_nul_runs(Some(4));
+        engine.set_stream_filter(Some(regex::bytes::Regex::new("err").unwrap()));
+        // Split inside the second run.
+        index_single(&mut engine, &text[..31]);
+        index_single(&mut engine, &text[31..]);
+
+        // Leading run, "start", "ok \0\0 short" (verbatim), "err", run, "after err".
+        let starts: Vec<u64> = engine.offsets().iter().collect();
+        assert_eq!(starts, [0, 5, 11, 23, 26, 32]);
+        assert_eq!(engine.skip_regions(), [(0, 5), (26, 32)]);
+        // Features count the runs as lines, so filter hits keep real line numbers.
+        assert_eq!(engine.stream_filter_results(), Some(vec![3, 5]));
+        // Lines cut at a run have no terminator.
+        let lengths = engine.longest_lines(6);
+        for line in [(0, 5), (3, 3), (4, 6), (5, 9)] {
+            assert!(lengths.contains(&line), "{line:?} in {lengths:?}");
+        }
+
+        let config = engine.decode_config();
+        let decoded = decode_window_line(&text[26..32], config).unwrap();
+        assert_eq!(decoded, "\u{27E8}6 NUL bytes skipped\u{27E9}");
+        assert_eq!(
+            decode_window_line(&text[11..23], config).unwrap(),
+            "ok \0\0 short\n"
+        );
+
+        // The threshold survives clear_data, the regions do not.
+        engine.clear_data();
+        assert!(engine.skip_regions().is_empty());
+        index_single(&mut engine, b"\0\0\0\0x");
+        assert_eq!(engine.skip_regions(), [(0, 4)]);
+        engine.clear();
+        assert_eq!(engine.decode_config().skip_nul_runs, 0);
+    }
+
+    #[test]
+    fn header_spanning_chunks_is_not_a_line() {
+        // A 10-byte binary header (with newlines in it) ahead of the first line.
+        let data = b"HDR\n\0\x01\n\x02\x03\x04INFO a\nERROR b\nINFO c";
+        let mut engine = LogEngine::new();
+        engine.set_header_skip(10);
+        engine.set_stream_filter(Some(regex::bytes::Regex::new("^ERROR").unwrap()));
+        // 4-byte chunks: the header covers the first two and part of the third.
+        for chunk in data.chunks(4) {
+            index_single(&mut engine, chunk);
+        }
+        engine.mark_complete();
+        assert_eq!(engine.offsets().iter().collect::<Vec<_>>(), [10, 17, 25]);
+        assert_eq!(engine.line_range(0), Some((10, 17)));
+        assert_eq!(engine.stream_filter_results(), Some(vec![1]));
+
+        // Raw regions take the same path; the setting survives clear_data.
+        engine.clear_data();
+        index_region(&mut engine, &data[..3], 0).unwrap();
+        index_region(&mut engine, &data[3..], 3).unwrap();
+        assert_eq!(engine.offsets().iter().collect::<Vec<_>>(), [10, 17, 25]);
+
+        // Records are counted from the end of the header.
+        engine.clear();
+        assert_eq!(engine.header_skip(), 0);
+        engine.set_header_skip(5);
+        engine.set_record_size(Some(4));
+        for chunk in b"HDR\n\0aaaabbbbcc".chunks(3) {
+            index_single(&mut engine, chunk);
+        }
+        assert_eq!(engine.offsets().iter().collect::<Vec<_>>(), [5, 9, 13]);
+    }
+
+    /// A chunk as observed: `(base_offset, bytes, line starts)`.
+    type Observed = (u64, Vec<u8>, Vec<u64>);
+
+    /// Records each chunk it observes.
+    #[derive(Clone, Default)]
+    struct Recorder(std::sync::Arc<std::sync::Mutex<Vec<Observed>>>);
+
+    impl ChunkObserver for Recorder {
+        fn observe(&mut self, chunk: &[u8], base_offset: u64, new_line_starts: &[u64]) {
+            let seen = (base_offset, chunk.to_vec(), new_line_starts.to_vec());
+            self.0.lock().unwrap().push(seen);
+        }
+
+        fn reset(&mut self) {
+            self.0.lock().unwrap().clear();
+        }
+    }
+
+    #[test]
+    fn observers_see_the_scanned_bytes_and_line_starts() {
+        let text = b"HDRfirst\nsecond line\n\nthird\nlast";
+        for split in 0..=text.len() {
+            let (head, tail) = text.split_at(split);
+            for path in 0..3 {
+                let recorder = Recorder::default();
+                let mut engine = LogEngine::new();
+                engine.set_header_skip(3);
+                engine.add_observer(Box::new(recorder.clone()));
+                match path {
+                    0 => {
+                        index_single(&mut engine, head);
+                        index_single(&mut engine, tail);
+                    }
+                    1 => index_batched(&mut engine, &[head, tail]),
+                    _ => {
+                        index_region(&mut engine, head, 0).unwrap();
+                        index_region(&mut engine, tail, split as u64).unwrap();
+                    }
+                }
+                // Contiguous chunks from the end of the header, each with the starts the
+                // scanner found in it: its bytes after a newline (or the header).
+                let (mut next, mut bytes, mut starts) = (3, Vec::new(), Vec::new());
+                for (base, chunk, chunk_starts) in recorder.0.lock().unwrap().iter() {
+                    assert_eq!(*base, next, "split {split}, path {path}");
+                    next += chunk.len() as u64;
+                    for &start in chunk_starts {
+                        assert!((*base..next).contains(&start));
+                        assert!(start == 3 || text[start as usize - 1] == b'\n');
+                    }
+                    bytes.extend_from_slice(chunk);
+                    starts.extend_from_slice(chunk_starts);
+                }
+                assert_eq!(bytes, &text[3..], "split {split}, path {path}");
+                assert_eq!(starts, engine.offsets().iter().collect::<Vec<_>>());
+                assert_eq!(starts, [3, 9, 21, 22, 28]);
+
+                engine.clear_data();
+                assert!(recorder.0.lock().unwrap().is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn trailing_newline_adds_no_empty_line() {
+        let cases: [(&[u8], &[u64]); 5] = [
+            (b"", &[]),
+            (b"a\nb\n", &[0, 2]),
+            (b"a\nb", &[0, 2]),
+            (b"a\n\nb\n", &[0, 2, 3]),
+            (b"\n", &[0]),
+        ];
+        for (text, starts) in cases {
+            // Any split, including right after a newline, gives the same lines.
+            for split in 0..=text.len() {
+                let (head, tail) = text.split_at(split);
+                let mut engine = LogEngine::new();
+                index_single(&mut engine, head);
+                index_single(&mut engine, tail);
+                assert_eq!(engine.offsets().iter().collect::<Vec<_>>(), starts);
+                let ranges = engine.get_line_ranges(0, starts.len());
+                assert_eq!(ranges.len(), starts.len());
+                assert_eq!(
+                    ranges.last().map(|r| r.1),
+                    starts.last().map(|_| text.len() as u64)
+                );
+            }
+        }
+        // The last line's terminator is not part of its length.
+        let engine = engine_with_resident(b"a\nbcd\n");
+        assert_eq!(engine.longest_lines(1), [(1, 3)]);
+    }
+
+    #[test]
+    fn reserved_lines_avoid_regrowth_without_changing_offsets() {
+        let text: Vec<u8> = (0..2000)
+            .flat_map(|i| format!("line {i}\n").into_bytes())
+            .collect();
+        let mut plain = LogEngine::new();
+        let mut reserved = LogEngine::new();
+        assert!(reserved.reserve_lines(2000));
+        let capacity = reserved.offsets().capacity();
+        assert!(capacity >= 2000);
+        for piece in text.chunks(333) {
+            index_single(&mut plain, piece);
+            index_single(&mut reserved, piece);
+        }
+        assert_eq!(reserved.offsets().capacity(), capacity);
+        assert_eq!(
+            reserved.offsets().iter().collect::<Vec<_>>(),
+            plain.offsets().iter().collect::<Vec<_>>()
+        );
+        // An absurd estimate fails instead of aborting, and reserves nothing.
+        assert!(!reserved.reserve_lines(usize::MAX));
+        assert_eq!(reserved.offsets().capacity(), capacity);
+    }
+
+    #[test]
+    fn batched_chunks_match_individual_submission() {
+        let mut rng = XorShift::new(0x9E37_79B9_7F4A_7C15);
+        for _ in 0..200 {
+            let len = rng.below(300) as usize;
+            let data: Vec<u8> = (0..len).map(|_| b"ab\n\r"[rng.below(4) as usize]).collect();
+            let mut cuts: Vec<usize> = (0..rng.below(8))
+                .map(|_| rng.below(len as u64 + 1) as usize)
+                .collect();
+            cuts.push(0);
+            cuts.push(len);
+            cuts.sort_unstable();
+            let pieces: Vec<&[u8]> = cuts.windows(2).map(|w| &data[w[0]..w[1]]).collect();
+
+            let mut batched = LogEngine::new();
+            let mut single = LogEngine::new();
+            // Split the batches in two so state also carries across index_chunks calls.
+            let mid = pieces.len() / 2;
+            index_batched(&mut batched, &pieces[..mid]);
+            index_batched(&mut batched, &pieces[mid..]);
+            for piece in &pieces {
+                index_single(&mut single, piece);
+            }
+            assert_eq!(
+                batched.offsets().iter().collect::<Vec<_>>(),
+                single.offsets().iter().collect::<Vec<_>>()
+            );
+            assert_eq!(batched.total_bytes_indexed(), single.total_bytes_indexed());
+            assert_eq!(
+                batched.last_chunk_ended_with_newline(),
+                single.last_chunk_ended_with_newline()
+            );
+            assert_eq!(batched.buffer_len(), 0);
+        }
+    }
+
+    #[test]
+    fn exported_results_round_trip() {
+        let text: Vec<u8> = (0..3000)
+            .flat_map(|i| {
+                let tag = if i % 7 == 0 || i == 2999 {
+                    "ERROR"
+                } else {
+                    "info"
+                };
+                format!("{tag} event {i}\n").into_bytes()
+            })
+            .collect();
+        let mut engine = engine_with_resident(&text);
+        let lines = search_resident(&mut engine, b"ERROR");
+        let packed = search::packed::encode_lines(&lines);
+        assert_eq!(search::packed::decode_lines(&packed).unwrap(), lines);
+        // One byte per gap under 128, against 8 per `Float64Array` entry.
+        assert_eq!(packed.len(), lines.len());
+    }
+
+    #[test]
+    fn search_records_bytes_scanned() {
+        let text = b"alpha\nbeta\ngamma alpha\n";
+        let mut engine = engine_with_resident(text);
+        assert_eq!(search_resident(&mut engine, b"alpha"), [0, 2]);
+        let stats = engine.last_search_stats();
+        assert_eq!(stats.bytes_scanned, text.len() as u64);
+        assert!(stats.bytes_scanned <= engine.total_bytes_indexed());
+        engine.clear();
+        assert_eq!(engine.last_search_stats(), SearchStats::default());
+    }
+
+    /// Every `skip_typescript` export must be declared in bindings.d.ts, and every declared
+    /// function must be a `skip_typescript` export (otherwise it would be declared twice).
+    #[test]
+    fn typescript_declarations_match_skipped_exports() {
+        let source = include_str!("lib.rs");
+        let mut skipped = Vec::new();
+        let mut lines = source.lines();
+        while let Some(line) = lines.next() {
+            if line == "#[wasm_bindgen(skip_typescript)]" {
+                let signature = lines.next().unwrap_or_default();
+                let name = signature
+                    .trim_start_matches("pub fn ")
+                    .split('(')
+                    .next()
+                    .unwrap_or_default();
+                skipped.push(name.to_owned());
+            }
+        }
+        let mut declared: Vec<String> = include_str!("bindings.d.ts")
+            .lines()
+            .filter_map(|l| l.strip_prefix("export function "))
+            .map(|l| l.split('(').next().unwrap_or_default().to_owned())
+            .collect();
+        skipped.sort();
+        declared.sort();
+        assert!(!skipped.is_empty());
+        assert_eq!(skipped, declared);
+    }
+
+    #[test]
+    fn index_region_reads_raw_memory_without_buffering() {
+        let data: Vec<u8> = b"first\nsecond\nthi".to_vec();
+        let tail: Vec<u8> = b"rd\nlast".to_vec();
+        let mut engine = LogEngine::new();
+        let region = unsafe { std::slice::from_raw_parts(data.as_ptr(), data.len()) };
+        index_region(&mut engine, region, 0).unwrap();
+        let region = unsafe { std::slice::from_raw_parts(tail.as_ptr(), tail.len()) };
+        index_region(&mut engine, region, data.len() as u64).unwrap();
+        assert_eq!(engine.offsets().iter().collect::<Vec<_>>(), [0, 6, 13, 19]);
+        assert_eq!(engine.total_bytes_indexed(), 23);
+        assert_eq!(engine.buffer_len(), 0);
+    }
+
+    #[test]
+    fn region_with_stale_base_offset_is_rejected() {
+        let mut engine = LogEngine::new();
+        index_region(&mut engine, b"first\nsecond\n", 0).unwrap();
+        // A buggy caller re-sends a region at an offset inside the indexed data.
+        let err = index_region(&mut engine, b"again\nx", 6).unwrap_err();
+        assert_eq!(
+            err,
+            OffsetOrderError {
+                index: 2,
+                offset: 6,
+                prev: 6
+            }
+        );
+        assert_eq!(engine.offsets().iter().collect::<Vec<_>>(), [0, 6]);
+        assert_eq!(engine.total_bytes_indexed(), 13);
+        assert_eq!(engine.get_line_ranges(0, 2), [(0, 6), (6, 13)]);
+        index_region(&mut engine, b"third\n", 13).unwrap();
+        assert_eq!(engine.line_count(), 3);
+    }
+
+    #[test]
+    fn stream_filter_sees_lines_split_across_chunks() {
+        let mut engine = LogEngine::new();
+        engine.set_stream_filter(Some(regex::bytes::Regex::new("^ERROR .*disk$").unwrap()));
+        index_single(&mut engine, b"INFO boot\nERROR full ");
+        index_single(&mut engine, b"disk\nINFO ok\nERROR no");
+        assert_eq!(engine.buffer_len(), 0);
+        assert_eq!(engine.stream_filter_results().unwrap(), [1]);
+        index_single(&mut engine, b" disk");
+        assert_eq!(engine.stream_filter_results().unwrap(), [1]);
+        engine.mark_complete();
+        assert_eq!(engine.stream_filter_results().unwrap(), [1, 3]);
+
+        engine.clear_data();
+        index_single(&mut engine, b"ERROR disk\n");
+        assert_eq!(engine.stream_filter_results().unwrap(), [0]);
+        engine.clear();
+        assert!(engine.stream_filter_results().is_none());
+    }
+
+    #[test]
+    fn compact_after_a_grow_clear_cycle_shrinks_live_bytes() {
+        let mut engine = LogEngine::new();
+        engine.set_stream_filter(Some(regex::bytes::Regex::new("^x").unwrap()));
+        engine.enable_ci_index();
+        for chunk in b"x line\n".repeat(20_000).chunks(4096) {
+            index_single(&mut engine, chunk);
+        }
+        engine.clear_data();
+        index_single(&mut engine, b"x small\nother\n");
+        let results = engine.stream_filter_results();
+        let ranges = engine.get_line_ranges(0, engine.line_count());
+
+        let before = engine.memory_stats();
+        assert!(before.match_set_bytes >= 20_000 * 8);
+        let freed = engine.compact() as u64;
+        let after = engine.memory_stats();
+        assert_eq!(freed, before.total_bytes - after.total_bytes);
+        assert!(after.total_bytes * 10 < before.total_bytes);
+        assert!(after.match_set_bytes < before.match_set_bytes);
+        assert_eq!(after.compact_before_bytes, before.total_bytes);
+        assert_eq!(after.compact_after_bytes, after.total_bytes);
+        assert_eq!(engine.stream_filter_results(), results);
+        assert_eq!(engine.get_line_ranges(0, engine.line_count()), ranges);
+    }
+
+    #[test]
+    fn line_previews_cut_before_a_split_character() {
+        let mut engine = LogEngine::new();
+        engine.set_utf8_mode(decode::lines::Utf8Mode::Replace);
+        engine.enable_line_previews(6);
+        // "caf\u{e9}" is 5 bytes, so the 6-byte cut falls inside the next "\u{e9}".
+        index_single(&mut engine, "ok\r\ncaf\u{e9}\u{e9}s and more\n".as_bytes());
+        index_single(&mut engine, "exact\u{e9}\n".as_bytes());
+        let previews = engine.line_previews(0, 10).unwrap();
+        assert_eq!(
+            previews
+                .into_iter()
+                .map(|p| (p.line, p.text, p.truncated))
+                .collect::<Vec<_>>(),
+            [
+                (0, "ok".to_string(), false),
+                (1, "caf\u{e9}".to_string(), true),
+                (2, "exact".to_string(), true),
+            ]
+        );
+
+        let stats = engine.memory_stats();
+        assert!(stats.previews_bytes >= 14 + 3 * 4);
+        engine.compact();
+        assert_eq!(engine.memory_stats().previews_bytes, 14 + 3 * 4);
+        engine.clear_data();
+        assert!(engine.line_previews(0, 10).unwrap().is_empty());
+        engine.clear();
+        assert!(engine.line_previews(0, 10).is_none());
+        assert_eq!(engine.memory_stats().previews_bytes, 0);
+    }
+
+    #[test]
+    fn offsets_capacity_covers_length_across_chunks() {
+        let mut engine = LogEngine::new();
+        for chunk in 0..8 {
+            let text = format!("chunk {chunk}\n").repeat(100);
+            index_single(&mut engine, text.as_bytes());
+            let offsets = engine.offsets();
+            assert_eq!(offsets.stored(), (chunk + 1) * 100);
+            assert!(offsets.capacity() >= offsets.stored());
+        }
+        engine.compact();
+        assert_eq!(engine.offsets().capacity(), engine.offsets().stored());
+    }
+
+    #[test]
+    fn render_window_highlights_every_match() {
+        let blob = "GET /a timeout\r\nok\n€ timeout, retry after timeout\ntimeout";
+        let ends = [16, 19, 52, 59];
+        let lines = render_window_lines(
+            blob.as_bytes(),
+            &ends,
+            "timeout",
+            40,
+            DecodeConfig::default(),
+        );
+        let rendered: Vec<_> = lines
+            .iter()
+            .map(|l| (l.n, l.text.as_str(), l.matches.clone()))
+            .collect();
+        assert_eq!(
+            rendered,
+            [
+                (40, "GET /a timeout", vec![[7, 14]]),
+                (41, "ok", vec![]),
+                // "€" is three bytes but one character.
+                (42, "€ timeout, retry after timeout", vec![[2, 9], [23, 30]]),
+                (43, "timeout", vec![[0, 7]]),
+            ]
+        );
+    }
+
+    #[test]
+    fn crlf_split_across_chunks_matches_unsplit_input() {
+        // Only `\n` ends a line, so a `\r` at the end of a chunk is plain line content
+        // until the next chunk's `\n`: no boundary state beyond the newline flag is needed.
+        let text = b"a\r\nb\n\r\nc\r";
+        let lines = |engine: &LogEngine| {
+            let ranges = engine.get_line_ranges(0, engine.line_count());
+            let ends: Vec<u64> = ranges.iter().map(|r| r.1).collect();
+            let auto = DecodeConfig {
+                line_endings: LineEndings::Auto,
+                ..Default::default()
+            };
+            (ranges, decode_lines_checked(text, &ends, auto).unwrap())
+        };
+        let filtered = |pieces: &[&[u8]]| {
+            let mut engine = LogEngine::new();
+            engine.set_stream_filter(Some(regex::bytes::Regex::new("^(a|c)$").unwrap()));
+            for piece in pieces {
+                index_single(&mut engine, piece);
+            }
+            engine.mark_complete();
+            engine
+        };
+        let whole = filtered(&[text]);
+        let (ranges, decoded) = lines(&whole);
+        assert_eq!(ranges, [(0, 3), (3, 5), (5, 7), (7, 9)]);
+        let expected = ["a", "b", "", "c"].map(|l| Some(l.to_string()));
+        assert_eq!(decoded, expected);
+        assert_eq!(whole.stream_filter_results().unwrap(), [0, 3]);
+        for split in 0..=text.len() {
+            let (head, tail) = text.split_at(split);
+            let engine = filtered(&[head, tail]);
+            assert_eq!(
+                lines(&engine),
+                (ranges.clone(), decoded.clone()),
+                "split {split}"
+            );
+            assert_eq!(engine.stream_filter_results().unwrap(), [0, 3]);
+        }
+    }
+
+    #[test]
+    fn detected_format_reports_dominant_format_of_mixed_file() {
+        let mut engine = LogEngine::new();
+        assert_eq!(engine.detected_format().format, "unknown");
+        let json = r#"{"level":"info","msg":"ok"}"#;
+        let logfmt = "level=info msg=ok";
+        let text = format!("{json}\n{logfmt}\n{json}\n\n{json}\n{json}");
+        let (head, tail) = text.as_bytes().split_at(10);
+        index_single(&mut engine, head);
+        index_single(&mut engine, tail);
+        let detected = engine.detected_format();
+        assert_eq!(
+            (detected.format.as_str(), detected.sampled_lines),
+            ("json", 4)
+        );
+        assert_eq!((detected.confidence, detected.scores.logfmt), (0.75, 0.25));
+        engine.mark_complete();
+        assert_eq!(engine.detected_format().confidence, 0.8);
+        engine.clear_data();
+        assert_eq!(engine.detected_format().sampled_lines, 0);
+    }
+
+    #[test]
+    fn needle_longer_than_any_line_skips_the_scan() {
+        let mut engine = engine_with_resident(b"short\nlonger line\nmid\n");
+        assert_eq!(search_resident(&mut engine, b"longer line"), [1]);
+        assert_eq!(engine.last_search_stats().bytes_scanned, 22);
+        assert!(search_resident(&mut engine, b"longer line!").is_empty());
+        assert_eq!(engine.last_search_stats().bytes_scanned, 0);
+    }
+
+    #[test]
+    fn counters_are_fed_by_indexed_chunks() {
+        let mut engine = LogEngine::new();
+        let ids = engine
+            .add_counters(vec![b"ERROR".to_vec(), b"db".to_vec()])
+            .unwrap();
+        assert_eq!(ids, [0, 1]);
+        assert_eq!(engine.add_counters(vec![b"ERR".to_vec()]).unwrap(), [2]);
+        index_single(&mut engine, b"ERROR db\nINFO db ok\nER");
+        index_single(&mut engine, b"ROR x\n");
+        assert_eq!(engine.all_counters(), [2, 2, 2]);
+        assert_eq!((engine.counter(1), engine.counter(3)), (Some(2), None));
+        engine.clear_data();
+        index_single(&mut engine, b"db\n");
+        assert_eq!(engine.all_counters(), [0, 1, 0]);
+        engine.clear();
+        assert!(engine.all_counters().is_empty());
+    }
+
+    #[test]
+    fn longest_lines_include_first_middle_and_last() {
+        let mut engine = LogEngine::new();
+        let long = |n: usize| "x".repeat(n);
+        let text = format!(
+            "{}\nab\n\nabc\n{}\r\nq\n{}",
+            long(300),
+            long(200),
+            long(100)
+        );
+        // A chunk boundary inside a long line must not split its length.
+        let (head, tail) = text.as_bytes().split_at(400);
+        index_single(&mut engine, head);
+        index_single(&mut engine, tail);
+        assert_eq!(
+            engine.longest_lines(3),
+            [(0, 300), (4, 201), (6, 100)],
+            "the CR of a CRLF counts, the LF does not; the last line has no terminator"
+        );
+        let histogram = engine.length_histogram();
+        // Empty, 1 (q), 2-3 (ab, abc), 64-127, 128-255, 256-511.
+        assert_eq!(histogram, [1, 1, 2, 0, 0, 0, 0, 1, 1, 1]);
+        assert_eq!(histogram.iter().sum::<u64>(), engine.line_count() as u64);
+    }
+
+    #[test]
+    fn fixed_records_ignore_newlines_across_chunks() {
+        let mut engine = LogEngine::new();
+        engine.set_record_size(Some(6));
+        // 4 full records plus a partial one, fed in 5-byte chunks.
+        let data = b"AB\nCDE\n\n\n\nFGHIJKLMNOPQRSTUV";
+        for chunk in data.chunks(5) {
+            index_single(&mut engine, chunk);
+        }
+        assert_eq!(
+            engine.offsets().iter().collect::<Vec<_>>(),
+            [0, 6, 12, 18, 24]
+        );
+        assert_eq!(engine.line_range(4), Some((24, 27)));
+        assert_eq!(engine.longest_lines(1), [(0, 6)]);
     }
-    arr.into()
 }
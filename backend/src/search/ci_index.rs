@@ -0,0 +1,168 @@
+//! Optional shadow index for case-insensitive literal search: one 64-bit fingerprint per
+//! line recording which ASCII-lowercased bytes occur in it. A needle whose bytes are not
+//! all present cannot match, so the line is rejected before the fold-and-compare. The
+//! fingerprint only prunes; every line it lets through is still matched exactly.
+
+use memchr::memchr_iter;
+
+/// Fingerprint that rejects nothing: used for lines containing non-ASCII bytes (Unicode
+/// case folding maps e.g. the Kelvin sign to `k`) and for lines indexed before the
+/// shadow index was enabled.
+const ANY: u64 = u64::MAX;
+
+/// Bit for one byte of a line or needle, after ASCII lowercasing.
+#[inline(always)]
+fn byte_bit(b: u8) -> u64 {
+    if b.is_ascii() {
+        1 << (b.to_ascii_lowercase() & 63)
+    } else {
+        ANY
+    }
+}
+
+/// Fingerprint bits a line must contain to possibly match `needle` case-insensitively.
+/// Returns 0 (prune nothing) for non-ASCII needles, whose folding is not bytewise.
+pub fn required_bits(needle: &[u8]) -> u64 {
+    if !needle.is_ascii() {
+        return 0;
+    }
+    needle.iter().fold(0, |bits, &b| bits | byte_bit(b))
+}
+
+#[derive(Debug)]
+pub struct CaseIndex {
+    /// Global index of the line `masks[0]` describes.
+    first_line: u64,
+    /// Fingerprints of complete lines from `first_line` on.
+    masks: Vec<u64>,
+    /// Bits of the line still being indexed (its terminator not seen yet).
+    current: u64,
+}
+
+impl CaseIndex {
+    /// Starts fingerprinting at line `first_line`. `mid_line` means that line is already
+    /// in progress; its fingerprint is unknown and it is never pruned.
+    pub fn new(first_line: u64, mid_line: bool) -> Self {
+        Self {
+            first_line,
+            masks: Vec::new(),
+            current: if mid_line { ANY } else { 0 },
+        }
+    }
+
+    /// Starts over at line 0 (for a new file).
+    pub fn reset(&mut self) {
+        *self = Self::new(0, false);
+    }
+
+    /// Drops the fingerprints of lines before `line` (evicted by retention).
+    pub fn evict_before(&mut self, line: u64) {
+        let evicted = line
+            .saturating_sub(self.first_line)
+            .min(self.masks.len() as u64);
+        self.masks.drain(..evicted as usize);
+        self.first_line += evicted;
+    }
+
+    /// Consumes the next bytes of the stream, fingerprinting every line they complete.
+    pub fn feed(&mut self, chunk: &[u8]) {
+        let mut start = 0;
+        for nl in memchr_iter(b'\n', chunk) {
+            let mask = fingerprint(&chunk[start..nl]) | self.current;
+            self.masks.push(mask);
+            self.current = 0;
+            start = nl + 1;
+        }
+        self.current |= fingerprint(&chunk[start..]);
+    }
+
+    /// False only when `line` certainly lacks a byte from `required` (see
+    /// `required_bits`). Lines outside the fingerprinted range are always candidates.
+    #[inline(always)]
+    pub fn may_contain(&self, line: u64, required: u64) -> bool {
+        let mask = line
+            .checked_sub(self.first_line)
+            .and_then(|i| self.masks.get(i as usize))
+            .copied()
+            .unwrap_or(ANY);
+        required & !mask == 0
+    }
+
+    /// Releases spare capacity of the fingerprint list.
+    pub fn shrink_to_fit(&mut self) {
+        self.masks.shrink_to_fit();
+    }
+
+    pub fn heap_bytes(&self) -> usize {
+        self.masks.capacity() * std::mem::size_of::<u64>()
+    }
+}
+
+fn fingerprint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |bits, &b| bits | byte_bit(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::XorShift;
+
+    /// Naive reference: ASCII-lowercase both sides and look for the needle.
+    fn contains_ignore_case(line: &[u8], needle: &[u8]) -> bool {
+        let line = line.to_ascii_lowercase();
+        let needle = needle.to_ascii_lowercase();
+        needle.is_empty() || line.windows(needle.len()).any(|w| w == needle)
+    }
+
+    #[test]
+    fn never_rejects_a_matching_line() {
+        const ALPHABET: &[u8] = b"aAbBzZ09_ -\n\xc3\xa9";
+        let mut rng = XorShift::new(0x5eed_1234);
+        for _ in 0..300 {
+            let len = rng.below(120) as usize;
+            let text: Vec<u8> = (0..len)
+                .map(|_| ALPHABET[rng.below(ALPHABET.len() as u64) as usize])
+                .collect();
+            let split = rng.below(text.len() as u64 + 1) as usize;
+            let mut index = CaseIndex::new(0, false);
+            index.feed(&text[..split]);
+            index.feed(&text[split..]);
+            let needle_len = 1 + rng.below(3) as usize;
+            let needle: Vec<u8> = (0..needle_len)
+                .map(|_| ALPHABET[rng.below(8) as usize])
+                .collect();
+            let required = required_bits(&needle);
+            for (i, line) in text.split(|&b| b == b'\n').enumerate() {
+                if contains_ignore_case(line, &needle) {
+                    assert!(index.may_contain(i as u64, required), "{text:?} {needle:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn prunes_ascii_lines_without_the_needle_bytes() {
+        let mut index = CaseIndex::new(0, false);
+        index.feed(b"Connection RESET\nok\nstra\xc3\x9fe\n");
+        let required = required_bits(b"reset");
+        assert!(index.may_contain(0, required));
+        assert!(!index.may_contain(1, required));
+        // Non-ASCII lines and the incomplete last line are never pruned.
+        assert!(index.may_contain(2, required));
+        assert!(index.may_contain(3, required));
+        assert_eq!(required_bits("é".as_bytes()), 0);
+    }
+
+    #[test]
+    fn enabled_mid_line_keeps_that_line() {
+        let mut index = CaseIndex::new(4, true);
+        index.feed(b"xyz\nxyz\n");
+        let required = required_bits(b"q");
+        assert!(index.may_contain(3, required));
+        assert!(index.may_contain(4, required));
+        assert!(!index.may_contain(5, required));
+        index.reset();
+        index.feed(b"xyz\n");
+        assert!(!index.may_contain(0, required));
+    }
+}
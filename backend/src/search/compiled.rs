@@ -0,0 +1,488 @@
+//! Prepared searches: a pattern compiled once (literal finder, regex, or glob) and run
+//! repeatedly over line ranges, e.g. to highlight each visible window or new tail lines.
+
+use memchr::memmem;
+use regex::bytes::{Regex, RegexBuilder};
+
+use super::ci_index::{required_bits, CaseIndex};
+use super::fold::fold_ascii;
+use super::matcher::{resident_line, resident_line_count, resident_lines_from, ChunkPattern};
+use crate::core::offsets::LineOffsets;
+use crate::decode::lines::strip_line_terminator;
+
+/// `pattern` is a regular expression.
+pub const SEARCH_REGEX: u32 = 1;
+/// `pattern` is a glob: `*` matches any run of bytes, `?` one character.
+pub const SEARCH_GLOB: u32 = 2;
+/// Match ASCII and Unicode letters case-insensitively.
+pub const SEARCH_IGNORE_CASE: u32 = 4;
+/// Only accept matches not touching a word character on either side, where word
+/// characters are ASCII letters, digits and `_`.
+pub const SEARCH_WHOLE_WORD: u32 = 8;
+/// Whole-word matching (implies `SEARCH_WHOLE_WORD`) where any Unicode letter or digit
+/// counts as a word character, so `café` does not match inside `cafés` or `écafé`.
+pub const SEARCH_UNICODE_WORD_BOUNDARY: u32 = 16;
+
+/// A compiled search. Every kind matches anywhere within a line (terminator excluded),
+/// optionally restricted to whole words.
+#[derive(Debug)]
+pub struct CompiledSearch {
+    matcher: Matcher,
+    boundary: Option<WordChars>,
+    /// For case-insensitive literals, the `CaseIndex` bits a line needs to match; 0 when
+    /// the shadow index cannot prune this search.
+    ci_required: u64,
+}
+
+#[derive(Debug)]
+enum Matcher {
+    /// Boxed: a `Finder` is several times larger than a `Regex` handle.
+    Literal(Box<memmem::Finder<'static>>),
+    /// Case-insensitive ASCII literal (see `FoldedLiteral`).
+    Folded(Box<FoldedLiteral>),
+    Regex(Regex),
+}
+
+/// Lines are folded into a stack buffer this many bytes at a time.
+const FOLD_WINDOW: usize = 256;
+
+/// Case-insensitive ASCII literal: ASCII lines are lowercased window by window (see
+/// `fold::fold_ascii`) and searched for the lowercased needle. Lines with non-ASCII bytes
+/// go to the case-insensitive regex, since Unicode folding maps e.g. the Kelvin sign to
+/// `k`; so does a needle longer than a window.
+#[derive(Debug)]
+struct FoldedLiteral {
+    finder: memmem::Finder<'static>,
+    regex: Regex,
+}
+
+impl FoldedLiteral {
+    fn is_match(&self, line: &[u8]) -> bool {
+        let needle_len = self.finder.needle().len();
+        if !line.is_ascii() || needle_len > FOLD_WINDOW {
+            return self.regex.is_match(line);
+        }
+        let mut window = [0u8; FOLD_WINDOW];
+        let mut start = 0;
+        loop {
+            let end = (start + FOLD_WINDOW).min(line.len());
+            let folded = &mut window[..end - start];
+            fold_ascii(&line[start..end], folded);
+            if self.finder.find(folded).is_some() {
+                return true;
+            }
+            if end == line.len() {
+                return false;
+            }
+            // Overlap by needle_len - 1 so a hit straddling two windows is still seen.
+            start = end + 1 - needle_len;
+        }
+    }
+}
+
+/// Which characters count as part of a word for whole-word matching.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WordChars {
+    Ascii,
+    Unicode,
+}
+
+impl WordChars {
+    fn contains(self, c: char) -> bool {
+        match self {
+            WordChars::Ascii => c.is_ascii_alphanumeric() || c == '_',
+            WordChars::Unicode => c.is_alphanumeric() || c == '_',
+        }
+    }
+}
+
+impl CompiledSearch {
+    /// Compiles `pattern` per `flags`. A case-insensitive literal is compiled as an
+    /// escaped regex, plus a fold-and-compare fast path when it is ASCII. Fails only for
+    /// invalid regexes.
+    pub fn compile(pattern: &str, flags: u32) -> Result<Self, regex::Error> {
+        let boundary = if flags & SEARCH_UNICODE_WORD_BOUNDARY != 0 {
+            Some(WordChars::Unicode)
+        } else if flags & SEARCH_WHOLE_WORD != 0 {
+            Some(WordChars::Ascii)
+        } else {
+            None
+        };
+        let literal = flags & (SEARCH_REGEX | SEARCH_GLOB) == 0;
+        let ci_required = if literal && flags & SEARCH_IGNORE_CASE != 0 {
+            required_bits(pattern.as_bytes())
+        } else {
+            0
+        };
+        Ok(Self {
+            matcher: Matcher::compile(pattern, flags)?,
+            boundary,
+            ci_required,
+        })
+    }
+
+    /// True when `line` (terminator stripped) matches.
+    pub fn is_match(&self, line: &[u8]) -> bool {
+        let line = strip_line_terminator(line);
+        match self.boundary {
+            None => self.matcher.is_match(line),
+            Some(word) => self
+                .matcher
+                .find_iter(line)
+                .any(|(start, end)| is_whole_word(line, start, end, word)),
+        }
+    }
+
+    /// Matching resident lines in `[start_line, end_line)`, ascending. Lines `ci_index`
+    /// rules out are skipped without matching.
+    pub fn matching_lines(
+        &self,
+        buffer: &[u8],
+        offsets: &LineOffsets,
+        start_line: usize,
+        end_line: usize,
+        ci_index: Option<&CaseIndex>,
+    ) -> Vec<u64> {
+        let ci_index = ci_index.filter(|_| self.ci_required != 0);
+        resident_lines_from(buffer, offsets, start_line)
+            .take(end_line.saturating_sub(start_line))
+            .filter(|&(i, _)| ci_index.is_none_or(|index| index.may_contain(i, self.ci_required)))
+            .filter(|(_, line)| self.is_match(line))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// First matching resident line after `after_line` (`None` = from the top). With
+    /// `wrap`, continues from the top up to and including `after_line`.
+    pub fn next_match(
+        &self,
+        buffer: &[u8],
+        offsets: &LineOffsets,
+        after_line: Option<usize>,
+        wrap: bool,
+    ) -> Option<u64> {
+        let count = resident_line_count(buffer, offsets);
+        let first = after_line.map_or(0, |l| l.saturating_add(1)).min(count);
+        let wrapped = if wrap { 0..first } else { 0..0 };
+        (first..count)
+            .chain(wrapped)
+            .find(|&i| self.is_match(resident_line(buffer, offsets, i)))
+            .map(|i| i as u64)
+    }
+
+    /// Last matching resident line before `before_line` (`None` = from the bottom),
+    /// scanning line indices in reverse. With `wrap`, continues from the bottom down to
+    /// and including `before_line`.
+    pub fn prev_match(
+        &self,
+        buffer: &[u8],
+        offsets: &LineOffsets,
+        before_line: Option<usize>,
+        wrap: bool,
+    ) -> Option<u64> {
+        let count = resident_line_count(buffer, offsets);
+        let end = before_line.unwrap_or(count).min(count);
+        let wrapped = if wrap { end..count } else { count..count };
+        (0..end)
+            .rev()
+            .chain(wrapped.rev())
+            .find(|&i| self.is_match(resident_line(buffer, offsets, i)))
+            .map(|i| i as u64)
+    }
+}
+
+/// Tests whole lines, so a `ChunkMatcher` can run it on lines as they are indexed.
+impl ChunkPattern for CompiledSearch {
+    fn max_hit_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn matches_line(&self, line: &[u8]) -> bool {
+        self.is_match(line)
+    }
+}
+
+impl Matcher {
+    fn compile(pattern: &str, flags: u32) -> Result<Self, regex::Error> {
+        let ignore_case = flags & SEARCH_IGNORE_CASE != 0;
+        let source = if flags & SEARCH_REGEX != 0 {
+            pattern.to_string()
+        } else if flags & SEARCH_GLOB != 0 {
+            glob_to_regex(pattern)
+        } else if ignore_case {
+            let regex = RegexBuilder::new(&regex::escape(pattern))
+                .case_insensitive(true)
+                .build()?;
+            if pattern.is_empty() || !pattern.is_ascii() {
+                return Ok(Matcher::Regex(regex));
+            }
+            let needle = pattern.to_ascii_lowercase().into_bytes();
+            let finder = memmem::Finder::new(&needle).into_owned();
+            return Ok(Matcher::Folded(Box::new(FoldedLiteral { finder, regex })));
+        } else {
+            let finder = memmem::Finder::new(pattern.as_bytes()).into_owned();
+            return Ok(Matcher::Literal(Box::new(finder)));
+        };
+        RegexBuilder::new(&source)
+            .case_insensitive(ignore_case)
+            .build()
+            .map(Matcher::Regex)
+    }
+
+    fn is_match(&self, line: &[u8]) -> bool {
+        match self {
+            Matcher::Literal(finder) => finder.find(line).is_some(),
+            Matcher::Folded(folded) => folded.is_match(line),
+            Matcher::Regex(regex) => regex.is_match(line),
+        }
+    }
+
+    /// Byte ranges of non-overlapping matches in `line`.
+    fn find_iter<'a>(&'a self, line: &'a [u8]) -> Box<dyn Iterator<Item = (usize, usize)> + 'a> {
+        match self {
+            Matcher::Literal(finder) => {
+                let len = finder.needle().len();
+                Box::new(
+                    finder
+                        .find_iter(line)
+                        .map(move |start| (start, start + len)),
+                )
+            }
+            Matcher::Folded(folded) => {
+                Box::new(folded.regex.find_iter(line).map(|m| (m.start(), m.end())))
+            }
+            Matcher::Regex(regex) => Box::new(regex.find_iter(line).map(|m| (m.start(), m.end()))),
+        }
+    }
+}
+
+/// True when the match `line[start..end]` has no word character immediately before or
+/// after it. Neighbours are decoded as UTF-8; invalid bytes count as non-word.
+fn is_whole_word(line: &[u8], start: usize, end: usize, word: WordChars) -> bool {
+    let before = char_before(line, start).is_some_and(|c| word.contains(c));
+    let after = char_after(line, end).is_some_and(|c| word.contains(c));
+    !before && !after
+}
+
+/// The character ending at byte `pos`, if the bytes there are valid UTF-8.
+fn char_before(line: &[u8], pos: usize) -> Option<char> {
+    (1..=4.min(pos)).find_map(|len| {
+        std::str::from_utf8(&line[pos - len..pos])
+            .ok()
+            .and_then(|s| s.chars().next())
+    })
+}
+
+/// The character starting at byte `pos`, if the bytes there are valid UTF-8.
+fn char_after(line: &[u8], pos: usize) -> Option<char> {
+    let tail = &line[pos..(pos + 4).min(line.len())];
+    match std::str::from_utf8(tail) {
+        Ok(s) => s.chars().next(),
+        Err(e) => std::str::from_utf8(&tail[..e.valid_up_to()])
+            .ok()
+            .and_then(|s| s.chars().next()),
+    }
+}
+
+/// Translates a glob into an unanchored regex: `*` → `.*`, `?` → `.`, the rest literal.
+fn glob_to_regex(glob: &str) -> String {
+    let mut out = String::with_capacity(glob.len() * 2);
+    let mut buf = [0u8; 4];
+    for c in glob.chars() {
+        match c {
+            '*' => out.push_str(".*"),
+            '?' => out.push('.'),
+            c => out.push_str(&regex::escape(c.encode_utf8(&mut buf))),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::XorShift;
+
+    const TEXT: &[u8] = b"GET /a 200\nPOST /b 500\nget /c 404\nGET /d 500\n";
+
+    fn offsets() -> LineOffsets {
+        LineOffsets::from(&[0, 11, 23, 34][..])
+    }
+
+    fn run(pattern: &str, flags: u32) -> Vec<u64> {
+        CompiledSearch::compile(pattern, flags)
+            .unwrap()
+            .matching_lines(TEXT, &offsets(), 0, 4, None)
+    }
+
+    #[test]
+    fn literal_regex_and_glob() {
+        assert_eq!(run("GET", 0), [0, 3]);
+        assert_eq!(run("GET", SEARCH_IGNORE_CASE), [0, 2, 3]);
+        assert_eq!(run(r"5\d\d$", SEARCH_REGEX), [1, 3]);
+        assert_eq!(run("/? 50?", SEARCH_GLOB), [1, 3]);
+        assert_eq!(run("get*500", SEARCH_GLOB | SEARCH_IGNORE_CASE), [3]);
+        assert_eq!(run("a.b", SEARCH_GLOB), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn reuse_across_windows_is_consistent() {
+        let search = CompiledSearch::compile("500", 0).unwrap();
+        let whole = search.matching_lines(TEXT, &offsets(), 0, 4, None);
+        let mut windowed = search.matching_lines(TEXT, &offsets(), 0, 2, None);
+        windowed.extend(search.matching_lines(TEXT, &offsets(), 2, 4, None));
+        assert_eq!(windowed, whole);
+        assert_eq!(search.matching_lines(TEXT, &offsets(), 3, 100, None), [3]);
+        assert!(search
+            .matching_lines(TEXT, &offsets(), 4, 2, None)
+            .is_empty());
+    }
+
+    fn word_matches(pattern: &str, flags: u32, line: &str) -> bool {
+        CompiledSearch::compile(pattern, flags)
+            .unwrap()
+            .is_match(line.as_bytes())
+    }
+
+    #[test]
+    fn whole_word_ascii_boundaries() {
+        assert!(word_matches("err", SEARCH_WHOLE_WORD, "an err here"));
+        assert!(word_matches("err", SEARCH_WHOLE_WORD, "err: x"));
+        assert!(!word_matches("err", SEARCH_WHOLE_WORD, "an error"));
+        assert!(!word_matches("err", SEARCH_WHOLE_WORD, "my_err"));
+        // A later occurrence can still be a whole word.
+        assert!(word_matches("err", SEARCH_WHOLE_WORD, "errors, err"));
+        // ASCII mode: accented letters are not word characters.
+        assert!(word_matches("caf", SEARCH_WHOLE_WORD, "café"));
+    }
+
+    #[test]
+    fn unicode_word_boundary_respects_accented_letters() {
+        let flags = SEARCH_UNICODE_WORD_BOUNDARY;
+        assert!(!word_matches("caf", flags, "café"));
+        assert!(word_matches("café", flags, "un café noir"));
+        assert!(!word_matches("café", flags, "cafés"));
+        assert!(!word_matches("naïve", flags, "ünaïve"));
+        assert!(word_matches("größe", flags, "(größe)"));
+        assert!(word_matches(
+            "größe",
+            flags | SEARCH_IGNORE_CASE,
+            "GRÖSSE größe"
+        ));
+        assert!(!word_matches("x", flags, "xй"));
+        assert!(word_matches(r"\d+", flags | SEARCH_REGEX, "id=42 ok"));
+        assert!(!word_matches(r"\d+", flags | SEARCH_REGEX, "v42é"));
+        // Invalid UTF-8 next to the match counts as a boundary.
+        assert!(CompiledSearch::compile("ab", flags)
+            .unwrap()
+            .is_match(b"\xffab\xfe"));
+    }
+
+    #[test]
+    fn next_and_prev_wrap_around() {
+        let search = CompiledSearch::compile("500", 0).unwrap();
+        let next = |after, wrap| search.next_match(TEXT, &offsets(), after, wrap);
+        let prev = |before, wrap| search.prev_match(TEXT, &offsets(), before, wrap);
+        assert_eq!(next(None, false), Some(1));
+        assert_eq!(next(Some(1), false), Some(3));
+        assert_eq!(next(Some(3), false), None);
+        assert_eq!(next(Some(3), true), Some(1));
+        assert_eq!(prev(None, false), Some(3));
+        assert_eq!(prev(Some(3), false), Some(1));
+        assert_eq!(prev(Some(1), false), None);
+        assert_eq!(prev(Some(1), true), Some(3));
+        assert_eq!(next(Some(100), true), Some(1));
+        assert_eq!(prev(Some(100), false), Some(3));
+    }
+
+    #[test]
+    fn single_match_wraps_to_itself() {
+        let search = CompiledSearch::compile("POST", 0).unwrap();
+        assert_eq!(search.next_match(TEXT, &offsets(), Some(1), false), None);
+        assert_eq!(search.next_match(TEXT, &offsets(), Some(1), true), Some(1));
+        assert_eq!(search.prev_match(TEXT, &offsets(), Some(1), true), Some(1));
+        assert_eq!(search.prev_match(TEXT, &offsets(), Some(0), false), None);
+        let none = CompiledSearch::compile("nope", 0).unwrap();
+        assert_eq!(none.next_match(TEXT, &offsets(), None, true), None);
+        assert_eq!(none.prev_match(TEXT, &offsets(), None, true), None);
+    }
+
+    #[test]
+    fn invalid_regex_fails_to_compile() {
+        assert!(CompiledSearch::compile("(", SEARCH_REGEX).is_err());
+        // The same text is fine as a literal or glob.
+        assert!(CompiledSearch::compile("(", 0).is_ok());
+        assert!(CompiledSearch::compile("(", SEARCH_GLOB).is_ok());
+    }
+
+    #[test]
+    fn case_index_never_changes_results() {
+        // Kelvin sign and long s fold to ASCII `k` and `s` under Unicode case folding.
+        const WORDS: &[&str] = &[
+            "Kiss",
+            "KISS",
+            "\u{212a}i\u{17f}\u{17f}",
+            "kit",
+            "is",
+            "\r",
+            "é",
+            "ÉK",
+        ];
+        let mut rng = XorShift::new(0x00c1_5eed);
+        for round in 0..200 {
+            let mut text = String::new();
+            let mut starts = vec![0u64];
+            for _ in 0..rng.below(12) {
+                for _ in 0..rng.below(4) {
+                    text.push_str(WORDS[rng.below(WORDS.len() as u64) as usize]);
+                    text.push(' ');
+                }
+                text.push('\n');
+                starts.push(text.len() as u64);
+            }
+            let offsets = LineOffsets::from(&starts[..]);
+            let split = rng.below(text.len() as u64 + 1) as usize;
+            let mut index = CaseIndex::new(0, false);
+            index.feed(&text.as_bytes()[..split]);
+            index.feed(&text.as_bytes()[split..]);
+            let needle = ["kiss", "KI", "s ", "it", "é", "k"][round % 6];
+            let search = CompiledSearch::compile(needle, SEARCH_IGNORE_CASE).unwrap();
+            let count = starts.len();
+            assert_eq!(
+                search.matching_lines(text.as_bytes(), &offsets, 0, count, Some(&index)),
+                search.matching_lines(text.as_bytes(), &offsets, 0, count, None),
+                "{text:?} {needle:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn folded_literal_agrees_with_case_insensitive_regex() {
+        let mut rng = XorShift::new(0xF01D_ED00_1234_5678);
+        const PIECES: &[&str] = &["ab", "AB", "aB", "x", "k", "K", " ", "\u{212a}", "é"];
+        for round in 0..300 {
+            // Odd rounds add non-ASCII pieces (regex fallback); long ASCII lines make
+            // hits straddle fold windows.
+            let pieces = if round % 2 == 0 {
+                7
+            } else {
+                PIECES.len() as u64
+            };
+            let line: String = (0..rng.below(400))
+                .map(|_| PIECES[rng.below(pieces) as usize])
+                .collect();
+            let needle = ["ab", "Abab", "xK", "k", "bx a", "AB AB", "KxK"][round % 7];
+            let search = CompiledSearch::compile(needle, SEARCH_IGNORE_CASE).unwrap();
+            assert!(matches!(search.matcher, Matcher::Folded(_)));
+            let regex = RegexBuilder::new(&regex::escape(needle))
+                .case_insensitive(true)
+                .build()
+                .unwrap();
+            assert_eq!(
+                search.is_match(line.as_bytes()),
+                regex.is_match(line.as_bytes()),
+                "{line:?} {needle:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! Content-line filtering: tells blank lines and comment lines (lines starting with one
+//! of a set of byte prefixes) apart from content lines. Works on resident lines only.
+
+use crate::core::offsets::LineOffsets;
+use crate::decode::lines::strip_line_terminator;
+use crate::search::matcher::resident_lines;
+
+/// Which lines count as non-content.
+pub struct ContentFilter {
+    /// Lines starting with any of these bytes are comments (e.g. `#`, `//`).
+    prefixes: Vec<Vec<u8>>,
+    /// Treat lines made only of ASCII whitespace as blank (otherwise only empty lines are).
+    whitespace_is_blank: bool,
+}
+
+impl ContentFilter {
+    pub fn new(prefixes: Vec<Vec<u8>>, whitespace_is_blank: bool) -> Self {
+        Self {
+            prefixes,
+            whitespace_is_blank,
+        }
+    }
+
+    /// True when `line` (terminator included or not) is neither blank nor a comment.
+    pub fn is_content(&self, line: &[u8]) -> bool {
+        let line = strip_line_terminator(line);
+        let blank = if self.whitespace_is_blank {
+            line.iter().all(u8::is_ascii_whitespace)
+        } else {
+            line.is_empty()
+        };
+        !blank && !self.prefixes.iter().any(|p| line.starts_with(p))
+    }
+
+    /// Number of resident content lines.
+    pub fn count(&self, buffer: &[u8], offsets: &LineOffsets) -> usize {
+        resident_lines(buffer, offsets)
+            .filter(|(_, line)| self.is_content(line))
+            .count()
+    }
+
+    /// Indices of resident content lines, ascending.
+    pub fn line_indices(&self, buffer: &[u8], offsets: &LineOffsets) -> Vec<u64> {
+        resident_lines(buffer, offsets)
+            .filter(|(_, line)| self.is_content(line))
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &[u8] = b"# header\nfirst\n\n   \n// note\nsecond # trailing\r\n\t\r\n#\nlast";
+
+    fn offsets() -> LineOffsets {
+        let mut starts = vec![0u64];
+        starts.extend(
+            memchr::memchr_iter(b'\n', TEXT)
+                .map(|p| p as u64 + 1)
+                .filter(|&s| s < TEXT.len() as u64),
+        );
+        LineOffsets::from(&starts[..])
+    }
+
+    #[test]
+    fn skips_blank_and_prefixed_lines() {
+        let filter = ContentFilter::new(vec![b"#".to_vec(), b"//".to_vec()], true);
+        assert_eq!(filter.line_indices(TEXT, &offsets()), [1, 5, 8]);
+        assert_eq!(filter.count(TEXT, &offsets()), 3);
+    }
+
+    #[test]
+    fn whitespace_lines_count_unless_requested() {
+        let filter = ContentFilter::new(vec![b"#".to_vec()], false);
+        // "   " and "\t" are content now; "// note" has no matching prefix.
+        assert_eq!(filter.line_indices(TEXT, &offsets()), [1, 3, 4, 5, 6, 8]);
+    }
+
+    #[test]
+    fn no_prefixes_only_drops_empty_lines() {
+        let filter = ContentFilter::new(Vec::new(), false);
+        assert_eq!(filter.count(TEXT, &offsets()), 8);
+        assert_eq!(filter.count(b"", &LineOffsets::new()), 0);
+    }
+}
@@ -0,0 +1,162 @@
+//! Ingest-time pattern counters: one Aho–Corasick pass per chunk counts, for each of a
+//! fixed set of literal patterns, how many lines contain it. A `ChunkMatcher` carries the
+//! last `longest - 1` bytes of each chunk into the next scan, so a hit split across chunks
+//! is found, and reports each line once per pattern.
+
+use aho_corasick::{AhoCorasick, BuildError, MatchKind};
+
+use super::matcher::{ChunkMatcher, ChunkPattern};
+
+/// Overlapping hits, so every pattern on a line is seen, not only the leftmost.
+impl ChunkPattern for AhoCorasick {
+    fn max_hit_len(&self) -> Option<usize> {
+        Some(self.max_pattern_len().max(1))
+    }
+
+    fn find_hits(&self, haystack: &[u8], hit: &mut dyn FnMut(usize, usize, usize)) {
+        for found in self.find_overlapping_iter(haystack) {
+            hit(found.start(), found.end(), found.pattern().as_usize());
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PatternCounters {
+    automaton: AhoCorasick,
+    /// Carried bytes and line numbering across chunks.
+    matcher: ChunkMatcher,
+    /// Matching lines per pattern.
+    counts: Vec<u64>,
+}
+
+impl PatternCounters {
+    /// Builds the automaton for `patterns` (non-empty literals; a hit may not contain a
+    /// newline except as its last byte). Counting starts at line 0.
+    pub fn new(patterns: &[Vec<u8>]) -> Result<Self, BuildError> {
+        let automaton = AhoCorasick::builder()
+            .match_kind(MatchKind::Standard)
+            .build(patterns)?;
+        Ok(Self {
+            automaton,
+            matcher: ChunkMatcher::new(0, false),
+            counts: vec![0; patterns.len()],
+        })
+    }
+
+    /// Zeroes the counts and restarts at line 0, keeping the patterns (for a new file).
+    pub fn reset(&mut self) {
+        self.matcher.reset();
+        self.counts.iter_mut().for_each(|c| *c = 0);
+    }
+
+    /// Counts the hits completed by the next bytes of the stream.
+    pub fn feed(&mut self, chunk: &[u8]) {
+        let counts = &mut self.counts;
+        self.matcher
+            .feed_text(&self.automaton, chunk, |m| counts[m.id] += 1);
+    }
+
+    /// Matching lines for pattern `id` so far, or `None` for an unknown id.
+    #[inline(always)]
+    pub fn count(&self, id: usize) -> Option<u64> {
+        self.counts.get(id).copied()
+    }
+
+    /// Heap bytes held (automaton, carried bytes and counts).
+    pub fn heap_bytes(&self) -> usize {
+        self.automaton.memory_usage()
+            + self.matcher.heap_bytes()
+            + self.counts.capacity() * std::mem::size_of::<u64>()
+    }
+
+    /// Matching lines per pattern, in registration order.
+    #[inline(always)]
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::XorShift;
+
+    fn counters(patterns: &[&str]) -> PatternCounters {
+        let patterns: Vec<Vec<u8>> = patterns.iter().map(|p| p.as_bytes().to_vec()).collect();
+        PatternCounters::new(&patterns).unwrap()
+    }
+
+    /// Reference: matching lines per pattern, searching each line on its own.
+    fn per_line(text: &[u8], patterns: &[&str]) -> Vec<u64> {
+        patterns
+            .iter()
+            .map(|p| {
+                let finder = memchr::memmem::Finder::new(p.as_bytes());
+                text.split_inclusive(|&b| b == b'\n')
+                    .filter(|line| finder.find(line).is_some())
+                    .count() as u64
+            })
+            .collect()
+    }
+
+    #[test]
+    fn overlapping_and_nested_patterns_each_count() {
+        let patterns = ["error", "err", "rror: db", "aa"];
+        let mut c = counters(&patterns);
+        c.feed(b"error: db down\nerr only\naaa aaaa\nfine\nerror error\n");
+        assert_eq!(c.counts(), [2, 3, 1, 1]);
+        assert_eq!(c.count(1), Some(3));
+        assert_eq!(c.count(4), None);
+    }
+
+    #[test]
+    fn hits_split_across_chunks_count_once() {
+        let patterns = ["timeout", "out\n", "t"];
+        let text = b"conn timeout\nread timeout after 5s\nok\ntimeout";
+        for size in 1..text.len() {
+            let mut c = counters(&patterns);
+            for chunk in text.chunks(size) {
+                c.feed(chunk);
+            }
+            assert_eq!(c.counts(), per_line(text, &patterns), "chunk size {size}");
+        }
+    }
+
+    #[test]
+    fn random_chunking_matches_per_line_counts() {
+        let mut rng = XorShift::new(0x9E37_79B9_7F4A_7C15);
+        let patterns = ["ab", "b", "aba", "b\na", "bb\n"];
+        for _ in 0..300 {
+            let text: Vec<u8> = (0..rng.below(120))
+                .map(|_| b"ab\n"[rng.below(3) as usize])
+                .collect();
+            let mut c = counters(&patterns);
+            let mut rest = &text[..];
+            while !rest.is_empty() {
+                let (head, tail) = rest.split_at(1 + rng.below(rest.len() as u64) as usize);
+                c.feed(head);
+                rest = tail;
+            }
+            // "b\na" spans lines and never counts.
+            assert_eq!(
+                c.counts(),
+                per_line(&text, &patterns)
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, n)| if i == 3 { 0 } else { n })
+                    .collect::<Vec<_>>(),
+                "{:?}",
+                String::from_utf8_lossy(&text)
+            );
+        }
+    }
+
+    #[test]
+    fn reset_keeps_patterns() {
+        let mut c = counters(&["x"]);
+        c.feed(b"x\nx");
+        c.reset();
+        c.feed(b"ax\n");
+        assert_eq!(c.counts(), [1]);
+    }
+}
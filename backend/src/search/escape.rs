@@ -0,0 +1,119 @@
+//! C-style escapes in typed search needles: `\n`, `\t`, `\r`, `\xNN` and `\\` become the
+//! bytes they name, so a user can search for control bytes from a text box.
+
+use std::fmt;
+
+/// A malformed escape in a needle. `position` is the byte index of its backslash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EscapeError {
+    /// A backslash at the very end.
+    Trailing { position: usize },
+    /// `\` followed by a character that is not a supported escape.
+    Unknown { position: usize, found: char },
+    /// `\x` not followed by two hex digits.
+    BadHex { position: usize },
+}
+
+impl fmt::Display for EscapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EscapeError::Trailing { position } => write!(
+                f,
+                "unfinished escape at byte {position}: trailing backslash"
+            ),
+            EscapeError::Unknown { position, found } => write!(
+                f,
+                "unknown escape \\{found} at byte {position}; use \\n, \\t, \\r, \\xNN or \\\\"
+            ),
+            EscapeError::BadHex { position } => write!(
+                f,
+                "\\x at byte {position} must be followed by two hex digits"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EscapeError {}
+
+/// Bytes of `pattern` with its escapes interpreted; other characters are kept as UTF-8.
+pub fn unescape_needle(pattern: &str) -> Result<Vec<u8>, EscapeError> {
+    let bytes = pattern.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'\\' {
+            out.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        let position = i;
+        let Some(&kind) = bytes.get(i + 1) else {
+            return Err(EscapeError::Trailing { position });
+        };
+        match kind {
+            b'n' => out.push(b'\n'),
+            b't' => out.push(b'\t'),
+            b'r' => out.push(b'\r'),
+            b'\\' => out.push(b'\\'),
+            b'x' => {
+                let hex = bytes
+                    .get(i + 2..i + 4)
+                    .ok_or(EscapeError::BadHex { position })?;
+                let digits =
+                    std::str::from_utf8(hex).map_err(|_| EscapeError::BadHex { position })?;
+                if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return Err(EscapeError::BadHex { position });
+                }
+                out.push(u8::from_str_radix(digits, 16).expect("two hex digits"));
+                i += 2;
+            }
+            _ => {
+                let found = pattern[i + 1..].chars().next().expect("non-empty rest");
+                return Err(EscapeError::Unknown { position, found });
+            }
+        }
+        i += 2;
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_become_bytes() {
+        assert_eq!(unescape_needle(r"a\tb\r\n").unwrap(), b"a\tb\r\n");
+        assert_eq!(
+            unescape_needle(r"\x00\x1B[31m\xff").unwrap(),
+            b"\x00\x1b[31m\xff"
+        );
+        assert_eq!(unescape_needle(r"C:\\logs").unwrap(), br"C:\logs");
+        assert_eq!(unescape_needle("grüße").unwrap(), "grüße".as_bytes());
+    }
+
+    #[test]
+    fn malformed_escapes_are_rejected() {
+        assert_eq!(
+            unescape_needle(r"ab\"),
+            Err(EscapeError::Trailing { position: 2 })
+        );
+        assert_eq!(
+            unescape_needle(r"x\é"),
+            Err(EscapeError::Unknown {
+                position: 1,
+                found: 'é'
+            })
+        );
+        for bad in [r"\x", r"\x4", r"\x4g", r"\xé"] {
+            assert_eq!(
+                unescape_needle(bad),
+                Err(EscapeError::BadHex { position: 0 })
+            );
+        }
+        assert!(unescape_needle(r"\q")
+            .unwrap_err()
+            .to_string()
+            .contains(r"unknown escape \q"));
+    }
+}
@@ -0,0 +1,77 @@
+//! ASCII case folding for case-insensitive literal search. With the `simd` feature on a
+//! wasm32 build with `-C target-feature=+simd128`, folds 16 bytes per step using v128
+//! range compares; everywhere else the scalar loop is used.
+
+/// Writes the ASCII-lowercase form of `src` into `dst` (same length). Non-ASCII bytes
+/// are copied unchanged.
+#[cfg(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128"))]
+pub use simd128::fold_ascii;
+
+/// Writes the ASCII-lowercase form of `src` into `dst` (same length). Non-ASCII bytes
+/// are copied unchanged.
+#[cfg(not(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128")))]
+pub fn fold_ascii(src: &[u8], dst: &mut [u8]) {
+    fold_ascii_scalar(src, dst);
+}
+
+/// Portable fallback, also used for the tail the SIMD loop leaves over.
+#[inline(always)]
+pub fn fold_ascii_scalar(src: &[u8], dst: &mut [u8]) {
+    assert_eq!(src.len(), dst.len());
+    for (d, &s) in dst.iter_mut().zip(src) {
+        *d = s.to_ascii_lowercase();
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128"))]
+mod simd128 {
+    use core::arch::wasm32::*;
+
+    pub fn fold_ascii(src: &[u8], dst: &mut [u8]) {
+        assert_eq!(src.len(), dst.len());
+        let lower_bound = u8x16_splat(b'A');
+        let upper_bound = u8x16_splat(b'Z');
+        let case_bit = u8x16_splat(0x20);
+        let mut src_blocks = src.chunks_exact(16);
+        let mut dst_blocks = dst.chunks_exact_mut(16);
+        for (s, d) in (&mut src_blocks).zip(&mut dst_blocks) {
+            // SAFETY: both blocks are exactly 16 bytes; v128 loads and stores are unaligned.
+            unsafe {
+                let v = v128_load(s.as_ptr() as *const v128);
+                let is_upper = v128_and(u8x16_ge(v, lower_bound), u8x16_le(v, upper_bound));
+                let folded = v128_or(v, v128_and(is_upper, case_bit));
+                v128_store(d.as_mut_ptr() as *mut v128, folded);
+            }
+        }
+        super::fold_ascii_scalar(src_blocks.remainder(), dst_blocks.into_remainder());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::XorShift;
+
+    #[test]
+    fn fold_matches_scalar_on_random_buffers() {
+        let mut rng = XorShift::new(0x0F01_D5EE_D000_0001);
+        for _ in 0..500 {
+            let len = rng.below(100) as usize;
+            // Full byte range: ASCII letters, the bytes around 'A'..='Z', and non-ASCII.
+            let src: Vec<u8> = (0..len).map(|_| rng.below(256) as u8).collect();
+            let mut folded = vec![0; len];
+            let mut expected = vec![0; len];
+            fold_ascii(&src, &mut folded);
+            fold_ascii_scalar(&src, &mut expected);
+            assert_eq!(folded, expected, "{src:?}");
+        }
+    }
+
+    #[test]
+    fn folds_only_ascii_uppercase() {
+        let src = "Hello, WORLD @[`{ ÄÖ".as_bytes();
+        let mut dst = vec![0; src.len()];
+        fold_ascii(src, &mut dst);
+        assert_eq!(dst, "hello, world @[`{ ÄÖ".as_bytes());
+    }
+}
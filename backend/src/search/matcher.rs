@@ -2,51 +2,1307 @@
 //!
 //! Uses memchr::memmem::find_iter over the whole buffer, then maps match positions
 //! to line indices via binary_search on line offsets (fast for large files).
+//!
+//! `ChunkMatcher` matches a `ChunkPattern` (a literal, a regex, a pattern set) over a file
+//! fed in chunks, carrying the bytes a hit may straddle or the line in progress from one
+//! chunk to the next, so it never needs the file in one buffer. The ingest-time features
+//! (stream filter, unseen matches, counters, numeric extractors) are built on it.
+//!
+//! Ordering contract: every function here returns line indices strictly ascending, each
+//! line at most once, however many times it matches. The one exception is `match_page`
+//! with `reverse`, whose pages are strictly descending.
+
+use std::collections::VecDeque;
 
 use memchr::memmem;
+use regex::bytes::Regex;
+
+use crate::core::offsets::LineOffsets;
+use crate::decode::lines::strip_line_terminator;
 
 /// Finds all line indices (0-based) whose line content contains `needle` as a substring.
 /// Uses find_iter over the full buffer, then binary_search to map byte positions to lines.
+/// A hit that spans a newline belongs to no single line and is rejected.
 pub fn match_lines(
     buffer: &[u8],
-    offsets: &[u64],
+    offsets: &LineOffsets,
     needle: &[u8],
 ) -> Vec<u64> {
     if needle.is_empty() {
         return (0..offsets.len() as u64).collect();
     }
-    if offsets.is_empty() || buffer.is_empty() {
-        return Vec::new();
+    line_hits(buffer, offsets, needle).collect()
+}
+
+/// Lines on which a match of `needle` starts, searching the buffer as one stream rather
+/// than line by line: the needle may contain newlines and span lines (a stack-trace header
+/// and the "at" line after it). Each line appears once, ascending. Overlapping matches
+/// count, so a match starting inside an earlier one is found. An empty needle matches
+/// every line.
+pub fn match_start_lines(buffer: &[u8], offsets: &LineOffsets, needle: &[u8]) -> Vec<u64> {
+    let line_count = offsets.len() as u64;
+    if needle.is_empty() {
+        return (0..line_count).collect();
+    }
+    let finder = memmem::Finder::new(needle);
+    let mut lines = Vec::new();
+    let mut pos = first_line_start(buffer, offsets);
+    while let Some(hit) = finder.find(&buffer[pos..]) {
+        let li = byte_pos_to_line_index(pos + hit, offsets);
+        if li >= line_count {
+            break;
+        }
+        lines.push(li);
+        // Later matches starting on this line add nothing: resume at the next line.
+        match offsets.get(li as usize + 1) {
+            Some(next) if (next as usize) < buffer.len() => pos = next as usize,
+            _ => break,
+        }
+    }
+    lines
+}
+
+/// Lines containing at least one of `needles`, strictly ascending: a line matched by
+/// several needles (or several times) appears once. An empty needle matches every line.
+pub fn match_lines_any(buffer: &[u8], offsets: &LineOffsets, needles: &[&[u8]]) -> Vec<u64> {
+    if needles.iter().any(|n| n.is_empty()) {
+        return (0..offsets.len() as u64).collect();
+    }
+    let mut lines: Vec<u64> = needles
+        .iter()
+        .flat_map(|needle| line_hits(buffer, offsets, needle))
+        .collect();
+    lines.sort_unstable();
+    lines.dedup();
+    lines
+}
+
+/// Lines containing at least one byte from `class`, e.g. every byte >= 0x80 to find
+/// non-ASCII lines. The line terminator is not content, so listing `\n` or `\r` only
+/// matches a stray `\r` inside a line.
+pub fn match_lines_byte_class(buffer: &[u8], offsets: &LineOffsets, class: &[u8]) -> Vec<u64> {
+    let mut table = [false; 256];
+    for &b in class {
+        table[b as usize] = true;
     }
+    resident_lines(buffer, offsets)
+        .filter(|(_, line)| {
+            strip_line_terminator(line)
+                .iter()
+                .any(|&b| table[b as usize])
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
 
-    let mut line_indices: Vec<u64> = memmem::find_iter(buffer, needle)
-        .map(|byte_pos| byte_pos_to_line_index(byte_pos, offsets))
-        .filter(|&li| li < offsets.len() as u64)
+/// Indices of the `region_size`-byte file regions (`line_start / region_size`) holding the
+/// start of at least one line that contains `needle`, ascending and deduplicated.
+/// `region_size` must be non-zero.
+pub fn match_regions(
+    buffer: &[u8],
+    offsets: &LineOffsets,
+    needle: &[u8],
+    region_size: u64,
+) -> Vec<u64> {
+    let mut regions: Vec<u64> = match_lines(buffer, offsets, needle)
+        .into_iter()
+        .filter_map(|line| offsets.get(line as usize))
+        .map(|start| start / region_size)
         .collect();
-    line_indices.sort_unstable();
-    line_indices.dedup();
-    line_indices
+    regions.dedup();
+    regions
+}
+
+/// True when at least one line contains `needle`. Stops at the first hit.
+pub fn any_line_matches(buffer: &[u8], offsets: &LineOffsets, needle: &[u8]) -> bool {
+    first_matching_line(buffer, offsets, needle).is_some()
+}
+
+/// Index of the first line containing `needle`, or `None`. Stops at the first hit. An
+/// empty needle matches the first (retained) line.
+pub fn first_matching_line(buffer: &[u8], offsets: &LineOffsets, needle: &[u8]) -> Option<u64> {
+    if needle.is_empty() {
+        return (!offsets.is_empty()).then(|| offsets.first_line() as u64);
+    }
+    line_hits(buffer, offsets, needle).next()
+}
+
+/// Number of lines containing `needle`, counting no further than `limit`.
+pub fn count_lines_limit(buffer: &[u8], offsets: &LineOffsets, needle: &[u8], limit: u64) -> u64 {
+    if needle.is_empty() {
+        return (offsets.len() as u64).min(limit);
+    }
+    line_hits(buffer, offsets, needle)
+        .take(limit as usize)
+        .count() as u64
+}
+
+/// Number of lines containing `needle` and the total of its non-overlapping occurrences
+/// in them, counted in one pass. An empty needle matches every line once.
+pub fn count_occurrences(buffer: &[u8], offsets: &LineOffsets, needle: &[u8]) -> (u64, u64) {
+    let line_count = offsets.len() as u64;
+    if needle.is_empty() {
+        return (line_count, line_count);
+    }
+    if offsets.is_empty() || spans_lines(needle) {
+        return (0, 0);
+    }
+    let skip = first_line_start(buffer, offsets);
+    let (mut lines, mut occurrences, mut last) = (0, 0, None);
+    for pos in memmem::find_iter(&buffer[skip..], needle) {
+        let li = byte_pos_to_line_index(skip + pos, offsets);
+        if li >= line_count {
+            break;
+        }
+        occurrences += 1;
+        if last.replace(li) != Some(li) {
+            lines += 1;
+        }
+    }
+    (lines, occurrences)
+}
+
+/// One page of matching line indices: skips the first `offset` matches and returns up to
+/// `limit`. Forward pages are ascending and stop scanning once the page is full. With
+/// `reverse`, lines are scanned from the end, so page 0 holds the last matches in
+/// descending order and no earlier part of the buffer is touched.
+pub fn match_page(
+    buffer: &[u8],
+    offsets: &LineOffsets,
+    needle: &[u8],
+    offset: usize,
+    limit: usize,
+    reverse: bool,
+) -> Vec<u64> {
+    if needle.is_empty() {
+        let all = 0..offsets.len() as u64;
+        return if reverse {
+            all.rev().skip(offset).take(limit).collect()
+        } else {
+            all.skip(offset).take(limit).collect()
+        };
+    }
+    if !reverse {
+        return line_hits(buffer, offsets, needle)
+            .skip(offset)
+            .take(limit)
+            .collect();
+    }
+    let finder = memmem::Finder::new(needle);
+    (0..resident_line_count(buffer, offsets))
+        .rev()
+        .filter(|&i| finder.find(resident_line(buffer, offsets, i)).is_some())
+        .skip(offset)
+        .take(limit)
+        .map(|i| i as u64)
+        .collect()
+}
+
+/// Lazily yields the distinct, ascending indices of lines containing the non-empty
+/// `needle`, so callers that stop early (exists, count with a cap) stop scanning too.
+fn line_hits<'a>(
+    buffer: &'a [u8],
+    offsets: &'a LineOffsets,
+    needle: &'a [u8],
+) -> impl Iterator<Item = u64> + 'a {
+    let skip = first_line_start(buffer, offsets);
+    let haystack = if offsets.is_empty() || spans_lines(needle) {
+        &[][..]
+    } else {
+        &buffer[skip..]
+    };
+    let line_count = offsets.len() as u64;
+    let mut last = None;
+    // find_iter yields ascending positions, so line indices arrive sorted.
+    memmem::find_iter(haystack, needle)
+        .map(move |pos| byte_pos_to_line_index(skip + pos, offsets))
+        .filter(move |&li| li < line_count && last.replace(li) != Some(li))
+}
+
+/// Buffer position of the first line's start (after evicted lines, or 0), clamped to the
+/// buffer. Bytes before it belong to no line, so scans start there.
+#[inline(always)]
+fn first_line_start(buffer: &[u8], offsets: &LineOffsets) -> usize {
+    offsets
+        .get(offsets.first_line())
+        .map_or(0, |start| (start as usize).min(buffer.len()))
+}
+
+/// True when every hit of `needle` would cross a line boundary: a newline before its last
+/// byte means the hit's start and end fall on different lines. (A trailing newline is the
+/// line's own terminator, so such hits stay within one line.)
+#[inline(always)]
+fn spans_lines(needle: &[u8]) -> bool {
+    needle
+        .split_last()
+        .is_some_and(|(_, head)| memchr::memchr(b'\n', head).is_some())
+}
+
+/// Result of `match_lines_limit`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimitedMatches {
+    /// Matching line indices, ascending, at most `max` of them.
+    pub lines: Vec<u64>,
+    /// True when another matching line exists after the last one returned.
+    pub truncated: bool,
+    /// Bytes of `buffer` examined before stopping.
+    pub bytes_scanned: u64,
+}
+
+/// Like `match_lines`, but stops scanning once `max` matching lines are found. Scanning
+/// continues only as far as the next match on a new line, so `truncated` is exact.
+pub fn match_lines_limit(
+    buffer: &[u8],
+    offsets: &LineOffsets,
+    needle: &[u8],
+    max: usize,
+) -> LimitedMatches {
+    if needle.is_empty() {
+        let total = offsets.len() as u64;
+        let taken = total.min(max as u64);
+        return LimitedMatches {
+            lines: (0..taken).collect(),
+            truncated: taken < total,
+            bytes_scanned: 0,
+        };
+    }
+    let mut result = LimitedMatches {
+        lines: Vec::new(),
+        truncated: false,
+        bytes_scanned: 0,
+    };
+    if offsets.is_empty() || buffer.is_empty() || spans_lines(needle) {
+        return result;
+    }
+
+    // find_iter yields ascending positions, so line indices arrive sorted; dedup adjacent.
+    let skip = first_line_start(buffer, offsets);
+    let mut scanned_to = buffer.len();
+    for pos in memmem::find_iter(&buffer[skip..], needle) {
+        let byte_pos = skip + pos;
+        let li = byte_pos_to_line_index(byte_pos, offsets);
+        if li >= offsets.len() as u64 || result.lines.last() == Some(&li) {
+            continue;
+        }
+        if result.lines.len() == max {
+            result.truncated = true;
+            scanned_to = byte_pos + needle.len();
+            break;
+        }
+        result.lines.push(li);
+    }
+    result.bytes_scanned = (scanned_to - skip) as u64;
+    result
+}
+
+/// One line of `match_positions`: where `needle` occurs in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinePositions {
+    pub line: u64,
+    /// Byte offsets of the hits from the line start, ascending and non-overlapping, at
+    /// most `max_per_line` of them.
+    pub positions: Vec<u32>,
+    /// True when the line has more hits than were reported.
+    pub truncated: bool,
+}
+
+/// Lines containing `needle` with the byte offset of each hit within its line. Each line
+/// reports at most `max_per_line` hits; once a line is full the scan jumps to the next
+/// line, so a pathological line costs no more than its first hits. Empty needles match
+/// nothing here (there is no position to report).
+pub fn match_positions(
+    buffer: &[u8],
+    offsets: &LineOffsets,
+    needle: &[u8],
+    max_per_line: usize,
+) -> Vec<LinePositions> {
+    let mut result: Vec<LinePositions> = Vec::new();
+    if needle.is_empty() || offsets.is_empty() || spans_lines(needle) {
+        return result;
+    }
+    let finder = memmem::Finder::new(needle);
+    let mut pos = first_line_start(buffer, offsets);
+    while let Some(found) = finder.find(&buffer[pos..]) {
+        let byte_pos = pos + found;
+        let li = byte_pos_to_line_index(byte_pos, offsets);
+        if li >= offsets.len() as u64 {
+            break;
+        }
+        let line_start = offsets.get(li as usize).unwrap_or(0) as usize;
+        if result.last().is_none_or(|last| last.line != li) {
+            result.push(LinePositions {
+                line: li,
+                positions: Vec::new(),
+                truncated: false,
+            });
+        }
+        let entry = result.last_mut().expect("pushed above");
+        if entry.positions.len() == max_per_line {
+            entry.truncated = true;
+            match offsets.get(li as usize + 1) {
+                Some(next) if (next as usize) < buffer.len() => pos = next as usize,
+                _ => break,
+            }
+            continue;
+        }
+        entry.positions.push((byte_pos - line_start) as u32);
+        pos = byte_pos + needle.len();
+    }
+    result
+}
+
+/// Iterates the lines whose bytes are resident in `buffer` (which holds the file from
+/// offset 0), yielding `(line_index, line_bytes)`. Line bytes include the terminator; the
+/// last resident line ends at `buffer.len()`.
+pub fn resident_lines<'a>(
+    buffer: &'a [u8],
+    offsets: &'a LineOffsets,
+) -> impl Iterator<Item = (u64, &'a [u8])> + 'a {
+    resident_lines_from(buffer, offsets, 0)
+}
+
+/// Like `resident_lines`, starting at line `first_line`.
+pub fn resident_lines_from<'a>(
+    buffer: &'a [u8],
+    offsets: &'a LineOffsets,
+    first_line: usize,
+) -> impl Iterator<Item = (u64, &'a [u8])> + 'a {
+    let resident = resident_line_count(buffer, offsets);
+    (first_line.min(resident)..resident).map(move |i| (i as u64, resident_line(buffer, offsets, i)))
+}
+
+/// Number of lines whose start lies inside `buffer`.
+#[inline(always)]
+pub fn resident_line_count(buffer: &[u8], offsets: &LineOffsets) -> usize {
+    offsets.partition_point(|s| s < buffer.len() as u64)
+}
+
+/// Bytes of resident line `i` (terminator included). `i` must be below
+/// `resident_line_count`.
+#[inline(always)]
+pub fn resident_line<'a>(buffer: &'a [u8], offsets: &LineOffsets, i: usize) -> &'a [u8] {
+    let start = offsets.get(i).unwrap_or(0) as usize;
+    let end = offsets
+        .get(i + 1)
+        .map_or(buffer.len(), |e| (e as usize).min(buffer.len()));
+    &buffer[start..end]
+}
+
+/// Resumable per-line substring search for cooperative callers (the async exports): each
+/// `step` scans at most `max_lines` resident lines, so the caller can yield in between.
+pub struct ChunkedSearch {
+    finder: memmem::Finder<'static>,
+    next_line: usize,
+    matches: Vec<u64>,
+}
+
+impl ChunkedSearch {
+    pub fn new(needle: &[u8]) -> Self {
+        Self {
+            finder: memmem::Finder::new(needle).into_owned(),
+            next_line: 0,
+            matches: Vec::new(),
+        }
+    }
+
+    /// Scans the next `max_lines` resident lines. Returns true once every resident line
+    /// has been scanned.
+    pub fn step(&mut self, buffer: &[u8], offsets: &LineOffsets, max_lines: usize) -> bool {
+        let mut scanned = 0;
+        for (i, line) in resident_lines_from(buffer, offsets, self.next_line).take(max_lines) {
+            if self.finder.find(line).is_some() {
+                self.matches.push(i);
+            }
+            scanned += 1;
+        }
+        self.next_line += scanned;
+        self.next_line >= resident_line_count(buffer, offsets)
+    }
+
+    /// Matching line indices found so far (strictly ascending).
+    pub fn into_matches(self) -> Vec<u64> {
+        self.matches
+    }
+}
+
+/// What a `ChunkMatcher` looks for. A pattern either finds hits in raw bytes, which may
+/// straddle chunks, or needs each line whole.
+pub trait ChunkPattern {
+    /// Longest hit in bytes: `ChunkMatcher` carries that many bytes minus one into the next
+    /// chunk and finds hits with `find_hits`. `Some(0)` matches every line. `None` means the
+    /// pattern tests whole lines with `matches_line`, and each line is buffered until its end.
+    fn max_hit_len(&self) -> Option<usize>;
+
+    /// Reports each hit in `haystack` as `hit(start, end, id)`, `id` telling patterns of a
+    /// set apart, in ascending order of `end`. Only called when `max_hit_len` is positive.
+    fn find_hits(&self, _haystack: &[u8], _hit: &mut dyn FnMut(usize, usize, usize)) {}
+
+    /// True when the complete `line` (terminator included) matches. Only called when
+    /// `max_hit_len` is `None`.
+    fn matches_line(&self, _line: &[u8]) -> bool {
+        false
+    }
+}
+
+impl ChunkPattern for memmem::Finder<'_> {
+    fn max_hit_len(&self) -> Option<usize> {
+        Some(self.needle().len())
+    }
+
+    fn find_hits(&self, haystack: &[u8], hit: &mut dyn FnMut(usize, usize, usize)) {
+        let len = self.needle().len();
+        for pos in self.find_iter(haystack) {
+            hit(pos, pos + len, 0);
+        }
+    }
+}
+
+impl ChunkPattern for Regex {
+    fn max_hit_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn matches_line(&self, line: &[u8]) -> bool {
+        self.is_match(strip_line_terminator(line))
+    }
+}
+
+/// A line matched by a `ChunkMatcher`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineMatch<'a> {
+    pub line: u64,
+    /// Which pattern of a set matched (0 for a single pattern).
+    pub id: usize,
+    /// The hit, or for a pattern that tests whole lines, the line with its terminator.
+    pub text: &'a [u8],
+}
+
+/// Matches a `ChunkPattern` over a file that arrives in consecutive chunks, each with the
+/// line starts the scanner found in it, so the streaming features never need the file in
+/// one buffer. For a hit pattern the last `max_hit_len - 1` bytes and the line starts they
+/// may belong to are carried to the next chunk, so a hit split across chunks is found and
+/// placed on the line it starts in; a hit spanning a line start belongs to no line. For a
+/// line pattern the line in progress is carried until it ends. Each line is reported once
+/// per pattern id, in ascending line order. Bytes before the first line start belong to no
+/// line and never match.
+///
+/// The pattern is passed to every call rather than owned, so a caller can keep it
+/// elsewhere (the engine's compiled searches); it must not change between calls.
+#[derive(Debug, Default)]
+pub struct ChunkMatcher {
+    /// Hit pattern: the tail of the bytes fed so far, shorter than the longest hit. Line
+    /// pattern: the bytes of the line in progress fed so far.
+    carry: Vec<u8>,
+    /// File offset just past the bytes fed so far.
+    end: u64,
+    /// Whether a chunk has been fed, fixing the offset the next one must start at.
+    started: bool,
+    /// `feed_text` takes the first byte fed as a line start.
+    text_starts_line: bool,
+    /// Line starts a hit in the carried or future bytes may belong to; for a line pattern,
+    /// the start of the line in progress.
+    starts: VecDeque<u64>,
+    /// Line index of `starts[0]`, or of the next line start fed while `starts` is empty.
+    first_index: u64,
+    /// Last line reported per pattern id.
+    reported: Vec<Option<u64>>,
+    /// Scratch for the line starts `feed_text` finds.
+    text_starts: Vec<u64>,
+}
+
+impl ChunkMatcher {
+    /// Numbers lines from `first_line`. `mid_line` means the stream is inside that line
+    /// already: it is skipped, because its beginning was never seen, and the next line start
+    /// is `first_line + 1`. Otherwise the next line start fed is `first_line`, and
+    /// `feed_text` takes the first byte fed as that start.
+    pub fn new(first_line: u64, mid_line: bool) -> Self {
+        Self {
+            text_starts_line: !mid_line,
+            first_index: first_line + mid_line as u64,
+            ..Self::default()
+        }
+    }
+
+    /// Restarts at line 0 with nothing carried (for a new file).
+    pub fn reset(&mut self) {
+        *self = Self::new(0, false);
+    }
+
+    /// Consumes the next chunk, the file bytes from `base_offset` (the end of the previous
+    /// chunk, if any), with `new_line_starts`, the ascending line starts inside it, and calls
+    /// `on_match` for each line it completes a match on: ascending, after every line
+    /// matched before.
+    pub fn feed<P: ChunkPattern + ?Sized>(
+        &mut self,
+        pattern: &P,
+        chunk: &[u8],
+        base_offset: u64,
+        new_line_starts: &[u64],
+        mut on_match: impl FnMut(LineMatch<'_>),
+    ) {
+        if !self.started {
+            self.started = true;
+            self.end = base_offset;
+        }
+        debug_assert_eq!(self.end, base_offset, "chunks must be consecutive");
+        match pattern.max_hit_len() {
+            None => self.feed_lines(pattern, chunk, base_offset, new_line_starts, &mut on_match),
+            Some(0) => {
+                for &start in new_line_starts {
+                    let line = self.push_line_start(start);
+                    on_match(LineMatch {
+                        line,
+                        id: 0,
+                        text: &[],
+                    });
+                }
+            }
+            Some(longest) => self.feed_hits(
+                pattern,
+                chunk,
+                base_offset,
+                longest,
+                new_line_starts,
+                &mut on_match,
+            ),
+        }
+        self.end = base_offset + chunk.len() as u64;
+    }
+
+    /// `feed` for a stream whose line starts are not known: a line starts after every `\n`
+    /// (and at the first byte, see `new`). Offsets count the bytes fed, so it also serves a
+    /// view of the file whose bytes do not sit at their file offsets.
+    pub fn feed_text<P: ChunkPattern + ?Sized>(
+        &mut self,
+        pattern: &P,
+        chunk: &[u8],
+        on_match: impl FnMut(LineMatch<'_>),
+    ) {
+        let base = self.end;
+        let mut starts = std::mem::take(&mut self.text_starts);
+        starts.clear();
+        if std::mem::take(&mut self.text_starts_line) {
+            starts.push(base);
+        }
+        starts.extend(memchr::memchr_iter(b'\n', chunk).map(|nl| base + nl as u64 + 1));
+        self.feed(pattern, chunk, base, &starts, on_match);
+        self.text_starts = starts;
+    }
+
+    /// Ends the stream: for a line pattern, tests the final line, which has no terminator.
+    pub fn finish<P: ChunkPattern + ?Sized>(
+        self,
+        pattern: &P,
+        mut on_match: impl FnMut(LineMatch<'_>),
+    ) {
+        let Some((line, text)) = self.pending().filter(|_| pattern.max_hit_len().is_none()) else {
+            return;
+        };
+        if pattern.matches_line(text) {
+            on_match(LineMatch { line, id: 0, text });
+        }
+    }
+
+    /// The line in progress and its bytes so far, for a line pattern: once the stream has
+    /// ended, the final line without a terminator. `None` when it is empty or was skipped.
+    pub fn pending(&self) -> Option<(u64, &[u8])> {
+        let line = self.last_line()?;
+        (!self.carry.is_empty()).then_some((line, &self.carry[..]))
+    }
+
+    /// Releases spare capacity of the carried bytes and line starts.
+    pub fn shrink_to_fit(&mut self) {
+        self.carry.shrink_to_fit();
+        self.starts.shrink_to_fit();
+        self.text_starts = Vec::new();
+    }
+
+    pub fn heap_bytes(&self) -> usize {
+        self.carry.capacity()
+            + (self.starts.capacity() + self.text_starts.capacity()) * std::mem::size_of::<u64>()
+            + self.reported.capacity() * std::mem::size_of::<Option<u64>>()
+    }
+
+    /// Index of the last line start held.
+    fn last_line(&self) -> Option<u64> {
+        (!self.starts.is_empty()).then(|| self.first_index + self.starts.len() as u64 - 1)
+    }
+
+    /// Makes `start` the only line start held and returns its line index.
+    fn push_line_start(&mut self, start: u64) -> u64 {
+        if !self.starts.is_empty() {
+            self.first_index += self.starts.len() as u64;
+            self.starts.clear();
+        }
+        self.starts.push_back(start);
+        self.first_index
+    }
+
+    /// Line pattern: tests each line that `new_line_starts` ends.
+    fn feed_lines<P: ChunkPattern + ?Sized>(
+        &mut self,
+        pattern: &P,
+        chunk: &[u8],
+        base_offset: u64,
+        new_line_starts: &[u64],
+        on_match: &mut impl FnMut(LineMatch<'_>),
+    ) {
+        let mut from = 0;
+        for &start in new_line_starts {
+            let at = (start - base_offset) as usize;
+            if let Some(line) = self.last_line() {
+                let text = if self.carry.is_empty() {
+                    &chunk[from..at]
+                } else {
+                    self.carry.extend_from_slice(&chunk[from..at]);
+                    &self.carry[..]
+                };
+                if pattern.matches_line(text) {
+                    on_match(LineMatch { line, id: 0, text });
+                }
+                self.carry.clear();
+            }
+            self.push_line_start(start);
+            from = at;
+        }
+        if !self.starts.is_empty() {
+            self.carry.extend_from_slice(&chunk[from..]);
+        }
+    }
+
+    /// Hit pattern: reports the hits ending in `chunk`.
+    fn feed_hits<P: ChunkPattern + ?Sized>(
+        &mut self,
+        pattern: &P,
+        chunk: &[u8],
+        base_offset: u64,
+        longest: usize,
+        new_line_starts: &[u64],
+        on_match: &mut impl FnMut(LineMatch<'_>),
+    ) {
+        self.starts.extend(new_line_starts);
+        // Hits starting in the carried bytes may run into this chunk. Those ending in the
+        // carried bytes were reported with an earlier chunk.
+        let carried = self.carry.len();
+        if carried > 0 {
+            let mut window = std::mem::take(&mut self.carry);
+            window.extend_from_slice(&chunk[..chunk.len().min(longest - 1)]);
+            let offset = base_offset - carried as u64;
+            pattern.find_hits(&window, &mut |start, end, id| {
+                if start < carried && end > carried {
+                    self.report_hit(&window, offset, start..end, id, on_match);
+                }
+            });
+            window.truncate(carried);
+            self.carry = window;
+        }
+        pattern.find_hits(chunk, &mut |start, end, id| {
+            self.report_hit(chunk, base_offset, start..end, id, on_match);
+        });
+
+        // Keep the bytes a later hit may start in, and the start of their line onward.
+        let keep = longest - 1;
+        if chunk.len() >= keep {
+            self.carry.clear();
+            self.carry.extend_from_slice(&chunk[chunk.len() - keep..]);
+        } else {
+            self.carry.extend_from_slice(chunk);
+            self.carry.drain(..self.carry.len().saturating_sub(keep));
+        }
+        let carry_offset = base_offset + chunk.len() as u64 - self.carry.len() as u64;
+        let done = self
+            .starts
+            .partition_point(|&s| s <= carry_offset)
+            .saturating_sub(1);
+        self.starts.drain(..done);
+        self.first_index += done as u64;
+    }
+
+    /// Reports the hit `haystack[hit]` (file bytes from `offset`) on the line it starts in,
+    /// unless it spans a line start, lies before the first line or that line was reported
+    /// for `id` already.
+    fn report_hit(
+        &mut self,
+        haystack: &[u8],
+        offset: u64,
+        hit: std::ops::Range<usize>,
+        id: usize,
+        on_match: &mut impl FnMut(LineMatch<'_>),
+    ) {
+        let (start, end) = (offset + hit.start as u64, offset + hit.end as u64);
+        let next = self.starts.partition_point(|&s| s <= start);
+        let Some(index) = next.checked_sub(1) else {
+            return;
+        };
+        if self.starts.get(next).is_some_and(|&s| s < end) {
+            return;
+        }
+        let line = self.first_index + index as u64;
+        if self.reported.len() <= id {
+            self.reported.resize(id + 1, None);
+        }
+        if self.reported[id] != Some(line) {
+            self.reported[id] = Some(line);
+            on_match(LineMatch {
+                line,
+                id,
+                text: &haystack[hit],
+            });
+        }
+    }
+}
+
+/// `match_lines` on `ChunkMatcher`: feeds the lines held in `window`, the file bytes from
+/// offset `window_start` on, in chunks of `chunk_bytes`, as the streaming features see the
+/// file. Lines starting before the window are not searched.
+pub fn match_lines_chunked(
+    window: &[u8],
+    window_start: u64,
+    offsets: &LineOffsets,
+    needle: &[u8],
+    chunk_bytes: usize,
+) -> Vec<u64> {
+    let window_end = window_start + window.len() as u64;
+    let mut line = offsets.partition_point(|s| s < window_start);
+    let resident = offsets.partition_point_in(line, offsets.len(), |s| s < window_end);
+    if needle.is_empty() {
+        return (line as u64..resident as u64).collect();
+    }
+    let finder = memmem::Finder::new(needle);
+    let mut matcher = ChunkMatcher::new(line as u64, false);
+    let mut matches = Vec::new();
+    let mut pos = offsets
+        .get(line)
+        .map_or(window_end, |start| start.min(window_end));
+    let mut starts = Vec::new();
+    while pos < window_end {
+        let end = (pos + chunk_bytes.max(1) as u64).min(window_end);
+        let next = offsets.partition_point_in(line, resident, |s| s < end);
+        starts.clear();
+        starts.extend(offsets.range(line, next));
+        let chunk = &window[(pos - window_start) as usize..(end - window_start) as usize];
+        matcher.feed(&finder, chunk, pos, &starts, |m| matches.push(m.line));
+        line = next;
+        pos = end;
+    }
+    matcher.finish(&finder, |m| matches.push(m.line));
+    matches
 }
 
 /// Maps a byte position in the file to the line index (line start offset <= pos).
 #[inline(always)]
-fn byte_pos_to_line_index(byte_pos: usize, offsets: &[u64]) -> u64 {
-    let pos = byte_pos as u64;
-    let i = offsets.partition_point(|&s| s <= pos);
-    i.saturating_sub(1) as u64
+fn byte_pos_to_line_index(byte_pos: usize, offsets: &LineOffsets) -> u64 {
+    offsets.line_containing(byte_pos as u64).unwrap_or(0) as u64
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::XorShift;
+
+    /// Reference strategy: search each resident line separately.
+    fn match_lines_per_line(buffer: &[u8], offsets: &LineOffsets, needle: &[u8]) -> Vec<u64> {
+        let finder = memmem::Finder::new(needle);
+        resident_lines(buffer, offsets)
+            .filter(|(_, line)| finder.find(line).is_some())
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Feeds one chunk and returns the lines it matched.
+    fn fed<P: ChunkPattern + ?Sized>(
+        matcher: &mut ChunkMatcher,
+        pattern: &P,
+        chunk: &[u8],
+        base_offset: u64,
+        new_line_starts: &[u64],
+    ) -> Vec<u64> {
+        let mut lines = Vec::new();
+        matcher.feed(pattern, chunk, base_offset, new_line_starts, |m| {
+            lines.push(m.line)
+        });
+        lines
+    }
+
+    #[test]
+    fn whole_buffer_matches_per_line_on_random_input() {
+        use crate::indexer::scanner::scan_chunk;
+        let mut rng = XorShift::new(0x2545_F491_4F6C_DD1D);
+        for _ in 0..500 {
+            let len = rng.below(200) as usize;
+            let buf: Vec<u8> = (0..len).map(|_| b"ab\n"[rng.below(3) as usize]).collect();
+            let mut starts = Vec::new();
+            scan_chunk(&buf, 0, &mut starts, true);
+            let offsets = LineOffsets::from(&starts[..]);
+            let needle: Vec<u8> = (0..1 + rng.below(4))
+                .map(|_| b"ab\n"[rng.below(3) as usize])
+                .collect();
+            assert_eq!(
+                match_lines(&buf, &offsets, &needle),
+                match_lines_per_line(&buf, &offsets, &needle),
+                "buf {:?} needle {:?}",
+                String::from_utf8_lossy(&buf),
+                String::from_utf8_lossy(&needle)
+            );
+        }
+    }
+
+    #[test]
+    fn byte_class_finds_non_ascii_lines() {
+        let buf = "plain\ncafé\r\nok\r\n\u{fffd} bad\nlast ü".as_bytes();
+        let mut starts = Vec::new();
+        crate::indexer::scanner::scan_chunk(buf, 0, &mut starts, true);
+        let offsets = LineOffsets::from(&starts[..]);
+        let non_ascii: Vec<u8> = (0x80..=0xff).collect();
+        assert_eq!(match_lines_byte_class(buf, &offsets, &non_ascii), [1, 3, 4]);
+        assert!(match_lines_byte_class(buf, &offsets, b"\r\n").is_empty());
+        assert_eq!(match_lines_byte_class(buf, &offsets, b"kx"), [2]);
+        assert!(match_lines_byte_class(buf, &offsets, b"").is_empty());
+    }
+
+    #[test]
+    fn positions_are_capped_per_line() {
+        let mut buf = b"x ab\n".to_vec();
+        buf.extend_from_slice(&b"ab".repeat(10_000));
+        buf.extend_from_slice(b"\nab..ab\nnone\nab");
+        let mut starts = Vec::new();
+        crate::indexer::scanner::scan_chunk(&buf, 0, &mut starts, true);
+        let offsets = LineOffsets::from(&starts[..]);
+        let found = match_positions(&buf, &offsets, b"ab", 3);
+        let summary: Vec<(u64, Vec<u32>, bool)> = found
+            .into_iter()
+            .map(|l| (l.line, l.positions, l.truncated))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (0, vec![2], false),
+                (1, vec![0, 2, 4], true),
+                (2, vec![0, 4], false),
+                (4, vec![0], false),
+            ]
+        );
+        let capped_out = match_positions(&buf, &offsets, b"ab", 0);
+        assert!(capped_out
+            .iter()
+            .all(|l| l.positions.is_empty() && l.truncated));
+        assert_eq!(capped_out.len(), 4);
+    }
+
+    #[test]
+    fn needle_longer_than_buffer_matches_nothing() {
+        let buf = b"ab\ncd";
+        let offsets = LineOffsets::from(&[0u64, 3][..]);
+        assert!(match_lines(buf, &offsets, b"ab\ncd!").is_empty());
+        assert!(!any_line_matches(buf, &offsets, b"abcdefg"));
+        assert_eq!(match_lines(buf, &offsets, b"cd"), [1]);
+    }
+
+    #[test]
+    fn regions_hold_the_start_of_matching_lines() {
+        // Lines start at 0, 6, 12, 18 and 24.
+        let buf = b"match\nnoise\nmatch\nmatch\nnoise";
+        let offsets = LineOffsets::from(&[0u64, 6, 12, 18, 24][..]);
+        assert_eq!(match_regions(buf, &offsets, b"match", 10), [0, 1]);
+        assert_eq!(match_regions(buf, &offsets, b"match", 6), [0, 2, 3]);
+        assert_eq!(match_regions(buf, &offsets, b"noise", 1000), [0]);
+        assert_eq!(match_regions(buf, &offsets, b"noise", 6), [1, 4]);
+        assert!(match_regions(buf, &offsets, b"absent", 6).is_empty());
+    }
+
+    #[test]
+    fn exists_finds_a_match_on_the_last_line() {
+        let mut buf = b"noise\n".repeat(10_000);
+        buf.extend_from_slice(b"needle");
+        let starts: Vec<u64> = (0..=10_000).map(|i| i * 6).collect();
+        let offsets = LineOffsets::from(&starts[..]);
+        assert!(any_line_matches(&buf, &offsets, b"needle"));
+        assert!(!any_line_matches(&buf, &offsets, b"absent"));
+        assert!(!any_line_matches(&buf, &offsets, b"e\nn"));
+        assert!(any_line_matches(&buf, &offsets, b""));
+        assert!(!any_line_matches(b"", &LineOffsets::new(), b""));
+    }
+
+    #[test]
+    fn multiline_needles_report_the_line_they_start_on() {
+        let buf = b"Error: boom\n\tat a()\nok\nError: again\n\tat b()\nError: none\nx\nx\nx";
+        let starts: Vec<u64> = std::iter::once(0)
+            .chain(memchr::memchr_iter(b'\n', buf).map(|i| i as u64 + 1))
+            .collect();
+        let offsets = LineOffsets::from(&starts[..]);
+        assert_eq!(match_start_lines(buf, &offsets, b"Error: "), [0, 3, 5]);
+        assert_eq!(match_start_lines(buf, &offsets, b":"), [0, 3, 5]);
+        // The per-line search rejects these; here they match where they start.
+        assert_eq!(match_start_lines(buf, &offsets, b"\n\tat "), [0, 3]);
+        assert_eq!(match_start_lines(buf, &offsets, b"boom\n\tat a()\nok"), [0]);
+        assert!(match_lines(buf, &offsets, b"boom\n\tat").is_empty());
+        // Overlapping matches: each of the first two "x" lines starts one.
+        assert_eq!(match_start_lines(buf, &offsets, b"x\nx"), [6, 7]);
+        assert!(match_start_lines(buf, &offsets, b"again\nError").is_empty());
+        assert_eq!(match_start_lines(buf, &offsets, b"").len(), starts.len());
+        assert!(match_start_lines(b"", &LineOffsets::new(), b"x").is_empty());
+    }
+
+    #[test]
+    fn first_matching_line_is_the_earliest_hit() {
+        let buf = b"noise\nan error\nerror again\nnoise\nerror";
+        let offsets = LineOffsets::from(&[0u64, 6, 15, 27, 33][..]);
+        assert_eq!(first_matching_line(buf, &offsets, b"error"), Some(1));
+        assert_eq!(first_matching_line(buf, &offsets, b"again"), Some(2));
+        assert_eq!(first_matching_line(buf, &offsets, b"absent"), None);
+        // A hit across a line break belongs to no line.
+        assert_eq!(first_matching_line(buf, &offsets, b"error\nerror"), None);
+        assert_eq!(first_matching_line(buf, &offsets, b""), Some(0));
+        assert_eq!(first_matching_line(b"", &LineOffsets::new(), b""), None);
+    }
+
+    #[test]
+    fn reverse_pages_cover_forward_results_descending() {
+        let buf = b"a1\nb\na2\na3\nb\nb\na4\na5\nb\na6";
+        let mut starts = Vec::new();
+        crate::indexer::scanner::scan_chunk(buf, 0, &mut starts, true);
+        let offsets = LineOffsets::from(&starts[..]);
+        let forward = match_lines(buf, &offsets, b"a");
+        assert_eq!(forward.len(), 6);
+
+        let mut union = Vec::new();
+        for page in 0..4 {
+            let lines = match_page(buf, &offsets, b"a", page * 4, 4, true);
+            assert!(lines.windows(2).all(|w| w[0] > w[1]), "{lines:?}");
+            union.extend(lines);
+        }
+        assert_eq!(union[0], *forward.last().unwrap());
+        union.sort_unstable();
+        assert_eq!(union, forward);
+
+        assert_eq!(match_page(buf, &offsets, b"a", 2, 3, false), forward[2..5]);
+        assert_eq!(match_page(buf, &offsets, b"", 0, 2, true), [9, 8]);
+        assert!(match_page(buf, &offsets, b"a", 6, 4, true).is_empty());
+    }
+
+    #[test]
+    fn count_stops_at_limit() {
+        let buf = b"x1\nx2\ny\nx3 x\n";
+        let offsets = LineOffsets::from(&[0, 3, 6, 8][..]);
+        assert_eq!(count_lines_limit(buf, &offsets, b"x", 100), 3);
+        assert_eq!(count_lines_limit(buf, &offsets, b"x", 3), 3);
+        assert_eq!(count_lines_limit(buf, &offsets, b"x", 2), 2);
+        assert_eq!(count_lines_limit(buf, &offsets, b"x", 0), 0);
+        assert_eq!(count_lines_limit(buf, &offsets, b"", 2), 2);
+    }
+
+    #[test]
+    fn occurrences_are_summed_over_matching_lines() {
+        let buf = b"err err\nok\nerr\nerrerr err\n";
+        let offsets = LineOffsets::from(&[0, 8, 11, 15][..]);
+        assert_eq!(count_occurrences(buf, &offsets, b"err"), (3, 6));
+        // Occurrences do not overlap: "aaaa" holds two "aa".
+        let offsets = LineOffsets::from(&[0][..]);
+        assert_eq!(count_occurrences(b"aaaa", &offsets, b"aa"), (1, 2));
+        assert_eq!(count_occurrences(buf, &offsets, b"missing"), (0, 0));
+        let two_lines = LineOffsets::from(&[0, 2][..]);
+        assert_eq!(count_occurrences(b"a\nb", &two_lines, b""), (2, 2));
+        assert_eq!(count_occurrences(buf, &LineOffsets::new(), b"err"), (0, 0));
+    }
+
+    #[test]
+    fn hits_spanning_a_newline_are_rejected() {
+        let buf = b"foo\nbar\n";
+        let offsets = LineOffsets::from(&[0, 4, 8][..]);
+        assert!(match_lines(buf, &offsets, b"o\nb").is_empty());
+        let limited = match_lines_limit(buf, &offsets, b"o\nb", 10);
+        assert!(limited.lines.is_empty());
+        assert_eq!(match_lines(buf, &offsets, b"r\n"), [1]);
+    }
+
+    #[test]
+    fn bytes_before_the_first_line_are_not_searched() {
+        // The only line starts at 5 (earlier lines evicted): "skip\n" belongs to no line.
+        let buf = b"skip\nlast line";
+        let offsets = LineOffsets::from(&[5][..]);
+        assert!(match_lines(buf, &offsets, b"skip").is_empty());
+        assert_eq!(match_lines(buf, &offsets, b"last"), [0]);
+        let limited = match_lines_limit(buf, &offsets, b"skip", 10);
+        assert!(limited.lines.is_empty());
+        assert_eq!(limited.bytes_scanned, 9);
+        assert!(match_positions(buf, &offsets, b"skip", 4).is_empty());
+        assert_eq!(match_positions(buf, &offsets, b"line", 4)[0].positions, [5]);
+        assert!(match_lines_any(buf, &offsets, &[b"skip", b"zzz"]).is_empty());
+    }
+
+    #[test]
+    fn needles_in_the_last_line_match() {
+        let buf = b"a\nb\nneedle";
+        let offsets = LineOffsets::from(&[0, 2, 4][..]);
+        // Only in the final unterminated line.
+        assert_eq!(match_lines(buf, &offsets, b"needle"), [2]);
+        assert_eq!(match_lines_limit(buf, &offsets, b"eed", 5).lines, [2]);
+        // The whole last line, with and without its terminator.
+        let buf = b"x\nlast\n";
+        let offsets = LineOffsets::from(&[0, 2][..]);
+        assert_eq!(match_lines(buf, &offsets, b"last"), [1]);
+        assert_eq!(match_lines(buf, &offsets, b"last\n"), [1]);
+        assert_eq!(match_lines(buf, &offsets, b"x\n"), [0]);
+        assert_eq!(
+            match_lines(buf, &offsets, b"last\n"),
+            match_lines_per_line(buf, &offsets, b"last\n")
+        );
+    }
 
     #[test]
     fn match_lines_basic() {
         let buf = b"hello\nworld\nfoo bar\n";
-        let offsets = vec![0, 6, 12, 20];
+        let offsets = LineOffsets::from(&[0, 6, 12, 20][..]);
         let r = match_lines(buf, &offsets, b"world");
         assert_eq!(r, [1]);
         let r = match_lines(buf, &offsets, b"o");
         assert_eq!(r, [0, 1, 2]);
     }
+
+    #[test]
+    fn limit_stops_early_and_reports_truncation() {
+        let mut buf = Vec::new();
+        let mut starts = Vec::new();
+        for i in 0..1000 {
+            starts.push(buf.len() as u64);
+            buf.extend_from_slice(format!("line {i} error here\n").as_bytes());
+        }
+        let offsets = LineOffsets::from(&starts[..]);
+
+        let limited = match_lines_limit(&buf, &offsets, b"error", 100);
+        assert_eq!(limited.lines, (0..100).collect::<Vec<u64>>());
+        assert!(limited.truncated);
+        assert!(limited.bytes_scanned < buf.len() as u64 / 5);
+
+        let all = match_lines_limit(&buf, &offsets, b"line 99", 100);
+        assert_eq!(all.lines, match_lines(&buf, &offsets, b"line 99"));
+        assert!(!all.truncated);
+        assert_eq!(all.bytes_scanned, buf.len() as u64);
+    }
+
+    #[test]
+    fn limit_dedups_and_handles_zero() {
+        let buf = b"aa aa\nbb\naa\n";
+        let offsets = LineOffsets::from(&[0, 6, 9][..]);
+        let limited = match_lines_limit(buf, &offsets, b"aa", 1);
+        assert_eq!(limited.lines, vec![0]);
+        assert!(limited.truncated);
+        let none = match_lines_limit(buf, &offsets, b"aa", 0);
+        assert!(none.lines.is_empty() && none.truncated);
+        let empty = match_lines_limit(buf, &offsets, b"", 2);
+        assert_eq!(empty.lines, vec![0, 1]);
+        assert!(empty.truncated);
+    }
+
+    #[test]
+    fn chunked_search_matches_whole_search() {
+        let buf = b"alpha\nbeta\ngamma alpha\n\nalphabet\nomega";
+        let offsets = LineOffsets::from(&[0, 6, 11, 23, 24, 33][..]);
+        let expected = match_lines(buf, &offsets, b"alpha");
+        for batch in [1, 2, 3, 5, 100] {
+            let mut search = ChunkedSearch::new(b"alpha");
+            let mut steps = 1;
+            while !search.step(buf, &offsets, batch) {
+                steps += 1;
+            }
+            assert!(steps >= 6 / batch);
+            assert_eq!(search.into_matches(), expected);
+        }
+    }
+
+    #[test]
+    fn resident_lines_cover_buffer() {
+        let buf = b"hello\nworld\ntail";
+        let offsets = LineOffsets::from(&[0, 6, 12][..]);
+        let lines: Vec<(u64, &[u8])> = resident_lines(buf, &offsets).collect();
+        assert_eq!(
+            lines,
+            [
+                (0, &b"hello\n"[..]),
+                (1, &b"world\n"[..]),
+                (2, &b"tail"[..])
+            ]
+        );
+        // Lines starting past the resident bytes are not yielded.
+        assert_eq!(resident_lines(&buf[..8], &offsets).count(), 2);
+    }
+
+    fn strictly_ascending(lines: &[u64]) -> bool {
+        lines.windows(2).all(|w| w[0] < w[1])
+    }
+
+    #[test]
+    fn every_variant_returns_strictly_ordered_lines() {
+        use crate::indexer::scanner::scan_chunk;
+        let mut rng = XorShift::new(0x9E37_79B9_7F4A_7C15);
+        for _ in 0..300 {
+            let len = rng.below(150) as usize;
+            let buf: Vec<u8> = (0..len).map(|_| b"aab\n"[rng.below(4) as usize]).collect();
+            let mut starts = Vec::new();
+            scan_chunk(&buf, 0, &mut starts, true);
+            let offsets = LineOffsets::from(&starts[..]);
+            let needles: Vec<Vec<u8>> = (0..1 + rng.below(3))
+                .map(|_| {
+                    (0..1 + rng.below(2))
+                        .map(|_| b"ab"[rng.below(2) as usize])
+                        .collect()
+                })
+                .collect();
+            let needle = &needles[0];
+
+            let all = match_lines(&buf, &offsets, needle);
+            assert!(strictly_ascending(&all), "match_lines {all:?}");
+
+            let limited = match_lines_limit(&buf, &offsets, needle, rng.below(5) as usize);
+            assert!(strictly_ascending(&limited.lines));
+            assert_eq!(limited.lines, all[..limited.lines.len()]);
+
+            let page = match_page(&buf, &offsets, needle, rng.below(3) as usize, 3, false);
+            assert!(strictly_ascending(&page));
+            let mut reverse = match_page(&buf, &offsets, needle, 0, usize::MAX, true);
+            reverse.reverse();
+            assert!(strictly_ascending(&reverse));
+
+            let mut chunked = ChunkedSearch::new(needle);
+            while !chunked.step(&buf, &offsets, 1 + rng.below(3) as usize) {}
+            let chunked = chunked.into_matches();
+            assert!(strictly_ascending(&chunked));
+
+            let refs: Vec<&[u8]> = needles.iter().map(Vec::as_slice).collect();
+            let any = match_lines_any(&buf, &offsets, &refs);
+            assert!(strictly_ascending(&any), "match_lines_any {any:?}");
+            let mut union: Vec<u64> = refs
+                .iter()
+                .flat_map(|n| match_lines(&buf, &offsets, n))
+                .collect();
+            union.sort_unstable();
+            union.dedup();
+            assert_eq!(any, union);
+        }
+    }
+
+    #[test]
+    fn chunk_matcher_matches_whole_buffer_for_random_splits() {
+        use crate::indexer::scanner::scan_chunk;
+        let mut rng = XorShift::new(0xD1B5_4A32_D192_ED03);
+        for _ in 0..500 {
+            let len = rng.below(120) as usize;
+            let buf: Vec<u8> = (0..len).map(|_| b"aab\n"[rng.below(4) as usize]).collect();
+            let mut starts = Vec::new();
+            scan_chunk(&buf, 0, &mut starts, true);
+            let offsets = LineOffsets::from(&starts[..]);
+            let needle: Vec<u8> = (0..1 + rng.below(4))
+                .map(|_| b"aab\n"[rng.below(4) as usize])
+                .collect();
+            let expected = match_lines(&buf, &offsets, &needle);
+
+            // Random cut points, including empty chunks.
+            let mut cuts: Vec<usize> = (0..rng.below(6))
+                .map(|_| rng.below(len as u64 + 1) as usize)
+                .collect();
+            cuts.extend([0, len]);
+            cuts.sort_unstable();
+            let finder = memmem::Finder::new(&needle);
+            let mut matcher = ChunkMatcher::new(0, false);
+            let mut text_matcher = ChunkMatcher::new(0, false);
+            let mut streamed = Vec::new();
+            let mut text_streamed = Vec::new();
+            for piece in cuts.windows(2) {
+                let (from, to) = (piece[0], piece[1]);
+                let inside: Vec<u64> = starts
+                    .iter()
+                    .copied()
+                    .filter(|&s| (from as u64..to as u64).contains(&s))
+                    .collect();
+                let chunk = &buf[from..to];
+                streamed.extend(fed(&mut matcher, &finder, chunk, from as u64, &inside));
+                text_matcher.feed_text(&finder, chunk, |m| text_streamed.push(m.line));
+            }
+            assert_eq!(streamed, expected, "{:?} in {:?}", needle, buf);
+            assert_eq!(text_streamed, expected, "{:?} in {:?}", needle, buf);
+
+            let chunk_bytes = 1 + rng.below(8) as usize;
+            assert_eq!(
+                match_lines_chunked(&buf, 0, &offsets, &needle, chunk_bytes),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn chunk_matcher_buffers_whole_lines_for_line_patterns() {
+        let regex = Regex::new("^a+b?$").unwrap();
+        let mut rng = XorShift::new(0x94D0_49BB_1331_11EB);
+        for _ in 0..300 {
+            let text: Vec<u8> = (0..rng.below(80))
+                .map(|_| b"aab\n"[rng.below(4) as usize])
+                .collect();
+            let mid_line = rng.below(2) == 1;
+            let expected: Vec<u64> = text
+                .split_inclusive(|&b| b == b'\n')
+                .enumerate()
+                .skip(mid_line as usize)
+                .filter(|(_, line)| regex.matches_line(line))
+                .map(|(i, _)| 5 + i as u64)
+                .collect();
+
+            let mut matcher = ChunkMatcher::new(5, mid_line);
+            let mut streamed = Vec::new();
+            let mut rest = &text[..];
+            while !rest.is_empty() {
+                let (head, tail) = rest.split_at(1 + rng.below(rest.len() as u64) as usize);
+                matcher.feed_text(&regex, head, |m| {
+                    assert_eq!(m.text.last(), Some(&b'\n'), "a completed line");
+                    streamed.push(m.line);
+                });
+                rest = tail;
+            }
+            // The last line has no terminator; it is tested once the stream has ended.
+            matcher.finish(&regex, |m| streamed.push(m.line));
+            assert_eq!(streamed, expected, "{:?}", String::from_utf8_lossy(&text));
+        }
+    }
+
+    #[test]
+    fn chunk_matcher_joins_hits_split_across_chunks() {
+        // "error" split three ways, a terminator-ended needle, and evicted leading lines.
+        let error = memmem::Finder::new(b"error");
+        let mut matcher = ChunkMatcher::new(0, false);
+        assert!(fed(&mut matcher, &error, b"ok\ner", 0, &[0, 3]).is_empty());
+        assert!(fed(&mut matcher, &error, b"r", 5, &[]).is_empty());
+        assert_eq!(fed(&mut matcher, &error, b"or\nerror\n", 6, &[9]), [1, 2]);
+
+        let mut matcher = ChunkMatcher::new(0, false);
+        let terminated = memmem::Finder::new(b"b\n");
+        fed(&mut matcher, &terminated, b"ab", 0, &[0]);
+        assert_eq!(fed(&mut matcher, &terminated, b"\nb", 2, &[3]), [0]);
+        let mut spanning = ChunkMatcher::new(0, false);
+        let across = memmem::Finder::new(b"a\nb");
+        assert!(fed(&mut spanning, &across, b"a\nb", 0, &[0, 2]).is_empty());
+
+        let buf = b"error 1\nerror 2\nok\nerror 4\n";
+        let mut offsets = LineOffsets::from(&[0, 8, 16, 19][..]);
+        offsets.evict_front(2);
+        assert_eq!(match_lines_chunked(buf, 0, &offsets, b"error", 3), [3]);
+        assert_eq!(match_lines_chunked(buf, 0, &offsets, b"", 3), [2, 3]);
+        // A window starting mid-line searches from the next line start on.
+        let offsets = LineOffsets::from(&[0, 8, 16, 19][..]);
+        assert_eq!(
+            match_lines_chunked(&buf[4..], 4, &offsets, b"error", 3),
+            [1, 3]
+        );
+    }
+
+    #[test]
+    fn any_dedups_lines_matched_by_several_needles() {
+        let buf = b"error warn\nok\nwarn\nerror\n";
+        let offsets = LineOffsets::from(&[0, 11, 14, 19][..]);
+        assert_eq!(
+            match_lines_any(buf, &offsets, &[b"warn", b"error"]),
+            [0, 2, 3]
+        );
+        assert_eq!(match_lines_any(buf, &offsets, &[b"zzz", b"ok"]), [1]);
+        assert_eq!(match_lines_any(buf, &offsets, &[b"zzz", b""]), [0, 1, 2, 3]);
+        assert!(match_lines_any(buf, &offsets, &[]).is_empty());
+    }
 }
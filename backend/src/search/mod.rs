@@ -1 +1,14 @@
+pub mod ci_index;
+pub mod compiled;
+pub mod content;
+pub mod counters;
+pub mod escape;
+pub mod fold;
 pub mod matcher;
+pub mod normalized;
+pub mod packed;
+pub mod report;
+pub mod rules;
+pub mod stream;
+pub mod tokens;
+pub mod unseen;
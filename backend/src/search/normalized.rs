@@ -0,0 +1,73 @@
+//! Search over decoded, NFC-normalized text, so a precomposed needle (`é`, U+00E9) finds
+//! lines that spell it with a combining sequence (`e` + U+0301) and vice versa. Each line
+//! is decoded and normalized before comparing, which is much slower than byte search, so
+//! callers scope it to a line range.
+
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+use super::matcher::resident_lines_from;
+use crate::core::offsets::LineOffsets;
+use crate::decode::lines::{decode_line_slice, strip_line_terminator, DecodeConfig};
+
+/// Resident lines in `[start_line, end_line)` whose NFC form contains the NFC form of
+/// `needle`, ascending. An empty needle matches every line.
+pub fn normalized_matches(
+    buffer: &[u8],
+    offsets: &LineOffsets,
+    start_line: usize,
+    end_line: usize,
+    needle: &str,
+    config: DecodeConfig,
+) -> Vec<u64> {
+    let needle = nfc(needle);
+    resident_lines_from(buffer, offsets, start_line)
+        .take(end_line.saturating_sub(start_line))
+        .filter(|(_, line)| {
+            let text = decode_line_slice(strip_line_terminator(line), config);
+            nfc(&text).contains(needle.as_str())
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// NFC form of `text`; skips the rewrite when a quick check proves it is already NFC
+/// (always true for ASCII).
+fn nfc(text: &str) -> String {
+    match is_nfc_quick(text.chars()) {
+        IsNormalized::Yes => text.to_string(),
+        _ => text.nfc().collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::lines::{LineEndings, Utf8Mode};
+
+    const UTF8: DecodeConfig = DecodeConfig {
+        ascii_mode: false,
+        utf8_mode: Utf8Mode::TrimTrailingOnly,
+        line_endings: LineEndings::Keep,
+        skip_nul_runs: 0,
+    };
+
+    #[test]
+    fn combining_sequences_match_precomposed_needles() {
+        // Line 1 spells "café" with e + COMBINING ACUTE ACCENT, line 2 precomposed.
+        let text = "plain cafe\nvisited cafe\u{301} today\ncaf\u{e9} again\n";
+        let mut offsets = LineOffsets::new();
+        offsets.extend_from_slice(&[0, 11, 32]);
+        let search = |needle: &str, start, end| {
+            normalized_matches(text.as_bytes(), &offsets, start, end, needle, UTF8)
+        };
+        assert_eq!(search("caf\u{e9}", 0, 3), [1, 2]);
+        assert_eq!(search("cafe\u{301}", 0, 3), [1, 2]);
+        assert_eq!(search("cafe", 0, 3), [0]);
+        assert_eq!(search("caf\u{e9}", 2, 3), [2]);
+        assert_eq!(search("caf\u{e9}", 0, 1), Vec::<u64>::new());
+        // Raw bytes do not match across the two spellings.
+        assert!(!text.as_bytes()[11..32]
+            .windows(5)
+            .any(|w| w == "caf\u{e9}".as_bytes()));
+    }
+}
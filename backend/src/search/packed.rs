@@ -0,0 +1,113 @@
+//! Compact binary form of a search result, for handing it to another worker: the
+//! ascending line indices are stored as gaps (the first index as is), each gap as an
+//! unsigned LEB128 varint. A result of dense hits costs about one byte per line instead
+//! of the eight of a `Float64Array` entry.
+
+use std::fmt;
+
+/// A buffer that is not a valid packed result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackedError {
+    /// The buffer ends inside the varint starting at byte `offset`.
+    Truncated { offset: usize },
+    /// The line at byte `offset` does not fit in 64 bits.
+    Overflow { offset: usize },
+}
+
+impl fmt::Display for PackedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PackedError::Truncated { offset } => {
+                write!(f, "packed result ends inside the value at byte {offset}")
+            }
+            PackedError::Overflow { offset } => {
+                write!(f, "packed result value at byte {offset} exceeds 64 bits")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PackedError {}
+
+/// Packs ascending line indices. Each is stored as its gap to the previous one.
+pub fn encode_lines(lines: &[u64]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(lines.len() + 8);
+    let mut prev = 0;
+    for &line in lines {
+        debug_assert!(line >= prev, "line indices must be ascending");
+        let mut gap = line - prev;
+        while gap >= 0x80 {
+            out.push(gap as u8 | 0x80);
+            gap >>= 7;
+        }
+        out.push(gap as u8);
+        prev = line;
+    }
+    out
+}
+
+/// Unpacks a buffer made by `encode_lines`.
+pub fn decode_lines(bytes: &[u8]) -> Result<Vec<u64>, PackedError> {
+    let mut lines = Vec::with_capacity(bytes.len());
+    let mut prev = 0u64;
+    let mut i = 0;
+    while i < bytes.len() {
+        let offset = i;
+        let mut gap = 0u64;
+        let mut shift = 0;
+        loop {
+            let &byte = bytes.get(i).ok_or(PackedError::Truncated { offset })?;
+            i += 1;
+            let bits = (byte & 0x7F) as u64;
+            if shift == 63 && bits > 1 || shift > 63 {
+                return Err(PackedError::Overflow { offset });
+            }
+            gap |= bits << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+        }
+        prev = prev
+            .checked_add(gap)
+            .ok_or(PackedError::Overflow { offset })?;
+        lines.push(prev);
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_round_trip() {
+        let lines = [0, 1, 2, 127, 128, 16_511, 1 << 40, u64::MAX];
+        let packed = encode_lines(&lines);
+        assert_eq!(decode_lines(&packed).unwrap(), lines);
+        // Gaps under 128 take one byte each.
+        assert_eq!(encode_lines(&[3, 5, 100, 227]), [3, 2, 95, 127]);
+        assert_eq!(encode_lines(&[128]), [0x80, 0x01]);
+        assert!(encode_lines(&[]).is_empty());
+        assert!(decode_lines(&[]).unwrap().is_empty());
+
+        assert_eq!(
+            decode_lines(&[5, 0x80]),
+            Err(PackedError::Truncated { offset: 1 })
+        );
+        // Eleven bytes of payload is more than 64 bits.
+        let mut wide = vec![0xFF; 10];
+        wide.push(0x01);
+        assert_eq!(
+            decode_lines(&wide),
+            Err(PackedError::Overflow { offset: 0 })
+        );
+        // Gaps summing past u64::MAX.
+        let mut past = encode_lines(&[u64::MAX]);
+        past.push(1);
+        assert_eq!(
+            decode_lines(&past),
+            Err(PackedError::Overflow { offset: 10 })
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Match metadata reports: one row per matching line (index, byte offset and, when the
+//! caller supplied the bytes, its text) serialized as a JSON array or as CSV, for sharing
+//! a match set outside the viewer.
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Json,
+    Csv,
+}
+
+impl ReportFormat {
+    /// Parses `"json"` or `"csv"`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// One matching line.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MatchRow {
+    /// Line index (0-based).
+    pub line: u64,
+    /// File offset of the line start.
+    pub offset: u64,
+    /// Line text without its terminator; `None` when its bytes were not provided.
+    pub text: Option<String>,
+}
+
+/// Serializes `rows`: a JSON array of `{ line, offset, text }` objects (`text` is `null`
+/// when unknown), or CSV with a `line,offset,text` header and RFC 4180 quoting (`text` is
+/// empty when unknown).
+pub fn format_rows(rows: &[MatchRow], format: ReportFormat) -> String {
+    match format {
+        ReportFormat::Json => serde_json::to_string(rows).expect("rows serialize to JSON"),
+        ReportFormat::Csv => {
+            let mut out = String::from("line,offset,text\r\n");
+            for row in rows {
+                out.push_str(&format!("{},{},", row.line, row.offset));
+                push_csv_field(&mut out, row.text.as_deref().unwrap_or(""));
+                out.push_str("\r\n");
+            }
+            out
+        }
+    }
+}
+
+/// Appends `field`, quoted (with inner quotes doubled) when it holds a comma, quote or
+/// line break.
+fn push_csv_field(out: &mut String, field: &str) {
+    if field.contains([',', '"', '\n', '\r']) {
+        out.push('"');
+        out.push_str(&field.replace('"', "\"\""));
+        out.push('"');
+    } else {
+        out.push_str(field);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows() -> Vec<MatchRow> {
+        vec![
+            MatchRow {
+                line: 3,
+                offset: 120,
+                text: Some(r#"user="bob", action=login"#.to_string()),
+            },
+            MatchRow {
+                line: 9,
+                offset: 5_000_000_000,
+                text: Some("multi\nline\r\ncell".to_string()),
+            },
+            MatchRow {
+                line: 10,
+                offset: 5_000_000_040,
+                text: Some("plain".to_string()),
+            },
+            MatchRow {
+                line: 11,
+                offset: 5_000_000_050,
+                text: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn csv_quotes_commas_quotes_and_newlines() {
+        assert_eq!(
+            format_rows(&rows(), ReportFormat::Csv),
+            "line,offset,text\r\n\
+             3,120,\"user=\"\"bob\"\", action=login\"\r\n\
+             9,5000000000,\"multi\nline\r\ncell\"\r\n\
+             10,5000000040,plain\r\n\
+             11,5000000050,\r\n"
+        );
+        assert_eq!(format_rows(&[], ReportFormat::Csv), "line,offset,text\r\n");
+    }
+
+    #[test]
+    fn json_parses_back() {
+        let json = format_rows(&rows(), ReportFormat::Json);
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let array = value.as_array().unwrap();
+        assert_eq!(array.len(), 4);
+        assert_eq!(array[0]["text"], r#"user="bob", action=login"#);
+        assert_eq!(array[1]["offset"].as_u64(), Some(5_000_000_000));
+        assert_eq!(array[1]["text"], "multi\nline\r\ncell");
+        assert!(array[3]["text"].is_null());
+        assert_eq!(format_rows(&[], ReportFormat::Json), "[]");
+        assert_eq!(ReportFormat::parse("csv"), Some(ReportFormat::Csv));
+        assert_eq!(ReportFormat::parse("xml"), None);
+    }
+}
@@ -0,0 +1,139 @@
+//! Rule-based line classification: several named regexes compiled once and evaluated in
+//! a single pass over the lines, producing a bitmask of matching rules per line.
+
+use std::fmt;
+
+use regex::bytes::Regex;
+
+use super::matcher::resident_lines;
+use crate::core::offsets::LineOffsets;
+use crate::decode::lines::strip_line_terminator;
+
+/// Maximum number of rules (one bit each in a `u32` mask).
+pub const MAX_RULES: usize = 32;
+
+/// Error building a `RuleSet`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleError {
+    /// More than `MAX_RULES` rules were given.
+    TooManyRules(usize),
+    /// Rule `index` (named `name`) failed to compile.
+    InvalidPattern {
+        index: usize,
+        name: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleError::TooManyRules(n) => {
+                write!(f, "{n} rules given, at most {MAX_RULES} are supported")
+            }
+            RuleError::InvalidPattern {
+                index,
+                name,
+                message,
+            } => write!(
+                f,
+                "rule {index} ({name:?}) has an invalid pattern: {message}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+/// Named regexes compiled once. Rule `i` sets bit `i` of a line's mask.
+#[derive(Debug)]
+pub struct RuleSet {
+    regexes: Vec<Regex>,
+}
+
+impl RuleSet {
+    /// Compiles `(name, pattern)` rules, failing on the first invalid pattern.
+    pub fn compile(rules: &[(String, String)]) -> Result<Self, RuleError> {
+        if rules.len() > MAX_RULES {
+            return Err(RuleError::TooManyRules(rules.len()));
+        }
+        let mut regexes = Vec::with_capacity(rules.len());
+        for (index, (name, pattern)) in rules.iter().enumerate() {
+            let regex = Regex::new(pattern).map_err(|e| RuleError::InvalidPattern {
+                index,
+                name: name.clone(),
+                message: e.to_string(),
+            })?;
+            regexes.push(regex);
+        }
+        Ok(Self { regexes })
+    }
+
+    /// Bitmask of rules matching `line` (terminator excluded, so `$` anchors at line end).
+    #[inline(always)]
+    pub fn mask(&self, line: &[u8]) -> u32 {
+        let line = strip_line_terminator(line);
+        self.regexes
+            .iter()
+            .enumerate()
+            .filter(|(_, re)| re.is_match(line))
+            .fold(0, |mask, (i, _)| mask | (1 << i))
+    }
+
+    /// Classifies every resident line, returning `(line_index, mask)` for lines matching
+    /// at least one rule, in ascending line order.
+    pub fn classify(&self, buffer: &[u8], offsets: &LineOffsets) -> Vec<(u64, u32)> {
+        resident_lines(buffer, offsets)
+            .map(|(i, line)| (i, self.mask(line)))
+            .filter(|&(_, mask)| mask != 0)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(specs: &[(&str, &str)]) -> Vec<(String, String)> {
+        specs
+            .iter()
+            .map(|&(n, p)| (n.to_owned(), p.to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn overlapping_rules_on_one_line() {
+        let set = RuleSet::compile(&rules(&[
+            ("error", "ERROR"),
+            ("db", r"db\.\w+"),
+            ("timeout", r"timeout after \d+ms$"),
+        ]))
+        .unwrap();
+        let buf = b"INFO ok\nERROR db.query timeout after 30ms\nERROR disk\r\nplain\n";
+        let offsets = LineOffsets::from(&[0, 8, 42, 54, 60][..]);
+        assert_eq!(set.classify(buf, &offsets), [(1, 0b111), (2, 0b001)]);
+    }
+
+    #[test]
+    fn invalid_pattern_names_the_rule() {
+        let err = RuleSet::compile(&rules(&[("ok", "a+"), ("broken", "(unclosed")])).unwrap_err();
+        match &err {
+            RuleError::InvalidPattern { index, name, .. } => {
+                assert_eq!((*index, name.as_str()), (1, "broken"));
+            }
+            other => panic!("unexpected error {other:?}"),
+        }
+        assert!(err.to_string().contains("\"broken\""));
+    }
+
+    #[test]
+    fn too_many_rules() {
+        let many: Vec<(String, String)> = (0..=MAX_RULES)
+            .map(|i| (i.to_string(), "x".into()))
+            .collect();
+        assert_eq!(
+            RuleSet::compile(&many).unwrap_err(),
+            RuleError::TooManyRules(MAX_RULES + 1)
+        );
+    }
+}
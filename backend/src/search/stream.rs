@@ -0,0 +1,153 @@
+//! Streaming regex filter: tests each line against a regex as chunks are indexed, so a
+//! filtered view exists as soon as ingestion ends, without a second pass over bytes that
+//! were discarded after indexing. A line split across chunks is buffered until its end by
+//! the filter's `ChunkMatcher`.
+
+use regex::bytes::Regex;
+
+use super::matcher::{ChunkMatcher, ChunkPattern};
+
+#[derive(Debug)]
+pub struct StreamFilter {
+    regex: Regex,
+    /// Line numbering and the line in progress.
+    matcher: ChunkMatcher,
+    /// Matching line indices, ascending.
+    matches: Vec<u64>,
+}
+
+impl StreamFilter {
+    /// Starts filtering at line `first_line`. `mid_line` means the stream is inside that
+    /// line already; it is skipped because its beginning was never seen.
+    pub fn new(regex: Regex, first_line: u64, mid_line: bool) -> Self {
+        Self {
+            regex,
+            matcher: ChunkMatcher::new(first_line, mid_line),
+            matches: Vec::new(),
+        }
+    }
+
+    /// Restarts at line 0 with no results, keeping the regex (for a new file).
+    pub fn reset(&mut self) {
+        self.matcher.reset();
+        self.matches.clear();
+    }
+
+    /// Forgets matches before `line` (evicted by retention).
+    pub fn evict_before(&mut self, line: u64) {
+        let evicted = self.matches.partition_point(|&l| l < line);
+        self.matches.drain(..evicted);
+    }
+
+    /// Consumes the next bytes of the stream, testing every line completed by them.
+    pub fn feed(&mut self, chunk: &[u8]) {
+        let matches = &mut self.matches;
+        self.matcher
+            .feed_text(&self.regex, chunk, |m| matches.push(m.line));
+    }
+
+    /// Matching lines so far. With `include_tail` (the stream has ended), the final line
+    /// without a terminator is tested too; otherwise it is still incomplete.
+    pub fn results(&self, include_tail: bool) -> Vec<u64> {
+        let mut out = self.matches.clone();
+        if let Some((line, text)) = self.matcher.pending().filter(|_| include_tail) {
+            if self.regex.matches_line(text) {
+                out.push(line);
+            }
+        }
+        out
+    }
+
+    /// Releases spare capacity of the match list and the partial line.
+    pub fn shrink_to_fit(&mut self) {
+        self.matches.shrink_to_fit();
+        self.matcher.shrink_to_fit();
+    }
+
+    pub fn heap_bytes(&self) -> usize {
+        self.matches.capacity() * std::mem::size_of::<u64>() + self.matcher.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::lines::strip_line_terminator;
+    use crate::test_util::XorShift;
+
+    fn filter(pattern: &str) -> StreamFilter {
+        StreamFilter::new(Regex::new(pattern).unwrap(), 0, false)
+    }
+
+    #[test]
+    fn line_split_across_chunks_is_matched_once() {
+        let mut f = filter(r"^ERROR .*timeout$");
+        f.feed(b"INFO ok\nERROR conn");
+        assert_eq!(f.results(false), Vec::<u64>::new());
+        f.feed(b"ection timeout\r\nINFO ");
+        f.feed(b"done\nERROR x timeout");
+        assert_eq!(f.results(false), [1]);
+        // The last line has no terminator; it counts once the stream has ended.
+        assert_eq!(f.results(true), [1, 3]);
+    }
+
+    #[test]
+    fn chunking_does_not_change_results() {
+        let text = b"a1\nb2\na3 split\n\na4\nb5 a";
+        let whole = {
+            let mut f = filter("a");
+            f.feed(text);
+            f.results(true)
+        };
+        assert_eq!(whole, [0, 2, 4, 5]);
+        for split in 0..=text.len() {
+            let mut f = filter("a");
+            f.feed(&text[..split]);
+            f.feed(&text[split..]);
+            assert_eq!(f.results(true), whole, "split at {split}");
+        }
+    }
+
+    #[test]
+    fn random_splits_match_per_line_results() {
+        let regex = Regex::new("^a+b|b$").unwrap();
+        let mut rng = XorShift::new(0xBF58_476D_1CE4_E5B9);
+        for _ in 0..300 {
+            let text: Vec<u8> = (0..rng.below(100))
+                .map(|_| b"ab\n\r"[rng.below(4) as usize])
+                .collect();
+            let mid_line = rng.below(2) == 1;
+            // Reference: each line tested on its own, the unterminated last one only with
+            // `include_tail`.
+            let (mut complete, mut all) = (Vec::new(), Vec::new());
+            for (i, line) in text.split_inclusive(|&b| b == b'\n').enumerate() {
+                if (i > 0 || !mid_line) && regex.is_match(strip_line_terminator(line)) {
+                    all.push(3 + i as u64);
+                    if line.ends_with(b"\n") {
+                        complete.push(3 + i as u64);
+                    }
+                }
+            }
+            let mut f = StreamFilter::new(regex.clone(), 3, mid_line);
+            let mut rest = &text[..];
+            while !rest.is_empty() {
+                let (head, tail) = rest.split_at(1 + rng.below(rest.len() as u64) as usize);
+                f.feed(head);
+                rest = tail;
+            }
+            let text = String::from_utf8_lossy(&text);
+            assert_eq!(f.results(false), complete, "{text:?}");
+            assert_eq!(f.results(true), all, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn starting_mid_line_skips_that_line() {
+        let mut f = StreamFilter::new(Regex::new("x").unwrap(), 7, true);
+        f.feed(b"x tail of line 7\nx line 8\n");
+        assert_eq!(f.results(true), [8]);
+        f.reset();
+        f.feed(b"x\n");
+        assert_eq!(f.results(true), [0]);
+    }
+}
@@ -0,0 +1,114 @@
+//! Token frequencies over a window of lines, for an "interesting words" panel. Tokens are
+//! runs of bytes between ASCII whitespace and punctuation (`_` stays inside tokens, so
+//! identifiers survive); ASCII letters are lowercased and non-ASCII bytes are kept as-is.
+
+use std::collections::HashMap;
+
+/// Distinct tokens tracked per call. Past this, new tokens are not added but tokens
+/// already seen keep counting.
+pub const MAX_DISTINCT_TOKENS: usize = 100_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenCounts {
+    /// `(token, count)`, highest count first; ties in byte order.
+    pub top: Vec<(Vec<u8>, u64)>,
+    /// True when the distinct-token cap was hit, so rarer tokens may be missing.
+    pub overflowed: bool,
+}
+
+#[inline(always)]
+fn is_separator(b: u8) -> bool {
+    b.is_ascii_whitespace() || (b.is_ascii_punctuation() && b != b'_')
+}
+
+/// Counts the tokens of `bytes` (which may span many lines) and returns the `top_k`
+/// most frequent. Tokens shorter than `min_len` bytes are skipped, and with
+/// `skip_numbers` so are tokens made only of ASCII digits.
+pub fn token_frequencies(
+    bytes: &[u8],
+    top_k: usize,
+    min_len: usize,
+    skip_numbers: bool,
+) -> TokenCounts {
+    let mut counts: HashMap<Vec<u8>, u64> = HashMap::new();
+    let mut overflowed = false;
+    for token in bytes.split(|&b| is_separator(b)) {
+        if token.is_empty() || token.len() < min_len {
+            continue;
+        }
+        if skip_numbers && token.iter().all(u8::is_ascii_digit) {
+            continue;
+        }
+        let token = token.to_ascii_lowercase();
+        if let Some(count) = counts.get_mut(&token) {
+            *count += 1;
+        } else if counts.len() < MAX_DISTINCT_TOKENS {
+            counts.insert(token, 1);
+        } else {
+            overflowed = true;
+        }
+    }
+    let mut top: Vec<(Vec<u8>, u64)> = counts.into_iter().collect();
+    top.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    top.truncate(top_k);
+    TokenCounts { top, overflowed }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn top(text: &str, k: usize, min_len: usize, skip_numbers: bool) -> Vec<(String, u64)> {
+        token_frequencies(text.as_bytes(), k, min_len, skip_numbers)
+            .top
+            .into_iter()
+            .map(|(t, c)| (String::from_utf8(t).unwrap(), c))
+            .collect()
+    }
+
+    #[test]
+    fn ties_break_in_byte_order() {
+        let text = "ERROR db: timeout\nwarn cache=miss\nerror DB timeout 42\n";
+        assert_eq!(
+            top(text, 4, 1, false),
+            [
+                ("db".to_string(), 2),
+                ("error".to_string(), 2),
+                ("timeout".to_string(), 2),
+                ("42".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn unicode_bytes_stay_in_tokens() {
+        let text = "Größe=10 größe ÄRGER ärger user_id";
+        // Only ASCII is lowercased, so "Ä" and "ä" stay distinct tokens.
+        assert_eq!(
+            top(text, 10, 1, true),
+            [
+                ("größe".to_string(), 2),
+                ("user_id".to_string(), 1),
+                ("Ärger".to_string(), 1),
+                ("ärger".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn numeric_skip_and_min_len() {
+        let text = "id 12345 took 12ms at 2024 id";
+        assert_eq!(top(text, 10, 1, false)[0], ("id".to_string(), 2));
+        let skipped = top(text, 10, 3, true);
+        assert_eq!(skipped, [("12ms".to_string(), 1), ("took".to_string(), 1)]);
+    }
+
+    #[test]
+    fn overflow_keeps_counting_known_tokens() {
+        let mut text: String = (0..MAX_DISTINCT_TOKENS).map(|i| format!("t{i} ")).collect();
+        text.push_str("t0 t0 late late late");
+        let counts = token_frequencies(text.as_bytes(), 1, 1, false);
+        assert!(counts.overflowed);
+        assert_eq!(counts.top, [(b"t0".to_vec(), 3)]);
+    }
+}
@@ -0,0 +1,24 @@
+//! Helpers shared by the unit tests.
+
+/// xorshift64: deterministic random inputs without a `rand` dependency.
+pub struct XorShift(u64);
+
+impl XorShift {
+    /// A generator starting from `seed`, which must be non-zero.
+    pub fn new(seed: u64) -> Self {
+        assert_ne!(seed, 0, "xorshift needs a non-zero seed");
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A value in `[0, bound)`; `bound` must be non-zero.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+}
@@ -0,0 +1,41 @@
+// Type-check fixture for src/bindings.d.ts. Not executed; `tsc --noEmit` fails if the
+// declarations drift from how the frontend uses them.
+import {
+  classify_by_rules,
+  decode_lines_from_blob,
+  get_line_byte_ranges,
+  get_memory_stats,
+  get_write_region,
+  plan_window,
+  search,
+  search_stats,
+  set_invalid_utf8_mode,
+  type ByteRange,
+  type DecodedLine,
+  type MemoryStats,
+  type WindowPlan,
+} from '../../src/bindings';
+
+const region = get_write_region(65536);
+const writable: number = region.capacity + region.ptr;
+
+const ranges: ByteRange[] = get_line_byte_ranges(0, 10);
+const [start, end] = ranges[0];
+const span: number = end - start;
+
+const plan: WindowPlan = plan_window(0, 10);
+const blob = new Uint8Array(plan.file_end - plan.file_start);
+const lines: DecodedLine[] = decode_lines_from_blob(blob, plan.line_ends, plan);
+const first: string = lines[0] ?? '';
+
+set_invalid_utf8_mode(3);
+// @ts-expect-error only 0 | 1 | 2 | 3 are valid modes
+set_invalid_utf8_mode(4);
+
+const matches: number[] = search(new TextEncoder().encode('error'));
+const tagged: Uint32Array = classify_by_rules([{ name: 'err', pattern: 'ERROR' }]);
+const scanned: number = search_stats().bytes_scanned;
+const memory: MemoryStats = get_memory_stats();
+const wide: boolean = memory.offsets_wide;
+
+export { writable, span, first, matches, tagged, scanned, wide };
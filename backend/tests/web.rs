@@ -0,0 +1,20 @@
+//! Browser tests for exports that need a JS event loop. Run with
+//! `wasm-pack test --headless --chrome` (compiled out on native targets).
+#![cfg(target_arch = "wasm32")]
+
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+#[wasm_bindgen_test]
+async fn search_async_cancel_before_complete_rejects() {
+    wasm_log_explorer::clear();
+    let needle = js_sys::Uint8Array::from(&b"x"[..]);
+    let promise = wasm_log_explorer::search_async(&needle, 42);
+    wasm_log_explorer::request_cancel(42);
+    let err = JsFuture::from(promise).await.unwrap_err();
+    let err: js_sys::Error = err.dyn_into().unwrap();
+    assert_eq!(err.name(), "Cancelled");
+}